@@ -13,6 +13,7 @@ pub mod messaging {
     use alloc::string::String;
     use alloc::vec::Vec;
     use codec::{Decode, Encode};
+    use core::convert::TryInto;
     use core::fmt::Debug;
     use sp_core::U256;
 
@@ -171,10 +172,34 @@ pub mod messaging {
     pub type U64F64Bits = u128;
 
     // Messages: System
-    #[derive(Encode, Decode, Debug)]
+    #[derive(Encode, Debug)]
     pub struct WorkerEventWithKey {
         pub pubkey: WorkerPublicKey,
         pub event: WorkerEvent,
+        /// Present in the events pushed by pallet-mining
+        pub seq: Option<WorkerEventSeq>,
+    }
+
+    impl Decode for WorkerEventWithKey {
+        fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+            let pubkey = Decode::decode(input)?;
+            let event = Decode::decode(input)?;
+            // The events emitted before the sequencing end here
+            let seq = match input.remaining_len()? {
+                Some(0) => None,
+                _ => Decode::decode(input)?,
+            };
+            Ok(WorkerEventWithKey { pubkey, event, seq })
+        }
+    }
+
+    /// The position of a worker event in the event stream of the worker
+    #[derive(Encode, Decode, Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct WorkerEventSeq {
+        /// The mining session of the worker when the event was pushed, or 0 if it has never mined
+        pub session_id: u32,
+        /// Increased by one for each event pushed to the worker
+        pub nonce: u64,
     }
 
     #[derive(Encode, Decode, Debug)]
@@ -210,6 +235,56 @@ pub mod messaging {
         ///  When a miner recovered to MiningIdle state from Unresponsive, push this message to the worker to
         ///  resume the subsequent heartbeat responses.
         MiningExitUnresponsive,
+        /// pallet-mining --> worker
+        ///  When a miner swapped its bound worker during mining, push this message to the new worker to take over
+        ///  the mining session of the previous worker, inheriting its V.
+        ///   session_id: Generated by pallet. The new session replaces the one of the previous worker.
+        ///   from: The previous worker.
+        SessionTransferred {
+            session_id: u32,
+            from: WorkerPublicKey,
+        },
+        /// pallet-mining --> worker
+        ///  When a miner finished cooling down and got its stake reclaimed, push this message to the worker, so
+        ///  that it knows it can be rebound or start mining again.
+        CoolDownEnded,
+        /// pallet-mining --> worker
+        ///  When a miner is bound to the worker, push this message to the worker, so that it knows which account
+        ///  it's mining for.
+        ///   miner: The SCALE-encoded miner account.
+        MinerBound { miner: Vec<u8> },
+        /// pallet-mining --> worker
+        ///  When the miner is unbound from the worker, push this message to the worker.
+        MinerUnbound,
+        /// pallet-mining --> worker
+        ///  When a trial miner passed its trial and got upgraded to normal mining, push this message to the worker
+        ///  to raise its V together with the uncapped Ve.
+        ///   v_delta: The raise of V.
+        TrialCompleted { v_delta: U64F64Bits },
+        /// pallet-mining --> worker
+        ///  When a miner is bound to the worker and starts mining in the same call, push this message
+        ///  instead of MinerBound and MiningStart. It has the same effect as the two messages in order.
+        MinerBoundAndStarted {
+            miner: Vec<u8>,
+            session_id: u32,
+            init_v: U64F64Bits,
+        },
+        /// pallet-mining --> worker
+        ///  When a miner starts mining with the stake-weighted heartbeat sampling enabled, push this message to
+        ///  the worker so that it can scale its heartbeat target by its stake.
+        ///   stake: The stake of the miner.
+        MiningStakeUpdated { stake: u128 },
+        /// pallet-mining --> worker
+        ///  When a miner starts observing, push this message to the worker to run the benchmark and respond to
+        ///  the heartbeat challenges as in mining, but without earning or being slashed. Ended by MiningStop or
+        ///  replaced by MiningStart.
+        ///   session_id: Generated by pallet, the same as MiningStart.
+        ObservingStart { session_id: u32 },
+        /// pallet-mining --> worker
+        ///  When the reputation tier of the miner changes, or a miner in a tier starts mining, push this message
+        ///  so that the gatekeeper scales the slash rate of the worker. Reset to the full rate by MiningStart.
+        ///   slash_multiplier: The multiplier of the slash rate, in parts per million.
+        TierChanged { slash_multiplier: u32 },
     }
 
     bind_topic!(SystemEvent, b"phala/system/event");
@@ -221,18 +296,135 @@ pub mod messaging {
 
     impl SystemEvent {
         pub fn new_worker_event(pubkey: WorkerPublicKey, event: WorkerEvent) -> SystemEvent {
-            SystemEvent::WorkerEvent(WorkerEventWithKey { pubkey, event })
+            SystemEvent::WorkerEvent(WorkerEventWithKey {
+                pubkey,
+                event,
+                seq: None,
+            })
+        }
+
+        pub fn new_sequenced_worker_event(
+            pubkey: WorkerPublicKey,
+            event: WorkerEvent,
+            seq: WorkerEventSeq,
+        ) -> SystemEvent {
+            SystemEvent::WorkerEvent(WorkerEventWithKey {
+                pubkey,
+                event,
+                seq: Some(seq),
+            })
         }
     }
 
-    #[derive(Encode, Decode, Debug, Default, Clone, PartialEq, Eq)]
+    #[derive(Encode, Debug, Default, Clone, PartialEq, Eq)]
     pub struct HeartbeatChallenge {
         pub seed: U256,
         pub online_target: U256,
+        /// If present, only the workers in the filter are challenged
+        pub targets: Option<WorkerFilter>,
+        /// If present, the online target of each worker is scaled by its stake
+        pub stake_weighting: Option<StakeWeighting>,
+    }
+
+    impl Decode for HeartbeatChallenge {
+        fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+            let seed = Decode::decode(input)?;
+            let online_target = Decode::decode(input)?;
+            // The challenges emitted before the worker targeting end here
+            let targets = match input.remaining_len()? {
+                Some(0) => None,
+                _ => Decode::decode(input)?,
+            };
+            // The challenges emitted before the stake weighting end here
+            let stake_weighting = match input.remaining_len()? {
+                Some(0) => None,
+                _ => Decode::decode(input)?,
+            };
+            Ok(HeartbeatChallenge {
+                seed,
+                online_target,
+                targets,
+                stake_weighting,
+            })
+        }
+    }
+
+    /// The stake sum of the online miners, to scale the online target of each worker by its stake
+    /// relative to the average stake
+    ///
+    /// The expected number of heartbeats is kept, since the scaled targets sum up to the same
+    /// total.
+    #[derive(Encode, Decode, Debug, Default, Clone, PartialEq, Eq)]
+    pub struct StakeWeighting {
+        pub online_stake: u128,
+        pub online_miners: u32,
+    }
+
+    impl StakeWeighting {
+        /// Returns `online_target * stake / (online_stake / online_miners)`, saturated
+        pub fn target_of(&self, online_target: U256, stake: u128) -> U256 {
+            if self.online_stake == 0 {
+                return online_target;
+            }
+            let weight = U256::from(stake).saturating_mul(U256::from(self.online_miners));
+            (online_target / U256::from(self.online_stake)).saturating_mul(weight)
+        }
+    }
+
+    /// A bloom filter of worker pubkeys
+    ///
+    /// The bit positions are taken from the blake2_256 hash of the pubkey, 4 bytes each.
+    #[derive(Encode, Decode, Debug, Default, Clone, PartialEq, Eq)]
+    pub struct WorkerFilter {
+        pub bits: Vec<u8>,
+        pub num_hashes: u8,
+    }
+
+    impl WorkerFilter {
+        /// Each hash takes 4 bytes of the 32 bytes pubkey hash
+        pub const MAX_HASHES: u8 = 8;
+
+        /// Creates a filter of the workers, with about 1% false positive rate
+        pub fn with_workers(workers: &[WorkerPublicKey]) -> Self {
+            let num_bytes = ((workers.len() * 10 + 7) / 8).max(8);
+            let mut filter = WorkerFilter {
+                bits: alloc::vec![0; num_bytes],
+                num_hashes: 7,
+            };
+            for worker in workers {
+                filter.insert(worker);
+            }
+            filter
+        }
+
+        pub fn insert(&mut self, worker: &WorkerPublicKey) {
+            for pos in self.positions(worker) {
+                self.bits[pos / 8] |= 1 << (pos % 8);
+            }
+        }
+
+        /// Checks if the worker may be in the filter
+        pub fn contains(&self, worker: &WorkerPublicKey) -> bool {
+            !self.bits.is_empty()
+                && self
+                    .positions(worker)
+                    .all(|pos| self.bits[pos / 8] & (1 << (pos % 8)) != 0)
+        }
+
+        fn positions(&self, worker: &WorkerPublicKey) -> impl Iterator<Item = usize> {
+            let hash = sp_core::hashing::blake2_256(worker.as_ref());
+            let num_bits = (self.bits.len() * 8).max(1);
+            let num_hashes = self.num_hashes.min(Self::MAX_HASHES) as usize;
+            (0..num_hashes).map(move |i| {
+                let mut word = [0u8; 4];
+                word.copy_from_slice(&hash[i * 4..i * 4 + 4]);
+                u32::from_le_bytes(word) as usize % num_bits
+            })
+        }
     }
 
     bind_topic!(MiningReportEvent, b"phala/mining/report");
-    #[derive(Encode, Decode, Clone, Debug)]
+    #[derive(Encode, Clone, Debug)]
     pub enum MiningReportEvent {
         Heartbeat {
             /// The mining session id.
@@ -243,11 +435,78 @@ pub mod messaging {
             challenge_time: u64,
             /// Benchmark iterations since mining_start_time.
             iterations: u64,
+            /// The platform status piggybacked by the worker, if known.
+            platform: Option<PlatformStatus>,
+            /// Set on the first heartbeat after the pRuntime restarted, when its benchmark
+            /// iterations counter may have been reset.
+            restarted: bool,
         },
     }
 
-    bind_topic!(MiningInfoUpdateEvent<BlockNumber>, b"^phala/mining/update");
+    impl Decode for MiningReportEvent {
+        fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+            match input.read_byte()? {
+                0 => {
+                    let session_id = Decode::decode(input)?;
+                    let challenge_block = Decode::decode(input)?;
+                    let challenge_time = Decode::decode(input)?;
+                    let iterations = Decode::decode(input)?;
+                    // The heartbeats sent before the platform status end here
+                    let platform = match input.remaining_len()? {
+                        Some(0) => None,
+                        _ => Decode::decode(input)?,
+                    };
+                    // So do the ones sent before the restarted flag
+                    let restarted = match input.remaining_len()? {
+                        Some(0) => false,
+                        _ => Decode::decode(input)?,
+                    };
+                    Ok(MiningReportEvent::Heartbeat {
+                        session_id,
+                        challenge_block,
+                        challenge_time,
+                        iterations,
+                        platform,
+                        restarted,
+                    })
+                }
+                _ => Err("Invalid MiningReportEvent variant".into()),
+            }
+        }
+    }
+
+    /// The compact SGX platform status of a worker
     #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+    pub struct PlatformStatus {
+        /// The confidence level of the last attestation of the platform (1 to 5, lower is
+        /// better), reflecting its TCB level
+        pub confidence_level: u8,
+        /// The time (in sec) since the worker was attested
+        pub uptime_sec: u64,
+    }
+
+    /// The latest version of the gatekeeper to chain mining protocol
+    ///
+    /// Version 0 is the legacy `MiningInfoUpdateEvent` without the version field. Version 2 adds
+    /// the settlement audit data, no longer sent and skipped in decoding. Version 3 adds the
+    /// `settle_bindings`. Version 4 paginates the status updates with the `continues` marker.
+    pub const GK_PROTOCOL_VERSION: u32 = 4;
+
+    /// The max number of the offline and recovered workers reported in a `MiningInfoUpdateEvent`
+    ///
+    /// The gatekeeper speaking version 4 splits the status updates of a block across several
+    /// messages beyond it.
+    pub const MAX_STATUS_UPDATES_PER_MESSAGE: u32 = 500;
+
+    /// The wire type of the block numbers in the gatekeeper mining messages
+    ///
+    /// The block number is always encoded as a `u32` regardless of the `BlockNumber` type of the
+    /// runtime, so that the runtimes and the tools with different block number types can share
+    /// the messages. It's the layout the `u32` runtimes have always used.
+    pub type GkBlockNumber = u32;
+
+    bind_topic!(MiningInfoUpdateEvent<BlockNumber>, b"^phala/mining/update");
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct MiningInfoUpdateEvent<BlockNumber> {
         /// The block emiting this message.
         pub block_number: BlockNumber,
@@ -259,10 +518,89 @@ pub mod messaging {
         pub recovered_to_online: Vec<WorkerPublicKey>,
         /// V update and payout info
         pub settle: Vec<SettleInfo>,
+        /// The protocol version the gatekeeper speaks, negotiated with `SupportedGkProtocol`
+        pub protocol_version: u32,
+        /// The miner bindings the settlements are made under (since version 3)
+        pub settle_bindings: Vec<SettleBinding>,
+        /// More status updates of the same block follow in the next message (since version 4)
+        pub continues: bool,
 
 		// NOTE: Take care of the is_empty method when adding fields
     }
 
+    impl<BlockNumber> Encode for MiningInfoUpdateEvent<BlockNumber>
+    where
+        BlockNumber: Clone + TryInto<GkBlockNumber>,
+    {
+        fn encode_to<W: codec::Output + ?Sized>(&self, dest: &mut W) {
+            // Saturated, a gatekeeper never reports a block beyond the wire type
+            let block_number: GkBlockNumber = self
+                .block_number
+                .clone()
+                .try_into()
+                .unwrap_or(GkBlockNumber::MAX);
+            block_number.encode_to(dest);
+            self.timestamp_ms.encode_to(dest);
+            self.offline.encode_to(dest);
+            self.recovered_to_online.encode_to(dest);
+            self.settle.encode_to(dest);
+            // Keep the legacy layout for version 0, so that the digest confirmed by a legacy
+            // gatekeeper quorum matches the re-encoded event
+            let has_bindings = !self.settle_bindings.is_empty() || self.continues;
+            if self.protocol_version != 0 || has_bindings {
+                self.protocol_version.encode_to(dest);
+            }
+            if has_bindings {
+                // The retired audit data, always empty
+                codec::Compact(0u32).encode_to(dest);
+                self.settle_bindings.encode_to(dest);
+            }
+            if self.continues {
+                self.continues.encode_to(dest);
+            }
+        }
+    }
+
+    impl<BlockNumber: From<GkBlockNumber>> Decode for MiningInfoUpdateEvent<BlockNumber> {
+        fn decode<I: codec::Input>(input: &mut I) -> Result<Self, codec::Error> {
+            let block_number = GkBlockNumber::decode(input)?.into();
+            let timestamp_ms = Decode::decode(input)?;
+            let offline = Decode::decode(input)?;
+            let recovered_to_online = Decode::decode(input)?;
+            let settle = Decode::decode(input)?;
+            // The events from the legacy gatekeepers end here
+            let protocol_version = match input.remaining_len()? {
+                Some(0) => 0,
+                _ => Decode::decode(input)?,
+            };
+            // The events without the audit data end here. The audit data of the legacy version 2
+            // gatekeepers (pubkey, blocks_idle, blocks_slashed) is skipped.
+            if input.remaining_len()? != Some(0) {
+                <Vec<(WorkerPublicKey, u32, u32)>>::decode(input)?;
+            }
+            // So do the ones without the bindings
+            let settle_bindings = match input.remaining_len()? {
+                Some(0) => Vec::new(),
+                _ => Decode::decode(input)?,
+            };
+            // Only the non-final pages carry the marker
+            let continues = match input.remaining_len()? {
+                Some(0) => false,
+                _ => Decode::decode(input)?,
+            };
+            Ok(MiningInfoUpdateEvent {
+                block_number,
+                timestamp_ms,
+                offline,
+                recovered_to_online,
+                settle,
+                protocol_version,
+                settle_bindings,
+                continues,
+            })
+        }
+    }
+
     impl<BlockNumber> MiningInfoUpdateEvent<BlockNumber> {
         pub fn new(block_number: BlockNumber, timestamp_ms: u64) -> Self {
             Self {
@@ -271,12 +609,71 @@ pub mod messaging {
                 offline: Default::default(),
                 recovered_to_online: Default::default(),
                 settle: Default::default(),
+                protocol_version: 0,
+                settle_bindings: Default::default(),
+                continues: false,
             }
         }
 
         pub fn is_empty(&self) -> bool {
             self.offline.is_empty() && self.settle.is_empty() && self.recovered_to_online.is_empty()
         }
+
+        /// Splits the status updates into pages of at most `max_updates` offline and recovered
+        /// workers
+        ///
+        /// The settlements go with the last page. All the pages but the last one are marked with
+        /// `continues`.
+        pub fn paginate(mut self, max_updates: usize) -> Vec<Self>
+        where
+            BlockNumber: Clone,
+        {
+            let max_updates = max_updates.max(1);
+            let mut pages = Vec::new();
+            while self.offline.len() + self.recovered_to_online.len() > max_updates {
+                let mut page = Self::new(self.block_number.clone(), self.timestamp_ms);
+                page.protocol_version = self.protocol_version;
+                page.continues = true;
+                let num_offline = self.offline.len().min(max_updates);
+                page.offline = self.offline.drain(..num_offline).collect();
+                let num_recovered = (max_updates - num_offline).min(self.recovered_to_online.len());
+                page.recovered_to_online =
+                    self.recovered_to_online.drain(..num_recovered).collect();
+                pages.push(page);
+            }
+            pages.push(self);
+            pages
+        }
+    }
+
+    bind_topic!(MiningInfoUpdateConfirm, b"^phala/mining/update/confirm");
+    /// Sent by each gatekeeper from its own worker identity to vouch for a `MiningInfoUpdateEvent`
+    #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+    pub struct MiningInfoUpdateConfirm {
+        /// The blake2_256 hash of the encoded `MiningInfoUpdateEvent`
+        pub digest: [u8; 32],
+    }
+
+    bind_topic!(GatekeeperTelemetry, b"^phala/mining/telemetry");
+    /// The performance telemetry periodically sent by each gatekeeper from its own worker
+    /// identity
+    #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+    pub struct GatekeeperTelemetry {
+        /// The block the gatekeeper processed when sending the telemetry
+        pub block_number: GkBlockNumber,
+        /// The number of workers tracked by the gatekeeper
+        pub workers_tracked: u32,
+        /// The number of `MiningInfoUpdateEvent` emitted since the last telemetry
+        pub events_emitted: u32,
+    }
+
+    bind_topic!(MiningEmergencyEvent, b"^phala/mining/emergency");
+    /// Sent by the gatekeeper when it detects an inconsistent state between the enclave and the
+    /// chain
+    #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+    pub enum MiningEmergencyEvent {
+        /// Pauses applying the settlements on chain until the governance resumes it
+        TokenomicFreeze { reason: Vec<u8> },
     }
 
     #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
@@ -286,6 +683,14 @@ pub mod messaging {
         pub payout: U64F64Bits,
     }
 
+    /// The miner binding of a worker known by the gatekeeper when settling it
+    #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+    pub struct SettleBinding {
+        pub pubkey: WorkerPublicKey,
+        /// The blake2_256 hash of the SCALE-encoded miner account, or None if not bound
+        pub binding_hash: Option<[u8; 32]>,
+    }
+
     // Messages: Gatekeeper
     bind_topic!(GatekeeperEvent, b"phala/gatekeeper/event");
     #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
@@ -294,6 +699,9 @@ pub mod messaging {
         DispatchMasterKey(DispatchMasterKeyEvent),
         NewRandomNumber(RandomNumberEvent),
         TokenomicParametersChanged(TokenomicParameters),
+        /// The scores of the confidence levels 1 to 5 (U64F64 bits), or None for the builtin
+        /// table
+        ConfidenceScoresChanged(Option<[U64F64Bits; 5]>),
     }
 
     // Walkaround for heavy dep on phala-crypto
@@ -370,6 +778,7 @@ pub mod messaging {
     }
 
     #[cfg_attr(feature = "enable_serde", derive(Serialize, Deserialize))]
+    #[cfg_attr(feature = "enable_type_info", derive(scale_info::TypeInfo))]
     #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
     pub struct TokenomicParameters {
         // V calculation
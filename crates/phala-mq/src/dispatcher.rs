@@ -14,9 +14,33 @@ impl Seq for (u64, Message) {
     }
 }
 
+struct Subscriber {
+    /// The topic sequence of the last message dispatched before subscribing
+    base_seq: u64,
+    sender: Sender<(u64, Message)>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    /// Number of messages dispatched to the topic so far
+    seq: u64,
+    subscribers: Vec<Subscriber>,
+}
+
+/// The receiving progress of a topic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TopicStatus {
+    /// Sequence of the last message dispatched to the topic
+    pub dispatched_seq: u64,
+    /// Sequence of the last message processed by the slowest receiver of the topic
+    pub last_seq: u64,
+    /// Number of messages waiting in the receivers of the topic
+    pub pending: usize,
+}
+
 #[derive(Default)]
 pub struct MessageDispatcher {
-    subscribers: BTreeMap<Path, Vec<Sender<(u64, Message)>>>,
+    subscribers: BTreeMap<Path, Subscribers>,
     local_index: u64,
     //match_subscribers: Vec<Matcher, Vec<Sender<Message>>>,
 }
@@ -36,7 +60,10 @@ impl MessageDispatcher {
     pub fn subscribe(&mut self, path: impl Into<Path>) -> Receiver<Message> {
         let (rx, tx) = channel();
         let entry = self.subscribers.entry(path.into()).or_default();
-        entry.push(tx);
+        entry.subscribers.push(Subscriber {
+            base_seq: entry.seq,
+            sender: tx,
+        });
         rx
     }
 
@@ -52,9 +79,10 @@ impl MessageDispatcher {
         let mut count = 0;
         let sn = self.local_index;
         self.local_index += 1;
-        if let Some(receivers) = self.subscribers.get_mut(message.destination.path()) {
-            receivers.retain(|receiver| {
-                if let Err(error) = receiver.send((sn, message.clone())) {
+        if let Some(topic) = self.subscribers.get_mut(message.destination.path()) {
+            topic.seq += 1;
+            topic.subscribers.retain(|receiver| {
+                if let Err(error) = receiver.sender.send((sn, message.clone())) {
                     use crate::simple_mpsc::SendError::*;
                     match error {
                         ReceiverGone => false,
@@ -75,11 +103,38 @@ impl MessageDispatcher {
     /// Drop all unhandled messages.
     pub fn clear(&mut self) -> usize {
         let mut count = 0;
-        for subscriber in self.subscribers.values_mut().flatten() {
-            count += subscriber.clear();
+        for topic in self.subscribers.values_mut() {
+            for subscriber in topic.subscribers.iter() {
+                count += subscriber.sender.clear();
+            }
         }
         count
     }
+
+    /// Returns the receiving progress of each subscribed topic.
+    ///
+    /// Topic sequences count the messages dispatched to the topic since the dispatcher was
+    /// created. A `last_seq` behind `dispatched_seq` with nothing pending means some messages were
+    /// dropped before being processed.
+    pub fn topic_status(&self) -> BTreeMap<Path, TopicStatus> {
+        self.subscribers
+            .iter()
+            .map(|(path, topic)| {
+                let mut status = TopicStatus {
+                    dispatched_seq: topic.seq,
+                    last_seq: topic.seq,
+                    pending: 0,
+                };
+                for subscriber in topic.subscribers.iter() {
+                    let channel = subscriber.sender.status();
+                    let last_seq = subscriber.base_seq + channel.last_received;
+                    status.last_seq = status.last_seq.min(last_seq);
+                    status.pending += channel.pending;
+                }
+                (path.clone(), status)
+            })
+            .collect()
+    }
 }
 
 #[derive(Display, Debug)]
@@ -16,9 +16,9 @@ mod send_queue;
 mod simple_mpsc;
 
 #[cfg(feature = "dispatcher")]
-pub use dispatcher::{MessageDispatcher, TypedReceiveError, TypedReceiver};
+pub use dispatcher::{MessageDispatcher, TopicStatus, TypedReceiveError, TypedReceiver};
 #[cfg(feature = "queue")]
-pub use send_queue::{MessageChannel, MessageSendQueue};
+pub use send_queue::{MessageChannel, MessageSendQueue, SendError};
 #[cfg(any(feature = "queue", feature = "dispatcher"))]
 pub use simple_mpsc::{ReceiveError, Receiver};
 
@@ -1,13 +1,27 @@
 use crate::types::{Message, MessageToBeSigned, SignedMessage};
 use crate::{MessageOrigin, MessageSigner, Mutex, SenderId};
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
-
+use derive_more::Display;
 
 #[derive(Default)]
 struct Channel {
     sequence: u64,
     messages: Vec<SignedMessage>,
     dummy: bool,
+    /// The max number of pending messages accepted by `try_enqueue_message`. Unlimited if None.
+    capacity: Option<usize>,
+}
+
+impl Channel {
+    fn is_full(&self) -> bool {
+        matches!(self.capacity, Some(cap) if self.messages.len() >= cap)
+    }
+}
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    #[display(fmt = "The egress queue of the channel is full")]
+    Full,
 }
 
 #[derive(Clone, Default)]
@@ -40,6 +54,46 @@ impl MessageSendQueue {
         entry.sequence += 1;
     }
 
+    /// Enqueues a message unless the queue of the sender has reached its capacity.
+    ///
+    /// No sequence is consumed if the message is rejected.
+    pub fn try_enqueue_message(
+        &self,
+        sender: SenderId,
+        constructor: impl FnOnce(u64) -> SignedMessage,
+    ) -> Result<(), SendError> {
+        let mut inner = self.inner.lock();
+        let entry = inner.entry(sender).or_default();
+        if !entry.dummy {
+            if entry.is_full() {
+                return Err(SendError::Full);
+            }
+            let message = constructor(entry.sequence);
+            entry.messages.push(message);
+        }
+        entry.sequence += 1;
+        Ok(())
+    }
+
+    /// Sets the max number of pending messages of a sender, or None for unlimited.
+    pub fn set_capacity(&self, sender: SenderId, capacity: Option<usize>) {
+        let mut inner = self.inner.lock();
+        let entry = inner.entry(sender).or_default();
+        entry.capacity = capacity;
+    }
+
+    /// The number of pending messages of a sender, not yet accepted on chain.
+    pub fn queue_depth(&self, sender: &SenderId) -> usize {
+        let inner = self.inner.lock();
+        inner.get(sender).map(|x| x.messages.len()).unwrap_or(0)
+    }
+
+    /// Returns true if the queue of a sender has reached its capacity.
+    pub fn is_full(&self, sender: &SenderId) -> bool {
+        let inner = self.inner.lock();
+        inner.get(sender).map(|x| x.is_full()).unwrap_or(false)
+    }
+
     pub fn set_dummy_mode(&self, sender: SenderId, dummy: bool) {
         let mut inner = self.inner.lock();
         let entry = inner.entry(sender).or_default();
@@ -106,11 +160,30 @@ mod msg_channel {
             }
         }
 
+        /// Sends a message regardless of the capacity of the channel.
         pub fn send_data(&self, payload: Vec<u8>, to: impl Into<Path>) {
             let sender = self.sender.clone();
-            let signer = &self.signer;
+            let constructor = self.message_constructor(payload, to);
+            self.queue.enqueue_message(sender, constructor)
+        }
+
+        /// Sends a message, or returns `SendError::Full` if the egress queue is full.
+        ///
+        /// Senders of non-critical messages should use it to respect the back-pressure.
+        pub fn try_send_data(&self, payload: Vec<u8>, to: impl Into<Path>) -> Result<(), SendError> {
+            let sender = self.sender.clone();
+            let constructor = self.message_constructor(payload, to);
+            self.queue.try_enqueue_message(sender, constructor)
+        }
 
-            self.queue.enqueue_message(sender.clone(), move |sequence| {
+        fn message_constructor<'a>(
+            &'a self,
+            payload: Vec<u8>,
+            to: impl Into<Path> + 'a,
+        ) -> impl FnOnce(u64) -> SignedMessage + 'a {
+            let sender = self.sender.clone();
+            let signer = &self.signer;
+            move |sequence| {
                 let message = Message {
                     sender,
                     destination: to.into().into(),
@@ -129,7 +202,7 @@ mod msg_channel {
                     sequence,
                     signature,
                 }
-            })
+            }
         }
 
         pub fn sendto<M: Encode>(&self, message: &M, to: impl Into<Path>) {
@@ -140,6 +213,29 @@ mod msg_channel {
             self.sendto(message, <M as BindTopic>::TOPIC)
         }
 
+        pub fn try_sendto<M: Encode>(&self, message: &M, to: impl Into<Path>) -> Result<(), SendError> {
+            self.try_send_data(message.encode(), to)
+        }
+
+        pub fn try_send<M: Encode + BindTopic>(&self, message: &M) -> Result<(), SendError> {
+            self.try_sendto(message, <M as BindTopic>::TOPIC)
+        }
+
+        /// The number of pending messages of this channel, not yet accepted on chain.
+        pub fn queue_depth(&self) -> usize {
+            self.queue.queue_depth(&self.sender)
+        }
+
+        /// The back-pressure signal. Returns true if the egress queue has reached its capacity.
+        pub fn is_congested(&self) -> bool {
+            self.queue.is_full(&self.sender)
+        }
+
+        /// Sets the max number of pending messages of this channel, or None for unlimited.
+        pub fn set_capacity(&self, capacity: Option<usize>) {
+            self.queue.set_capacity(self.sender.clone(), capacity);
+        }
+
         /// Set the channel to dummy mode which increasing the sequence but dropping the message.
         pub fn set_dummy(&self, dummy: bool) {
             self.queue.set_dummy_mode(self.sender.clone(), dummy);
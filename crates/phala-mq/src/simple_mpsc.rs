@@ -8,6 +8,10 @@ struct Channel<T> {
     deque: VecDeque<T>,
     sender_count: usize,
     receiver_gone: bool,
+    /// Number of values taken out of the channel, by `try_next` or dropped
+    consumed: u64,
+    /// The 1-based position of the last value returned by `try_next`, 0 if none yet
+    last_received: u64,
 }
 
 impl<T> Channel<T> {
@@ -20,6 +24,8 @@ impl<T> Channel<T> {
             deque: VecDeque::with_capacity(cap),
             sender_count: 1,
             receiver_gone: false,
+            consumed: 0,
+            last_received: 0,
         }
     }
 }
@@ -47,10 +53,30 @@ impl<T> Sender<T> {
 
     pub fn clear(&self) -> usize {
         let mut ch = self.0.lock();
-        ch.deque.drain(..).count()
+        let count = ch.deque.drain(..).count();
+        ch.consumed += count as u64;
+        count
+    }
+
+    /// Returns the consuming progress of the receiver end.
+    pub fn status(&self) -> ChannelStatus {
+        let ch = self.0.lock();
+        ChannelStatus {
+            last_received: ch.last_received,
+            pending: ch.deque.len(),
+        }
     }
 }
 
+/// The consuming progress of a channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStatus {
+    /// The 1-based position of the last value received, 0 if nothing received yet
+    pub last_received: u64,
+    /// Number of values waiting to be received
+    pub pending: usize,
+}
+
 impl<T> Clone for Sender<T> {
     fn clone(&self) -> Sender<T> {
         self.0.lock().sender_count += 1;
@@ -81,6 +107,8 @@ impl<T> Receiver<T> {
     pub fn try_next(&mut self) -> Result<Option<T>, ReceiveError> {
         let mut ch = self.0.lock();
         if let Some(value) = ch.deque.pop_front() {
+            ch.consumed += 1;
+            ch.last_received = ch.consumed;
             return Ok(Some(value));
         } else if ch.sender_count == 0 {
             return Err(ReceiveError::SenderGone);
@@ -90,11 +118,18 @@ impl<T> Receiver<T> {
 
     pub fn drain(&mut self) -> impl Iterator<Item = T> {
         let mut ch = self.0.lock();
-        ch.deque.drain(..).collect::<Vec<_>>().into_iter()
+        let values = ch.deque.drain(..).collect::<Vec<_>>();
+        if !values.is_empty() {
+            ch.consumed += values.len() as u64;
+            ch.last_received = ch.consumed;
+        }
+        values.into_iter()
     }
 
     pub fn clear(&mut self) {
-        let _ = self.0.lock().deque.drain(..);
+        let mut ch = self.0.lock();
+        let count = ch.deque.drain(..).count();
+        ch.consumed += count as u64;
     }
 }
 
@@ -24,6 +24,14 @@ pub enum MessageOrigin {
     MultiLocation(Vec<u8>),
     /// All gatekeepers share the same origin
     Gatekeeper,
+    /// A confidential contract signing with the identity key of its hosting worker
+    ///
+    /// Unlike `Contract`, it's only vouched by the worker rather than the key registered for
+    /// the contract.
+    HostedContract {
+        id: H256,
+        worker: sp_core::sr25519::Public,
+    },
 }
 
 impl Hash for MessageOrigin {
@@ -41,7 +49,18 @@ impl MessageOrigin {
 
     /// Returns if the origin is located off-chain
     pub fn is_offchain(&self) -> bool {
-        matches!(self, Self::Contract(_) | Self::Worker(_) | Self::Gatekeeper)
+        matches!(
+            self,
+            Self::Contract(_) | Self::Worker(_) | Self::Gatekeeper | Self::HostedContract { .. }
+        )
+    }
+
+    /// Returns the contract id if the origin is a contract, regardless of its signer
+    pub fn contract_id(&self) -> Option<&H256> {
+        match self {
+            Self::Contract(id) | Self::HostedContract { id, .. } => Some(id),
+            _ => None,
+        }
     }
 
     /// Returns if the origin is from a Pallet
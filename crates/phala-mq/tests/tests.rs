@@ -72,6 +72,46 @@ fn test_send_message() {
     }
 }
 
+#[cfg(feature = "queue")]
+#[test]
+fn test_send_queue_capacity() {
+    use phala_mq::{MessageSendQueue, MessageSigner, SendError};
+
+    struct TestSigner;
+
+    impl MessageSigner for TestSigner {
+        fn sign(&self, _data: &[u8]) -> Vec<u8> {
+            Vec::new()
+        }
+    }
+
+    let queue = MessageSendQueue::new();
+    let sender = MessageOrigin::native_contract(1);
+    let handle = queue.channel(sender.clone(), TestSigner);
+    handle.set_capacity(Some(2));
+
+    assert_eq!(handle.try_send_data(b"0".to_vec(), b"/path".to_vec()), Ok(()));
+    assert_eq!(handle.try_send_data(b"1".to_vec(), b"/path".to_vec()), Ok(()));
+    assert!(handle.is_congested());
+    assert_eq!(
+        handle.try_send_data(b"2".to_vec(), b"/path".to_vec()),
+        Err(SendError::Full)
+    );
+    assert_eq!(handle.queue_depth(), 2);
+
+    // The forced sending ignores the capacity, and the sequence is not consumed by the rejected one
+    handle.send_data(b"3".to_vec(), b"/path".to_vec());
+    let messages = queue.messages(&sender);
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[2].sequence, 2);
+
+    // Accepted messages release the pressure
+    queue.purge(|_| 3);
+    assert_eq!(handle.queue_depth(), 0);
+    assert!(!handle.is_congested());
+    assert_eq!(handle.try_send_data(b"4".to_vec(), b"/path".to_vec()), Ok(()));
+}
+
 #[cfg(feature = "dispatcher")]
 #[test]
 fn test_dispatcher() {
@@ -140,6 +180,65 @@ fn test_dispatcher() {
     }
 }
 
+#[cfg(feature = "dispatcher")]
+#[test]
+fn test_topic_status() {
+    use phala_mq::{Message, MessageDispatcher, TopicStatus};
+    let sender = MessageOrigin::Pallet(b"sender".to_vec());
+    let mut dispatcher = MessageDispatcher::new();
+
+    let mut sub0 = dispatcher.subscribe(*b"path0");
+    let mut sub1 = dispatcher.subscribe(*b"path1");
+    for i in 0..3u8 {
+        dispatcher.dispatch(Message::new(sender.clone(), *b"path0", vec![i]));
+    }
+    dispatcher.dispatch(Message::new(sender.clone(), *b"path1", b"1".to_vec()));
+    // Messages to a topic without subscribers are not tracked
+    dispatcher.dispatch(Message::new(sender.clone(), *b"path2", b"2".to_vec()));
+
+    assert!(sub0.try_next().unwrap().is_some());
+    let status = dispatcher.topic_status();
+    assert_eq!(status.len(), 2);
+    assert_eq!(
+        status[&b"path0".to_vec()],
+        TopicStatus {
+            dispatched_seq: 3,
+            last_seq: 1,
+            pending: 2,
+        }
+    );
+    assert_eq!(
+        status[&b"path1".to_vec()],
+        TopicStatus {
+            dispatched_seq: 1,
+            last_seq: 0,
+            pending: 1,
+        }
+    );
+
+    // A late subscriber starts from the current sequence, and the slowest receiver wins
+    let mut sub2 = dispatcher.subscribe(*b"path0");
+    dispatcher.dispatch(Message::new(sender.clone(), *b"path0", b"3".to_vec()));
+    assert_eq!(sub0.drain().count(), 3);
+    assert!(sub2.try_next().unwrap().is_some());
+    let status = dispatcher.topic_status();
+    assert_eq!(
+        status[&b"path0".to_vec()],
+        TopicStatus {
+            dispatched_seq: 4,
+            last_seq: 4,
+            pending: 0,
+        }
+    );
+
+    // Dropped messages don't count as processed
+    assert_eq!(dispatcher.clear(), 1);
+    assert!(sub1.try_next().unwrap().is_none());
+    let status = dispatcher.topic_status();
+    assert_eq!(status[&b"path1".to_vec()].last_seq, 0);
+    assert_eq!(status[&b"path1".to_vec()].pending, 0);
+}
+
 #[cfg(feature = "dispatcher")]
 #[test]
 fn test_select_order() {
@@ -172,3 +271,23 @@ fn test_select_order() {
     }
     assert_eq!(payloads, [0, 1, 2, 3, 4]);
 }
+
+#[test]
+fn test_hosted_contract_origin() {
+    use parity_scale_codec::Encode;
+    use primitive_types::H256;
+
+    let id = H256::from_low_u64_be(1);
+    let hosted = MessageOrigin::HostedContract {
+        id,
+        worker: sp_core::sr25519::Public([1u8; 32]),
+    };
+    assert!(hosted.is_offchain());
+    assert_eq!(hosted.contract_id(), Some(&id));
+    assert_eq!(MessageOrigin::Contract(id).contract_id(), Some(&id));
+    assert_eq!(MessageOrigin::Gatekeeper.contract_id(), None);
+    // The encoding of the existing origins is untouched
+    assert_eq!(MessageOrigin::Gatekeeper.encode(), vec![5]);
+    assert_eq!(hosted.encode()[0], 6);
+    assert_ne!(hosted, MessageOrigin::Contract(id));
+}
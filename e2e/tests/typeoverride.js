@@ -108,7 +108,9 @@ const types = {
         "vUpdatedAt": "u64",
         "benchmark": "Benchmark",
         "coolDownStart": "u64",
-        "stats": "MinerStats"
+        "stats": "MinerStats",
+        "heartbeatLatency": "u64",
+        "vBalance": "u128"
     },
     "Benchmark": {
         "pInstant": "u32",
@@ -126,11 +128,20 @@ const types = {
         }
     },
     "MinerStats": {
-        "totalReward": "Balance"
+        "totalReward": "Balance",
+        "totalSlashed": "Compact<Balance>",
+        "totalHeartbeats": "Compact<u32>",
+        "sessionsCount": "Compact<u32>",
+        "lastSessionId": "Compact<u32>"
     },
     "HeartbeatChallenge": {
         "seed": "U256",
-        "onlineTarget": "U256"
+        "onlineTarget": "U256",
+        "targets": "Option<WorkerFilter>"
+    },
+    "WorkerFilter": {
+        "bits": "Vec<u8>",
+        "numHashes": "u8"
     },
     "GatekeeperEvent": {
         "_enum": {
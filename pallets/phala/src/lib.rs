@@ -9,15 +9,17 @@
 //! - `registry`: Manages the public key of offchain components (i.e. workers and contracts)
 //! - `mining`: Manages mining lifecycle, reward and slashes
 //! - `stakepool`: Pool for collaboratively mining staking
+//! - `runtime_api`: The runtime APIs to query the pallets
 
 // Re-export
-use utils::{accumulator, attestation, balance_convert, constants, fixed_point};
+use utils::{accumulator, attestation, balance_convert, bounded_history, constants, fixed_point};
 
 mod utils;
 
 pub mod mining;
 pub mod mq;
 pub mod registry;
+pub mod runtime_api;
 pub mod stakepool;
 
 // Alias
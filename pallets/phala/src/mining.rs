@@ -1,5 +1,12 @@
 pub use self::pallet::*;
 
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+#[cfg(test)]
+mod invariant_tests;
+#[cfg(feature = "std")]
+pub mod simulator;
+
 #[allow(unused_variables)]
 #[frame_support::pallet]
 pub mod pallet {
@@ -8,73 +15,251 @@ pub mod pallet {
 	use frame_support::{
 		dispatch::DispatchResult,
 		pallet_prelude::*,
-		traits::{Currency, ExistenceRequirement::KeepAlive, Randomness, UnixTime},
+		storage::child::{self, ChildInfo},
+		traits::{
+			tokens::fungibles,
+			Currency, EnsureOrigin,
+			ExistenceRequirement::{AllowDeath, KeepAlive},
+			LockIdentifier, LockableCurrency, Randomness, UnixTime, WithdrawReasons,
+		},
 		PalletId,
 	};
 	use frame_system::pallet_prelude::*;
 	use phala_types::{
 		messaging::{
-			DecodedMessage, GatekeeperEvent, HeartbeatChallenge, MessageOrigin,
-			MiningInfoUpdateEvent, MiningReportEvent, SettleInfo, SystemEvent,
-			TokenomicParameters as TokenomicParams, WorkerEvent,
+			BindTopic, DecodedMessage, GatekeeperEvent, GatekeeperTelemetry, HeartbeatChallenge,
+			MessageOrigin, MiningEmergencyEvent, MiningInfoUpdateConfirm, MiningInfoUpdateEvent,
+			MiningReportEvent, SettleBinding, SettleInfo, StakeWeighting, SystemEvent,
+			TokenomicParameters as TokenomicParams, WorkerEvent, WorkerEventSeq, WorkerFilter,
+			GK_PROTOCOL_VERSION,
 		},
 		WorkerPublicKey,
 	};
+	use scale_info::TypeInfo;
+	#[cfg(feature = "std")]
+	use serde::{Deserialize, Serialize};
 	use sp_core::U256;
-	use sp_runtime::{traits::AccountIdConversion, SaturatedConversion};
+	use sp_runtime::{
+		traits::{AccountIdConversion, Hash, Saturating, TrailingZeroInput, Zero},
+		Perbill, Permill, SaturatedConversion,
+	};
 	use sp_std::cmp;
+	use sp_std::marker::PhantomData;
 	use sp_std::vec::Vec;
 
-	use crate::balance_convert::FixedPointConvert;
-	use fixed::types::U64F64 as FixedPoint;
+	use crate::balance_convert::{self, FixedPointConvert};
+	use crate::bounded_history::{self, BoundedHistory};
+	pub use crate::fixed_point::FixedBits;
+	use fixed::types::{I64F64, U64F64 as FixedPoint};
 	use fixed_sqrt::FixedSqrt;
 
 	const DEFAULT_EXPECTED_HEARTBEAT_COUNT: u32 = 20;
-	const MINING_PALLETID: PalletId = PalletId(*b"phala/pp");
+	/// The bounds of `ExpectedHeartbeatCount` accepted by `set_expected_heartbeat_count`
+	const MIN_EXPECTED_HEARTBEAT_COUNT: u32 = 1;
+	const MAX_EXPECTED_HEARTBEAT_COUNT: u32 = 1000;
+	/// The block utilization above which the dynamic heartbeat count is lowered
+	const HEARTBEAT_HIGH_UTILIZATION: Perbill = Perbill::from_percent(75);
+	/// The block utilization below which the dynamic heartbeat count is raised
+	const HEARTBEAT_LOW_UTILIZATION: Perbill = Perbill::from_percent(25);
+	const DEFAULT_HEARTBEAT_CHALLENGE_INTERVAL: u32 = 1;
+	const MAX_TOKENOMIC_HISTORY: usize = 64;
+	/// The lock of the stake of the solo miners of the default instance, see `solo_staking_id`
+	const SOLO_STAKING_ID: LockIdentifier = *b"phala/mn";
+	/// The current storage layout version, see `on_runtime_upgrade`
+	const STORAGE_VERSION: u32 = 1;
+	/// The max number of the status transitions in a `SettlementTrace`
+	const MAX_TRACE_TRANSITIONS: usize = 64;
+	/// The interval (in blocks) to start a new round of the online miner counters recount
+	const ONLINE_MINERS_RECOUNT_INTERVAL: u32 = 600;
+	/// The max number of miners to scan in a block for the online miner counters recount
+	const ONLINE_MINERS_RECOUNT_CHUNK: u32 = 64;
+	/// The max number of heartbeats kept in the history of a miner
+	const MAX_HEARTBEAT_HISTORY: usize = 16;
+	/// The time (in sec) to keep a heartbeat in the history
+	const HEARTBEAT_HISTORY_TTL_SEC: u64 = 24 * 3600;
+	/// The number of blocks to keep the emission time of a heartbeat challenge for the clock skew
+	/// detection
+	const CHALLENGE_TIME_TTL_BLOCKS: u32 = 600;
+	/// The max number of heartbeat histories to scan in a block for the pruning
+	const HEARTBEAT_HISTORY_PRUNE_CHUNK: u32 = 64;
+	/// The max number of settlements kept in the history of a miner
+	const MAX_SETTLEMENT_HISTORY: usize = 256;
+	/// The time (in sec) to keep a settlement in the history
+	const SETTLEMENT_HISTORY_TTL_SEC: u64 = 90 * 24 * 3600;
+	/// The number of miners in a page of `miners_by_state`
+	const MINERS_BY_STATE_PAGE_SIZE: usize = 256;
+	/// The max number of operations scheduled at a single block
+	const MAX_SCHEDULED_OPS_PER_BLOCK: usize = 32;
+	/// The max number of blocks to look ahead for a free slot when scheduling an internal operation
+	const MAX_SCHEDULE_LOOKAHEAD: u32 = 16;
+	/// The max number of workers in a targeted heartbeat challenge
+	const MAX_HEARTBEAT_TARGETS: usize = 256;
+	/// The max number of mining updates waiting for the gatekeeper quorum
+	const MAX_PENDING_MINING_UPDATES: usize = 64;
+	/// The max number of blocks to hold the mining updates after a runtime upgrade, in case the
+	/// migrations never complete
+	const UPGRADE_PAUSE_MAX_BLOCKS: u32 = 10;
+	/// The max number of gatekeeper telemetry records kept in `GatekeeperTelemetryLog`
+	const MAX_GATEKEEPER_TELEMETRY: usize = 64;
+	/// The cap of the liveness score in `MinerReputation`
+	const MAX_LIVENESS_SCORE: u32 = 10_000;
+	/// The min dust of a miner in the smallest unit of the balance to flush to `FlushedDust`
+	const DUST_FLUSH_THRESHOLD: u128 = 1_000;
+	/// The multiple of the equal budget share a miner can be paid in a session, see `payout_cap`
+	const PAYOUT_CAP_SHARE_MULTIPLIER: u32 = 10;
+	/// The max number of the chunks in `LockedRewards` of a beneficiary
+	const MAX_LOCKED_REWARDS: usize = 64;
+	/// The max number of tiers in `UptimeStreakBonuses`
+	const MAX_STREAK_BONUS_TIERS: usize = 8;
+	/// The max extra payout ratio of an uptime streak
+	const MAX_STREAK_BONUS: Permill = Permill::from_percent(20);
+	/// The period (in sec) the effective slash rates are annualized to
+	const SLASH_RATE_PERIOD_SEC: u64 = 365 * 24 * 3600;
+	/// The max number of workers with settlements buffered during a tokenomic freeze
+	const MAX_FROZEN_SETTLEMENTS: usize = 4096;
+	/// The estimated weight to apply a settlement, besides the storage access
+	const SETTLEMENT_BASE_WEIGHT: Weight = 10_000_000;
+	/// The period (in sec) to release the daily drip of a smoothed payout
+	const PAYOUT_DRIP_PERIOD_SEC: u64 = 24 * 3600;
+	/// The era (in sec) of the `pha_rate` update from the price feed
+	const PHA_RATE_UPDATE_PERIOD_SEC: u64 = 24 * 3600;
+	/// A gap between two blocks longer than this number of expected block times is regarded as a
+	/// chain downtime
+	const CHAIN_DOWNTIME_MIN_BLOCKS: u64 = 10;
+	/// The child trie storing the worker-miner bindings of the default instance, keyed by the
+	/// encoded worker pubkey, see `bindings_child_info`
+	const BINDINGS_CHILD_KEY: &[u8] = b"phala/mining/bindings";
 
-	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	/// The lifecycle state of a miner
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 	pub enum MinerState {
+		/// Bound to a worker, but not mining
 		Ready,
+		/// Mining and responsive to the heartbeat challenges
 		MiningIdle,
+		/// Reserved, never entered
 		MiningActive,
+		/// Mining, but missed the recent heartbeat challenges
 		MiningUnresponsive,
+		/// Stopped mining, waiting for the stake to be released
 		MiningCoolingDown,
 	}
 
 	impl MinerState {
+		/// Whether the miner is counted in `online_miners`
+		pub fn is_online(&self) -> bool {
+			matches!(
+				self,
+				MinerState::MiningIdle | MinerState::MiningActive | MinerState::MiningUnresponsive
+			)
+		}
 		fn can_unbind(&self) -> bool {
 			matches!(self, MinerState::Ready | MinerState::MiningCoolingDown)
 		}
+		/// The settlements of the miners cooling down are left to `WrongStateSettlePolicy`
 		fn can_settle(&self) -> bool {
 			matches!(
 				self,
-				MinerState::MiningIdle
-				| MinerState::MiningActive
-				| MinerState::MiningCoolingDown  // TODO: allowed?
-				| MinerState::MiningUnresponsive // TODO: allowed?
+				MinerState::MiningIdle | MinerState::MiningActive | MinerState::MiningUnresponsive
 			)
 		}
 	}
 
-	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	/// The numbers of the miners in each online state, see `OnlineMinersRecount`
+	#[derive(Encode, Decode, TypeInfo, Clone, Copy, PartialEq, Eq, Default, RuntimeDebug)]
+	pub struct OnlineMinerCounts {
+		pub idle: u32,
+		pub active: u32,
+		pub unresponsive: u32,
+	}
+
+	impl OnlineMinerCounts {
+		/// Reads `IdleMiners`, `ActiveMiners` and `UnresponsiveMiners`
+		fn current<T: Config<I>, I: 'static>() -> Self {
+			OnlineMinerCounts {
+				idle: IdleMiners::<T, I>::get(),
+				active: ActiveMiners::<T, I>::get(),
+				unresponsive: UnresponsiveMiners::<T, I>::get(),
+			}
+		}
+		fn total(&self) -> u32 {
+			self.idle
+				.saturating_add(self.active)
+				.saturating_add(self.unresponsive)
+		}
+		/// Counts a miner entering or leaving `state`, if it's an online state
+		fn update(&mut self, state: &MinerState, enter: bool) {
+			let counter = match state {
+				MinerState::MiningIdle => &mut self.idle,
+				MinerState::MiningActive => &mut self.active,
+				MinerState::MiningUnresponsive => &mut self.unresponsive,
+				_ => return,
+			};
+			*counter = if enter {
+				counter.saturating_add(1)
+			} else {
+				counter.saturating_sub(1)
+			};
+		}
+	}
+
+	/// How the settlements of the miners not in a settleable state (i.e. cooling down, or `Ready`
+	/// after the reclaim) are handled, see `MinerState::can_settle`
+	#[derive(Encode, Decode, TypeInfo, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub enum WrongStateSettlePolicy {
+		/// Skipped with a `SettleIgnoredWrongState` event
+		Ignore,
+		/// Buffered in `PendingSettlements` while the miner is cooling down, and applied to the
+		/// session at the reclaim. The settlements after the reclaim are skipped as by `Ignore`.
+		Buffer,
+	}
+
+	impl Default for WrongStateSettlePolicy {
+		/// The gatekeeper settles the V of the session till the stop, which often arrives in the
+		/// cool down
+		fn default() -> Self {
+			WrongStateSettlePolicy::Buffer
+		}
+	}
+
+	/// The performance of a miner measured by its heartbeats
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
 	pub struct Benchmark {
+		/// The instant performance score, normalized to the iterations in 6 seconds
 		p_instant: u32,
+		/// The accumulated iterations reported by the last heartbeat
 		iterations: u64,
+		/// The time (in secs) the current mining session started
 		mining_start_time: u64,
+		/// The time (in secs) of the last heartbeat
 		updated_at: u64,
 	}
 
 	impl Benchmark {
 		/// Records the latest benchmark status snapshot and updates `p_instant`
+		///
+		/// If `allow_reset`, a non-increasing `iterations` restarts the baseline of the
+		/// iterations, keeping the last `p_instant`.
 		fn update(
 			&mut self,
 			updated_at: u64,
 			iterations: u64,
 			initial_score: u32,
+			allow_reset: bool,
 		) -> Result<(), ()> {
-			if updated_at <= self.updated_at || iterations <= self.iterations {
+			if updated_at <= self.updated_at {
 				return Err(());
 			}
+			if iterations <= self.iterations {
+				if !allow_reset {
+					return Err(());
+				}
+				self.updated_at = updated_at;
+				self.iterations = iterations;
+				return Ok(());
+			}
 			let delta_iter = iterations - self.iterations;
 			let delta_ts = updated_at - self.updated_at;
 			self.updated_at = updated_at;
@@ -88,640 +273,6234 @@ pub mod pallet {
 		}
 	}
 
-	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	/// The mining status of a miner account
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
 	pub struct MinerInfo {
 		pub state: MinerState,
-		/// The intiial V, in U64F64 bits
-		pub ve: u128,
-		/// The last updated V, in U64F64 bits
-		pub v: u128,
+		/// The intiial V
+		pub ve: FixedBits,
+		/// The last updated V
+		pub v: FixedBits,
+		/// The time (in secs) V was last updated
 		v_updated_at: u64,
 		benchmark: Benchmark,
+		/// The time (in secs) the cool down period started
 		cool_down_start: u64,
 		stats: MinerStats,
+		/// The rolling average of the delay between a heartbeat challenge and the inclusion of
+		/// its response (in ms), measured from the challenge emission time in `ChallengeTimes`
+		heartbeat_latency: u64,
+		/// The last updated V in the balance scale, derived from `v` at each update so that all
+		/// the clients agree on the rounding
+		pub v_balance: u128,
+		/// The time (in secs) the current session started
+		session_start: u64,
+		/// The total payout of the current session in U64F64 bits, bounded by `payout_cap`
+		session_payout: u128,
 	}
 
-	pub trait OnReward {
-		fn on_reward(settle: &Vec<SettleInfo>) {}
+	impl MinerInfo {
+		/// Updates V together with its balance scaled value
+		fn set_v(&mut self, v: FixedBits) {
+			self.v_balance = FixedPointConvert::from_fixed(&v.to_fixed());
+			self.v = v;
+		}
+
+		/// Folds a heartbeat latency sample into the rolling average (weight of 1/8)
+		fn record_heartbeat_latency(&mut self, latency: u64) {
+			self.heartbeat_latency = if self.heartbeat_latency == 0 {
+				latency
+			} else {
+				self.heartbeat_latency - self.heartbeat_latency / 8 + latency / 8
+			};
+		}
 	}
 
-	pub trait OnUnbound {
-		/// Called wthen a worker was unbound from a miner.
-		///
-		/// `force` is set if the unbinding caused an unexpected miner shutdown.
-		fn on_unbound(worker: &WorkerPublicKey, force: bool) {}
+	/// The estimated outcome of a hypothetical mining period
+	///
+	/// All the fields are fixed point numbers in bits: `v_delta` in I64F64, and the rest in U64F64.
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug, Default)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub struct SettlementSimulation {
+		pub v_delta: i128,
+		pub payout_estimate: u128,
+		pub slash_estimate: u128,
 	}
 
-	pub trait OnReclaim<AccountId, Balance> {
-		/// Called when the miner has finished reclaiming and a given amount of the stake should be
-		/// returned
-		///
-		/// When called, it's not guaranteed there's still a worker associated to the miner.
-		fn on_reclaim(worker: &AccountId, orig_stake: Balance, slashed: Balance) {}
+	/// The condition blocking a miner from reclaiming its stake, see `why_cannot_reclaim`
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub enum ReclaimBlocker {
+		MinerNotFound,
+		/// The miner is not cooling down
+		NotCoolingDown(MinerState),
+		/// The seconds left in the cool down period
+		CoolDownRemaining(u64),
 	}
 
-	#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug)]
-	pub struct MinerStats {
-		total_reward: u128,
+	/// The condition blocking a miner from starting mining, see `why_cannot_start`
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub enum StartBlocker<Balance> {
+		MinerNotFound,
+		/// The miner is not in Ready state
+		NotReady(MinerState),
+		/// The seconds left before the worker can start a new session, see `MinSessionInterval`
+		SessionIntervalRemaining(u64),
+		WorkerNotRegistered,
+		/// The worker runtime is older than the enforced `MinWorkerVersion`
+		WorkerVersionTooOld,
+		/// The number of the online miners reached `MaxOnlineMiners`
+		NetworkAtCapacity,
+		BenchmarkMissing,
+		/// The benchmark is older than `BenchmarkValidityPeriod`
+		BenchmarkExpired,
+		/// The stake is lower than the minimal stake by the amount
+		InsufficientStake(Balance),
+		/// The stake is higher than the stake cap by the amount
+		StakeExceedsCap(Balance),
+		/// The initial V would exceed `v_max`
+		TooMuchStake,
 	}
 
-	impl MinerStats {
-		fn on_reward(&mut self, payout_bits: u128) {
-			let payout: u128 = FixedPointConvert::from_bits(payout_bits);
-			self.total_reward += payout;
-		}
+	/// The direction of a message queue topic seen from the mining pallet
+	#[derive(Encode, Decode, TypeInfo, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub enum TopicDirection {
+		Produced,
+		Consumed,
 	}
 
-	#[pallet::config]
-	pub trait Config: frame_system::Config + mq::Config + registry::Config {
-		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
-		type ExpectedBlockTimeSec: Get<u32>;
+	/// A message queue topic of the mining pallet, see `topics`
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub struct TopicInfo {
+		/// The topic of this instance, with `TopicPrefix` applied
+		pub topic: Vec<u8>,
+		pub direction: TopicDirection,
+		/// The name of the SCALE type of the payload in `phala_types::messaging`
+		pub type_name: Vec<u8>,
+		/// The latest accepted protocol version of the payload, 0 for the unversioned ones
+		pub version: u32,
+	}
 
-		type Currency: Currency<Self::AccountId>;
-		type Randomness: Randomness<Self::Hash, Self::BlockNumber>;
-		type OnReward: OnReward;
-		type OnUnbound: OnUnbound;
-		type OnReclaim: OnReclaim<Self::AccountId, BalanceOf<Self>>;
+	/// A record of a tokenomic parameters update
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct TokenomicChange<BlockNumber, Hash, AccountId> {
+		pub block: BlockNumber,
+		/// The hash of the replaced parameters, if any
+		pub old_params_hash: Option<Hash>,
+		pub new_params_hash: Hash,
+		/// The signed account proposed the change, or None if it's from root
+		pub proposer: Option<AccountId>,
 	}
 
-	#[pallet::pallet]
-	#[pallet::generate_store(pub(super) trait Store)]
-	pub struct Pallet<T>(_);
+	/// A snapshot of the mining state, exported by `export_mining_state` and restored by
+	/// `import_mining_state` to start a chain from the state of another one
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct MiningStateSnapshot<AccountId, Balance> {
+		pub miners: Vec<(AccountId, MinerInfo)>,
+		/// The miner-worker bindings
+		pub bindings: Vec<(AccountId, WorkerPublicKey)>,
+		pub stakes: Vec<(AccountId, Balance)>,
+		pub solo_miners: Vec<AccountId>,
+		pub online_miners: u32,
+		pub observing_miners: u32,
+		pub online_stake: Balance,
+		pub next_session_id: u32,
+		/// The cool down period (in sec)
+		pub cool_down_period: u64,
+		pub tokenomic_parameters: Option<TokenomicParams>,
+	}
 
-	/// Tokenomic parameters used by Gatekeepers to compute the V promote.
-	#[pallet::storage]
-	pub type TokenomicParameters<T> = StorageValue<_, TokenomicParams>;
+	/// The minimal pRuntime version of the workers to bind or to start mining
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct MinWorkerVersionConfig {
+		pub version: u32,
+		/// The time (in sec) the requirement is enforced from. The outdated workers are only
+		/// warned before it.
+		pub enforced_at: u64,
+	}
 
-	/// Total online miners
-	///
-	/// Increased when a miner is turned to MininIdle; decreased when turned to CoolingDown
-	#[pallet::storage]
-	#[pallet::getter(fn online_miners)]
-	pub type OnlineMiners<T> = StorageValue<_, u32, ValueQuery>;
+	/// A settlement made under a miner binding other than the on-chain one, held for the
+	/// governance to resolve
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct SettlementDispute<BlockNumber> {
+		pub info: SettleInfo,
+		/// The binding hash reported by the gatekeeper
+		pub binding_hash: Option<[u8; 32]>,
+		/// The block the gatekeeper settled at
+		pub block_number: BlockNumber,
+	}
 
-	/// The expected heartbeat count (default: 20)
-	#[pallet::storage]
-	pub type ExpectedHeartbeatCount<T> = StorageValue<_, u32>;
+	/// A telemetry report of a gatekeeper, as received on chain
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct GatekeeperTelemetryRecord<BlockNumber> {
+		pub gatekeeper: WorkerPublicKey,
+		/// The block the telemetry was received at
+		pub received_at: BlockNumber,
+		pub workers_tracked: u32,
+		pub events_emitted: u32,
+		/// The number of blocks the gatekeeper was behind the chain when sending the telemetry
+		pub processing_lag: u32,
+	}
 
-	/// The miner state.
-	///
-	/// The miner state is created when a miner is bounded with a worker, but it will be kept even
-	/// if the worker is force unbound. A re-bind of a worker will reset the mining state.
-	#[pallet::storage]
-	#[pallet::getter(fn miners)]
-	pub(super) type Miners<T: Config> = StorageMap<_, Twox64Concat, T::AccountId, MinerInfo>;
+	/// A settlement waiting in `SettlementQueue`
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct QueuedSettlement<BlockNumber> {
+		pub info: SettleInfo,
+		/// The gatekeeper block of the settlement if it's to be audited
+		pub block: Option<BlockNumber>,
+	}
 
-	/// The bound worker for a miner account
-	#[pallet::storage]
-	pub(super) type MinerBindings<T: Config> =
-		StorageMap<_, Twox64Concat, T::AccountId, WorkerPublicKey>;
+	/// The chain's own record of a miner since its last settlement, replayed by the settlement
+	/// audit
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct SettlementTrace {
+		/// The `SettlementAuditEpoch` the trace is recorded in
+		pub epoch: u32,
+		/// The gatekeeper block of the last settlement, or the block the mining started
+		pub since: u32,
+		/// The `p_instant` of the miner as of `since`, None before the first heartbeat
+		pub p_instant: Option<u32>,
+		/// Whether the miner was unresponsive as of `since`
+		pub unresponsive: bool,
+		/// The gatekeeper blocks the miner was reported offline (true) or recovered (false)
+		pub transitions: Vec<(u32, bool)>,
+	}
 
-	/// The bound miner account for a worker
-	#[pallet::storage]
-	pub(super) type WorkerBindings<T: Config> =
-		StorageMap<_, Twox64Concat, WorkerPublicKey, T::AccountId>;
+	/// A worker status change reported by the gatekeeper, waiting in `DeferredStatusUpdates`
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub enum StatusUpdate {
+		Offline(WorkerPublicKey),
+		Recovered(WorkerPublicKey),
+	}
 
-	/// The cool down period (in sec)
-	#[pallet::storage]
-	#[pallet::getter(fn cool_down_period)]
-	pub(super) type CoolDownPeriod<T> = StorageValue<_, u64, ValueQuery>;
+	/// A heartbeat received from the worker
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct HeartbeatRecord {
+		pub session_id: u32,
+		pub challenge_block: u32,
+		pub iterations: u64,
+	}
 
-	/// The next id to assign to a mining session
-	#[pallet::storage]
-	pub(super) type NextSessionId<T> = StorageValue<_, u32, ValueQuery>;
+	/// A payout or slash of a miner, indexed by the block for the accounting tools
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub struct SettlementRecord<Balance> {
+		/// The V after the settlement, in U64F64 bits
+		pub v: u128,
+		/// The payout before the lockup
+		pub payout: Balance,
+		/// The stake slashed when the miner is reclaimed
+		pub slashed: Balance,
+	}
 
-	/// The stakes of miner accounts.
-	///
-	/// Only presents for mining and cooling down miners.
-	#[pallet::storage]
-	#[pallet::getter(fn stakes)]
-	pub(super) type Stakes<T: Config> = StorageMap<_, Twox64Concat, T::AccountId, BalanceOf<T>>;
+	/// The vesting policy applied to the miner payouts
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct RewardLockupConfig {
+		/// The portion of each payout to lock
+		pub percentage: Permill,
+		/// The lock duration (in sec)
+		pub duration: u64,
+	}
 
-	#[pallet::event]
-	#[pallet::generate_deposit(pub(super) fn deposit_event)]
-	pub enum Event<T: Config> {
-		/// [period]
-		CoolDownExpirationChanged(u64),
-		/// [miner]
-		MinerStarted(T::AccountId),
-		/// [miner]
-		MinerStopped(T::AccountId),
-		/// [miner, original_stake, slashed]
-		MinerReclaimed(T::AccountId, BalanceOf<T>, BalanceOf<T>),
-		/// [miner, worker]
-		MinerBound(T::AccountId, WorkerPublicKey),
-		/// [miner, worker]
-		MinerUnbound(T::AccountId, WorkerPublicKey),
-		/// [miner]
-		MinerEnterUnresponsive(T::AccountId),
-		/// [miner]
-		MinerExitUnresponive(T::AccountId),
-		/// [miner, v, payout]
-		MinerSettled(T::AccountId, u128, u128),
-		/// [miner, amount]
-		_MinerStaked(T::AccountId, BalanceOf<T>),
-		/// [miner, amount]
-		_MinerWithdrew(T::AccountId, BalanceOf<T>),
+	/// The tip paid to the third party relayers of the heartbeats
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct RelayerTipConfig<Balance> {
+		/// The amount paid from the subsidy pool for each relayed heartbeat
+		pub amount: Balance,
+		/// The max number of tips paid in a block
+		pub max_per_block: u32,
 	}
 
-	#[pallet::error]
-	pub enum Error<T> {
-		BadSender,
-		InvalidMessage,
-		WorkerNotRegistered,
-		GatekeeperNotRegistered,
-		DuplicateBoundMiner,
-		BenchmarkMissing,
-		MinerNotFound,
-		MinerNotBound,
-		MinerNotReady,
-		MinerNotMining,
-		WorkerNotBound,
-		CoolDownNotReady,
-		InsufficientStake,
-		TooMuchStake,
+	/// The reputation tier of a miner
+	#[derive(Encode, Decode, TypeInfo, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, RuntimeDebug)]
+	pub enum MinerTier {
+		Bronze,
+		Silver,
+		Gold,
 	}
 
-	type BalanceOf<T> =
-		<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+	impl Default for MinerTier {
+		fn default() -> Self {
+			MinerTier::Bronze
+		}
+	}
 
-	#[pallet::call]
-	impl<T: Config> Pallet<T>
-	where
-		BalanceOf<T>: FixedPointConvert,
-	{
-		#[pallet::weight(0)]
-		pub fn set_cool_down_expiration(origin: OriginFor<T>, period: u64) -> DispatchResult {
-			ensure_root(origin)?;
+	/// The requirements and the perks of a reputation tier
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct TierRule {
+		/// The min liveness score to reach the tier
+		pub min_liveness: u32,
+		/// The min number of the mining sessions started to reach the tier
+		pub min_sessions: u32,
+		/// The multiplier of the slash rate applied by the gatekeeper
+		pub slash_multiplier: Permill,
+		/// The min number of blocks between two targeted heartbeat challenges of the worker
+		pub target_interval: u32,
+	}
 
-			CoolDownPeriod::<T>::mutate(|p| *p = period);
-			Self::deposit_event(Event::<T>::CoolDownExpirationChanged(period));
-			Ok(())
+	/// The rules of the reputation tiers. Bronze is the base tier without any perk.
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct ReputationTierConfig {
+		pub silver: TierRule,
+		pub gold: TierRule,
+	}
+
+	impl ReputationTierConfig {
+		/// Gets the highest tier the liveness score and the session history qualify for
+		pub fn tier_of(&self, liveness: u32, sessions: u32) -> MinerTier {
+			let qualified =
+				|rule: &TierRule| liveness >= rule.min_liveness && sessions >= rule.min_sessions;
+			if qualified(&self.gold) {
+				MinerTier::Gold
+			} else if qualified(&self.silver) {
+				MinerTier::Silver
+			} else {
+				MinerTier::Bronze
+			}
 		}
 
-		/// Unbinds a worker from the given miner (or pool sub-account).
-		///
-		/// It will trigger a force stop of mining if the miner is still in mining state.
-		#[pallet::weight(0)]
-		pub fn unbind(origin: OriginFor<T>, miner: T::AccountId) -> DispatchResult {
-			let who = ensure_signed(origin)?;
-			let pubkey = Self::ensure_miner_bound(&miner)?;
-			let worker =
-				registry::Workers::<T>::get(&pubkey).ok_or(Error::<T>::WorkerNotRegistered)?;
-			ensure!(worker.operator == Some(who), Error::<T>::BadSender);
-			// Always notify the subscriber. Please note that even if the miner is not mining, we
-			// still have to notify the subscriber that an unbinding operation has just happened.
-			Self::unbind_miner(&miner, true)
+		/// Gets the rule of the tier, or None for Bronze
+		pub fn rule(&self, tier: MinerTier) -> Option<&TierRule> {
+			match tier {
+				MinerTier::Bronze => None,
+				MinerTier::Silver => Some(&self.silver),
+				MinerTier::Gold => Some(&self.gold),
+			}
 		}
 
-		/// Turns the miner back to Ready state after cooling down and trigger stake releasing.
-		///
-		/// Note: anyone can trigger cleanup
-		/// Requires:
-		/// 1. Ther miner is in CoolingDown state and the cool down period has passed
-		#[pallet::weight(0)]
-		pub fn reclaim(origin: OriginFor<T>, miner: T::AccountId) -> DispatchResult {
-			ensure_signed(origin)?;
-			let mut miner_info = Miners::<T>::get(&miner).ok_or(Error::<T>::MinerNotFound)?;
-			ensure!(Self::can_reclaim(&miner_info), Error::<T>::CoolDownNotReady);
-			miner_info.state = MinerState::Ready;
-			miner_info.cool_down_start = 0u64;
-			Miners::<T>::insert(&miner, &miner_info);
+		/// Gets the slash rate multiplier of the tier
+		pub fn slash_multiplier(&self, tier: MinerTier) -> Permill {
+			self.rule(tier)
+				.map(|rule| rule.slash_multiplier)
+				.unwrap_or_else(Permill::one)
+		}
+	}
 
-			// Calcualte remaining stake
-			let v = FixedPoint::from_bits(miner_info.v);
-			let ve = FixedPoint::from_bits(miner_info.ve);
-			let return_rate = (v / ve).min(FixedPoint::from_num(1));
-			let orig_stake = Stakes::<T>::take(&miner).unwrap_or_default();
-			// If we consider kappa as a panelty of frequent exit:
-			// 	let tokenomic = Self::tokenomic();
-			// 	let returned = return_rate * orig_stake.to_fixed() * tokenomic.kappa();
-			let returned = return_rate * orig_stake.to_fixed();
-			// Convert to Balance
-			let returned = FixedPointConvert::from_fixed(&returned);
-			let slashed = orig_stake - returned;
+	/// The reputation of a miner
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+	pub struct MinerReputation {
+		pub tier: MinerTier,
+		/// Raised by one for each accepted heartbeat up to `MAX_LIVENESS_SCORE`, and halved each
+		/// time the miner goes unresponsive
+		pub liveness: u32,
+	}
 
-			T::OnReclaim::on_reclaim(&miner, orig_stake, slashed);
-			Self::deposit_event(Event::<T>::MinerReclaimed(miner, orig_stake, slashed));
-			Ok(())
-		}
+	/// An uptime streak threshold and the payout bonus of the miners reaching it
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct StreakBonusTier {
+		/// The min number of the consecutive settlements without going unresponsive
+		pub min_streak: u32,
+		/// The extra payout ratio
+		pub bonus: Permill,
+	}
 
-		/// Triggers a force heartbeat request to all workers by sending a MAX pow target
-		///
-		/// Only for integration test.
-		#[pallet::weight(1)]
-		pub fn force_heartbeat(origin: OriginFor<T>) -> DispatchResult {
-			ensure_root(origin)?;
-			Self::push_message(SystemEvent::HeartbeatChallenge(HeartbeatChallenge {
-				seed: U256::zero(),
-				online_target: U256::MAX,
-			}));
-			Ok(())
-		}
+	/// A compact summary of the mining state, updated in every block for the light clients
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+	pub struct MiningDigestInfo<BlockNumber, Balance> {
+		/// The block the digest was updated at
+		pub block: BlockNumber,
+		/// The block of the last applied settlements
+		pub settled_at: BlockNumber,
+		/// The number of the miners settled in `settled_at`
+		pub settled_miners: u32,
+		/// The total payout of the settlements in `settled_at`
+		pub settled_payout: Balance,
+		/// The total decrease of V of the settlements in `settled_at`, in the balance scale
+		pub settled_v_slash: Balance,
+		pub online_miners: u32,
+		pub idle_miners: u32,
+		pub active_miners: u32,
+		pub unresponsive_miners: u32,
+		/// The block of the last heartbeat challenge
+		pub challenged_at: BlockNumber,
+		/// The online target of the last heartbeat challenge
+		pub challenge_target: U256,
+	}
 
-		/// Start mining
-		///
-		/// Only for integration test.
-		#[pallet::weight(1)]
-		pub fn force_start_mining(
-			origin: OriginFor<T>,
-			miner: T::AccountId,
-			stake: BalanceOf<T>,
-		) -> DispatchResult {
-			ensure_root(origin)?;
-			Self::start_mining(miner, stake)?;
-			Ok(())
+	/// The personal bond of a worker operator, slashed before the stake of the miner
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct OperatorBond<AccountId, Balance> {
+		pub operator: AccountId,
+		pub bonded: Balance,
+		/// The amount being unbonded, still slashable until withdrawn
+		pub unbonding: Balance,
+		/// The time (in sec) the unbonding amount can be withdrawn
+		pub unlock_at: u64,
+	}
+
+	/// The trial mining policy for the new workers
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct TrialMiningConfig<BlockNumber> {
+		/// The number of blocks a trial miner must stay responsive before upgrading to normal
+		/// mining
+		pub duration: BlockNumber,
+		/// The minimal stake of a trial, relative to the normal minimal stake
+		pub stake_ratio: Permill,
+		/// The cap of the initial V of a trial (U64F64 bits)
+		pub ve_cap: u128,
+		/// The cap of the payout of each settlement during a trial (U64F64 bits)
+		pub payout_cap: u128,
+	}
+
+	/// The policy to pay a part of the rewards in a secondary asset
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct SecondaryPayoutConfig {
+		/// The asset id in `Config::SecondaryAssets`
+		pub asset: u32,
+		/// The portion of each payout to pay in the asset
+		pub ratio: Permill,
+		/// The asset amount (in the smallest unit) paid for each PHA unit, in U64F64 bits
+		pub exchange_rate: u128,
+	}
+
+	/// The per-block tokenomic parameters in block time independent units
+	///
+	/// Converted to the per-block `rho`, `slash_rate` and `heartbeat_window` with
+	/// `ExpectedBlockTimeSec` when applied, so that a different block time doesn't skew the
+	/// tokenomic. The rates are converted linearly, which is accurate enough for the small rates.
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct TokenomicHourlyRates {
+		/// The hourly V increment rate (U64F64 bits), e.g. 1.0002
+		pub rho: u128,
+		/// The hourly V slash rate when offline (U64F64 bits), e.g. 0.001
+		pub slash_rate: u128,
+		/// The heartbeat window (in sec)
+		pub heartbeat_window_sec: u32,
+	}
+
+	impl TokenomicHourlyRates {
+		/// Overwrites the per-block parameters in `params` with the rates
+		pub fn apply(&self, params: &mut TokenomicParams, block_time_sec: u32) {
+			let one = FixedPoint::from_num(1);
+			let blocks_per_hour = FixedPoint::from_num(3600) / Self::block_time(block_time_sec);
+			let rho = FixedPoint::from_bits(self.rho).saturating_sub(one);
+			let slash_rate = FixedPoint::from_bits(self.slash_rate);
+			params.rho = (one + rho / blocks_per_hour).to_bits();
+			params.slash_rate = (slash_rate / blocks_per_hour).to_bits();
+			let block_time_sec = block_time_sec.max(1);
+			params.heartbeat_window =
+				(self.heartbeat_window_sec + block_time_sec - 1) / block_time_sec;
 		}
 
-		/// Stop mining
-		///
-		/// Only for integration test.
-		#[pallet::weight(1)]
-		pub fn force_stop_mining(origin: OriginFor<T>, miner: T::AccountId) -> DispatchResult {
-			ensure_root(origin)?;
-			Self::stop_mining(miner)?;
-			Ok(())
+		/// Reads the rates from the per-block parameters in `params`
+		pub fn from_params(params: &TokenomicParams, block_time_sec: u32) -> Self {
+			let one = FixedPoint::from_num(1);
+			let blocks_per_hour = FixedPoint::from_num(3600) / Self::block_time(block_time_sec);
+			let rho = FixedPoint::from_bits(params.rho).saturating_sub(one);
+			let slash_rate = FixedPoint::from_bits(params.slash_rate);
+			TokenomicHourlyRates {
+				rho: (one + rho * blocks_per_hour).to_bits(),
+				slash_rate: (slash_rate * blocks_per_hour).to_bits(),
+				heartbeat_window_sec: params
+					.heartbeat_window
+					.saturating_mul(block_time_sec.max(1)),
+			}
 		}
 
-		/// Updates the tokenomic parameters
-		#[pallet::weight(1)]
-		pub fn update_tokenomic(
-			origin: OriginFor<T>,
-			new_params: TokenomicParams,
-		) -> DispatchResult {
-			ensure_root(origin)?;
-			Self::update_tokenomic_parameters(new_params);
-			Ok(())
+		fn block_time(block_time_sec: u32) -> FixedPoint {
+			FixedPoint::from_num(block_time_sec.max(1))
 		}
 	}
 
-	#[pallet::hooks]
-	impl<T: Config> Hooks<T::BlockNumber> for Pallet<T>
-	where
-		BalanceOf<T>: FixedPointConvert,
-	{
-		fn on_finalize(_n: T::BlockNumber) {
-			Self::heartbeat_challenge();
-		}
+	/// An operation delayed to a future block, dispatched in `on_initialize`
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub enum ScheduledOp<AccountId> {
+		/// Replaces the tokenomic parameters
+		UpdateTokenomic(TokenomicParams),
+		/// Reclaims a miner at the end of its cool down
+		Reclaim(AccountId),
 	}
 
-	// - Properly handle heartbeat message.
-	impl<T: Config> Pallet<T>
-	where
-		BalanceOf<T>: FixedPointConvert,
-	{
-		pub fn account_id() -> T::AccountId {
-			MINING_PALLETID.into_account()
-		}
+	/// A chunk of the payout locked until `unlock_at`
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct LockedReward<Balance> {
+		pub amount: Balance,
+		/// The unlock time (in sec)
+		pub unlock_at: u64,
+	}
 
-		fn heartbeat_challenge() {
-			// Random seed for the heartbeat challenge
-			let seed_hash = T::Randomness::random(crate::constants::RANDOMNESS_SUBJECT).0;
-			let seed: U256 = AsRef::<[u8]>::as_ref(&seed_hash).into();
-			// PoW target for the random sampling
-			let online_miners = OnlineMiners::<T>::get();
-			let num_tx =
-				ExpectedHeartbeatCount::<T>::get().unwrap_or(DEFAULT_EXPECTED_HEARTBEAT_COUNT);
-			let online_target = pow_target(num_tx, online_miners, T::ExpectedBlockTimeSec::get());
-			let seed_info = HeartbeatChallenge {
-				seed,
-				online_target,
-			};
-			Self::push_message(SystemEvent::HeartbeatChallenge(seed_info));
+	/// The owner of the locked rewards
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub enum RewardBeneficiary<AccountId> {
+		/// A solo miner, or the refund beneficiary of a miner in no pool
+		Owner(AccountId),
+		/// A stake pool, see `StakePoolRouting`
+		Pool(u64),
+	}
+
+	/// Where the settlement payouts go, switched by the governance in emergencies
+	#[derive(Encode, Decode, TypeInfo, Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub enum RewardSink {
+		/// Dispatched to the solo miners and the `LifecycleObserver` (e.g. the stake pools)
+		Normal,
+		/// Parked in the pallet until drained by the governance with `drain_parked_rewards`
+		ParkInPallet,
+		/// Burnt from the subsidy pool
+		Burn,
+	}
+
+	impl Default for RewardSink {
+		fn default() -> Self {
+			RewardSink::Normal
 		}
+	}
 
-		pub fn on_mining_message_received(
-			message: DecodedMessage<MiningReportEvent>,
-		) -> DispatchResult {
-			if let MessageOrigin::Worker(worker) = message.sender {
-				match message.payload {
-					MiningReportEvent::Heartbeat { iterations, .. } => {
-						// Handle with great care!
-						//
-						// In some cases, a message can be delayed, but the worker has been already
-						// unbound or removed (possible?). So when we receive a message, don't
-						// assume the worker is always there and the miner state is complete. So
-						// far it sounds safe to just discard this message, but not interrupt the
-						// entire message queue.
-						//
-						// So we call `ensure_worker_bound` here, and return an error if the worker
-						// is not bound. However if the worker is indeed bound, the rest of the
-						// code assumes the Miners, Workers, and worker score must exist.
-						let miner = Self::ensure_worker_bound(&worker)?;
-						let mut miner_info = Self::miners(&miner).expect("Bound miner; qed.");
-						let worker =
-							registry::Workers::<T>::get(&worker).expect("Bound worker; qed.");
-						let initial_score = worker
-							.initial_score
-							.expect("Mining worker has benchmark; qed.");
-						let now = Self::now_sec();
-						miner_info
-							.benchmark
-							.update(now, iterations, initial_score)
-							.expect("Benchmark report must be valid; qed.");
-						Miners::<T>::insert(&miner, miner_info);
-					}
-				};
-			}
-			Ok(())
-		}
+	/// The stake cap `k * p + b` of a miner with the performance score `p`, in U64F64 bits
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+	pub struct StakeCapParams {
+		pub k: u128,
+		pub b: u128,
+	}
 
-		pub fn on_gk_message_received(
-			message: DecodedMessage<MiningInfoUpdateEvent<T::BlockNumber>>,
-		) -> DispatchResult {
-			if !matches!(message.sender, MessageOrigin::Gatekeeper) {
-				return Err(Error::<T>::BadSender.into());
-			}
+	/// The payouts of a miner parked by `RewardSink::ParkInPallet`
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct ParkedReward<Balance> {
+		/// The worker of the last parked payout
+		pub worker: WorkerPublicKey,
+		pub amount: Balance,
+	}
 
-			let event = message.payload;
-			if !event.is_empty() {
-				let now = Self::now_sec();
+	/// The payouts of a miner held back by the payout smoothing
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug, Default)]
+	pub struct PayoutReserve<Balance> {
+		pub amount: Balance,
+		/// The last time (in sec) the drip was released
+		pub released_at: u64,
+	}
 
-				// worker offline, update bound miner state to unresponsive
-				for worker in event.offline {
-					if let Some(account) = WorkerBindings::<T>::get(&worker) {
-						let mut miner_info =
-							Self::miners(&account).ok_or(Error::<T>::MinerNotFound)?;
-						miner_info.state = MinerState::MiningUnresponsive;
-						Miners::<T>::insert(&account, &miner_info);
-						Self::deposit_event(Event::<T>::MinerEnterUnresponsive(account));
-					}
-				}
+	/// A mining lifecycle event delivered to the `MiningLifecycleObserver`
+	pub enum MiningLifecycleEvent<'a, AccountId, Balance> {
+		/// A miner was bound to a worker
+		Bound {
+			miner: &'a AccountId,
+			worker: &'a WorkerPublicKey,
+		},
+		/// A miner started mining with the given stake
+		Started {
+			miner: &'a AccountId,
+			worker: &'a WorkerPublicKey,
+			stake: Balance,
+		},
+		/// A miner entered CoolingDown state
+		Stopped {
+			miner: &'a AccountId,
+			worker: &'a WorkerPublicKey,
+		},
+		/// A miner was settled by the gatekeeper
+		Settled {
+			miner: &'a AccountId,
+			settle: &'a SettleInfo,
+		},
+		/// A miner got a part of its stake slashed when reclaiming
+		Slashed {
+			miner: &'a AccountId,
+			slashed: Balance,
+		},
+		/// A miner has finished reclaiming and the remaining stake should be returned
+		///
+		/// When delivered, it's not guaranteed there's still a worker associated to the miner.
+		Reclaimed {
+			miner: &'a AccountId,
+			orig_stake: Balance,
+			slashed: Balance,
+			/// The refund beneficiary recorded when the miner started mining
+			beneficiary: Option<&'a AccountId>,
+		},
+		/// A mining miner was moved from a worker to a replacement worker
+		WorkerSwapped {
+			miner: &'a AccountId,
+			from: &'a WorkerPublicKey,
+			to: &'a WorkerPublicKey,
+		},
+		/// The worker bound to a miner rotated its identity key from `from` to `to`
+		WorkerKeyRotated {
+			miner: &'a AccountId,
+			from: &'a WorkerPublicKey,
+			to: &'a WorkerPublicKey,
+		},
+		/// A worker was unbound from a miner
+		///
+		/// `force` is set if the unbinding caused an unexpected miner shutdown.
+		Unbound {
+			miner: &'a AccountId,
+			worker: &'a WorkerPublicKey,
+			force: bool,
+		},
+	}
 
-				// worker recovered to online, update bound miner state to idle
-				for worker in event.recovered_to_online {
-					if let Some(account) = WorkerBindings::<T>::get(&worker) {
-						let mut miner_info =
-							Self::miners(&account).ok_or(Error::<T>::MinerNotFound)?;
-						miner_info.state = MinerState::MiningIdle;
-						Miners::<T>::insert(&account, &miner_info);
-						Self::deposit_event(Event::<T>::MinerExitUnresponive(account));
-					}
-				}
+	/// The registration of a worker used by the mining pallet
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct MiningWorkerInfo<AccountId> {
+		pub operator: Option<AccountId>,
+		pub confidence_level: u8,
+		/// The initial benchmark score, or None if the benchmark hasn't finished
+		pub initial_score: Option<u32>,
+		/// The pRuntime version reported in the last registration
+		pub runtime_version: u32,
+		/// The time (in seconds) of the last benchmark, or None if unknown
+		pub benchmarked_at: Option<u64>,
+	}
 
-				for info in &event.settle {
-					if let Some(account) = WorkerBindings::<T>::get(&info.pubkey) {
-						let mut miner_info =
-							Self::miners(&account).ok_or(Error::<T>::MinerNotFound)?;
-						debug_assert!(miner_info.state.can_settle(), "Miner cannot settle now");
-						miner_info.v = info.v; // in bits
-						miner_info.v_updated_at = now;
-						miner_info.stats.on_reward(info.payout);
-						Miners::<T>::insert(&account, &miner_info);
-						Self::deposit_event(Event::<T>::MinerSettled(account, info.v, info.payout));
-					}
-				}
+	/// A source of the registered workers
+	///
+	/// Implemented by the registry pallet. The runtimes and the tests without the registry can
+	/// supply the workers directly.
+	pub trait WorkerInfoProvider<AccountId> {
+		/// Returns the worker, or None if it's not registered
+		fn worker_info(pubkey: &WorkerPublicKey) -> Option<MiningWorkerInfo<AccountId>>;
+		/// Checks if `who` is the operator of the worker, or the delegate of the operator
+		fn is_operator_or_delegate(worker: &MiningWorkerInfo<AccountId>, who: &AccountId) -> bool;
+	}
 
-				T::OnReward::on_reward(&event.settle);
-			}
+	/// A source of the PHA price (e.g. an oracle pallet) to update `pha_rate`
+	pub trait PriceFeed {
+		/// Returns the latest PHA price in USD, in U64F64 bits
+		fn pha_price() -> Option<u128>;
+	}
 
-			Ok(())
+	impl PriceFeed for () {
+		fn pha_price() -> Option<u128> {
+			None
 		}
+	}
 
-		fn can_reclaim(miner_info: &MinerInfo) -> bool {
-			if miner_info.state != MinerState::MiningCoolingDown {
-				return false;
-			}
-			let now = Self::now_sec();
-			now - miner_info.cool_down_start >= Self::cool_down_period()
-		}
+	/// The assets to pay the `SecondaryPayout` with
+	///
+	/// Usually backed by a `fungibles` implementation (e.g. pallet-assets) through
+	/// `FungiblesPayout`.
+	pub trait SecondaryAssets<AccountId> {
+		/// Returns the balance of `asset` held by `who`
+		fn balance(asset: u32, who: &AccountId) -> u128;
+		/// Transfers `amount` of `asset` from `source` to `dest`
+		fn transfer(
+			asset: u32,
+			source: &AccountId,
+			dest: &AccountId,
+			amount: u128,
+		) -> DispatchResult;
+	}
 
-		/// Binds a miner to a worker
-		///
-		/// This will bind the miner account to the worker, and then create a `Miners` entry to
-		/// track the mining session in the future. The mining session will exist until ther miner
-		/// and the worker is unbound.
-		///
-		/// Requires:
-		/// 1. The worker is alerady registered
-		/// 2. The worker has an initial benchmark
-		/// 3. Both the worker and the miner are not bound
-		pub fn bind(miner: T::AccountId, pubkey: WorkerPublicKey) -> DispatchResult {
-			let worker =
-				registry::Workers::<T>::get(&pubkey).ok_or(Error::<T>::WorkerNotRegistered)?;
-			// Check the worker has finished the benchmark
-			ensure!(worker.initial_score != None, Error::<T>::BenchmarkMissing);
-			// Check miner and worker not bound
-			ensure!(
-				Self::ensure_miner_bound(&miner).is_err(),
-				Error::<T>::DuplicateBoundMiner
-			);
-			ensure!(
-				Self::ensure_worker_bound(&pubkey).is_err(),
-				Error::<T>::DuplicateBoundMiner
-			);
+	impl<AccountId> SecondaryAssets<AccountId> for () {
+		fn balance(_asset: u32, _who: &AccountId) -> u128 {
+			0
+		}
+		fn transfer(
+			_asset: u32,
+			_source: &AccountId,
+			_dest: &AccountId,
+			_amount: u128,
+		) -> DispatchResult {
+			Err(DispatchError::Other("No secondary assets"))
+		}
+	}
 
-			let now = Self::now_sec();
-			MinerBindings::<T>::insert(&miner, &pubkey);
-			WorkerBindings::<T>::insert(&pubkey, &miner);
-			Miners::<T>::insert(
-				&miner,
-				MinerInfo {
-					state: MinerState::Ready,
-					ve: 0,
-					v: 0,
-					v_updated_at: now,
-					benchmark: Benchmark {
-						p_instant: 0u32,
-						iterations: 0u64,
-						mining_start_time: now,
-						updated_at: 0u64,
-					},
-					cool_down_start: 0u64,
-					stats: Default::default(),
-				},
-			);
+	/// Pays the `SecondaryPayout` with the `fungibles` implementation `F`
+	pub struct FungiblesPayout<F>(PhantomData<F>);
 
-			Self::deposit_event(Event::<T>::MinerBound(miner, pubkey));
-			Ok(())
+	impl<AccountId, F> SecondaryAssets<AccountId> for FungiblesPayout<F>
+	where
+		F: fungibles::Transfer<AccountId>,
+		F::AssetId: From<u32>,
+	{
+		fn balance(asset: u32, who: &AccountId) -> u128 {
+			F::balance(asset.into(), who).saturated_into()
+		}
+		fn transfer(
+			asset: u32,
+			source: &AccountId,
+			dest: &AccountId,
+			amount: u128,
+		) -> DispatchResult {
+			F::transfer(asset.into(), source, dest, amount.saturated_into(), true).map(|_| ())
 		}
+	}
 
-		/// Unbinds a miner from a worker
-		///
-		/// - `notify`: whether to notify the subscribe the unbinding event.
-		///
-		/// Requires:
-		/// 1. The miner is bounded with a worker
-		pub fn unbind_miner(miner: &T::AccountId, notify: bool) -> DispatchResult {
-			let worker = Self::ensure_miner_bound(miner)?;
-			let miner_info = Miners::<T>::get(miner)
-				.expect("A bounded miner must has the associated MinerInfo; qed.");
+	/// A reproducible `Config::Randomness` for tests and dev chains
+	///
+	/// The output only depends on the subject, the block number and the base seed, so the
+	/// heartbeat challenges of each block can be predicted exactly. Enabled by the
+	/// `deterministic-seed` feature.
+	#[cfg(any(test, feature = "deterministic-seed"))]
+	pub struct DeterministicRandomness<T>(PhantomData<T>);
 
-			let force = !miner_info.state.can_unbind();
-			if force {
-				// Force unbinding. Stop the miner first.
-				Self::stop_mining(miner.clone())?;
-				// TODO: consider the final state sync (could cause slash) when stopping mining
-			}
-			MinerBindings::<T>::remove(miner);
-			WorkerBindings::<T>::remove(&worker);
-			Self::deposit_event(Event::<T>::MinerUnbound(miner.clone(), worker.clone()));
-			if notify {
-				T::OnUnbound::on_unbound(&worker, force);
-			}
+	#[cfg(any(test, feature = "deterministic-seed"))]
+	impl<T: frame_system::Config> DeterministicRandomness<T> {
+		/// The raw storage key of the base seed, which can also be set by `system.setStorage`
+		pub const BASE_SEED_KEY: &'static [u8] = b":phala:deterministic_seed:";
 
-			Ok(())
+		/// Sets the base seed (0 by default)
+		pub fn set_base_seed(seed: u64) {
+			frame_support::storage::unhashed::put(Self::BASE_SEED_KEY, &seed);
 		}
 
-		/// Starts mining with the given `stake`, assuming the stake is already locked externally
-		pub fn start_mining(miner: T::AccountId, stake: BalanceOf<T>) -> DispatchResult {
-			let worker = MinerBindings::<T>::get(&miner).ok_or(Error::<T>::MinerNotFound)?;
+		/// Returns the output for `subject` at `block_number`
+		pub fn random_at(subject: &[u8], block_number: T::BlockNumber) -> T::Hash {
+			let base_seed: u64 =
+				frame_support::storage::unhashed::get_or_default(Self::BASE_SEED_KEY);
+			T::Hashing::hash_of(&(subject, block_number, base_seed))
+		}
+	}
 
-			ensure!(
-				Miners::<T>::get(&miner).unwrap().state == MinerState::Ready,
-				Error::<T>::MinerNotReady
-			);
+	#[cfg(any(test, feature = "deterministic-seed"))]
+	impl<T: frame_system::Config> Randomness<T::Hash, T::BlockNumber> for DeterministicRandomness<T> {
+		fn random(subject: &[u8]) -> (T::Hash, T::BlockNumber) {
+			let now = frame_system::Pallet::<T>::block_number();
+			(Self::random_at(subject, now), now)
+		}
+	}
 
-			let worker_info =
-				registry::Workers::<T>::get(&worker).expect("Bounded worker must exist; qed.");
-			let p = worker_info
-				.initial_score
-				.ok_or(Error::<T>::BenchmarkMissing)?;
+	/// The stake pools the workers are assigned to, so that the rewards the mining pallet holds
+	/// back (e.g. locked) can be credited to the pool they were earned for
+	pub trait StakePoolRouting<Balance> {
+		/// Returns the pool the worker is assigned to
+		fn pool_of(worker: &WorkerPublicKey) -> Option<u64>;
+		/// Credits a reward released by the mining pallet to the stakers of the pool
+		fn credit_pool_reward(pid: u64, reward: Balance);
+		/// Whether the pool owner approved to swap the pool worker to `new_worker`
+		fn is_swap_approved(worker: &WorkerPublicKey, new_worker: &WorkerPublicKey) -> bool;
+	}
 
-			let tokenomic = Self::tokenomic();
-			let min_stake = tokenomic.minimal_stake(p);
-			ensure!(stake >= min_stake, Error::<T>::InsufficientStake);
+	impl<Balance> StakePoolRouting<Balance> for () {
+		fn pool_of(_worker: &WorkerPublicKey) -> Option<u64> {
+			None
+		}
+		fn credit_pool_reward(_pid: u64, _reward: Balance) {}
+		fn is_swap_approved(_worker: &WorkerPublicKey, _new_worker: &WorkerPublicKey) -> bool {
+			false
+		}
+	}
 
-			let ve = tokenomic.ve(stake, p, worker_info.confidence_level);
-			let v_max = tokenomic.v_max();
-			ensure!(ve <= v_max, Error::<T>::TooMuchStake);
+	/// Read-only queries of the mining status for the pallets built on top of mining (e.g. the
+	/// stake pool), so that they don't depend on the storage layout of this pallet
+	pub trait MiningInfoProvider<AccountId, Balance> {
+		/// Returns the state of the miner, or None if the miner doesn't exist
+		fn miner_state(miner: &AccountId) -> Option<MinerState>;
+		/// Returns the stake of the miner, or None if it's neither mining nor cooling down
+		fn miner_stake(miner: &AccountId) -> Option<Balance>;
+		/// Returns the Ve and V of the miner, in U64F64 bits
+		fn miner_ve_v(miner: &AccountId) -> Option<(u128, u128)>;
+		/// Returns if the miner has finished cooling down and its stake can be reclaimed
+		fn can_reclaim(miner: &AccountId) -> bool;
+		/// Returns if the miner is staked by itself rather than by a stake pool
+		fn is_solo_miner(miner: &AccountId) -> bool;
+	}
 
-			let now = Self::now_sec();
+	/// Listener of the mining lifecycle. Multiple observers can be combined as a tuple.
+	#[impl_trait_for_tuples::impl_for_tuples(10)]
+	pub trait MiningLifecycleObserver<AccountId, Balance> {
+		fn on_mining_event(event: &MiningLifecycleEvent<AccountId, Balance>);
+	}
 
-			Stakes::<T>::insert(&miner, stake);
-			Miners::<T>::mutate(&miner, |info| {
-				if let Some(info) = info {
-					info.state = MinerState::MiningIdle;
-					info.ve = ve.to_bits();
-					info.v = ve.to_bits();
-					info.v_updated_at = now;
-				}
-			});
-			OnlineMiners::<T>::mutate(|v| *v += 1);
+	/// Typed access to the U64F64 bits in `SettleInfo`
+	pub trait SettleInfoExt {
+		fn v_bits(&self) -> FixedBits;
+		fn payout_bits(&self) -> FixedBits;
+	}
 
-			let session_id = NextSessionId::<T>::get();
-			NextSessionId::<T>::put(session_id + 1);
-			Self::push_message(SystemEvent::new_worker_event(
-				worker,
-				WorkerEvent::MiningStart {
-					session_id: session_id,
-					init_v: ve.to_bits(),
-				},
-			));
-			Self::deposit_event(Event::<T>::MinerStarted(miner));
-			Ok(())
+	impl SettleInfoExt for SettleInfo {
+		fn v_bits(&self) -> FixedBits {
+			FixedBits::from_bits(self.v)
 		}
+		fn payout_bits(&self) -> FixedBits {
+			FixedBits::from_bits(self.payout)
+		}
+	}
 
-		/// Stops mining, enterying cool down state
-		///
-		/// Requires:
-		/// 1. Ther miner is in Idle, MiningActive, or MiningUnresponsive state
-		pub fn stop_mining(miner: T::AccountId) -> DispatchResult {
-			let worker = MinerBindings::<T>::get(&miner).ok_or(Error::<T>::MinerNotBound)?;
-			let mut miner_info = Miners::<T>::get(&miner).ok_or(Error::<T>::MinerNotFound)?;
-
-			ensure!(
-				miner_info.state != MinerState::Ready
-					&& miner_info.state != MinerState::MiningCoolingDown,
-				Error::<T>::MinerNotMining
-			);
-
-			let now = Self::now_sec();
-			miner_info.state = MinerState::MiningCoolingDown;
-			miner_info.cool_down_start = now;
-			Miners::<T>::insert(&miner, &miner_info);
-			OnlineMiners::<T>::mutate(|v| *v -= 1); // v cannot be 0
+	/// The accumulated statistics of a miner
+	///
+	/// The fields added after `total_reward` are compact encoded to keep `MinerInfo` small.
+	#[derive(Encode, Decode, TypeInfo, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+	pub struct MinerStats {
+		/// The total payout, in the smallest unit of the balance
+		total_reward: u128,
+		/// The total stake slashed, in the smallest unit of the balance
+		#[codec(compact)]
+		total_slashed: u128,
+		/// The number of the accepted heartbeats
+		#[codec(compact)]
+		total_heartbeats: u32,
+		/// The number of the mining sessions started
+		#[codec(compact)]
+		sessions_count: u32,
+		/// The id of the latest mining session
+		#[codec(compact)]
+		last_session_id: u32,
+	}
 
-			Self::push_message(SystemEvent::new_worker_event(
-				worker,
-				WorkerEvent::MiningStop,
-			));
-			Self::deposit_event(Event::<T>::MinerStopped(miner));
-			Ok(())
+	impl MinerStats {
+		/// Records the payout rounded down, returning the dust dropped by the rounding
+		fn on_reward(&mut self, payout: FixedBits) -> FixedPoint {
+			let (payout, dust): (u128, _) = balance_convert::floor_with_dust(&payout.to_fixed());
+			self.total_reward += payout;
+			dust
 		}
-
-		/// Returns if the worker is already bounded to a miner
-		pub fn ensure_worker_bound(pubkey: &WorkerPublicKey) -> Result<T::AccountId, Error<T>> {
-			WorkerBindings::<T>::get(&pubkey).ok_or(Error::<T>::WorkerNotBound)
+		fn on_slash(&mut self, slashed: u128) {
+			self.total_slashed = self.total_slashed.saturating_add(slashed);
 		}
-
-		/// Returns if the miner is already bounded to a worker
-		pub fn ensure_miner_bound(miner: &T::AccountId) -> Result<WorkerPublicKey, Error<T>> {
-			MinerBindings::<T>::get(&miner).ok_or(Error::<T>::MinerNotBound)
+		fn on_heartbeat(&mut self) {
+			self.total_heartbeats = self.total_heartbeats.saturating_add(1);
 		}
-
-		fn update_tokenomic_parameters(params: TokenomicParams) {
-			TokenomicParameters::<T>::put(params.clone());
-			Self::push_message(GatekeeperEvent::TokenomicParametersChanged(params));
+		fn on_session(&mut self, session_id: u32) {
+			self.sessions_count = self.sessions_count.saturating_add(1);
+			self.last_session_id = session_id;
 		}
+	}
 
-		pub fn withdraw_subsidy_pool(target: &T::AccountId, value: BalanceOf<T>) -> DispatchResult {
-			let wallet = Self::account_id();
-			T::Currency::transfer(&wallet, &target, value, KeepAlive)
-		}
+	/// The storage layout of `MinerInfo` before the storage versioning
+	mod v0 {
+		use super::*;
 
-		fn tokenomic() -> Tokenomic<T> {
-			let params =
-				TokenomicParameters::<T>::get().expect("TokenomicParameters must exist; qed.");
-			Tokenomic::<T>::new(params)
+		#[derive(Decode)]
+		pub struct MinerStats {
+			pub total_reward: u128,
 		}
 
-		fn now_sec() -> u64 {
-			<T as registry::Config>::UnixTime::now()
-				.as_secs()
-				.saturated_into::<u64>()
+		#[derive(Decode)]
+		pub struct MinerInfo {
+			pub state: MinerState,
+			pub ve: u128,
+			pub v: u128,
+			pub v_updated_at: u64,
+			pub benchmark: Benchmark,
+			pub cool_down_start: u64,
+			pub stats: MinerStats,
 		}
 	}
 
-	struct Tokenomic<T> {
-		params: TokenomicParams,
-		mark: PhantomData<T>,
+	#[pallet::config]
+	pub trait Config<I: 'static = ()>:
+		frame_system::Config + mq::Config + registry::Config
+	{
+		type Event: From<Event<Self, I>> + IsType<<Self as frame_system::Config>::Event>;
+		/// The block time the per-block tokenomic parameters are derived with, see
+		/// `TokenomicHourlyRates`
+		#[pallet::constant]
+		type ExpectedBlockTimeSec: Get<u32>;
+		/// The max number of the offline and recovered workers applied per message or per block.
+		/// The excess are deferred to `DeferredStatusUpdates`.
+		#[pallet::constant]
+		type MaxStatusUpdatesPerMessage: Get<u32>;
+		/// The subject of the randomness the heartbeat challenges are seeded with. Each instance
+		/// of the pallet in a runtime must have its own.
+		#[pallet::constant]
+		type RandomnessSubject: Get<&'static [u8]>;
+		/// The id of the subsidy pool account, see `account_id`. Each instance of the pallet in a
+		/// runtime must have its own.
+		#[pallet::constant]
+		type PalletId: Get<PalletId>;
+		/// The prefix of the topics of the messages to and from this instance, see
+		/// `instance_topic`. Also namespaces the stake lock and the bindings child trie. Empty for
+		/// the default instance.
+		#[pallet::constant]
+		type TopicPrefix: Get<&'static [u8]>;
+		/// The bindings of the other instances of the pallet in the runtime, so that a worker is
+		/// bound in one instance at most. `()` if there's only one instance.
+		type OtherInstances: registry::WorkerBindingInfo;
+
+		type Currency: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
+		type Randomness: Randomness<Self::Hash, Self::BlockNumber>;
+		type LifecycleObserver: MiningLifecycleObserver<Self::AccountId, BalanceOf<Self, I>>;
+		/// The whitelisted price source to update `pha_rate`, enabled by `PhaRateBounds`
+		type PriceFeed: PriceFeed;
+		/// The assets the subsidy pool can pay a part of the rewards with, see `SecondaryPayout`
+		type SecondaryAssets: SecondaryAssets<Self::AccountId>;
+		/// The source of the registered workers, usually the registry pallet
+		type WorkerInfo: WorkerInfoProvider<Self::AccountId>;
+		/// The origin to update the parameters of the pallet and to resolve the settlements
+		type GovernanceOrigin: EnsureOrigin<Self::Origin>;
+		/// The origin of the calls only for the integration tests, e.g. `force_heartbeat`
+		type TestingOrigin: EnsureOrigin<Self::Origin>;
+		/// The stake pools the locked rewards of the pool miners go to. `()` if no stake pool is
+		/// deployed.
+		type StakePools: StakePoolRouting<BalanceOf<Self, I>>;
 	}
 
-	impl<T> Tokenomic<T>
-	where
-		T: Config,
-		BalanceOf<T>: FixedPointConvert,
-	{
-		fn new(params: TokenomicParams) -> Self {
-			Tokenomic {
-				params,
-				mark: Default::default(),
-			}
-		}
+	#[pallet::pallet]
+	#[pallet::generate_store(pub(super) trait Store)]
+	pub struct Pallet<T, I = ()>(PhantomData<(T, I)>);
 
-		/// Gets the minimal stake with the given performance score
-		fn minimal_stake(&self, p: u32) -> BalanceOf<T> {
-			let p = FixedPoint::from_num(p);
-			let k = FixedPoint::from_bits(self.params.k);
-			let min_stake = k * p.sqrt();
-			FixedPointConvert::from_fixed(&min_stake)
-		}
+	/// Tokenomic parameters used by Gatekeepers to compute the V promote.
+	#[pallet::storage]
+	pub type TokenomicParameters<T, I = ()> = StorageValue<_, TokenomicParams>;
 
-		/// Calcuates the initial Ve
-		fn ve(&self, s: BalanceOf<T>, p: u32, confidence_level: u8) -> FixedPoint {
-			let f1 = FixedPoint::from_num(1);
-			let score = Self::confidence_score(confidence_level);
-			let re = FixedPoint::from_bits(self.params.re);
-			let tweaked_re = (re - f1) * score + f1;
-			let s = s.to_fixed();
-			let c = self.rig_cost(p);
-			tweaked_re * (s + c)
-		}
+	/// The benchmark-scaled stake cap of the miners, disabled if absent
+	///
+	/// Kept apart from `TokenomicParameters`, whose layout the gatekeepers decode.
+	#[pallet::storage]
+	#[pallet::getter(fn stake_cap_params)]
+	pub type StakeCap<T, I = ()> = StorageValue<_, StakeCapParams>;
 
-		/// Gets the max v in fixed point
-		fn v_max(&self) -> FixedPoint {
-			FixedPoint::from_bits(self.params.v_max)
-		}
+	/// The storage layout version of the pallet
+	#[pallet::storage]
+	pub(super) type MiningStorageVersion<T, I = ()> = StorageValue<_, u32, ValueQuery>;
 
-		/// Gets the estimated rig costs in PHA
-		fn rig_cost(&self, p: u32) -> FixedPoint {
-			let cost_k = FixedPoint::from_bits(self.params.rig_k);
-			let cost_b = FixedPoint::from_bits(self.params.rig_b);
-			let pha_rate = FixedPoint::from_bits(self.params.pha_rate);
-			let p = FixedPoint::from_num(p);
-			(cost_k * p + cost_b) / pha_rate
-		}
+	/// The number of the miners in `MiningIdle` state
+	#[pallet::storage]
+	#[pallet::getter(fn idle_miners)]
+	pub type IdleMiners<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// The number of the miners in `MiningActive` state
+	#[pallet::storage]
+	#[pallet::getter(fn active_miners)]
+	pub type ActiveMiners<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// The number of the miners in `MiningUnresponsive` state
+	#[pallet::storage]
+	#[pallet::getter(fn unresponsive_miners)]
+	pub type UnresponsiveMiners<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// The reason of the tokenomic freeze requested by the gatekeeper. The settlements are
+	/// buffered in `FrozenSettlements` until `resume_settlement` if present.
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_frozen)]
+	pub type SettlementFrozen<T, I = ()> = StorageValue<_, Vec<u8>>;
+
+	/// The settlements received during the tokenomic freeze, merged by worker
+	#[pallet::storage]
+	#[pallet::getter(fn frozen_settlements)]
+	pub type FrozenSettlements<T, I = ()> = StorageValue<_, Vec<SettleInfo>, ValueQuery>;
+
+	/// How the settlements of the miners in a wrong state are handled
+	#[pallet::storage]
+	#[pallet::getter(fn wrong_state_settle_policy)]
+	pub type WrongStateSettle<T, I = ()> = StorageValue<_, WrongStateSettlePolicy, ValueQuery>;
+
+	/// The settlements buffered by `WrongStateSettlePolicy::Buffer`, merged by miner
+	#[pallet::storage]
+	#[pallet::getter(fn pending_settlement)]
+	pub type PendingSettlements<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, SettleInfo>;
+
+	/// The weight budget per block to apply the settlements queued in `SettlementQueue`
+	///
+	/// The settlements are applied as soon as received if absent, unless some are still queued.
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_weight_budget)]
+	pub type SettlementWeightBudget<T, I = ()> = StorageValue<_, Weight>;
+
+	/// The settlements waiting to be applied in `on_initialize`, indexed by the arrival order
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_queue)]
+	pub type SettlementQueue<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, u64, QueuedSettlement<T::BlockNumber>>;
+
+	/// The (head, tail) indices of `SettlementQueue`
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_queue_range)]
+	pub type SettlementQueueRange<T, I = ()> = StorageValue<_, (u64, u64), ValueQuery>;
+
+	/// The status updates beyond `MaxStatusUpdatesPerMessage`, applied in `on_initialize` in the
+	/// arrival order
+	#[pallet::storage]
+	#[pallet::getter(fn deferred_status_updates)]
+	pub type DeferredStatusUpdates<T, I = ()> = StorageValue<_, Vec<StatusUpdate>, ValueQuery>;
+
+	/// The gatekeeper block whose status updates are being paginated, and the pages received
+	#[pallet::storage]
+	#[pallet::getter(fn status_continuation)]
+	pub type StatusContinuation<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, (T::BlockNumber, u32)>;
+
+	/// The cap of `online_miners` set by the governance. No new mining session can be started at
+	/// the cap. No limit if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn max_online_miners)]
+	pub type MaxOnlineMiners<T, I = ()> = StorageValue<_, u32>;
+
+	/// How long (in seconds) a benchmark stays valid to start mining. The workers with an older
+	/// or unknown benchmark are asked to re-benchmark. No limit if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn benchmark_validity_period)]
+	pub type BenchmarkValidityPeriod<T, I = ()> = StorageValue<_, u64>;
+
+	/// The ongoing recount of the online miner counters in `on_idle`
+	///
+	/// It's the raw key of the last scanned miner (empty if none scanned yet) and the numbers of
+	/// the idle, active and unresponsive miners counted so far.
+	#[pallet::storage]
+	pub(super) type OnlineMinersRecount<T, I = ()> =
+		StorageValue<_, (Vec<u8>, OnlineMinerCounts)>;
+
+	/// The recent tokenomic parameters changes, with the oldest first
+	///
+	/// Only the last `MAX_TOKENOMIC_HISTORY` changes are kept.
+	#[pallet::storage]
+	#[pallet::getter(fn tokenomic_history)]
+	pub type TokenomicHistory<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, Vec<TokenomicChange<T::BlockNumber, T::Hash, T::AccountId>>, ValueQuery>;
+
+	/// The recent heartbeats of the miners, kept for `HEARTBEAT_HISTORY_TTL_SEC`
+	#[pallet::storage]
+	#[pallet::getter(fn heartbeat_history)]
+	pub type HeartbeatHistory<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, BoundedHistory<HeartbeatRecord>, ValueQuery>;
+
+	/// The raw key of the last `HeartbeatHistory` entry scanned by the pruning in `on_idle`
+	#[pallet::storage]
+	pub(super) type HeartbeatHistoryPruneCursor<T, I = ()> = StorageValue<_, Vec<u8>>;
+
+	/// The sub-unit dust dropped by rounding down the payouts and the returned stakes of each
+	/// miner, in U64F64 bits
+	#[pallet::storage]
+	#[pallet::getter(fn reward_dust)]
+	pub type RewardDust<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, u128, ValueQuery>;
+
+	/// The total dust flushed from `RewardDust`. The dust never leaves the subsidy pool.
+	#[pallet::storage]
+	#[pallet::getter(fn flushed_dust)]
+	pub type FlushedDust<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, BalanceOf<T, I>, ValueQuery>;
+
+	/// The recent settlements of the miners by block number, kept for
+	/// `SETTLEMENT_HISTORY_TTL_SEC`
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_history)]
+	pub type SettlementHistory<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Twox64Concat,
+		T::AccountId,
+		BoundedHistory<SettlementRecord<BalanceOf<T, I>>>,
+		ValueQuery,
+	>;
+
+	/// The raw key of the last `SettlementHistory` entry scanned by the pruning in `on_idle`
+	#[pallet::storage]
+	pub(super) type SettlementHistoryPruneCursor<T, I = ()> = StorageValue<_, Vec<u8>>;
+
+	/// The expected heartbeat count (default: 20)
+	#[pallet::storage]
+	pub type ExpectedHeartbeatCount<T, I = ()> = StorageValue<_, u32>;
+
+	/// The (min, max) bounds of the dynamic heartbeat count. The count is static if not set.
+	#[pallet::storage]
+	#[pallet::getter(fn heartbeat_count_bounds)]
+	pub type HeartbeatCountBounds<T, I = ()> = StorageValue<_, (u32, u32)>;
+
+	/// The heartbeat count currently in effect, adjusted by the block utilization if
+	/// `HeartbeatCountBounds` is set
+	#[pallet::storage]
+	#[pallet::getter(fn effective_heartbeat_count)]
+	pub type EffectiveHeartbeatCount<T, I = ()> = StorageValue<_, u32>;
+
+	/// The moving average of the block weight utilization, fed to the heartbeat count controller
+	#[pallet::storage]
+	#[pallet::getter(fn block_utilization)]
+	pub type BlockUtilization<T, I = ()> = StorageValue<_, Perbill, ValueQuery>;
+
+	/// The number of blocks between two heartbeat challenges (default: 1)
+	#[pallet::storage]
+	pub type HeartbeatChallengeInterval<T, I = ()> = StorageValue<_, u32>;
+
+	/// The workers to challenge specifically at the end of the block, e.g. the newly recovered
+	/// ones
+	///
+	/// Only the first `MAX_HEARTBEAT_TARGETS` workers are kept.
+	#[pallet::storage]
+	#[pallet::getter(fn heartbeat_targets)]
+	pub type HeartbeatTargets<T, I = ()> = StorageValue<_, Vec<WorkerPublicKey>, ValueQuery>;
+
+	/// The sequence of the last event pushed to each worker
+	///
+	/// The workers ignore the events not newer than the last one they have applied, and the
+	/// auditors can detect the missed or duplicated worker events by the gaps of the nonces.
+	#[pallet::storage]
+	#[pallet::getter(fn worker_event_seq)]
+	pub type WorkerEventSeqs<T, I = ()> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, WorkerEventSeq, ValueQuery>;
+
+	/// The timestamp (in sec) of the last finalized block
+	#[pallet::storage]
+	pub type LastBlockTime<T, I = ()> = StorageValue<_, u64>;
+
+	/// The last period (start and end time in sec) no block was produced
+	///
+	/// The offline reports shortly after the period are ignored, see `is_in_downtime_grace`.
+	#[pallet::storage]
+	#[pallet::getter(fn last_chain_downtime)]
+	pub type LastChainDowntime<T, I = ()> = StorageValue<_, (u64, u64)>;
+
+	/// The heartbeat challenge is skipped if the outbound messages of the block exceed this
+	/// threshold. No limit if absent.
+	#[pallet::storage]
+	pub type HeartbeatChallengeMaxOutbound<T, I = ()> = StorageValue<_, u32>;
+
+	/// The bounds `(min, max)` of the `pha_rate` fed by `Config::PriceFeed`, in U64F64 bits. The
+	/// price feed is disabled if absent.
+	#[pallet::storage]
+	pub type PhaRateBounds<T, I = ()> = StorageValue<_, (u128, u128)>;
+
+	/// The last time (in sec) `pha_rate` was fed by `Config::PriceFeed`
+	#[pallet::storage]
+	pub type PhaRateUpdatedAt<T, I = ()> = StorageValue<_, u64, ValueQuery>;
+
+	/// The number of distinct gatekeepers required to confirm a `MiningInfoUpdateEvent` before
+	/// it's applied. Disabled if less than 2.
+	#[pallet::storage]
+	#[pallet::getter(fn gatekeeper_quorum)]
+	pub type GatekeeperQuorum<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// The total stake of the online miners
+	#[pallet::storage]
+	#[pallet::getter(fn online_stake)]
+	pub type OnlineStake<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, BalanceOf<T, I>, ValueQuery>;
+
+	/// The miners in the observation mode, with the time (in sec) they started
+	///
+	/// An observing miner stays in `Ready` state. Its worker responds to the heartbeat challenges,
+	/// but it earns nothing and can't be slashed.
+	#[pallet::storage]
+	#[pallet::getter(fn observing_since)]
+	pub type Observers<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, u64>;
+
+	/// The number of the observing miners, not counted in `online_miners`
+	#[pallet::storage]
+	#[pallet::getter(fn observing_miners)]
+	pub type ObservingMiners<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// Scales the heartbeat challenge target of each worker by its stake if enabled
+	#[pallet::storage]
+	#[pallet::getter(fn stake_weighted_sampling)]
+	pub type StakeWeightedSampling<T, I = ()> = StorageValue<_, bool, ValueQuery>;
+
+	/// The max deviation of the settled V from the one replayed on chain, if the settlements are
+	/// audited
+	///
+	/// Only checked with the `settlement-audit` feature. The V is replayed from `SettlementTraces`
+	/// rather than anything reported by the audited gatekeeper.
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_audit_tolerance)]
+	pub type SettlementAuditTolerance<T, I = ()> = StorageValue<_, Permill>;
+
+	/// Bumped whenever `SettlementAuditTolerance` changes, invalidating the traces recorded before
+	#[pallet::storage]
+	pub type SettlementAuditEpoch<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// The traces of the mining miners recorded while the settlements are audited
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_trace)]
+	pub type SettlementTraces<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, SettlementTrace>;
+
+	/// The time (in sec) the last mining session of the worker ended
+	#[pallet::storage]
+	#[pallet::getter(fn last_session_end)]
+	pub type LastSessionEnd<T, I = ()> = StorageMap<_, Twox64Concat, WorkerPublicKey, u64>;
+
+	/// The minimal interval (in sec) between the end of a mining session of a worker and the start
+	/// of its next session, no matter which miner it's bound to. Disabled if zero.
+	#[pallet::storage]
+	#[pallet::getter(fn min_session_interval)]
+	pub type MinSessionInterval<T, I = ()> = StorageValue<_, u64, ValueQuery>;
+
+	/// The timestamp (in ms) of the recent blocks emitting a heartbeat challenge
+	#[pallet::storage]
+	pub(super) type ChallengeTimes<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::BlockNumber, u64>;
+
+	/// The clock skew (in ms) of the workers, observed from the `challenge_time` of their latest
+	/// heartbeats
+	///
+	/// A positive skew means the worker reports a later challenge time than the chain.
+	#[pallet::storage]
+	#[pallet::getter(fn clock_skew)]
+	pub type ClockSkews<T, I = ()> = StorageMap<_, Twox64Concat, WorkerPublicKey, i64>;
+
+	/// The max clock skew (in ms) of a worker before it's flagged in the registry. Disabled if
+	/// absent.
+	#[pallet::storage]
+	#[pallet::getter(fn max_clock_skew)]
+	pub type MaxClockSkew<T, I = ()> = StorageValue<_, u64>;
+
+	/// The scores of the confidence levels 1 to 5 (U64F64 bits) to tweak the initial Ve. The
+	/// builtin table is used if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn confidence_scores)]
+	pub type ConfidenceScores<T, I = ()> = StorageValue<_, [u128; 5]>;
+
+	/// Accepts a lower benchmark iterations in a heartbeat of a new session or a restarted
+	/// worker, restarting the baseline, if enabled
+	#[pallet::storage]
+	#[pallet::getter(fn benchmark_reset_allowed)]
+	pub type BenchmarkResetAllowed<T, I = ()> = StorageValue<_, bool, ValueQuery>;
+
+	/// The minimal pRuntime version of the workers to bind or to start mining. Disabled if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn min_worker_version)]
+	pub type MinWorkerVersion<T, I = ()> = StorageValue<_, MinWorkerVersionConfig>;
+
+	/// The (min, max) versions of the gatekeeper protocol accepted in the mining updates
+	///
+	/// Defaults to all the versions known by the pallet. The gatekeepers read it to negotiate the
+	/// version to speak.
+	#[pallet::storage]
+	pub type SupportedGkProtocol<T, I = ()> = StorageValue<_, (u32, u32)>;
+
+	/// The mining updates waiting for the gatekeeper quorum, by their digests
+	#[pallet::storage]
+	pub(super) type PendingMiningUpdates<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, [u8; 32], MiningInfoUpdateEvent<T::BlockNumber>>;
+
+	/// The gatekeepers confirmed a mining update, by the update digest
+	///
+	/// A confirmation may arrive before the update itself.
+	#[pallet::storage]
+	pub(super) type MiningUpdateConfirms<T, I = ()> =
+		StorageMap<_, Twox64Concat, [u8; 32], Vec<WorkerPublicKey>, ValueQuery>;
+
+	/// The digests tracked in `PendingMiningUpdates` and `MiningUpdateConfirms`, with the oldest
+	/// first
+	///
+	/// Only the last `MAX_PENDING_MINING_UPDATES` digests are kept.
+	#[pallet::storage]
+	pub(super) type MiningUpdateDigests<T, I = ()> = StorageValue<_, Vec<[u8; 32]>, ValueQuery>;
+
+	/// The block of the runtime upgrade that paused applying the mining updates
+	///
+	/// Set by `on_runtime_upgrade` when a storage migration is pending, and lifted automatically
+	/// in a later block once the migrations complete, or after `UPGRADE_PAUSE_MAX_BLOCKS`.
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_paused)]
+	pub type SettlementPaused<T: Config<I>, I: 'static = ()> = StorageValue<_, T::BlockNumber>;
+
+	/// The encoded mining updates received during the settlement pause, with the oldest first
+	///
+	/// Kept encoded one by one, as the codec of `MiningInfoUpdateEvent` isn't self-delimiting. At
+	/// most `MAX_PENDING_MINING_UPDATES` updates are buffered.
+	#[pallet::storage]
+	pub(super) type PausedMiningUpdates<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, Vec<Vec<u8>>, ValueQuery>;
+
+	/// The miner state.
+	///
+	/// The miner state is created when a miner is bounded with a worker, but it will be kept even
+	/// if the worker is force unbound. A re-bind of a worker will reset the mining state.
+	#[pallet::storage]
+	#[pallet::getter(fn miners)]
+	pub(super) type Miners<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, MinerInfo>;
+
+	/// The disputed settlements by id
+	#[pallet::storage]
+	#[pallet::getter(fn settlement_disputes)]
+	pub type SettlementDisputes<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, u64, SettlementDispute<T::BlockNumber>>;
+
+	/// The id of the next disputed settlement
+	#[pallet::storage]
+	pub(super) type NextSettlementDisputeId<T, I = ()> = StorageValue<_, u64, ValueQuery>;
+
+	/// The recent telemetry reports of the gatekeepers, with the oldest first
+	///
+	/// Only the last `MAX_GATEKEEPER_TELEMETRY` reports are kept.
+	#[pallet::storage]
+	#[pallet::getter(fn gatekeeper_telemetry_log)]
+	pub type GatekeeperTelemetryLog<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, Vec<GatekeeperTelemetryRecord<T::BlockNumber>>, ValueQuery>;
+
+	/// The index of the miners by their states, updated on every state transition
+	#[pallet::storage]
+	pub(super) type MinersByState<T: Config<I>, I: 'static = ()> =
+		StorageDoubleMap<_, Twox64Concat, MinerState, Twox64Concat, T::AccountId, ()>;
+
+	/// The bound worker for a miner account
+	#[pallet::storage]
+	pub(super) type MinerBindings<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, WorkerPublicKey>;
+
+	/// The bound miner account for a worker
+	#[pallet::storage]
+	pub(super) type WorkerBindings<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, T::AccountId>;
+
+	/// The root of the bindings child trie, committing all the `WorkerBindings`
+	///
+	/// The offchain components can verify a binding with a child trie read proof against it.
+	#[pallet::storage]
+	#[pallet::getter(fn bindings_commitment)]
+	pub type BindingsCommitment<T: Config<I>, I: 'static = ()> = StorageValue<_, T::Hash>;
+
+	/// The cool down period (in sec)
+	#[pallet::storage]
+	#[pallet::getter(fn cool_down_period)]
+	pub(super) type CoolDownPeriod<T, I = ()> = StorageValue<_, u64, ValueQuery>;
+
+	/// The next id to assign to a mining session
+	#[pallet::storage]
+	pub(super) type NextSessionId<T, I = ()> = StorageValue<_, u32, ValueQuery>;
+
+	/// The stakes of miner accounts.
+	///
+	/// Only presents for mining and cooling down miners.
+	#[pallet::storage]
+	#[pallet::getter(fn stakes)]
+	pub(super) type Stakes<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, BalanceOf<T, I>>;
+
+	/// The reward lockup policy. Payouts are not locked if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn reward_lockup)]
+	pub type RewardLockup<T, I = ()> = StorageValue<_, RewardLockupConfig>;
+
+	/// The locked rewards of the beneficiaries, ordered by the unlock time and bounded by
+	/// `MAX_LOCKED_REWARDS`
+	#[pallet::storage]
+	#[pallet::getter(fn locked_rewards)]
+	pub(super) type LockedRewards<T: Config<I>, I: 'static = ()> = StorageMap<
+		_,
+		Twox64Concat,
+		RewardBeneficiary<T::AccountId>,
+		Vec<LockedReward<BalanceOf<T, I>>>,
+		ValueQuery,
+	>;
+
+	/// The tip to the relayers of the heartbeats. No tip if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn relayer_tip)]
+	pub type RelayerTip<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, RelayerTipConfig<BalanceOf<T, I>>>;
+
+	/// The block and the number of the relayer tips paid in it
+	#[pallet::storage]
+	pub type RelayerTipsPaid<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, (T::BlockNumber, u32), ValueQuery>;
+
+	/// The rules of the miner reputation tiers. The reputations are not tracked if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn reputation_tiers)]
+	pub type ReputationTiers<T, I = ()> = StorageValue<_, ReputationTierConfig>;
+
+	/// The uptime streak bonus tiers, in the ascending order of `min_streak`. The streaks are not
+	/// tracked if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn uptime_streak_bonuses)]
+	pub type UptimeStreakBonuses<T, I = ()> = StorageValue<_, Vec<StreakBonusTier>>;
+
+	/// The number of the consecutive settlements of the miners without going unresponsive,
+	/// tracked since `UptimeStreakBonuses` is set. Reset when a session starts.
+	#[pallet::storage]
+	#[pallet::getter(fn uptime_streak)]
+	pub type UptimeStreaks<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, u32, ValueQuery>;
+
+	/// Where the settlement payouts go, `RewardSink::Normal` by default
+	#[pallet::storage]
+	#[pallet::getter(fn reward_sink)]
+	pub type ActiveRewardSink<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, RewardSink, ValueQuery>;
+
+	/// The payouts parked by `RewardSink::ParkInPallet`, not yet drained
+	#[pallet::storage]
+	#[pallet::getter(fn parked_reward)]
+	pub type ParkedRewards<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, ParkedReward<BalanceOf<T, I>>>;
+
+	/// The rolling average (weight of 1/8) of the annualized V decay rate of the miners, sampled
+	/// at each settlement. Reset when a session starts.
+	///
+	/// A high rate warns the operators of the misconfigured or failing workers before the
+	/// stake is slashed at the reclaim.
+	#[pallet::storage]
+	#[pallet::getter(fn effective_slash_rate)]
+	pub type EffectiveSlashRates<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, Permill, ValueQuery>;
+
+	/// The reputation of the miners, tracked since `ReputationTiers` is set
+	#[pallet::storage]
+	#[pallet::getter(fn reputations)]
+	pub type Reputations<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, MinerReputation, ValueQuery>;
+
+	/// The block each worker was last targeted at after a recovery, tracked with the reputation
+	/// tiers
+	#[pallet::storage]
+	pub type LastTargetedAt<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, T::BlockNumber>;
+
+	/// The trial mining policy. Trial mining is disabled if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn trial_mining)]
+	pub type TrialMining<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, TrialMiningConfig<T::BlockNumber>>;
+
+	/// The block each miner in trial mining ends its trial at
+	///
+	/// The trial restarts when the miner enters unresponsive state.
+	#[pallet::storage]
+	#[pallet::getter(fn trial_ends_at)]
+	pub type TrialMiners<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, T::BlockNumber>;
+
+	/// The miners staking directly from their own accounts instead of a stake pool
+	///
+	/// The stake of a solo miner is locked in its account until reclaimed, and its rewards are
+	/// paid to `LockedRewards` unlocked immediately.
+	#[pallet::storage]
+	#[pallet::getter(fn is_solo_miner)]
+	pub type SoloMiners<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, bool, ValueQuery>;
+
+	/// The daily amount of the payout released to a miner in the payout smoothing mode
+	///
+	/// The payouts of the miners present are accrued in `PayoutReserves` and released at this
+	/// rate. The payouts are paid immediately otherwise.
+	#[pallet::storage]
+	#[pallet::getter(fn payout_smoothing)]
+	pub type PayoutSmoothing<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, BalanceOf<T, I>>;
+
+	/// The payouts held back by the payout smoothing, not yet released to the miners
+	#[pallet::storage]
+	#[pallet::getter(fn payout_reserve)]
+	pub type PayoutReserves<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, PayoutReserve<BalanceOf<T, I>>>;
+
+	/// The accounts to refund when the miners are reclaimed, recorded at the mining start
+	///
+	/// Used by the miners on keyless sub-accounts, so that the funds left on the miner account
+	/// can be routed to the owner directly.
+	#[pallet::storage]
+	#[pallet::getter(fn refund_beneficiary)]
+	pub type RefundBeneficiaries<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, T::AccountId>;
+
+	/// The summary of the mining state at the end of the last block, so that the light clients
+	/// can poll a single key instead of the events
+	#[pallet::storage]
+	#[pallet::getter(fn mining_digest)]
+	pub type MiningDigest<T: Config<I>, I: 'static = ()> =
+		StorageValue<_, MiningDigestInfo<T::BlockNumber, BalanceOf<T, I>>, ValueQuery>;
+
+	/// The bonds posted by the worker operators, held by `bond_account_id`
+	#[pallet::storage]
+	#[pallet::getter(fn operator_bonds)]
+	pub type OperatorBonds<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, OperatorBond<T::AccountId, BalanceOf<T, I>>>;
+
+	/// The worker whose operator bond covers the slash of the session of the miner, recorded at
+	/// `start_mining` until the reclaim
+	///
+	/// Kept even if the worker is unbound or swapped during the session.
+	#[pallet::storage]
+	pub type BondedWorkers<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, WorkerPublicKey>;
+
+	/// The reverse index of `BondedWorkers`, the sessions whose slash a worker's bond covers
+	#[pallet::storage]
+	pub type BondedSessions<T: Config<I>, I: 'static = ()> =
+		StorageDoubleMap<_, Twox64Concat, WorkerPublicKey, Twox64Concat, T::AccountId, ()>;
+
+	/// The time (in sec) an unbonded operator bond stays slashable before it can be withdrawn
+	#[pallet::storage]
+	#[pallet::getter(fn operator_unbonding_period)]
+	pub type OperatorUnbondingPeriod<T, I = ()> = StorageValue<_, u64, ValueQuery>;
+
+	/// The min ratio of the stake slashed at the reclaim of a session ended by the attestation
+	/// revocation of its worker. The whole stake of the session is slashed if absent.
+	#[pallet::storage]
+	pub type RevocationSlashRatio<T, I = ()> = StorageValue<_, Permill>;
+
+	/// The miners whose session was ended by the attestation revocation of the worker, to be
+	/// slashed by `RevocationSlashRatio` at the reclaim
+	#[pallet::storage]
+	#[pallet::getter(fn is_session_revoked)]
+	pub type RevokedSessions<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::AccountId, bool, ValueQuery>;
+
+	/// The policy to pay a part of the rewards in a secondary asset. Disabled if absent.
+	#[pallet::storage]
+	#[pallet::getter(fn secondary_payout)]
+	pub type SecondaryPayout<T, I = ()> = StorageValue<_, SecondaryPayoutConfig>;
+
+	/// The total amount of each secondary asset paid out of the subsidy pool
+	#[pallet::storage]
+	#[pallet::getter(fn secondary_paid_total)]
+	pub type SecondaryPaidTotal<T, I = ()> = StorageMap<_, Twox64Concat, u32, u128, ValueQuery>;
+
+	/// The operations scheduled at each block, in the order they are dispatched
+	#[pallet::storage]
+	#[pallet::getter(fn scheduled_ops)]
+	pub type ScheduledOps<T: Config<I>, I: 'static = ()> =
+		StorageMap<_, Twox64Concat, T::BlockNumber, Vec<ScheduledOp<T::AccountId>>, ValueQuery>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config<I>, I: 'static = ()> {
+		/// [period]
+		CoolDownExpirationChanged(u64),
+		/// [miner]
+		MinerStarted(T::AccountId),
+		/// [miner]
+		MinerStopped(T::AccountId),
+		/// [miner, original_stake, slashed]
+		MinerReclaimed(T::AccountId, BalanceOf<T, I>, BalanceOf<T, I>),
+		/// [miner, worker]
+		MinerBound(T::AccountId, WorkerPublicKey),
+		/// [miner, worker]
+		MinerUnbound(T::AccountId, WorkerPublicKey),
+		/// [miner]
+		MinerEnterUnresponsive(T::AccountId),
+		/// [miner]
+		MinerExitUnresponive(T::AccountId),
+		/// The raw U64F64 bits followed by the values in the balance scale. [miner, v_bits,
+		/// payout_bits, v, payout]
+		MinerSettled(T::AccountId, u128, u128, BalanceOf<T, I>, BalanceOf<T, I>),
+		/// The stake of a miner is reserved by starting mining. Takes the index of the former
+		/// placeholder `_MinerStaked`. [miner, amount]
+		MinerStaked(T::AccountId, BalanceOf<T, I>),
+		/// The stake of a miner is released by the reclaim, excluding the slashed part. Takes the
+		/// index of the former placeholder `_MinerWithdrew`. [miner, amount]
+		MinerWithdrew(T::AccountId, BalanceOf<T, I>),
+		/// [interval, max_outbound]
+		HeartbeatChallengePolicyChanged(u32, Option<u32>),
+		/// [outbound_messages]
+		HeartbeatChallengeSkipped(u32),
+		/// [num_targets]
+		TargetedHeartbeatChallenge(u32),
+		/// [count]
+		ExpectedHeartbeatCountChanged(u32),
+		/// [recorded, recounted]
+		CounterReconciled(u32, u32),
+		/// [bounds]
+		PhaRateBoundsChanged(Option<(u128, u128)>),
+		/// [pha_rate]
+		PhaRateUpdated(u128),
+		/// [miner, old_worker, new_worker]
+		MinerWorkerSwapped(T::AccountId, WorkerPublicKey, WorkerPublicKey),
+		/// [lockup]
+		RewardLockupChanged(Option<RewardLockupConfig>),
+		/// [miner, amount, unlock_at]
+		RewardLocked(T::AccountId, BalanceOf<T, I>, u64),
+		/// [miner, amount]
+		UnlockedRewardsClaimed(T::AccountId, BalanceOf<T, I>),
+		/// [threshold]
+		GatekeeperQuorumChanged(u32),
+		/// [digest, confirmations]
+		MiningUpdateConfirmed([u8; 32], u32),
+		/// [digest]
+		MiningUpdateDropped([u8; 32]),
+		/// [config]
+		TrialMiningChanged(Option<TrialMiningConfig<T::BlockNumber>>),
+		/// [miner, ends_at]
+		MinerTrialStarted(T::AccountId, T::BlockNumber),
+		/// [miner, ve]
+		MinerTrialCompleted(T::AccountId, u128),
+		/// [at, op]
+		OperationScheduled(T::BlockNumber, ScheduledOp<T::AccountId>),
+		/// [op, success]
+		OperationDispatched(ScheduledOp<T::AccountId>, bool),
+		/// [config]
+		SecondaryPayoutChanged(Option<SecondaryPayoutConfig>),
+		/// [beneficiary, asset, amount]
+		SecondaryRewardPaid(T::AccountId, u32, u128),
+		/// [rates, block_time_sec]
+		TokenomicRatesApplied(TokenomicHourlyRates, u32),
+		/// [start, end]
+		ChainDowntimeDetected(u64, u64),
+		/// [downtime_start, downtime_end, num_workers]
+		OfflineReportsSuppressed(u64, u64, u32),
+		/// [max_online_miners]
+		MaxOnlineMinersChanged(Option<u32>),
+		/// [miner, max_online_miners]
+		MiningStartBlockedByCapacity(T::AccountId, u32),
+		/// [reason]
+		SettlementFrozen(Vec<u8>),
+		/// [num_workers]
+		SettlementResumed(u32),
+		/// A settlement not buffered because `FrozenSettlements` is full. [worker]
+		FrozenSettlementDropped(WorkerPublicKey),
+		/// [budget]
+		SettlementWeightBudgetChanged(Option<Weight>),
+		/// [num_settlements, queue_len]
+		SettlementsQueued(u32, u64),
+		/// [num_settlements, queue_len]
+		QueuedSettlementsApplied(u32, u64),
+		/// [bounds]
+		HeartbeatCountBoundsChanged(Option<(u32, u32)>),
+		/// [min_version, max_version]
+		SupportedGkProtocolChanged(u32, u32),
+		/// [enabled]
+		StakeWeightedSamplingChanged(bool),
+		/// [miner]
+		MinerObservingStarted(T::AccountId),
+		/// [miner]
+		MinerObservingStopped(T::AccountId),
+		/// The undelivered commands of a terminated session are withdrawn. [worker, session_id,
+		/// num_messages]
+		SessionBacklogWithdrawn(WorkerPublicKey, u32, u32),
+		/// [tolerance]
+		SettlementAuditToleranceChanged(Option<Permill>),
+		/// A settlement rejected by the audit, in the raw U64F64 bits followed by the values in
+		/// the balance scale. [miner, expected_v_bits, reported_v_with_payout_bits, expected_v,
+		/// reported_v_with_payout]
+		SettlementAuditFailed(T::AccountId, u128, u128, BalanceOf<T, I>, BalanceOf<T, I>),
+		/// [interval_sec]
+		MinSessionIntervalChanged(u64),
+		/// [max_skew_ms]
+		MaxClockSkewChanged(Option<u64>),
+		/// [worker, skew_ms]
+		WorkerClockSkewed(WorkerPublicKey, i64),
+		/// [worker]
+		WorkerClockSkewCleared(WorkerPublicKey),
+		/// [allowed]
+		BenchmarkResetAllowedChanged(bool),
+		/// [config]
+		MinWorkerVersionChanged(Option<MinWorkerVersionConfig>),
+		/// An outdated worker accepted in the grace period. [worker, runtime_version]
+		OutdatedWorkerInGrace(WorkerPublicKey, u32),
+		/// The benchmark baseline restarted by a heartbeat with lower iterations. [miner,
+		/// iterations]
+		BenchmarkBaselineReset(T::AccountId, u64),
+		/// [count, block_utilization]
+		EffectiveHeartbeatCountChanged(u32, Perbill),
+		/// [miner, daily_drip]
+		PayoutSmoothingChanged(T::AccountId, Option<BalanceOf<T, I>>),
+		/// [miner, released, reserve_left]
+		PayoutReleased(T::AccountId, BalanceOf<T, I>, BalanceOf<T, I>),
+		/// A settlement deferred for its binding hash mismatching the on-chain binding. [id,
+		/// worker]
+		SettlementDisputed(u64, WorkerPublicKey),
+		/// [id, accepted]
+		SettlementDisputeResolved(u64, bool),
+		/// [gatekeeper, workers_tracked, events_emitted, processing_lag]
+		GatekeeperTelemetryReported(WorkerPublicKey, u32, u32, u32),
+		/// [scores]
+		ConfidenceScoresChanged(Option<[u128; 5]>),
+		/// The free balance of a reclaimed miner was routed to its refund beneficiary. [miner,
+		/// beneficiary, amount]
+		RefundRouted(T::AccountId, T::AccountId, BalanceOf<T, I>),
+		/// Applying the mining updates is paused by a runtime upgrade. [block]
+		SettlementPaused(T::BlockNumber),
+		/// The settlement pause is lifted and the buffered mining updates are applied.
+		/// [num_updates]
+		SettlementPauseLifted(u32),
+		/// A miner moved from one state to another, either by a call or by the gatekeeper.
+		/// [miner, old_state, new_state, block]
+		MinerStateChanged(T::AccountId, MinerState, MinerState, T::BlockNumber),
+		/// [period]
+		BenchmarkValidityPeriodChanged(Option<u64>),
+		/// [config]
+		RelayerTipChanged(Option<RelayerTipConfig<BalanceOf<T, I>>>),
+		/// [relayer, worker, amount]
+		RelayerTipped(T::AccountId, WorkerPublicKey, BalanceOf<T, I>),
+		/// [num_deferred, backlog]
+		StatusUpdatesDeferred(u32, u32),
+		/// [num_applied, backlog]
+		DeferredStatusUpdatesApplied(u32, u32),
+		/// All the status pages of a gatekeeper block are received. [block_number, pages]
+		StatusUpdatesPaginated(T::BlockNumber, u32),
+		/// The status pages of a gatekeeper block ended without the final page.
+		/// [block_number, pages]
+		StatusPagesIncomplete(T::BlockNumber, u32),
+		/// [config]
+		ReputationTiersChanged(Option<ReputationTierConfig>),
+		/// [miner, old_tier, new_tier]
+		MinerTierChanged(T::AccountId, MinerTier, MinerTier),
+		/// The dust of a miner reached `DUST_FLUSH_THRESHOLD` and was flushed to the subsidy
+		/// pool. [miner, amount]
+		RewardDustFlushed(T::AccountId, BalanceOf<T, I>),
+		/// [worker, operator, amount]
+		OperatorBonded(WorkerPublicKey, T::AccountId, BalanceOf<T, I>),
+		/// [worker, amount, unlock_at]
+		OperatorUnbonding(WorkerPublicKey, BalanceOf<T, I>, u64),
+		/// [worker, amount]
+		OperatorBondWithdrawn(WorkerPublicKey, BalanceOf<T, I>),
+		/// The slash of a miner was charged to the bond of the operator of its worker.
+		/// [worker, operator, amount]
+		OperatorBondSlashed(WorkerPublicKey, T::AccountId, BalanceOf<T, I>),
+		/// [period]
+		OperatorUnbondingPeriodChanged(u64),
+		/// A settlement payout exceeded the payout cap of the session and was truncated, in the
+		/// raw U64F64 bits. [miner, reported_payout, capped_payout]
+		PayoutCapped(T::AccountId, u128, u128),
+		/// The part of the reserved stake of a miner slashed at the reclaim, after the operator
+		/// bond is charged. [miner, amount]
+		MinerStakeSlashed(T::AccountId, BalanceOf<T, I>),
+		/// The miner was unbound for the attestation revocation of its worker. [miner, worker,
+		/// session_ended]
+		MinerAttestationRevoked(T::AccountId, WorkerPublicKey, bool),
+		/// [ratio]
+		RevocationSlashRatioChanged(Option<Permill>),
+		/// [tiers]
+		UptimeStreakBonusesChanged(Option<Vec<StreakBonusTier>>),
+		/// A settlement payout raised by the uptime streak of the miner, in the raw U64F64 bits.
+		/// [miner, streak, bonus]
+		UptimeStreakBonus(T::AccountId, u32, u128),
+		/// [sink]
+		RewardSinkChanged(RewardSink),
+		/// A payout parked instead of dispatched. [miner, amount]
+		RewardParked(T::AccountId, BalanceOf<T, I>),
+		/// A payout burnt from the subsidy pool instead of dispatched. [miner, amount]
+		RewardBurnt(T::AccountId, BalanceOf<T, I>),
+		/// The parked payouts of a miner dispatched. [miner, amount]
+		ParkedRewardDrained(T::AccountId, BalanceOf<T, I>),
+		/// [policy]
+		WrongStateSettlePolicyChanged(WrongStateSettlePolicy),
+		/// A settlement skipped because the miner can't be settled in its state.
+		/// [miner, worker, state]
+		SettleIgnoredWrongState(T::AccountId, WorkerPublicKey, MinerState),
+		/// A settlement buffered because the miner can't be settled in its state. [miner, worker]
+		SettleBuffered(T::AccountId, WorkerPublicKey),
+		/// The buffered settlements applied to the session at the reclaim. [miner, payout]
+		PendingSettlementApplied(T::AccountId, u128),
+		/// [cap]
+		StakeCapChanged(Option<StakeCapParams>),
+		/// The unlocked rewards of a pool credited to its stakers. [pid, amount]
+		PoolRewardsReleased(u64, BalanceOf<T, I>),
+		/// A mining update rejected during the settlement pause as the buffer is full. The
+		/// gatekeeper should resend it after the pause. [gk_block_number]
+		PausedMiningUpdateDropped(T::BlockNumber),
+	}
+
+	#[pallet::error]
+	pub enum Error<T, I = ()> {
+		BadSender,
+		InvalidMessage,
+		WorkerNotRegistered,
+		GatekeeperNotRegistered,
+		DuplicateBoundMiner,
+		BenchmarkMissing,
+		MinerNotFound,
+		MinerNotBound,
+		MinerNotReady,
+		MinerNotMining,
+		WorkerNotBound,
+		CoolDownNotReady,
+		InsufficientStake,
+		TooMuchStake,
+		/// The stake is above the cap of the worker's benchmark score
+		StakeExceedsCap,
+		NoUnlockedRewards,
+		/// The replacement worker has a lower benchmark than the current one
+		InferiorBenchmark,
+		InvalidParameter,
+		TrialMiningDisabled,
+		/// Too many operations scheduled at the block
+		ScheduleFull,
+		/// The stake is above the balance not frozen by the other locks
+		InsufficientStakableBalance,
+		NotSoloMiner,
+		/// `online_miners` has reached `MaxOnlineMiners`
+		NetworkAtCapacity,
+		SettlementNotFrozen,
+		/// The gatekeeper protocol version is out of `SupportedGkProtocol`
+		UnsupportedGkProtocol,
+		AlreadyObserving,
+		MinerNotObserving,
+		/// The worker ended its last mining session less than `MinSessionInterval` ago
+		SessionIntervalNotElapsed,
+		/// The pRuntime version of the worker is below `MinWorkerVersion`
+		WorkerVersionTooOld,
+		SettlementDisputeNotFound,
+		/// Too many mining updates buffered during the settlement pause
+		PausedMiningUpdatesFull,
+		/// The benchmark of the worker is older than `BenchmarkValidityPeriod`
+		BenchmarkExpired,
+		/// The worker has no operator bond to unbond or withdraw
+		NoOperatorBond,
+		/// The unbonding operator bond is not unlocked yet
+		OperatorBondLocked,
+		/// The attestation of the worker is revoked until it's attested again
+		AttestationRevoked,
+		/// The parked rewards can only be drained with `RewardSink::Normal`
+		RewardSinkNotNormal,
+		/// The operator bond is slashable by the reclaim of the bound miner
+		OperatorBondInUse,
+		/// The swap of the miner of a stake pool is not approved by the pool owner
+		PoolMinerNotSwappable,
+		/// The tokenomic parameters are not set yet
+		TokenomicParametersNotSet,
+	}
+
+	pub type BalanceOf<T, I = ()> =
+		<<T as Config<I>>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+	#[pallet::call]
+	impl<T: Config<I>, I: 'static> Pallet<T, I>
+	where
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		#[pallet::weight(0)]
+		pub fn set_cool_down_expiration(origin: OriginFor<T>, period: u64) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+
+			CoolDownPeriod::<T, I>::mutate(|p| *p = period);
+			Self::deposit_event(Event::<T, I>::CoolDownExpirationChanged(period));
+			Ok(())
+		}
+
+		/// Unbinds a worker from the given miner (or pool sub-account).
+		///
+		/// It will trigger a force stop of mining if the miner is still in mining state. The sender
+		/// must be the worker operator or its delegate (see `registry::OperatorDelegate`).
+		#[pallet::weight(0)]
+		pub fn unbind(origin: OriginFor<T>, miner: T::AccountId) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let pubkey = Self::ensure_miner_bound(&miner)?;
+			let worker =
+				T::WorkerInfo::worker_info(&pubkey).ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			ensure!(
+				T::WorkerInfo::is_operator_or_delegate(&worker, &who),
+				Error::<T, I>::BadSender
+			);
+			// Always notify the subscriber. Please note that even if the miner is not mining, we
+			// still have to notify the subscriber that an unbinding operation has just happened.
+			Self::unbind_miner(&miner, true)
+		}
+
+		/// Moves a mining session to a replacement worker without stopping the miner.
+		///
+		/// The sender must be the operator of both workers. The miners of the stake pools also
+		/// need the approval of the pool owner (see `stakepool::approve_worker_swap`), which is
+		/// consumed by the swap. The new worker must have an equal or better benchmark.
+		#[pallet::weight(0)]
+		pub fn swap_worker(
+			origin: OriginFor<T>,
+			miner: T::AccountId,
+			new_worker: WorkerPublicKey,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let pubkey = Self::ensure_miner_bound(&miner)?;
+			let worker =
+				T::WorkerInfo::worker_info(&pubkey).ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			ensure!(
+				worker.operator == Some(who.clone()),
+				Error::<T, I>::BadSender
+			);
+			let worker = T::WorkerInfo::worker_info(&new_worker)
+				.ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			ensure!(worker.operator == Some(who), Error::<T, I>::BadSender);
+			ensure!(
+				T::StakePools::pool_of(&pubkey).is_none()
+					|| T::StakePools::is_swap_approved(&pubkey, &new_worker),
+				Error::<T, I>::PoolMinerNotSwappable
+			);
+			Self::swap_miner_worker(&miner, new_worker)
+		}
+
+		/// Turns the miner back to Ready state after cooling down and trigger stake releasing.
+		///
+		/// Note: anyone can trigger cleanup, though it's also scheduled automatically when the
+		/// miner stops mining
+		/// Requires:
+		/// 1. Ther miner is in CoolingDown state and the cool down period has passed
+		#[pallet::weight(0)]
+		pub fn reclaim(origin: OriginFor<T>, miner: T::AccountId) -> DispatchResult {
+			ensure_signed(origin)?;
+			Self::do_reclaim(miner)
+		}
+
+		/// Binds a worker to the sender as a solo miner
+		///
+		/// The sender must be the worker operator.
+		#[pallet::weight(0)]
+		pub fn bind_solo(origin: OriginFor<T>, pubkey: WorkerPublicKey) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let worker =
+				T::WorkerInfo::worker_info(&pubkey).ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			ensure!(
+				worker.operator == Some(who.clone()),
+				Error::<T, I>::BadSender
+			);
+			Self::bind(who, pubkey)
+		}
+
+		/// Starts mining with the stake locked in the sender's account
+		///
+		/// The stake must not exceed `free_stakable_balance`, i.e. it cannot overlap with the
+		/// balance already frozen by the other locks.
+		#[pallet::weight(0)]
+		pub fn start_solo_mining(origin: OriginFor<T>, stake: BalanceOf<T, I>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(
+				stake <= Self::free_stakable_balance(&who),
+				Error::<T, I>::InsufficientStakableBalance
+			);
+			Self::start_mining(who.clone(), stake)?;
+			Self::lock_solo_stake(&who, stake);
+			Ok(())
+		}
+
+		/// Binds a worker to the sender as a solo miner and starts mining in one call
+		///
+		/// The same as `bind_solo` followed by `start_solo_mining`, but atomic.
+		#[pallet::weight(0)]
+		pub fn bind_and_start_solo(
+			origin: OriginFor<T>,
+			pubkey: WorkerPublicKey,
+			stake: BalanceOf<T, I>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let worker =
+				T::WorkerInfo::worker_info(&pubkey).ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			ensure!(
+				worker.operator == Some(who.clone()),
+				Error::<T, I>::BadSender
+			);
+			ensure!(
+				stake <= Self::free_stakable_balance(&who),
+				Error::<T, I>::InsufficientStakableBalance
+			);
+			Self::bind_and_start(who.clone(), pubkey, stake)?;
+			Self::lock_solo_stake(&who, stake);
+			Ok(())
+		}
+
+		/// Stops the solo mining of the sender. The stake is released by `reclaim`.
+		#[pallet::weight(0)]
+		pub fn stop_solo_mining(origin: OriginFor<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::is_solo_miner(&who), Error::<T, I>::NotSoloMiner);
+			Self::stop_mining(who)
+		}
+
+		/// Starts observing the worker bound to the sender, without any stake
+		#[pallet::weight(0)]
+		pub fn start_solo_observing(origin: OriginFor<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			Self::start_observing(who)
+		}
+
+		/// Stops observing the worker bound to the sender
+		#[pallet::weight(0)]
+		pub fn stop_solo_observing(origin: OriginFor<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::is_solo_miner(&who), Error::<T, I>::NotSoloMiner);
+			Self::stop_observing(who)
+		}
+
+		/// Sets or clears the daily payout drip of the sender as a solo miner
+		#[pallet::weight(0)]
+		pub fn set_solo_payout_smoothing(
+			origin: OriginFor<T>,
+			daily_drip: Option<BalanceOf<T, I>>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			ensure!(Self::is_solo_miner(&who), Error::<T, I>::NotSoloMiner);
+			Self::set_payout_smoothing(who, daily_drip)
+		}
+
+		/// Schedules the tokenomic parameters to be updated at block `at`
+		#[pallet::weight(0)]
+		pub fn schedule_tokenomic_update(
+			origin: OriginFor<T>,
+			at: T::BlockNumber,
+			new_params: TokenomicParams,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let op = ScheduledOp::UpdateTokenomic(new_params);
+			Self::schedule(at, op.clone())?;
+			Self::deposit_event(Event::<T, I>::OperationScheduled(at, op));
+			Ok(())
+		}
+
+		/// Triggers a force heartbeat request to all workers by sending a MAX pow target
+		///
+		/// Only for integration test.
+		#[pallet::weight(1)]
+		pub fn force_heartbeat(origin: OriginFor<T>) -> DispatchResult {
+			T::TestingOrigin::ensure_origin(origin)?;
+			Self::push_message(SystemEvent::HeartbeatChallenge(HeartbeatChallenge {
+				seed: U256::zero(),
+				online_target: U256::MAX,
+				targets: None,
+				stake_weighting: None,
+			}));
+			Ok(())
+		}
+
+		/// Requests a heartbeat from each of the given workers at the end of the block
+		#[pallet::weight(0)]
+		pub fn request_heartbeats(
+			origin: OriginFor<T>,
+			workers: Vec<WorkerPublicKey>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(
+				workers.len() <= MAX_HEARTBEAT_TARGETS,
+				Error::<T, I>::InvalidParameter
+			);
+			for worker in workers {
+				Self::add_heartbeat_target(worker);
+			}
+			Ok(())
+		}
+
+		/// Start mining
+		///
+		/// Only for integration test.
+		#[pallet::weight(1)]
+		pub fn force_start_mining(
+			origin: OriginFor<T>,
+			miner: T::AccountId,
+			stake: BalanceOf<T, I>,
+		) -> DispatchResult {
+			T::TestingOrigin::ensure_origin(origin)?;
+			Self::start_mining(miner, stake)?;
+			Ok(())
+		}
+
+		/// Stop mining
+		///
+		/// Only for integration test.
+		#[pallet::weight(1)]
+		pub fn force_stop_mining(origin: OriginFor<T>, miner: T::AccountId) -> DispatchResult {
+			T::TestingOrigin::ensure_origin(origin)?;
+			Self::stop_mining(miner)?;
+			Ok(())
+		}
+
+		/// Updates the tokenomic parameters
+		#[pallet::weight(1)]
+		pub fn update_tokenomic(
+			origin: OriginFor<T>,
+			new_params: TokenomicParams,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			Self::update_tokenomic_parameters(new_params, None);
+			Ok(())
+		}
+
+		/// Updates the per-block tokenomic parameters from the block time independent rates
+		///
+		/// The rates are converted with `ExpectedBlockTimeSec`, and the other parameters are kept.
+		#[pallet::weight(1)]
+		pub fn update_tokenomic_rates(
+			origin: OriginFor<T>,
+			rates: TokenomicHourlyRates,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let block_time_sec = T::ExpectedBlockTimeSec::get();
+			ensure!(block_time_sec > 0, Error::<T, I>::InvalidParameter);
+			let mut params = TokenomicParameters::<T, I>::get()
+				.ok_or(Error::<T, I>::TokenomicParametersNotSet)?;
+			rates.apply(&mut params, block_time_sec);
+			Self::update_tokenomic_parameters(params, None);
+			Self::deposit_event(Event::<T, I>::TokenomicRatesApplied(rates, block_time_sec));
+			Ok(())
+		}
+
+		/// Sets how often the heartbeat challenge is emitted
+		///
+		/// - `interval`: emits a challenge every `interval` blocks
+		/// - `max_outbound`: skips the challenge when the block already has more outbound messages
+		#[pallet::weight(0)]
+		pub fn set_heartbeat_challenge_policy(
+			origin: OriginFor<T>,
+			interval: u32,
+			max_outbound: Option<u32>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(interval > 0, Error::<T, I>::InvalidParameter);
+			HeartbeatChallengeInterval::<T, I>::put(interval);
+			HeartbeatChallengeMaxOutbound::<T, I>::set(max_outbound);
+			Self::deposit_event(Event::<T, I>::HeartbeatChallengePolicyChanged(
+				interval,
+				max_outbound,
+			));
+			Ok(())
+		}
+
+		/// Sets the expected number of heartbeats per block, applied from the next heartbeat
+		/// challenge
+		#[pallet::weight(0)]
+		pub fn set_expected_heartbeat_count(origin: OriginFor<T>, count: u32) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(
+				(MIN_EXPECTED_HEARTBEAT_COUNT..=MAX_EXPECTED_HEARTBEAT_COUNT).contains(&count),
+				Error::<T, I>::InvalidParameter
+			);
+			ExpectedHeartbeatCount::<T, I>::put(count);
+			Self::deposit_event(Event::<T, I>::ExpectedHeartbeatCountChanged(count));
+			Ok(())
+		}
+
+		/// Resolves a disputed settlement, applying it to the miner currently bound to the worker
+		/// if accepted, or dropping it otherwise
+		#[pallet::weight(0)]
+		pub fn resolve_settlement_dispute(
+			origin: OriginFor<T>,
+			id: u64,
+			accept: bool,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let dispute = SettlementDisputes::<T, I>::take(id)
+				.ok_or(Error::<T, I>::SettlementDisputeNotFound)?;
+			if accept {
+				Self::schedule_settlements(vec![dispute.info], None, Self::now_sec())?;
+			}
+			Self::deposit_event(Event::<T, I>::SettlementDisputeResolved(id, accept));
+			Ok(())
+		}
+
+		/// Sets the scores of the confidence levels 1 to 5, or restores the builtin table with
+		/// None
+		///
+		/// Each score must be in [0, 1]. The gatekeepers are notified to keep the shares
+		/// consistent with the chain.
+		#[pallet::weight(0)]
+		pub fn set_confidence_scores(
+			origin: OriginFor<T>,
+			scores: Option<[u128; 5]>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			if let Some(scores) = &scores {
+				let one = FixedPoint::from_num(1).to_bits();
+				ensure!(
+					scores.iter().all(|score| *score <= one),
+					Error::<T, I>::InvalidParameter
+				);
+			}
+			ConfidenceScores::<T, I>::set(scores);
+			Self::push_message(GatekeeperEvent::ConfidenceScoresChanged(scores));
+			Self::deposit_event(Event::<T, I>::ConfidenceScoresChanged(scores));
+			Ok(())
+		}
+
+		/// Lifts the tokenomic freeze requested by the gatekeeper, and applies the buffered
+		/// settlements
+		#[pallet::weight(0)]
+		pub fn resume_settlement(origin: OriginFor<T>) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(
+				SettlementFrozen::<T, I>::take().is_some(),
+				Error::<T, I>::SettlementNotFrozen
+			);
+			let settle = FrozenSettlements::<T, I>::take();
+			let num_settlements = settle.len() as u32;
+			// Released by the governance, not audited
+			Self::schedule_settlements(settle, None, Self::now_sec())?;
+			Self::deposit_event(Event::<T, I>::SettlementResumed(num_settlements));
+			Ok(())
+		}
+
+		/// Sets the weight budget per block to apply the settlements, or applies them in place
+		/// with None
+		#[pallet::weight(0)]
+		pub fn set_settlement_weight_budget(
+			origin: OriginFor<T>,
+			budget: Option<Weight>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			SettlementWeightBudget::<T, I>::set(budget);
+			Self::deposit_event(Event::<T, I>::SettlementWeightBudgetChanged(budget));
+			Ok(())
+		}
+
+		/// Enables the settlement audit with the given tolerance, or disables it with None
+		#[pallet::weight(0)]
+		pub fn set_settlement_audit_tolerance(
+			origin: OriginFor<T>,
+			tolerance: Option<Permill>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			SettlementAuditTolerance::<T, I>::set(tolerance);
+			SettlementAuditEpoch::<T, I>::mutate(|epoch| *epoch = epoch.wrapping_add(1));
+			Self::deposit_event(Event::<T, I>::SettlementAuditToleranceChanged(tolerance));
+			Ok(())
+		}
+
+		/// Sets the minimal interval (in sec) before a worker can start a new mining session
+		#[pallet::weight(0)]
+		pub fn set_min_session_interval(origin: OriginFor<T>, interval: u64) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			MinSessionInterval::<T, I>::put(interval);
+			Self::deposit_event(Event::<T, I>::MinSessionIntervalChanged(interval));
+			Ok(())
+		}
+
+		/// Sets or clears the minimal pRuntime version of the workers, enforced after the grace
+		/// period (in sec)
+		#[pallet::weight(0)]
+		pub fn set_min_worker_version(
+			origin: OriginFor<T>,
+			version: Option<u32>,
+			grace_period: u64,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let config = version.map(|version| MinWorkerVersionConfig {
+				version,
+				enforced_at: Self::now_sec().saturating_add(grace_period),
+			});
+			MinWorkerVersion::<T, I>::set(config.clone());
+			Self::deposit_event(Event::<T, I>::MinWorkerVersionChanged(config));
+			Ok(())
+		}
+
+		/// Enables or disables the benchmark baseline reset on the restarted workers
+		#[pallet::weight(0)]
+		pub fn set_benchmark_reset_allowed(origin: OriginFor<T>, allowed: bool) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			BenchmarkResetAllowed::<T, I>::put(allowed);
+			Self::deposit_event(Event::<T, I>::BenchmarkResetAllowedChanged(allowed));
+			Ok(())
+		}
+
+		/// Sets or clears the max clock skew (in ms) of the workers
+		#[pallet::weight(0)]
+		pub fn set_max_clock_skew(origin: OriginFor<T>, max_skew: Option<u64>) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			MaxClockSkew::<T, I>::set(max_skew);
+			Self::deposit_event(Event::<T, I>::MaxClockSkewChanged(max_skew));
+			Ok(())
+		}
+
+		/// Sets or clears the cap of the online miners, applied to the subsequent starts
+		#[pallet::weight(0)]
+		pub fn set_max_online_miners(origin: OriginFor<T>, max: Option<u32>) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			MaxOnlineMiners::<T, I>::set(max);
+			Self::deposit_event(Event::<T, I>::MaxOnlineMinersChanged(max));
+			Ok(())
+		}
+
+		/// Sets or clears the validity period (in seconds) of the benchmarks to start mining
+		#[pallet::weight(0)]
+		pub fn set_benchmark_validity_period(
+			origin: OriginFor<T>,
+			period: Option<u64>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			BenchmarkValidityPeriod::<T, I>::set(period);
+			Self::deposit_event(Event::<T, I>::BenchmarkValidityPeriodChanged(period));
+			Ok(())
+		}
+
+		/// Enables or disables the stake-weighted heartbeat sampling
+		///
+		/// Only the workers started after enabling know their stake. The others are sampled with
+		/// the average weight.
+		#[pallet::weight(0)]
+		pub fn set_stake_weighted_sampling(origin: OriginFor<T>, enabled: bool) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			StakeWeightedSampling::<T, I>::put(enabled);
+			Self::deposit_event(Event::<T, I>::StakeWeightedSamplingChanged(enabled));
+			Ok(())
+		}
+
+		/// Sets the range of the gatekeeper protocol versions accepted in the mining updates
+		///
+		/// Raise `min_version` only after all the gatekeepers are upgraded.
+		#[pallet::weight(0)]
+		pub fn set_supported_gk_protocol(
+			origin: OriginFor<T>,
+			min_version: u32,
+			max_version: u32,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(
+				min_version <= max_version && max_version <= GK_PROTOCOL_VERSION,
+				Error::<T, I>::InvalidParameter
+			);
+			SupportedGkProtocol::<T, I>::put((min_version, max_version));
+			Self::deposit_event(Event::<T, I>::SupportedGkProtocolChanged(
+				min_version,
+				max_version,
+			));
+			Ok(())
+		}
+
+		/// Sets or clears the bounds of the heartbeat count adjusted by the block utilization
+		///
+		/// The effective count starts from the expected heartbeat count, clamped to the bounds.
+		#[pallet::weight(0)]
+		pub fn set_heartbeat_count_bounds(
+			origin: OriginFor<T>,
+			bounds: Option<(u32, u32)>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			match bounds {
+				Some((min, max)) => {
+					ensure!(
+						MIN_EXPECTED_HEARTBEAT_COUNT <= min
+							&& min <= max && max <= MAX_EXPECTED_HEARTBEAT_COUNT,
+						Error::<T, I>::InvalidParameter
+					);
+					let count = Self::static_heartbeat_count().clamp(min, max);
+					EffectiveHeartbeatCount::<T, I>::put(count);
+				}
+				None => EffectiveHeartbeatCount::<T, I>::kill(),
+			}
+			HeartbeatCountBounds::<T, I>::set(bounds);
+			Self::deposit_event(Event::<T, I>::HeartbeatCountBoundsChanged(bounds));
+			Ok(())
+		}
+
+		/// Sets the number of distinct gatekeepers required to confirm a mining update
+		///
+		/// Set to 0 or 1 to apply the updates from the gatekeeper directly.
+		#[pallet::weight(0)]
+		pub fn set_gatekeeper_quorum(origin: OriginFor<T>, threshold: u32) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			let num_gatekeepers = registry::Gatekeeper::<T>::get().len() as u32;
+			ensure!(
+				threshold <= num_gatekeepers.max(1),
+				Error::<T, I>::InvalidParameter
+			);
+			GatekeeperQuorum::<T, I>::put(threshold);
+			Self::deposit_event(Event::<T, I>::GatekeeperQuorumChanged(threshold));
+			Ok(())
+		}
+
+		/// Sets the bounds of `pha_rate` fed by the price feed, or disables the feed with `None`
+		///
+		/// The bounds are U64F64 bits.
+		#[pallet::weight(0)]
+		pub fn set_pha_rate_bounds(
+			origin: OriginFor<T>,
+			bounds: Option<(u128, u128)>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			if let Some((min, max)) = bounds {
+				ensure!(min > 0 && min <= max, Error::<T, I>::InvalidParameter);
+			}
+			PhaRateBounds::<T, I>::set(bounds);
+			Self::deposit_event(Event::<T, I>::PhaRateBoundsChanged(bounds));
+			Ok(())
+		}
+
+		/// Sets or clears the reward lockup policy
+		///
+		/// Only affects the payouts settled afterward.
+		#[pallet::weight(0)]
+		pub fn set_reward_lockup(
+			origin: OriginFor<T>,
+			lockup: Option<RewardLockupConfig>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			RewardLockup::<T, I>::set(lockup.clone());
+			Self::deposit_event(Event::<T, I>::RewardLockupChanged(lockup));
+			Ok(())
+		}
+
+		/// Sets or clears the trial mining policy
+		///
+		/// Only affects the trials started afterward, except the payout cap.
+		#[pallet::weight(0)]
+		pub fn set_trial_mining(
+			origin: OriginFor<T>,
+			config: Option<TrialMiningConfig<T::BlockNumber>>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			TrialMining::<T, I>::set(config.clone());
+			Self::deposit_event(Event::<T, I>::TrialMiningChanged(config));
+			Ok(())
+		}
+
+		/// Sets or clears the tip to the relayers of the heartbeats
+		#[pallet::weight(0)]
+		pub fn set_relayer_tip(
+			origin: OriginFor<T>,
+			config: Option<RelayerTipConfig<BalanceOf<T, I>>>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			RelayerTip::<T, I>::set(config.clone());
+			Self::deposit_event(Event::<T, I>::RelayerTipChanged(config));
+			Ok(())
+		}
+
+		/// Sets or clears the rules of the miner reputation tiers
+		///
+		/// The reputations are tracked from scratch once the tiers are set.
+		#[pallet::weight(0)]
+		pub fn set_reputation_tiers(
+			origin: OriginFor<T>,
+			config: Option<ReputationTierConfig>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			if let Some(config) = &config {
+				ensure!(
+					config.gold.min_liveness >= config.silver.min_liveness
+						&& config.gold.min_sessions >= config.silver.min_sessions,
+					Error::<T, I>::InvalidParameter
+				);
+			}
+			ReputationTiers::<T, I>::set(config.clone());
+			Self::deposit_event(Event::<T, I>::ReputationTiersChanged(config));
+			Ok(())
+		}
+
+		/// Sets or clears the policy to pay a part of the rewards in a secondary asset
+		#[pallet::weight(0)]
+		pub fn set_secondary_payout(
+			origin: OriginFor<T>,
+			config: Option<SecondaryPayoutConfig>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			SecondaryPayout::<T, I>::set(config.clone());
+			Self::deposit_event(Event::<T, I>::SecondaryPayoutChanged(config));
+			Ok(())
+		}
+
+		/// Claims all the locked rewards of the sender whose lock period has passed
+		#[pallet::weight(0)]
+		pub fn claim_unlocked_rewards(origin: OriginFor<T>) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let amount = Self::take_unlocked_rewards(&RewardBeneficiary::Owner(who.clone()))?;
+			Self::withdraw_subsidy_pool(&who, amount)?;
+			Self::deposit_event(Event::<T, I>::UnlockedRewardsClaimed(who, amount));
+			Ok(())
+		}
+
+		/// Credits the locked rewards of the pool whose lock period has passed to its stakers
+		///
+		/// Anyone can release them, as the rewards only go to the pool.
+		#[pallet::weight(0)]
+		pub fn release_pool_rewards(origin: OriginFor<T>, pid: u64) -> DispatchResult {
+			ensure_signed(origin)?;
+			let amount = Self::take_unlocked_rewards(&RewardBeneficiary::Pool(pid))?;
+			T::StakePools::credit_pool_reward(pid, amount);
+			Self::deposit_event(Event::<T, I>::PoolRewardsReleased(pid, amount));
+			Ok(())
+		}
+
+		/// Posts a personal bond of the worker operator, slashed before the stake of the miner
+		/// when the worker gets slashed
+		///
+		/// The sender must be the worker operator. The bond is moved to `bond_account_id`.
+		#[pallet::weight(0)]
+		pub fn operator_bond(
+			origin: OriginFor<T>,
+			worker: WorkerPublicKey,
+			amount: BalanceOf<T, I>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let worker_info =
+				T::WorkerInfo::worker_info(&worker).ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			ensure!(
+				worker_info.operator == Some(who.clone()),
+				Error::<T, I>::BadSender
+			);
+			ensure!(!amount.is_zero(), Error::<T, I>::InvalidParameter);
+			let mut bond = match OperatorBonds::<T, I>::get(&worker) {
+				// A bond left by a former operator must be withdrawn first
+				Some(bond) => {
+					ensure!(bond.operator == who, Error::<T, I>::BadSender);
+					bond
+				}
+				None => OperatorBond {
+					operator: who.clone(),
+					bonded: Zero::zero(),
+					unbonding: Zero::zero(),
+					unlock_at: 0,
+				},
+			};
+			T::Currency::transfer(&who, &Self::bond_account_id(), amount, KeepAlive)?;
+			bond.bonded = bond.bonded.saturating_add(amount);
+			OperatorBonds::<T, I>::insert(&worker, bond);
+			Self::deposit_event(Event::<T, I>::OperatorBonded(worker, who, amount));
+			Ok(())
+		}
+
+		/// Starts unbonding a part of the operator bond of the worker
+		///
+		/// The amount stays slashable for `OperatorUnbondingPeriod`, and the unbonding period of
+		/// the amount unbonded earlier is restarted. Not allowed while the miner bound to the
+		/// worker is mining or cooling down, as the slash is only charged at the reclaim.
+		#[pallet::weight(0)]
+		pub fn operator_unbond(
+			origin: OriginFor<T>,
+			worker: WorkerPublicKey,
+			amount: BalanceOf<T, I>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut bond =
+				OperatorBonds::<T, I>::get(&worker).ok_or(Error::<T, I>::NoOperatorBond)?;
+			ensure!(bond.operator == who, Error::<T, I>::BadSender);
+			Self::ensure_operator_bond_unslashable(&worker)?;
+			ensure!(
+				!amount.is_zero() && amount <= bond.bonded,
+				Error::<T, I>::InvalidParameter
+			);
+			let unlock_at = Self::now_sec().saturating_add(OperatorUnbondingPeriod::<T, I>::get());
+			bond.bonded -= amount;
+			bond.unbonding = bond.unbonding.saturating_add(amount);
+			bond.unlock_at = unlock_at;
+			OperatorBonds::<T, I>::insert(&worker, bond);
+			Self::deposit_event(Event::<T, I>::OperatorUnbonding(worker, amount, unlock_at));
+			Ok(())
+		}
+
+		/// Withdraws the unlocked unbonding operator bond of the worker to the operator
+		///
+		/// Not allowed while the miner bound to the worker is mining or cooling down.
+		#[pallet::weight(0)]
+		pub fn withdraw_operator_bond(
+			origin: OriginFor<T>,
+			worker: WorkerPublicKey,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			let mut bond =
+				OperatorBonds::<T, I>::get(&worker).ok_or(Error::<T, I>::NoOperatorBond)?;
+			ensure!(bond.operator == who, Error::<T, I>::BadSender);
+			Self::ensure_operator_bond_unslashable(&worker)?;
+			ensure!(!bond.unbonding.is_zero(), Error::<T, I>::NoOperatorBond);
+			ensure!(
+				Self::now_sec() >= bond.unlock_at,
+				Error::<T, I>::OperatorBondLocked
+			);
+			let amount = bond.unbonding;
+			T::Currency::transfer(&Self::bond_account_id(), &who, amount, AllowDeath)?;
+			bond.unbonding = Zero::zero();
+			if bond.bonded.is_zero() {
+				OperatorBonds::<T, I>::remove(&worker);
+			} else {
+				OperatorBonds::<T, I>::insert(&worker, bond);
+			}
+			Self::deposit_event(Event::<T, I>::OperatorBondWithdrawn(worker, amount));
+			Ok(())
+		}
+
+		/// Sets the time (in sec) an unbonded operator bond stays slashable
+		#[pallet::weight(0)]
+		pub fn set_operator_unbonding_period(origin: OriginFor<T>, period: u64) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			OperatorUnbondingPeriod::<T, I>::put(period);
+			Self::deposit_event(Event::<T, I>::OperatorUnbondingPeriodChanged(period));
+			Ok(())
+		}
+
+		/// Sets the min ratio of the stake slashed for a session ended by an attestation
+		/// revocation, or slashes the whole stake with `None`
+		#[pallet::weight(0)]
+		pub fn set_revocation_slash_ratio(
+			origin: OriginFor<T>,
+			ratio: Option<Permill>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			RevocationSlashRatio::<T, I>::set(ratio);
+			Self::deposit_event(Event::<T, I>::RevocationSlashRatioChanged(ratio));
+			Ok(())
+		}
+
+		/// Sets or clears the payout bonus tiers of the uptime streaks
+		///
+		/// The tiers must be in the strictly ascending order of `min_streak`, with the bonuses
+		/// not decreasing and up to `MAX_STREAK_BONUS`.
+		#[pallet::weight(0)]
+		pub fn set_uptime_streak_bonuses(
+			origin: OriginFor<T>,
+			tiers: Option<Vec<StreakBonusTier>>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			if let Some(tiers) = &tiers {
+				ensure!(
+					tiers.len() <= MAX_STREAK_BONUS_TIERS,
+					Error::<T, I>::InvalidParameter
+				);
+				ensure!(
+					tiers
+						.windows(2)
+						.all(|w| w[0].min_streak < w[1].min_streak && w[0].bonus <= w[1].bonus),
+					Error::<T, I>::InvalidParameter
+				);
+				ensure!(
+					tiers.iter().all(|t| t.bonus <= MAX_STREAK_BONUS),
+					Error::<T, I>::InvalidParameter
+				);
+			}
+			UptimeStreakBonuses::<T, I>::set(tiers.clone());
+			Self::deposit_event(Event::<T, I>::UptimeStreakBonusesChanged(tiers));
+			Ok(())
+		}
+
+		/// Switches where the settlement payouts go
+		///
+		/// Used to park or burn the payouts when the `LifecycleObserver` can't handle them safely
+		/// (e.g. a critical bug in the stake pools).
+		#[pallet::weight(0)]
+		pub fn set_reward_sink(origin: OriginFor<T>, sink: RewardSink) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ActiveRewardSink::<T, I>::put(sink);
+			Self::deposit_event(Event::<T, I>::RewardSinkChanged(sink));
+			Ok(())
+		}
+
+		/// Sets how the settlements of the miners in a wrong state are handled
+		#[pallet::weight(0)]
+		pub fn set_wrong_state_settle_policy(
+			origin: OriginFor<T>,
+			policy: WrongStateSettlePolicy,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			WrongStateSettle::<T, I>::put(policy);
+			Self::deposit_event(Event::<T, I>::WrongStateSettlePolicyChanged(policy));
+			Ok(())
+		}
+
+		/// Sets or disables the benchmark-scaled stake cap of the miners
+		#[pallet::weight(0)]
+		pub fn set_stake_cap(origin: OriginFor<T>, cap: Option<StakeCapParams>) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			StakeCap::<T, I>::set(cap.clone());
+			Self::deposit_event(Event::<T, I>::StakeCapChanged(cap));
+			Ok(())
+		}
+
+		/// Dispatches the parked payouts of the `miners` as normal settlements
+		///
+		/// Only allowed after the sink is switched back to `RewardSink::Normal`.
+		#[pallet::weight(0)]
+		pub fn drain_parked_rewards(
+			origin: OriginFor<T>,
+			miners: Vec<T::AccountId>,
+		) -> DispatchResult {
+			T::GovernanceOrigin::ensure_origin(origin)?;
+			ensure!(
+				Self::reward_sink() == RewardSink::Normal,
+				Error::<T, I>::RewardSinkNotNormal
+			);
+			let now = Self::now_sec();
+			for miner in miners {
+				let parked = match ParkedRewards::<T, I>::take(&miner) {
+					Some(parked) => parked,
+					None => continue,
+				};
+				let settle = SettleInfo {
+					pubkey: parked.worker,
+					v: Self::miners(&miner)
+						.map(|info| info.v.to_bits())
+						.unwrap_or_default(),
+					payout: parked.amount.to_fixed().to_bits(),
+				};
+				Self::deliver_reward(&miner, &settle, now);
+				Self::deposit_event(Event::<T, I>::ParkedRewardDrained(miner, parked.amount));
+			}
+			Ok(())
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		/// Returns `topic` of this instance, with `TopicPrefix` inserted after the leading `^` of
+		/// the gatekeeper topics
+		///
+		/// Applies to both the consumed and the produced topics, so the gatekeeper serving an
+		/// instance other than the default one must subscribe and send with the same prefix.
+		pub fn instance_topic(topic: &[u8]) -> Vec<u8> {
+			let (mark, path) = match topic.split_first() {
+				Some((b'^', path)) => (&topic[..1], path),
+				_ => (&topic[..0], topic),
+			};
+			[mark, T::TopicPrefix::get(), path].concat()
+		}
+	}
+
+	#[pallet::extra_constants]
+	impl<T: Config<I>, I: 'static> Pallet<T, I> {
+		/// The number of blocks per hour with `ExpectedBlockTimeSec`
+		#[allow(non_snake_case)]
+		fn BlocksPerHour() -> u32 {
+			3600 / T::ExpectedBlockTimeSec::get().max(1)
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config<I>, I: 'static> Hooks<T::BlockNumber> for Pallet<T, I>
+	where
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		fn on_initialize(n: T::BlockNumber) -> Weight {
+			Self::maybe_lift_settlement_pause(n)
+				.saturating_add(Self::dispatch_scheduled_ops(n))
+				.saturating_add(Self::drain_settlement_queue())
+				.saturating_add(Self::drain_status_updates())
+		}
+
+		fn on_finalize(n: T::BlockNumber) {
+			ChallengeTimes::<T, I>::remove(n.saturating_sub(CHALLENGE_TIME_TTL_BLOCKS.into()));
+			Self::adjust_heartbeat_count();
+			if Self::should_challenge(n) {
+				Self::heartbeat_challenge();
+			}
+			Self::challenge_heartbeat_targets();
+			Self::maybe_feed_pha_rate();
+			Self::track_chain_downtime();
+			Self::update_digest(n);
+		}
+
+		fn on_idle(n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+			let mut used_weight = Self::recount_on_idle(n, remaining_weight);
+			used_weight +=
+				Self::prune_heartbeat_history(remaining_weight.saturating_sub(used_weight));
+			used_weight +=
+				Self::prune_settlement_history(remaining_weight.saturating_sub(used_weight));
+			used_weight
+		}
+
+		fn on_runtime_upgrade() -> Weight {
+			let mut weight = T::DbWeight::get().reads(1);
+			if MiningStorageVersion::<T, I>::get() >= STORAGE_VERSION {
+				return weight;
+			}
+			if MiningStorageVersion::<T, I>::get() < 1 {
+				weight += Self::migrate_miners_v1();
+			}
+			// Holds the mining updates racing with the migrations until a later block
+			let now = frame_system::Pallet::<T>::block_number();
+			SettlementPaused::<T, I>::put(now);
+			Self::deposit_event(Event::<T, I>::SettlementPaused(now));
+			weight + T::DbWeight::get().writes(1)
+		}
+
+		/// Checks the mining invariants after the migrations, since the hooks of this FRAME version
+		/// have no `try_state`
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			Self::do_try_state()
+		}
+	}
+
+	// - Properly handle heartbeat message.
+	impl<T: Config<I>, I: 'static> Pallet<T, I>
+	where
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		pub fn account_id() -> T::AccountId {
+			T::PalletId::get().into_account()
+		}
+
+		/// The account holding the operator bonds, apart from the subsidy pool
+		pub fn bond_account_id() -> T::AccountId {
+			T::PalletId::get().into_sub_account(b"operator-bonds")
+		}
+
+		/// All the message queue topics the pallet produces or consumes, for the relayers to
+		/// validate their subscriptions
+		pub fn topics() -> Vec<TopicInfo> {
+			fn info(
+				topic: Vec<u8>,
+				direction: TopicDirection,
+				type_name: &[u8],
+				version: u32,
+			) -> TopicInfo {
+				TopicInfo {
+					topic,
+					direction,
+					type_name: type_name.to_vec(),
+					version,
+				}
+			}
+			fn consumed<T: Config<I>, I: 'static, M: BindTopic>(
+				type_name: &[u8],
+				version: u32,
+			) -> TopicInfo
+			where
+				BalanceOf<T, I>: FixedPointConvert,
+			{
+				info(
+					Pallet::<T, I>::instance_topic(M::TOPIC),
+					TopicDirection::Consumed,
+					type_name,
+					version,
+				)
+			}
+			fn produced<T: Config<I>, I: 'static, M: BindTopic>(type_name: &[u8]) -> TopicInfo
+			where
+				BalanceOf<T, I>: FixedPointConvert,
+			{
+				info(
+					Pallet::<T, I>::instance_topic(M::TOPIC),
+					TopicDirection::Produced,
+					type_name,
+					0,
+				)
+			}
+			let (_, gk_version) = Self::supported_gk_protocol();
+			vec![
+				consumed::<T, I, MiningInfoUpdateEvent<T::BlockNumber>>(
+					b"MiningInfoUpdateEvent<BlockNumber>",
+					gk_version,
+				),
+				consumed::<T, I, MiningInfoUpdateConfirm>(b"MiningInfoUpdateConfirm", 0),
+				consumed::<T, I, MiningEmergencyEvent>(b"MiningEmergencyEvent", 0),
+				consumed::<T, I, GatekeeperTelemetry>(b"GatekeeperTelemetry", 0),
+				consumed::<T, I, MiningReportEvent>(b"MiningReportEvent", 0),
+				produced::<T, I, SystemEvent>(b"SystemEvent"),
+				produced::<T, I, GatekeeperEvent>(b"GatekeeperEvent"),
+			]
+		}
+
+		/// Continues the recount of the online miner counters with the `remaining_weight`,
+		/// starting a new round every `ONLINE_MINERS_RECOUNT_INTERVAL` blocks
+		fn recount_on_idle(n: T::BlockNumber, remaining_weight: Weight) -> Weight {
+			let db_weight = T::DbWeight::get();
+			let base_weight = db_weight.reads_writes(2, 2);
+			if remaining_weight < base_weight {
+				return 0;
+			}
+			if !OnlineMinersRecount::<T, I>::exists() {
+				if !(n % ONLINE_MINERS_RECOUNT_INTERVAL.into()).is_zero() {
+					return db_weight.reads(1);
+				}
+				OnlineMinersRecount::<T, I>::put((Vec::<u8>::new(), OnlineMinerCounts::default()));
+			}
+			let item_weight = db_weight.reads(1);
+			let max_items = if item_weight == 0 {
+				ONLINE_MINERS_RECOUNT_CHUNK
+			} else {
+				((remaining_weight - base_weight) / item_weight)
+					.min(ONLINE_MINERS_RECOUNT_CHUNK as Weight) as u32
+			};
+			let scanned = Self::recount_online_miners(max_items);
+			base_weight + item_weight * scanned as Weight
+		}
+
+		/// Prunes the expired heartbeats with the `remaining_weight`, continuing from the last
+		/// scanned miner
+		fn prune_heartbeat_history(remaining_weight: Weight) -> Weight {
+			let db_weight = T::DbWeight::get();
+			let base_weight = db_weight.reads_writes(1, 1);
+			if remaining_weight < base_weight {
+				return 0;
+			}
+			let item_weight = db_weight.reads_writes(1, 1);
+			let max_items = if item_weight == 0 {
+				HEARTBEAT_HISTORY_PRUNE_CHUNK
+			} else {
+				((remaining_weight - base_weight) / item_weight)
+					.min(HEARTBEAT_HISTORY_PRUNE_CHUNK as Weight) as u32
+			};
+			let expire_before = Self::now_sec().saturating_sub(HEARTBEAT_HISTORY_TTL_SEC);
+			let (cursor, scanned) = bounded_history::prune_map::<HeartbeatHistory<T, I>, _, _>(
+				HeartbeatHistoryPruneCursor::<T, I>::get(),
+				expire_before,
+				max_items,
+			);
+			HeartbeatHistoryPruneCursor::<T, I>::set(cursor);
+			base_weight + item_weight * scanned as Weight
+		}
+
+		/// Prunes the expired settlement histories within `remaining_weight`, the same way as
+		/// `prune_heartbeat_history`. The records are timed by block numbers.
+		fn prune_settlement_history(remaining_weight: Weight) -> Weight {
+			let db_weight = T::DbWeight::get();
+			let base_weight = db_weight.reads_writes(2, 1);
+			if remaining_weight < base_weight {
+				return 0;
+			}
+			let item_weight = db_weight.reads_writes(1, 1);
+			let max_items = if item_weight == 0 {
+				HEARTBEAT_HISTORY_PRUNE_CHUNK
+			} else {
+				((remaining_weight - base_weight) / item_weight)
+					.min(HEARTBEAT_HISTORY_PRUNE_CHUNK as Weight) as u32
+			};
+			let block_time = T::ExpectedBlockTimeSec::get().max(1) as u64;
+			let ttl_blocks = SETTLEMENT_HISTORY_TTL_SEC / block_time;
+			let expire_before = Self::block_number_u64().saturating_sub(ttl_blocks);
+			let (cursor, scanned) = bounded_history::prune_map::<SettlementHistory<T, I>, _, _>(
+				SettlementHistoryPruneCursor::<T, I>::get(),
+				expire_before,
+				max_items,
+			);
+			SettlementHistoryPruneCursor::<T, I>::set(cursor);
+			base_weight + item_weight * scanned as Weight
+		}
+
+		fn block_number_u64() -> u64 {
+			frame_system::Pallet::<T>::block_number().saturated_into()
+		}
+
+		/// Adds a settlement of the miner to `SettlementHistory` at the current block
+		fn record_settlement(miner: &T::AccountId, record: SettlementRecord<BalanceOf<T, I>>) {
+			let block = Self::block_number_u64();
+			SettlementHistory::<T, I>::mutate(miner, |history| {
+				history.push(block, record, MAX_SETTLEMENT_HISTORY);
+			});
+		}
+
+		/// Returns the `page`-th page of the miners in `state`
+		pub fn miners_by_state(state: MinerState, page: u32) -> Vec<T::AccountId> {
+			MinersByState::<T, I>::iter_prefix(&state)
+				.skip(page as usize * MINERS_BY_STATE_PAGE_SIZE)
+				.take(MINERS_BY_STATE_PAGE_SIZE)
+				.map(|(miner, _)| miner)
+				.collect()
+		}
+
+		/// Exports the miners, the bindings, the stakes, the counters and the parameters
+		pub fn export_mining_state() -> MiningStateSnapshot<T::AccountId, BalanceOf<T, I>> {
+			MiningStateSnapshot {
+				miners: Miners::<T, I>::iter().collect(),
+				bindings: MinerBindings::<T, I>::iter().collect(),
+				stakes: Stakes::<T, I>::iter().collect(),
+				solo_miners: SoloMiners::<T, I>::iter()
+					.filter_map(|(miner, solo)| if solo { Some(miner) } else { None })
+					.collect(),
+				online_miners: Self::online_miners(),
+				observing_miners: ObservingMiners::<T, I>::get(),
+				online_stake: OnlineStake::<T, I>::get(),
+				next_session_id: NextSessionId::<T, I>::get(),
+				cool_down_period: CoolDownPeriod::<T, I>::get(),
+				tokenomic_parameters: TokenomicParameters::<T, I>::get(),
+			}
+		}
+
+		/// Imports a snapshot exported by `export_mining_state`, used by the genesis builder
+		///
+		/// The bindings commitment is rebuilt from the imported bindings. The balances and the
+		/// locks of the stakes are not imported.
+		pub fn import_mining_state(snapshot: MiningStateSnapshot<T::AccountId, BalanceOf<T, I>>) {
+			for (miner, info) in snapshot.miners {
+				MinersByState::<T, I>::insert(&info.state, &miner, ());
+				Miners::<T, I>::insert(&miner, info);
+			}
+			for (miner, worker) in snapshot.bindings {
+				MinerBindings::<T, I>::insert(&miner, &worker);
+				WorkerBindings::<T, I>::insert(&worker, &miner);
+				Self::update_bindings_commitment(&worker, Some(&miner));
+			}
+			for (miner, stake) in snapshot.stakes {
+				Stakes::<T, I>::insert(&miner, stake);
+			}
+			for miner in snapshot.solo_miners {
+				SoloMiners::<T, I>::insert(&miner, true);
+			}
+			Self::recount_miner_states();
+			ObservingMiners::<T, I>::put(snapshot.observing_miners);
+			OnlineStake::<T, I>::put(snapshot.online_stake);
+			NextSessionId::<T, I>::put(snapshot.next_session_id);
+			CoolDownPeriod::<T, I>::put(snapshot.cool_down_period);
+			if let Some(params) = snapshot.tokenomic_parameters {
+				TokenomicParameters::<T, I>::put(&params);
+				Self::queue_message(GatekeeperEvent::TokenomicParametersChanged(params));
+			}
+		}
+
+		/// Returns the settlements of the miner from `from_block` to `to_block` (inclusive) still
+		/// in `SettlementHistory`
+		pub fn settlements_in_range(
+			miner: &T::AccountId,
+			from_block: T::BlockNumber,
+			to_block: T::BlockNumber,
+		) -> Vec<(T::BlockNumber, SettlementRecord<BalanceOf<T, I>>)> {
+			let from: u64 = from_block.saturated_into();
+			let to: u64 = to_block.saturated_into();
+			SettlementHistory::<T, I>::get(miner)
+				.iter()
+				.filter(|(block, _)| (from..=to).contains(block))
+				.map(|(block, record)| ((*block).saturated_into(), record.clone()))
+				.collect()
+		}
+
+		/// Scans at most `max_items` miners to continue the ongoing recount of the online miner
+		/// counters, and fixes them if the round is finished. Returns the number of scanned miners.
+		fn recount_online_miners(max_items: u32) -> u32 {
+			let (cursor, mut counted) = match OnlineMinersRecount::<T, I>::get() {
+				Some(recount) => recount,
+				None => return 0,
+			};
+			let mut iter = if cursor.is_empty() {
+				Miners::<T, I>::iter()
+			} else {
+				Miners::<T, I>::iter_from(cursor)
+			};
+			let mut scanned = 0u32;
+			while scanned < max_items {
+				match iter.next() {
+					Some((_, info)) => {
+						scanned += 1;
+						counted.update(&info.state, true);
+					}
+					None => {
+						OnlineMinersRecount::<T, I>::kill();
+						let recorded = OnlineMinerCounts::current::<T, I>();
+						if recorded != counted {
+							IdleMiners::<T, I>::put(counted.idle);
+							ActiveMiners::<T, I>::put(counted.active);
+							UnresponsiveMiners::<T, I>::put(counted.unresponsive);
+							Self::deposit_event(Event::<T, I>::CounterReconciled(
+								recorded.total(),
+								counted.total(),
+							));
+						}
+						return scanned;
+					}
+				}
+			}
+			OnlineMinersRecount::<T, I>::put((iter.last_raw_key().to_vec(), counted));
+			scanned
+		}
+
+		/// Validates the invariants of the mining storage
+		///
+		/// - the miner and worker bindings are symmetric
+		/// - `online_miners`, the sum of `IdleMiners`, `ActiveMiners` and `UnresponsiveMiners`,
+		///   equals the number of the online miners
+		/// - the counters of the online states match `MinersByState`
+		/// - all the mining and cooling down miners have their stake recorded
+		/// - no miner has V above v_max
+		#[cfg(any(test, feature = "try-runtime"))]
+		pub fn do_try_state() -> Result<(), &'static str> {
+			for (miner, worker) in MinerBindings::<T, I>::iter() {
+				if WorkerBindings::<T, I>::get(&worker).as_ref() != Some(&miner) {
+					return Err("MinerBindings entry without the matching WorkerBindings");
+				}
+			}
+			for (worker, miner) in WorkerBindings::<T, I>::iter() {
+				if MinerBindings::<T, I>::get(&miner).as_ref() != Some(&worker) {
+					return Err("WorkerBindings entry without the matching MinerBindings");
+				}
+			}
+			let v_max = TokenomicParameters::<T, I>::get().map(|params| params.v_max);
+			let mut online = 0u32;
+			for (miner, info) in Miners::<T, I>::iter() {
+				if info.state.is_online() {
+					online += 1;
+				}
+				// The stake is held until the reclaim
+				if info.state != MinerState::Ready && !Stakes::<T, I>::contains_key(&miner) {
+					return Err("Mining miner without stake");
+				}
+				if let Some(v_max) = v_max {
+					if info.v.to_bits() > v_max {
+						return Err("Miner V exceeds v_max");
+					}
+				}
+			}
+			if Self::online_miners() != online {
+				return Err("online_miners mismatches the online miners");
+			}
+			let count =
+				|state: MinerState| MinersByState::<T, I>::iter_prefix(&state).count() as u32;
+			if IdleMiners::<T, I>::get() != count(MinerState::MiningIdle)
+				|| ActiveMiners::<T, I>::get() != count(MinerState::MiningActive)
+				|| UnresponsiveMiners::<T, I>::get() != count(MinerState::MiningUnresponsive)
+			{
+				return Err("The miner state counters mismatch MinersByState");
+			}
+			Ok(())
+		}
+
+		/// Returns the last updated V of a miner, in the raw U64F64 bits and in the balance scale
+		pub fn miner_v(miner: &T::AccountId) -> Option<(u128, BalanceOf<T, I>)> {
+			Miners::<T, I>::get(miner)
+				.map(|info| (info.v.to_bits(), info.v_balance.saturated_into()))
+		}
+
+		/// Returns the rolling average heartbeat latency (in ms) of a miner
+		pub fn heartbeat_latency(miner: &T::AccountId) -> Option<u64> {
+			Miners::<T, I>::get(miner).map(|info| info.heartbeat_latency)
+		}
+
+		/// Migrates `MinerInfo` to the current layout, keeping the accumulated reward, and builds
+		/// `MinersByState` and the per state counters of the existing miners
+		///
+		/// The sessions in progress are counted from now on.
+		fn migrate_miners_v1() -> Weight {
+			let now = Self::now_sec();
+			let mut translated = 0u64;
+			Miners::<T, I>::translate::<v0::MinerInfo, _>(|_, old| {
+				translated += 1;
+				let mut info = MinerInfo {
+					state: old.state,
+					ve: FixedBits::from_bits(old.ve),
+					v: FixedBits::from_bits(old.v),
+					v_updated_at: old.v_updated_at,
+					benchmark: old.benchmark,
+					cool_down_start: old.cool_down_start,
+					stats: MinerStats {
+						total_reward: old.stats.total_reward,
+						..Default::default()
+					},
+					heartbeat_latency: 0,
+					v_balance: 0,
+					session_start: now,
+					session_payout: 0,
+				};
+				info.set_v(FixedBits::from_bits(old.v));
+				Some(info)
+			});
+			for (miner, info) in Miners::<T, I>::iter() {
+				MinersByState::<T, I>::insert(&info.state, &miner, ());
+			}
+			Self::recount_miner_states();
+			// Replaced by the sum of the state counters
+			let module_prefix =
+				<Miners<T, I> as frame_support::storage::StoragePrefixedMap<_>>::module_prefix();
+			frame_support::storage::migration::remove_storage_prefix(
+				module_prefix,
+				b"OnlineMiners",
+				&[],
+			);
+			MiningStorageVersion::<T, I>::put(1);
+			T::DbWeight::get().reads_writes(translated * 2, translated * 2 + 5)
+		}
+
+		/// Rebuilds `IdleMiners`, `ActiveMiners` and `UnresponsiveMiners` from `MinersByState`,
+		/// returning the number of the counted miners
+		fn recount_miner_states() -> u32 {
+			let count =
+				|state: MinerState| MinersByState::<T, I>::iter_prefix(&state).count() as u32;
+			let idle = count(MinerState::MiningIdle);
+			let active = count(MinerState::MiningActive);
+			let unresponsive = count(MinerState::MiningUnresponsive);
+			IdleMiners::<T, I>::put(idle);
+			ActiveMiners::<T, I>::put(active);
+			UnresponsiveMiners::<T, I>::put(unresponsive);
+			idle + active + unresponsive
+		}
+
+		/// Checks if the heartbeat challenge should be emitted at block `n`
+		fn should_challenge(n: T::BlockNumber) -> bool {
+			let interval = HeartbeatChallengeInterval::<T, I>::get()
+				.unwrap_or(DEFAULT_HEARTBEAT_CHALLENGE_INTERVAL)
+				.max(1);
+			if n.saturated_into::<u32>() % interval != 0 {
+				return false;
+			}
+			if let Some(max_outbound) = HeartbeatChallengeMaxOutbound::<T, I>::get() {
+				let outbound = mq::OutboundMessages::<T>::decode_len().unwrap_or(0) as u32;
+				if outbound > max_outbound {
+					Self::deposit_event(Event::<T, I>::HeartbeatChallengeSkipped(outbound));
+					return false;
+				}
+			}
+			true
+		}
+
+		/// Returns the seed of the heartbeat challenges of the current block
+		pub fn challenge_seed() -> U256 {
+			let seed_hash = T::Randomness::random(T::RandomnessSubject::get()).0;
+			AsRef::<[u8]>::as_ref(&seed_hash).into()
+		}
+
+		fn heartbeat_challenge() {
+			// Random seed for the heartbeat challenge
+			let seed = Self::challenge_seed();
+			// PoW target for the random sampling. The unresponsive miners don't answer the
+			// challenges, so they are not counted for the expected heartbeats.
+			let online_miners = Self::online_miners();
+			let num_tx = Self::heartbeat_count();
+			let online_target = pow_target(
+				num_tx,
+				Self::responsive_miners(),
+				T::ExpectedBlockTimeSec::get(),
+			);
+			let stake_weighting = if StakeWeightedSampling::<T, I>::get() {
+				Some(StakeWeighting {
+					online_stake: OnlineStake::<T, I>::get().saturated_into(),
+					online_miners,
+				})
+			} else {
+				None
+			};
+			let seed_info = HeartbeatChallenge {
+				seed,
+				online_target,
+				targets: None,
+				stake_weighting,
+			};
+			Self::push_message(SystemEvent::HeartbeatChallenge(seed_info));
+			Self::record_challenge_time();
+			MiningDigest::<T, I>::mutate(|digest| {
+				digest.challenged_at = frame_system::Pallet::<T>::block_number();
+				digest.challenge_target = online_target;
+			});
+		}
+
+		/// Updates the block and the miner counters of `MiningDigest`
+		fn update_digest(n: T::BlockNumber) {
+			MiningDigest::<T, I>::mutate(|digest| {
+				digest.block = n;
+				digest.online_miners = Self::online_miners();
+				digest.idle_miners = IdleMiners::<T, I>::get();
+				digest.active_miners = ActiveMiners::<T, I>::get();
+				digest.unresponsive_miners = UnresponsiveMiners::<T, I>::get();
+			});
+		}
+
+		/// Adds a settlement to the totals of the block in `MiningDigest`
+		fn digest_settlement(old_v: FixedPoint, info: &SettleInfo) {
+			let now = frame_system::Pallet::<T>::block_number();
+			let payout: BalanceOf<T, I> =
+				FixedPointConvert::from_fixed(&info.payout_bits().to_fixed());
+			let v_slash: BalanceOf<T, I> =
+				FixedPointConvert::from_fixed(&old_v.saturating_sub(info.v_bits().to_fixed()));
+			MiningDigest::<T, I>::mutate(|digest| {
+				if digest.settled_at != now {
+					digest.settled_at = now;
+					digest.settled_miners = 0;
+					digest.settled_payout = Zero::zero();
+					digest.settled_v_slash = Zero::zero();
+				}
+				digest.settled_miners = digest.settled_miners.saturating_add(1);
+				digest.settled_payout = digest.settled_payout.saturating_add(payout);
+				digest.settled_v_slash = digest.settled_v_slash.saturating_add(v_slash);
+			});
+		}
+
+		/// Folds the annualized V decay since the last update of V into the effective slash rate
+		/// of the miner
+		fn record_slash_rate(
+			miner: &T::AccountId,
+			miner_info: &MinerInfo,
+			info: &SettleInfo,
+			now: u64,
+		) {
+			let old_v = miner_info.v.to_fixed();
+			let elapsed = now.saturating_sub(miner_info.v_updated_at);
+			if elapsed == 0 || old_v == FixedPoint::from_num(0) {
+				return;
+			}
+			let decay = old_v.saturating_sub(info.v_bits().to_fixed()) / old_v;
+			let annualized = decay.saturating_mul(FixedPoint::from_num(SLASH_RATE_PERIOD_SEC))
+				/ FixedPoint::from_num(elapsed);
+			let sample = Permill::from_parts(
+				annualized
+					.saturating_mul(FixedPoint::from_num(Permill::ACCURACY))
+					.to_num::<u64>()
+					.min(Permill::ACCURACY as u64) as u32,
+			);
+			EffectiveSlashRates::<T, I>::mutate(miner, |rate| {
+				let average = rate.deconstruct() as u64 * 7 / 8 + sample.deconstruct() as u64 / 8;
+				*rate = Permill::from_parts(average as u32);
+			});
+		}
+
+		/// Records the timestamp of the current block as the emission time of its heartbeat
+		/// challenge
+		fn record_challenge_time() {
+			let now_ms = <T as registry::Config>::UnixTime::now()
+				.as_millis()
+				.saturated_into::<u64>();
+			ChallengeTimes::<T, I>::insert(frame_system::Pallet::<T>::block_number(), now_ms);
+		}
+
+		/// Tracks the clock skew of a worker by comparing the challenge time in its heartbeat with
+		/// the emission time of the challenge, and flags the worker in the registry if the skew
+		/// exceeds `MaxClockSkew`
+		fn track_clock_skew(worker: &WorkerPublicKey, challenge_block: u32, challenge_time: u64) {
+			let emitted_at =
+				match ChallengeTimes::<T, I>::get(T::BlockNumber::from(challenge_block)) {
+					Some(emitted_at) => emitted_at,
+					// Too old or not a challenge block
+					None => return,
+				};
+			let skew = (challenge_time as i128 - emitted_at as i128)
+				.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+			ClockSkews::<T, I>::insert(worker, skew);
+			let max_skew = match MaxClockSkew::<T, I>::get() {
+				Some(max_skew) => max_skew,
+				None => return,
+			};
+			let skewed = skew.unsigned_abs() > max_skew;
+			let was_skewed = registry::Pallet::<T>::is_clock_skewed(worker);
+			if skewed == was_skewed {
+				return;
+			}
+			registry::Pallet::<T>::set_clock_skewed(worker, skewed);
+			if skewed {
+				Self::deposit_event(Event::<T, I>::WorkerClockSkewed(worker.clone(), skew));
+			} else {
+				Self::deposit_event(Event::<T, I>::WorkerClockSkewCleared(worker.clone()));
+			}
+		}
+
+		fn static_heartbeat_count() -> u32 {
+			ExpectedHeartbeatCount::<T, I>::get().unwrap_or(DEFAULT_EXPECTED_HEARTBEAT_COUNT)
+		}
+
+		/// Returns the expected heartbeat count of the next challenge
+		pub fn heartbeat_count() -> u32 {
+			match HeartbeatCountBounds::<T, I>::get() {
+				Some(_) => EffectiveHeartbeatCount::<T, I>::get()
+					.unwrap_or_else(Self::static_heartbeat_count),
+				None => Self::static_heartbeat_count(),
+			}
+		}
+
+		/// Feeds the weight utilization of the current block to the moving average, and steps
+		/// the effective heartbeat count by 1/8 towards the bounds if the blocks are too full or
+		/// too empty
+		fn adjust_heartbeat_count() {
+			let (min, max) = match HeartbeatCountBounds::<T, I>::get() {
+				Some(bounds) => bounds,
+				None => return,
+			};
+			let max_weight = <T as frame_system::Config>::BlockWeights::get().max_block;
+			let used_weight = frame_system::Pallet::<T>::block_weight().total();
+			let utilization =
+				Perbill::from_rational(used_weight.min(max_weight), max_weight.max(1));
+			// Exponential moving average with alpha = 1/8
+			let average = BlockUtilization::<T, I>::get();
+			let average = Perbill::from_parts(
+				((average.deconstruct() as u64 * 7 + utilization.deconstruct() as u64) / 8) as u32,
+			);
+			BlockUtilization::<T, I>::put(average);
+
+			let count = Self::heartbeat_count();
+			let step = (count / 8).max(1);
+			let new_count = if average > HEARTBEAT_HIGH_UTILIZATION {
+				count.saturating_sub(step)
+			} else if average < HEARTBEAT_LOW_UTILIZATION {
+				count.saturating_add(step)
+			} else {
+				count
+			}
+			.clamp(min, max);
+			if new_count != count {
+				EffectiveHeartbeatCount::<T, I>::put(new_count);
+				Self::deposit_event(Event::<T, I>::EffectiveHeartbeatCountChanged(
+					new_count, average,
+				));
+			}
+		}
+
+		/// Challenges all the workers in `HeartbeatTargets`, and clears the list
+		fn challenge_heartbeat_targets() {
+			let targets = HeartbeatTargets::<T, I>::take();
+			if targets.is_empty() {
+				return;
+			}
+			Self::push_message(SystemEvent::HeartbeatChallenge(HeartbeatChallenge {
+				seed: Self::challenge_seed(),
+				online_target: U256::MAX,
+				targets: Some(WorkerFilter::with_workers(&targets)),
+				stake_weighting: None,
+			}));
+			Self::record_challenge_time();
+			Self::deposit_event(Event::<T, I>::TargetedHeartbeatChallenge(
+				targets.len() as u32
+			));
+		}
+
+		fn add_heartbeat_target(worker: WorkerPublicKey) {
+			HeartbeatTargets::<T, I>::mutate(|targets| {
+				if targets.len() < MAX_HEARTBEAT_TARGETS && !targets.contains(&worker) {
+					targets.push(worker);
+				}
+			});
+		}
+
+		/// Targets the recovered worker with a heartbeat challenge, unless its reputation tier
+		/// spares it from being targeted again so soon
+		fn add_recovery_target(worker: WorkerPublicKey) {
+			if let Some(config) = ReputationTiers::<T, I>::get() {
+				let now = frame_system::Pallet::<T>::block_number();
+				let interval = WorkerBindings::<T, I>::get(&worker)
+					.and_then(|miner| {
+						config
+							.rule(Reputations::<T, I>::get(&miner).tier)
+							.map(|rule| rule.target_interval)
+					})
+					.unwrap_or(0);
+				if let Some(last) = LastTargetedAt::<T, I>::get(&worker) {
+					if now < last.saturating_add(interval.into()) {
+						return;
+					}
+				}
+				LastTargetedAt::<T, I>::insert(&worker, now);
+			}
+			Self::add_heartbeat_target(worker);
+		}
+
+		/// Updates the liveness score of the miner on a heartbeat or an offline report, moving it
+		/// across the reputation tiers
+		///
+		/// The gatekeeper is told the slash rate multiplier of the new tier.
+		fn update_reputation(miner: &T::AccountId, online: bool) {
+			let config = match ReputationTiers::<T, I>::get() {
+				Some(config) => config,
+				None => return,
+			};
+			let sessions = match Miners::<T, I>::get(miner) {
+				Some(info) => info.stats.sessions_count,
+				None => return,
+			};
+			let (old_tier, new_tier) = Reputations::<T, I>::mutate(miner, |reputation| {
+				reputation.liveness = if online {
+					reputation
+						.liveness
+						.saturating_add(1)
+						.min(MAX_LIVENESS_SCORE)
+				} else {
+					reputation.liveness / 2
+				};
+				let old_tier = reputation.tier;
+				reputation.tier = config.tier_of(reputation.liveness, sessions);
+				(old_tier, reputation.tier)
+			});
+			if old_tier == new_tier {
+				return;
+			}
+			if let Some(worker) = MinerBindings::<T, I>::get(miner) {
+				Self::push_slash_multiplier(worker, config.slash_multiplier(new_tier));
+			}
+			Self::deposit_event(Event::<T, I>::MinerTierChanged(
+				miner.clone(),
+				old_tier,
+				new_tier,
+			));
+		}
+
+		/// Gets the slash rate multiplier of the miner by its reputation tier
+		pub fn slash_multiplier(miner: &T::AccountId) -> Permill {
+			match ReputationTiers::<T, I>::get() {
+				Some(config) => config.slash_multiplier(Reputations::<T, I>::get(miner).tier),
+				None => Permill::one(),
+			}
+		}
+
+		fn push_slash_multiplier(worker: WorkerPublicKey, multiplier: Permill) {
+			Self::push_worker_event(
+				worker,
+				WorkerEvent::TierChanged {
+					slash_multiplier: multiplier.deconstruct(),
+				},
+			);
+		}
+
+		pub fn on_mining_message_received(
+			message: DecodedMessage<MiningReportEvent>,
+		) -> DispatchResult {
+			if let MessageOrigin::Worker(worker) = message.sender {
+				match message.payload {
+					MiningReportEvent::Heartbeat {
+						session_id,
+						challenge_block,
+						challenge_time,
+						iterations,
+						platform,
+						restarted,
+					} => {
+						// Handle with great care!
+						//
+						// In some cases, a message can be delayed, but the worker has been already
+						// unbound or removed (possible?). So when we receive a message, don't
+						// assume the worker is always there and the miner state is complete. So
+						// far it sounds safe to just discard this message, but not interrupt the
+						// entire message queue.
+						//
+						// So we call `ensure_worker_bound` here, and return an error if the worker
+						// is not bound. However if the worker is indeed bound, the rest of the
+						// code assumes the Miners, Workers, and worker score must exist.
+						let miner = Self::ensure_worker_bound(&worker)?;
+						let initial_score = T::WorkerInfo::worker_info(&worker)
+							.expect("Bound worker; qed.")
+							.initial_score
+							.expect("Mining worker has benchmark; qed.");
+						let now = Self::now_sec();
+						let now_ms = <T as registry::Config>::UnixTime::now()
+							.as_millis()
+							.saturated_into::<u64>();
+						// The iterations counter starts over in a new session or a restarted
+						// pRuntime
+						let new_session = HeartbeatHistory::<T, I>::get(&miner)
+							.latest()
+							.map(|(_, record)| record.session_id != session_id)
+							.unwrap_or(false);
+						let allow_reset =
+							BenchmarkResetAllowed::<T, I>::get() && (restarted || new_session);
+						// Measured from the on-chain emission time rather than the reported
+						// `challenge_time`. The responses to the pruned challenges are not sampled.
+						let challenged_at =
+							ChallengeTimes::<T, I>::get(T::BlockNumber::from(challenge_block));
+						Miners::<T, I>::mutate(&miner, |miner_info| {
+							let miner_info = miner_info.as_mut().expect("Bound miner; qed.");
+							let last_iterations = miner_info.benchmark.iterations;
+							miner_info
+								.benchmark
+								.update(now, iterations, initial_score, allow_reset)
+								.expect("Benchmark report must be valid; qed.");
+							if iterations <= last_iterations {
+								Self::deposit_event(Event::<T, I>::BenchmarkBaselineReset(
+									miner.clone(),
+									iterations,
+								));
+							}
+							miner_info.stats.on_heartbeat();
+							if let Some(challenged_at) = challenged_at {
+								let latency = now_ms.saturating_sub(challenged_at);
+								miner_info.record_heartbeat_latency(latency);
+							}
+						});
+						HeartbeatHistory::<T, I>::mutate(&miner, |history| {
+							let record = HeartbeatRecord {
+								session_id,
+								challenge_block,
+								iterations,
+							};
+							history.push(now, record, MAX_HEARTBEAT_HISTORY);
+						});
+						Self::track_clock_skew(&worker, challenge_block, challenge_time);
+						if let Some(status) = platform {
+							registry::Pallet::<T>::on_platform_status(&worker, status);
+						}
+						Self::maybe_tip_relayer(&worker, &miner);
+						Self::update_reputation(&miner, true);
+					}
+				};
+			}
+			Ok(())
+		}
+
+		/// Pays the tip to the third party who submitted the heartbeat of the worker, if any
+		///
+		/// The operator of the worker (or its delegates) and the miner get no tip.
+		fn maybe_tip_relayer(worker: &WorkerPublicKey, miner: &T::AccountId) {
+			let config = match RelayerTip::<T, I>::get() {
+				Some(config) => config,
+				None => return,
+			};
+			let relayer = match mq::Pallet::<T>::current_relayer() {
+				Some(relayer) => relayer,
+				None => return,
+			};
+			if &relayer == miner {
+				return;
+			}
+			if let Some(worker_info) = T::WorkerInfo::worker_info(worker) {
+				if T::WorkerInfo::is_operator_or_delegate(&worker_info, &relayer) {
+					return;
+				}
+			}
+			let now = frame_system::Pallet::<T>::block_number();
+			let (block, paid) = RelayerTipsPaid::<T, I>::get();
+			let paid = if block == now { paid } else { 0 };
+			if paid >= config.max_per_block {
+				return;
+			}
+			if T::Currency::transfer(&Self::account_id(), &relayer, config.amount, KeepAlive)
+				.is_err()
+			{
+				return;
+			}
+			RelayerTipsPaid::<T, I>::put((now, paid + 1));
+			Self::deposit_event(Event::<T, I>::RelayerTipped(
+				relayer,
+				worker.clone(),
+				config.amount,
+			));
+		}
+
+		pub fn on_gk_message_received(
+			message: DecodedMessage<MiningInfoUpdateEvent<T::BlockNumber>>,
+		) -> DispatchResult {
+			if !matches!(message.sender, MessageOrigin::Gatekeeper) {
+				return Err(Error::<T, I>::BadSender.into());
+			}
+
+			let event = message.payload;
+			let (min_version, max_version) = Self::supported_gk_protocol();
+			ensure!(
+				(min_version..=max_version).contains(&event.protocol_version),
+				Error::<T, I>::UnsupportedGkProtocol
+			);
+			if SettlementPaused::<T, I>::exists() {
+				let mut buffer = PausedMiningUpdates::<T, I>::get();
+				if buffer.len() >= MAX_PENDING_MINING_UPDATES {
+					// The message queue ignores the errors, so the gatekeeper learns it here
+					Self::deposit_event(Event::<T, I>::PausedMiningUpdateDropped(
+						event.block_number,
+					));
+					return Err(Error::<T, I>::PausedMiningUpdatesFull.into());
+				}
+				buffer.push(event.encode());
+				PausedMiningUpdates::<T, I>::put(buffer);
+				return Ok(());
+			}
+			Self::accept_mining_update(event)
+		}
+
+		/// Applies a mining update, or waits for the gatekeeper quorum to confirm it
+		fn accept_mining_update(event: MiningInfoUpdateEvent<T::BlockNumber>) -> DispatchResult {
+			if GatekeeperQuorum::<T, I>::get() < 2 {
+				return Self::apply_mining_update(event);
+			}
+			// The block number is re-encoded in the `GkBlockNumber` wire type, so the digest
+			// matches the one confirmed by the gatekeepers whatever `T::BlockNumber` is
+			let digest = crate::hashing::blake2_256(&event.encode());
+			Self::track_mining_update(digest);
+			PendingMiningUpdates::<T, I>::insert(&digest, event);
+			Self::try_apply_mining_update(digest)
+		}
+
+		/// Lifts the settlement pause of a runtime upgrade after the upgrade block, once the
+		/// migrations complete or the pause lasts for `UPGRADE_PAUSE_MAX_BLOCKS`
+		fn maybe_lift_settlement_pause(n: T::BlockNumber) -> Weight {
+			let paused_at = match SettlementPaused::<T, I>::get() {
+				Some(paused_at) => paused_at,
+				None => return T::DbWeight::get().reads(1),
+			};
+			let migrated = MiningStorageVersion::<T, I>::get() >= STORAGE_VERSION;
+			let timeout = n >= paused_at.saturating_add(UPGRADE_PAUSE_MAX_BLOCKS.into());
+			if n <= paused_at || !(migrated || timeout) {
+				return T::DbWeight::get().reads(2);
+			}
+			SettlementPaused::<T, I>::kill();
+			let updates = PausedMiningUpdates::<T, I>::take();
+			let num_updates = updates.len() as u32;
+			for encoded in updates {
+				let event =
+					match MiningInfoUpdateEvent::<T::BlockNumber>::decode(&mut &encoded[..]) {
+						Ok(event) => event,
+						Err(_) => {
+							log::warn!("Failed to decode a paused mining update");
+							continue;
+						}
+					};
+				// Dropped like the ones failing outside of the pause
+				let _ = Self::accept_mining_update(event);
+			}
+			Self::deposit_event(Event::<T, I>::SettlementPauseLifted(num_updates));
+			T::DbWeight::get().reads_writes(3, 2)
+				+ SETTLEMENT_BASE_WEIGHT.saturating_mul(num_updates as Weight)
+		}
+
+		/// Returns the range of the accepted gatekeeper protocol versions
+		pub fn supported_gk_protocol() -> (u32, u32) {
+			SupportedGkProtocol::<T, I>::get().unwrap_or((0, GK_PROTOCOL_VERSION))
+		}
+
+		/// Handles the emergency requests from the gatekeeper
+		pub fn on_gk_emergency_received(
+			message: DecodedMessage<MiningEmergencyEvent>,
+		) -> DispatchResult {
+			if !matches!(message.sender, MessageOrigin::Gatekeeper) {
+				return Err(Error::<T, I>::BadSender.into());
+			}
+			match message.payload {
+				MiningEmergencyEvent::TokenomicFreeze { reason } => {
+					SettlementFrozen::<T, I>::put(&reason);
+					Self::deposit_event(Event::<T, I>::SettlementFrozen(reason));
+				}
+			}
+			Ok(())
+		}
+
+		/// Checks the binding hash of each settlement against the on-chain binding of the worker,
+		/// deferring the mismatched ones to `SettlementDisputes`
+		///
+		/// Returns the settlements with the matched bindings.
+		fn verify_settle_bindings(
+			settle: Vec<SettleInfo>,
+			bindings: &[SettleBinding],
+			block_number: T::BlockNumber,
+		) -> Vec<SettleInfo> {
+			let mut verified = Vec::new();
+			for info in settle {
+				let expected = WorkerBindings::<T, I>::get(&info.pubkey)
+					.map(|miner| crate::hashing::blake2_256(&miner.encode()));
+				let reported = match bindings.iter().find(|b| b.pubkey == info.pubkey) {
+					Some(binding) => binding.binding_hash,
+					None => {
+						Self::dispute_settlement(info, None, block_number);
+						continue;
+					}
+				};
+				if reported == expected {
+					verified.push(info);
+				} else {
+					Self::dispute_settlement(info, reported, block_number);
+				}
+			}
+			verified
+		}
+
+		fn dispute_settlement(
+			info: SettleInfo,
+			binding_hash: Option<[u8; 32]>,
+			block_number: T::BlockNumber,
+		) {
+			let id = NextSettlementDisputeId::<T, I>::get();
+			NextSettlementDisputeId::<T, I>::put(id + 1);
+			let worker = info.pubkey.clone();
+			SettlementDisputes::<T, I>::insert(
+				id,
+				SettlementDispute {
+					info,
+					binding_hash,
+					block_number,
+				},
+			);
+			Self::deposit_event(Event::<T, I>::SettlementDisputed(id, worker));
+		}
+
+		/// Handles a settlement of a miner not in a settleable state by `WrongStateSettle`
+		///
+		/// The buffered settlements of a miner are merged, taking the latest V and the sum of the
+		/// payouts.
+		fn on_wrong_state_settle(miner: &T::AccountId, state: &MinerState, info: &SettleInfo) {
+			match (Self::wrong_state_settle_policy(), state) {
+				(WrongStateSettlePolicy::Buffer, MinerState::MiningCoolingDown) => {
+					PendingSettlements::<T, I>::mutate(miner, |pending| match pending {
+						Some(buffered) => {
+							let payout = FixedPoint::from_bits(buffered.payout)
+								.saturating_add(FixedPoint::from_bits(info.payout));
+							buffered.v = info.v;
+							buffered.payout = payout.to_bits();
+						}
+						None => *pending = Some(info.clone()),
+					});
+					Self::deposit_event(Event::<T, I>::SettleBuffered(
+						miner.clone(),
+						info.pubkey.clone(),
+					));
+				}
+				// The session of a reclaimed miner is closed, leaving nothing to settle
+				_ => {
+					Self::deposit_event(Event::<T, I>::SettleIgnoredWrongState(
+						miner.clone(),
+						info.pubkey.clone(),
+						state.clone(),
+					));
+				}
+			}
+		}
+
+		/// Applies the settlements buffered in the cool down of the miner to its session, before
+		/// the reclaim
+		fn apply_pending_settlement(
+			miner: &T::AccountId,
+			miner_info: &mut MinerInfo,
+			now: u64,
+		) {
+			if let Some(pending) = PendingSettlements::<T, I>::take(miner) {
+				Self::settle_miner(miner, miner_info, &pending, now);
+				Self::deposit_event(Event::<T, I>::PendingSettlementApplied(
+					miner.clone(),
+					pending.payout,
+				));
+			}
+		}
+
+		/// Buffers the settlements during the tokenomic freeze
+		///
+		/// The settlements of the same worker are merged, taking the latest V and the sum of the
+		/// payouts.
+		fn buffer_frozen_settlements(settle: Vec<SettleInfo>) {
+			let mut buffer = FrozenSettlements::<T, I>::get();
+			for info in settle {
+				match buffer.iter_mut().find(|b| b.pubkey == info.pubkey) {
+					Some(buffered) => {
+						let payout = FixedPoint::from_bits(buffered.payout)
+							.saturating_add(FixedPoint::from_bits(info.payout));
+						buffered.v = info.v;
+						buffered.payout = payout.to_bits();
+					}
+					None if buffer.len() < MAX_FROZEN_SETTLEMENTS => buffer.push(info),
+					None => {
+						Self::deposit_event(Event::<T, I>::FrozenSettlementDropped(info.pubkey));
+					}
+				}
+			}
+			FrozenSettlements::<T, I>::put(buffer);
+		}
+
+		/// Records the confirmation of a mining update from a gatekeeper
+		pub fn on_gk_confirm_received(
+			message: DecodedMessage<MiningInfoUpdateConfirm>,
+		) -> DispatchResult {
+			let gatekeeper = match message.sender {
+				MessageOrigin::Worker(pubkey) => pubkey,
+				_ => return Err(Error::<T, I>::BadSender.into()),
+			};
+			ensure!(
+				registry::Gatekeeper::<T>::get().contains(&gatekeeper),
+				Error::<T, I>::BadSender
+			);
+			let digest = message.payload.digest;
+			Self::track_mining_update(digest);
+			MiningUpdateConfirms::<T, I>::mutate(&digest, |confirms| {
+				if !confirms.contains(&gatekeeper) {
+					confirms.push(gatekeeper);
+				}
+			});
+			Self::try_apply_mining_update(digest)
+		}
+
+		/// Records the telemetry of a gatekeeper in `GatekeeperTelemetryLog`
+		pub fn on_gk_telemetry_received(
+			message: DecodedMessage<GatekeeperTelemetry>,
+		) -> DispatchResult {
+			let gatekeeper = match message.sender {
+				MessageOrigin::Worker(pubkey) => pubkey,
+				_ => return Err(Error::<T, I>::BadSender.into()),
+			};
+			ensure!(
+				registry::Gatekeeper::<T>::get().contains(&gatekeeper),
+				Error::<T, I>::BadSender
+			);
+			let telemetry = message.payload;
+			let now = frame_system::Pallet::<T>::block_number();
+			let processing_lag = now
+				.saturated_into::<u32>()
+				.saturating_sub(telemetry.block_number);
+			GatekeeperTelemetryLog::<T, I>::mutate(|log| {
+				if log.len() >= MAX_GATEKEEPER_TELEMETRY {
+					log.remove(0);
+				}
+				log.push(GatekeeperTelemetryRecord {
+					gatekeeper: gatekeeper.clone(),
+					received_at: now,
+					workers_tracked: telemetry.workers_tracked,
+					events_emitted: telemetry.events_emitted,
+					processing_lag,
+				});
+			});
+			Self::deposit_event(Event::<T, I>::GatekeeperTelemetryReported(
+				gatekeeper,
+				telemetry.workers_tracked,
+				telemetry.events_emitted,
+				processing_lag,
+			));
+			Ok(())
+		}
+
+		/// Starts tracking a mining update digest, dropping the oldest one if there are too many
+		fn track_mining_update(digest: [u8; 32]) {
+			MiningUpdateDigests::<T, I>::mutate(|digests| {
+				if digests.contains(&digest) {
+					return;
+				}
+				if digests.len() >= MAX_PENDING_MINING_UPDATES {
+					let dropped = digests.remove(0);
+					PendingMiningUpdates::<T, I>::remove(&dropped);
+					MiningUpdateConfirms::<T, I>::remove(&dropped);
+					Self::deposit_event(Event::<T, I>::MiningUpdateDropped(dropped));
+				}
+				digests.push(digest);
+			});
+		}
+
+		/// Applies a pending mining update if it has been confirmed by enough gatekeepers
+		fn try_apply_mining_update(digest: [u8; 32]) -> DispatchResult {
+			let confirms = MiningUpdateConfirms::<T, I>::get(&digest).len() as u32;
+			if confirms < GatekeeperQuorum::<T, I>::get() {
+				return Ok(());
+			}
+			let event = match PendingMiningUpdates::<T, I>::take(&digest) {
+				Some(event) => event,
+				None => return Ok(()),
+			};
+			MiningUpdateConfirms::<T, I>::remove(&digest);
+			MiningUpdateDigests::<T, I>::mutate(|digests| digests.retain(|d| d != &digest));
+			Self::deposit_event(Event::<T, I>::MiningUpdateConfirmed(digest, confirms));
+			Self::apply_mining_update(event)
+		}
+
+		/// Applies the offline, recovery and settlement reports from the gatekeeper
+		fn apply_mining_update(event: MiningInfoUpdateEvent<T::BlockNumber>) -> DispatchResult {
+			Self::track_status_pages(event.block_number, event.continues);
+			if !event.is_empty() {
+				let now = Self::now_sec();
+
+				// The workers couldn't respond while the chain was down
+				let mut offline = event.offline;
+				if let Some((start, end)) = Self::is_in_downtime_grace(event.timestamp_ms / 1000) {
+					if !offline.is_empty() {
+						Self::deposit_event(Event::<T, I>::OfflineReportsSuppressed(
+							start,
+							end,
+							offline.len() as u32,
+						));
+					}
+					offline.clear();
+				}
+
+				#[cfg(any(test, feature = "settlement-audit"))]
+				Self::trace_status_changes(
+					&offline,
+					&event.recovered_to_online,
+					event.block_number,
+				);
+				let updates = offline
+					.into_iter()
+					.map(StatusUpdate::Offline)
+					.chain(
+						event
+							.recovered_to_online
+							.into_iter()
+							.map(StatusUpdate::Recovered),
+					)
+					.collect();
+				Self::apply_status_updates(updates)?;
+
+				let settle = if event.protocol_version >= 3 {
+					Self::verify_settle_bindings(
+						event.settle,
+						&event.settle_bindings,
+						event.block_number,
+					)
+				} else {
+					event.settle
+				};
+				if SettlementFrozen::<T, I>::exists() {
+					Self::buffer_frozen_settlements(settle);
+				} else {
+					Self::schedule_settlements(settle, Some(event.block_number), now)?;
+				}
+			}
+
+			Ok(())
+		}
+
+		/// Tracks the pages of the status updates paginated by the gatekeeper
+		fn track_status_pages(block_number: T::BlockNumber, continues: bool) {
+			let pages = match StatusContinuation::<T, I>::take() {
+				Some((block, pages)) if block == block_number => pages,
+				Some((block, pages)) => {
+					Self::deposit_event(Event::<T, I>::StatusPagesIncomplete(block, pages));
+					0
+				}
+				None => 0,
+			};
+			if continues {
+				StatusContinuation::<T, I>::put((block_number, pages + 1));
+			} else if pages > 0 {
+				Self::deposit_event(Event::<T, I>::StatusUpdatesPaginated(
+					block_number,
+					pages + 1,
+				));
+			}
+		}
+
+		/// Applies up to `MaxStatusUpdatesPerMessage` status updates in place, deferring the rest
+		/// to `DeferredStatusUpdates`
+		///
+		/// The updates are deferred as a whole behind a non-empty backlog, so that the updates of
+		/// a worker are always applied in order.
+		fn apply_status_updates(mut updates: Vec<StatusUpdate>) -> DispatchResult {
+			let max_updates = T::MaxStatusUpdatesPerMessage::get() as usize;
+			let mut backlog = DeferredStatusUpdates::<T, I>::get();
+			if backlog.is_empty() && updates.len() <= max_updates {
+				for update in updates {
+					Self::apply_status_update(update)?;
+				}
+				return Ok(());
+			}
+			let deferred = if backlog.is_empty() {
+				updates.split_off(max_updates)
+			} else {
+				sp_std::mem::take(&mut updates)
+			};
+			let num_deferred = deferred.len() as u32;
+			backlog.extend(deferred);
+			DeferredStatusUpdates::<T, I>::put(&backlog);
+			Self::deposit_event(Event::<T, I>::StatusUpdatesDeferred(
+				num_deferred,
+				backlog.len() as u32,
+			));
+			for update in updates {
+				Self::apply_status_update(update)?;
+			}
+			Ok(())
+		}
+
+		/// Applies up to `MaxStatusUpdatesPerMessage` deferred status updates
+		fn drain_status_updates() -> Weight {
+			let db_weight = T::DbWeight::get();
+			let mut updates = DeferredStatusUpdates::<T, I>::get();
+			if updates.is_empty() {
+				return db_weight.reads(1);
+			}
+			let max_updates = T::MaxStatusUpdatesPerMessage::get() as usize;
+			let backlog = updates.split_off(updates.len().min(max_updates));
+			let num_applied = updates.len() as u32;
+			for update in updates {
+				if let Err(err) = Self::apply_status_update(update) {
+					log::warn!("Failed to apply a deferred status update: {:?}", err);
+				}
+			}
+			if backlog.is_empty() {
+				DeferredStatusUpdates::<T, I>::kill();
+			} else {
+				DeferredStatusUpdates::<T, I>::put(&backlog);
+			}
+			Self::deposit_event(Event::<T, I>::DeferredStatusUpdatesApplied(
+				num_applied,
+				backlog.len() as u32,
+			));
+			db_weight.reads_writes(1, 1).saturating_add(
+				db_weight
+					.reads_writes(4, 3)
+					.saturating_mul(num_applied as Weight),
+			)
+		}
+
+		/// Updates the state of the miner bound to the worker reported offline or recovered
+		fn apply_status_update(update: StatusUpdate) -> DispatchResult {
+			match update {
+				// worker offline, update bound miner state to unresponsive
+				StatusUpdate::Offline(worker) => {
+					if let Some(account) = WorkerBindings::<T, I>::get(&worker) {
+						// The observing miners can't be slashed
+						if Observers::<T, I>::contains_key(&account) {
+							return Ok(());
+						}
+						let mut miner_info =
+							Self::miners(&account).ok_or(Error::<T, I>::MinerNotFound)?;
+						Self::set_miner_state(
+							&account,
+							&mut miner_info,
+							MinerState::MiningUnresponsive,
+						);
+						Miners::<T, I>::insert(&account, &miner_info);
+						Self::restart_trial(&account);
+						Self::update_reputation(&account, false);
+						UptimeStreaks::<T, I>::remove(&account);
+						Self::deposit_event(Event::<T, I>::MinerEnterUnresponsive(account));
+					}
+				}
+				// worker recovered to online, update bound miner state to idle
+				StatusUpdate::Recovered(worker) => {
+					if let Some(account) = WorkerBindings::<T, I>::get(&worker) {
+						if Observers::<T, I>::contains_key(&account) {
+							return Ok(());
+						}
+						let mut miner_info =
+							Self::miners(&account).ok_or(Error::<T, I>::MinerNotFound)?;
+						Self::set_miner_state(&account, &mut miner_info, MinerState::MiningIdle);
+						Miners::<T, I>::insert(&account, &miner_info);
+						Self::deposit_event(Event::<T, I>::MinerExitUnresponive(account));
+						// Make sure the worker is really back
+						Self::add_recovery_target(worker);
+					}
+				}
+			}
+			Ok(())
+		}
+
+		/// Applies the settlements in place, or queues them if throttled by
+		/// `SettlementWeightBudget`
+		///
+		/// The settlements are queued behind the ones already queued, so that the settlements of a
+		/// worker are always applied in order. The settlements of a message are shuffled by
+		/// `shuffle_settlements` before queued.
+		fn schedule_settlements(
+			settle: Vec<SettleInfo>,
+			block: Option<T::BlockNumber>,
+			now: u64,
+		) -> DispatchResult {
+			let (head, mut tail) = SettlementQueueRange::<T, I>::get();
+			if head == tail && !SettlementWeightBudget::<T, I>::exists() {
+				return Self::apply_settlements(&settle, block, now);
+			}
+			if settle.is_empty() {
+				return Ok(());
+			}
+			let num_settlements = settle.len() as u32;
+			for info in Self::shuffle_settlements(settle) {
+				SettlementQueue::<T, I>::insert(tail, QueuedSettlement { info, block });
+				tail += 1;
+			}
+			SettlementQueueRange::<T, I>::put((head, tail));
+			Self::deposit_event(Event::<T, I>::SettlementsQueued(
+				num_settlements,
+				tail - head,
+			));
+			Ok(())
+		}
+
+		/// Shuffles the settlements with the randomness of the block, so that no worker is
+		/// systematically applied later than the others by the order of the gatekeeper
+		///
+		/// The settlements of the same worker keep their relative order.
+		fn shuffle_settlements(settle: Vec<SettleInfo>) -> Vec<SettleInfo> {
+			let n = settle.len();
+			let mut order: Vec<usize> = (0..n).collect();
+			let mut seed = [0u8; 32];
+			Self::challenge_seed().to_big_endian(&mut seed);
+			let mut hash = crate::hashing::blake2_256(&(b"settlement-shuffle", seed).encode());
+			// Fisher-Yates shuffle with the hash chain of the seed
+			for i in (1..n).rev() {
+				hash = crate::hashing::blake2_256(&hash);
+				let mut buf = [0u8; 8];
+				buf.copy_from_slice(&hash[..8]);
+				let j = (u64::from_le_bytes(buf) % (i as u64 + 1)) as usize;
+				order.swap(i, j);
+			}
+			let workers: Vec<WorkerPublicKey> = settle.iter().map(|s| s.pubkey.clone()).collect();
+			let mut slots: Vec<Option<SettleInfo>> = settle.into_iter().map(Some).collect();
+			order
+				.into_iter()
+				.filter_map(|i| {
+					// The earliest settlement of the worker not taken yet
+					let j = (0..n).find(|&j| workers[j] == workers[i] && slots[j].is_some())?;
+					slots[j].take()
+				})
+				.collect()
+		}
+
+		/// The estimated weight to apply a settlement
+		fn settlement_weight() -> Weight {
+			SETTLEMENT_BASE_WEIGHT.saturating_add(T::DbWeight::get().reads_writes(8, 6))
+		}
+
+		/// Applies the queued settlements within `SettlementWeightBudget`, at least one per block
+		///
+		/// All the queued settlements are applied if the budget is removed. The queue is held
+		/// during the tokenomic freeze.
+		fn drain_settlement_queue() -> Weight {
+			let db_weight = T::DbWeight::get();
+			let (mut head, tail) = SettlementQueueRange::<T, I>::get();
+			let mut weight = db_weight.reads(1);
+			if head == tail {
+				return weight;
+			}
+			weight += db_weight.reads(2);
+			if SettlementFrozen::<T, I>::exists() {
+				return weight;
+			}
+			let budget = SettlementWeightBudget::<T, I>::get().unwrap_or(Weight::max_value());
+			let item_weight = Self::settlement_weight();
+			let now = Self::now_sec();
+			let mut applied = 0u32;
+			while head < tail && (applied == 0 || weight.saturating_add(item_weight) <= budget) {
+				if let Some(queued) = SettlementQueue::<T, I>::take(head) {
+					if let Err(err) = Self::apply_settlements(&[queued.info], queued.block, now) {
+						log::warn!("Failed to apply a queued settlement: {:?}", err);
+					}
+				}
+				head += 1;
+				applied += 1;
+				weight = weight.saturating_add(item_weight);
+			}
+			if head == tail {
+				SettlementQueueRange::<T, I>::kill();
+			} else {
+				SettlementQueueRange::<T, I>::put((head, tail));
+			}
+			Self::deposit_event(Event::<T, I>::QueuedSettlementsApplied(
+				applied,
+				tail - head,
+			));
+			weight.saturating_add(db_weight.writes(1))
+		}
+
+		/// Applies the V updates and the payouts to the bound miners
+		///
+		/// The settlements are audited in the audit mode if the gatekeeper `block` of them is
+		/// given.
+		#[cfg_attr(not(any(test, feature = "settlement-audit")), allow(unused_variables))]
+		fn apply_settlements(
+			settle: &[SettleInfo],
+			block: Option<T::BlockNumber>,
+			now: u64,
+		) -> DispatchResult {
+			for info in settle {
+				if let Some(account) = WorkerBindings::<T, I>::get(&info.pubkey) {
+					let mut miner_info =
+						Self::miners(&account).ok_or(Error::<T, I>::MinerNotFound)?;
+					// The miners may have been reclaimed before the settlement arrives, e.g. during a
+					// tokenomic freeze
+					if !miner_info.state.can_settle() {
+						Self::on_wrong_state_settle(&account, &miner_info.state, info);
+						continue;
+					}
+					#[cfg(any(test, feature = "settlement-audit"))]
+					if let Some(block) = block {
+						if let Err(expected) =
+							Self::audit_settlement(&account, &miner_info, info, block)
+						{
+							let reported = info.v.saturating_add(info.payout);
+							Self::deposit_event(Event::<T, I>::SettlementAuditFailed(
+								account.clone(),
+								expected.to_bits(),
+								reported,
+								FixedPointConvert::from_fixed(&expected),
+								Self::balance_of_bits(reported),
+							));
+							// The payout is dropped, but the gatekeeper has deducted it from its V
+							// anyway. Resync to it, so that the later settlements are audited from
+							// where the gatekeeper is.
+							miner_info.set_v(info.v_bits());
+							miner_info.v_updated_at = now;
+							Miners::<T, I>::insert(&account, &miner_info);
+							continue;
+						}
+					}
+					Self::settle_miner(&account, &mut miner_info, info, now);
+				}
+			}
+			Ok(())
+		}
+
+		/// Applies the V update and the payout of a settlement to the session of the miner
+		fn settle_miner(
+			account: &T::AccountId,
+			miner_info: &mut MinerInfo,
+			info: &SettleInfo,
+			now: u64,
+		) {
+			let info = &Self::settle_trial(account, miner_info, info);
+			let info = &Self::apply_streak_bonus(account, info);
+			let info = &Self::cap_payout(account, miner_info, info, now);
+			Self::digest_settlement(miner_info.v.to_fixed(), info);
+			Self::record_slash_rate(account, miner_info, info, now);
+			miner_info.set_v(info.v_bits());
+			miner_info.v_updated_at = now;
+			let dust = miner_info.stats.on_reward(info.payout_bits());
+			Miners::<T, I>::insert(account, &*miner_info);
+			Self::accrue_dust(account, dust);
+			let settle = Self::lock_reward(account, info, now);
+			let settle = Self::pay_secondary(account, &settle);
+			let settle = Self::smooth_payout(account, &settle, now);
+			Self::dispatch_reward(account, &settle, now);
+			Self::record_settlement(
+				account,
+				SettlementRecord {
+					v: info.v,
+					payout: FixedPointConvert::from_bits(info.payout),
+					slashed: Zero::zero(),
+				},
+			);
+			Self::deposit_event(Event::<T, I>::MinerSettled(
+				account.clone(),
+				info.v,
+				info.payout,
+				Self::balance_of_bits(info.v),
+				Self::balance_of_bits(info.payout),
+			));
+		}
+
+		/// The max total payout of a session of `miner_info` at `now`
+		///
+		/// It's Ve plus `PAYOUT_CAP_SHARE_MULTIPLIER` times the equal share of the budget among the
+		/// online miners since the session started. It only guards against the payouts far off
+		/// the tokenomic, e.g. caused by a gatekeeper bug.
+		pub(crate) fn payout_cap(miner_info: &MinerInfo, now: u64) -> FixedPoint {
+			let budget_per_sec = FixedPoint::from_bits(Self::tokenomic().params.budget_per_sec);
+			let elapsed = FixedPoint::from_num(now.saturating_sub(miner_info.session_start));
+			let online_miners = FixedPoint::from_num(Self::online_miners().max(1));
+			let share = (FixedPoint::from_num(PAYOUT_CAP_SHARE_MULTIPLIER) / online_miners)
+				.min(FixedPoint::from_num(1));
+			miner_info
+				.ve
+				.to_fixed()
+				.saturating_add(budget_per_sec.saturating_mul(elapsed).saturating_mul(share))
+		}
+
+		/// Extends the uptime streak of the miner by a settlement, raising the payout by the bonus
+		/// of the highest tier in `UptimeStreakBonuses` the streak reaches
+		fn apply_streak_bonus(miner: &T::AccountId, info: &SettleInfo) -> SettleInfo {
+			let tiers = match UptimeStreakBonuses::<T, I>::get() {
+				Some(tiers) => tiers,
+				None => return info.clone(),
+			};
+			let streak = UptimeStreaks::<T, I>::mutate(miner, |streak| {
+				*streak = streak.saturating_add(1);
+				*streak
+			});
+			let bonus = match tiers.iter().rev().find(|t| streak >= t.min_streak) {
+				Some(tier) => tier.bonus,
+				None => return info.clone(),
+			};
+			let payout = info.payout_bits().to_fixed();
+			let bonus = payout * FixedPoint::from_num(bonus.deconstruct())
+				/ FixedPoint::from_num(Permill::ACCURACY);
+			if bonus == FixedPoint::from_num(0) {
+				return info.clone();
+			}
+			Self::deposit_event(Event::<T, I>::UptimeStreakBonus(
+				miner.clone(),
+				streak,
+				bonus.to_bits(),
+			));
+			SettleInfo {
+				payout: payout.saturating_add(bonus).to_bits(),
+				..info.clone()
+			}
+		}
+
+		/// Truncates the payout of a settlement to the rest of `payout_cap` of the session, and
+		/// adds it to the session payout
+		fn cap_payout(
+			miner: &T::AccountId,
+			miner_info: &mut MinerInfo,
+			info: &SettleInfo,
+			now: u64,
+		) -> SettleInfo {
+			let paid = FixedPoint::from_bits(miner_info.session_payout);
+			let remaining = Self::payout_cap(miner_info, now).saturating_sub(paid);
+			let payout = info.payout_bits().to_fixed();
+			let mut info = info.clone();
+			if payout > remaining {
+				info.payout = remaining.to_bits();
+				Self::deposit_event(Event::<T, I>::PayoutCapped(
+					miner.clone(),
+					payout.to_bits(),
+					info.payout,
+				));
+			}
+			miner_info.session_payout =
+				paid.saturating_add(info.payout_bits().to_fixed()).to_bits();
+			info
+		}
+
+		/// Adds the rounding dust to the miner, flushing the whole units to `FlushedDust` once
+		/// they reach `DUST_FLUSH_THRESHOLD`
+		fn accrue_dust(miner: &T::AccountId, dust: FixedPoint) {
+			if dust == FixedPoint::from_num(0) {
+				return;
+			}
+			let total = FixedPoint::from_bits(RewardDust::<T, I>::get(miner)).saturating_add(dust);
+			let (units, remainder): (BalanceOf<T, I>, _) = balance_convert::floor_with_dust(&total);
+			if units.saturated_into::<u128>() < DUST_FLUSH_THRESHOLD {
+				RewardDust::<T, I>::insert(miner, total.to_bits());
+				return;
+			}
+			RewardDust::<T, I>::insert(miner, remainder.to_bits());
+			FlushedDust::<T, I>::mutate(|flushed| *flushed = flushed.saturating_add(units));
+			Self::deposit_event(Event::<T, I>::RewardDustFlushed(miner.clone(), units));
+		}
+
+		/// Converts the raw U64F64 bits to the balance scale for the events
+		fn balance_of_bits(bits: u128) -> BalanceOf<T, I> {
+			FixedPointConvert::from_fixed(&FixedPoint::from_bits(bits))
+		}
+
+		/// Replays the V update of a settlement since the last audited one from `SettlementTraces`,
+		/// and rebases the trace of the miner to the settlement at the gatekeeper `block`
+		///
+		/// The replay follows the gatekeeper: V grows as idle scaled by the performance of the
+		/// miner, and is slashed scaled by its slash multiplier while it's unresponsive. Returns
+		/// the replayed V if the settled V plus the payout deviates from it beyond
+		/// `SettlementAuditTolerance`. A miner without a trace of the current epoch (e.g. the
+		/// audit was just enabled, or the trace overflowed) isn't audited until the next
+		/// settlement.
+		#[cfg(any(test, feature = "settlement-audit"))]
+		fn audit_settlement(
+			miner: &T::AccountId,
+			miner_info: &MinerInfo,
+			info: &SettleInfo,
+			block: T::BlockNumber,
+		) -> Result<(), FixedPoint> {
+			let tolerance = match SettlementAuditTolerance::<T, I>::get() {
+				Some(tolerance) => tolerance,
+				None => {
+					SettlementTraces::<T, I>::remove(miner);
+					return Ok(());
+				}
+			};
+			let epoch = SettlementAuditEpoch::<T, I>::get();
+			let until: u32 = block.saturated_into();
+			let trace = SettlementTraces::<T, I>::get(miner).filter(|trace| trace.epoch == epoch);
+			let (unresponsive, transitions) = match &trace {
+				Some(trace) => (trace.unresponsive, &trace.transitions[..]),
+				None => (miner_info.state == MinerState::MiningUnresponsive, &[][..]),
+			};
+			let since = trace.as_ref().map(|trace| trace.since).unwrap_or(until);
+			let (segments, unresponsive) = trace_segments(since, until, unresponsive, transitions);
+			SettlementTraces::<T, I>::insert(
+				miner,
+				SettlementTrace {
+					epoch,
+					since: until,
+					p_instant: Some(miner_info.benchmark.p_instant)
+						.filter(|_| miner_info.benchmark.updated_at > 0),
+					unresponsive,
+					transitions: transitions
+						.iter()
+						.filter(|(block, _)| *block >= until)
+						.cloned()
+						.collect(),
+				},
+			);
+			let trace = match trace {
+				Some(trace) => trace,
+				None => return Ok(()),
+			};
+
+			let v = miner_info.v.to_fixed();
+			let p = T::WorkerInfo::worker_info(&info.pubkey)
+				.and_then(|worker| worker.initial_score)
+				.unwrap_or_default();
+			let perf = match trace.p_instant {
+				Some(p_instant) if p > 0 => {
+					FixedPoint::from_num(p_instant) / FixedPoint::from_num(p)
+				}
+				_ => FixedPoint::from_num(1),
+			};
+			let expected =
+				Self::tokenomic().replay_v(v, p, perf, Self::slash_multiplier(miner), &segments);
+			let reported =
+				FixedPoint::from_bits(info.v).saturating_add(FixedPoint::from_bits(info.payout));
+			let deviation = if reported > expected {
+				reported - expected
+			} else {
+				expected - reported
+			};
+			let max_deviation = expected * FixedPoint::from_num(tolerance.deconstruct())
+				/ FixedPoint::from_num(Permill::ACCURACY);
+			if deviation > max_deviation {
+				return Err(expected);
+			}
+			Ok(())
+		}
+
+		/// Starts the `SettlementTraces` of a miner starting mining, if the settlements are audited
+		#[cfg(any(test, feature = "settlement-audit"))]
+		fn start_settlement_trace(miner: &T::AccountId) {
+			if !SettlementAuditTolerance::<T, I>::exists() {
+				SettlementTraces::<T, I>::remove(miner);
+				return;
+			}
+			SettlementTraces::<T, I>::insert(
+				miner,
+				SettlementTrace {
+					epoch: SettlementAuditEpoch::<T, I>::get(),
+					since: frame_system::Pallet::<T>::block_number().saturated_into(),
+					p_instant: None,
+					unresponsive: false,
+					transitions: Vec::new(),
+				},
+			);
+		}
+
+		/// Records the status changes reported at the gatekeeper `block` to `SettlementTraces`
+		///
+		/// A trace overflowing `MAX_TRACE_TRANSITIONS` is dropped, skipping the audit of the next
+		/// settlement of the miner.
+		#[cfg(any(test, feature = "settlement-audit"))]
+		fn trace_status_changes(
+			offline: &[WorkerPublicKey],
+			recovered: &[WorkerPublicKey],
+			block: T::BlockNumber,
+		) {
+			if !SettlementAuditTolerance::<T, I>::exists() {
+				return;
+			}
+			let block: u32 = block.saturated_into();
+			let changes = offline
+				.iter()
+				.map(|worker| (worker, true))
+				.chain(recovered.iter().map(|worker| (worker, false)));
+			for (worker, unresponsive) in changes {
+				let miner = match WorkerBindings::<T, I>::get(worker) {
+					Some(miner) => miner,
+					None => continue,
+				};
+				SettlementTraces::<T, I>::mutate_exists(&miner, |trace| {
+					let overflow = match trace {
+						Some(trace) => trace.transitions.len() >= MAX_TRACE_TRANSITIONS,
+						None => return,
+					};
+					if overflow {
+						*trace = None;
+					} else if let Some(trace) = trace {
+						trace.transitions.push((block, unresponsive));
+					}
+				});
+			}
+		}
+
+		/// Records a chain downtime if the gap since the last block is too long
+		fn track_chain_downtime() {
+			let now = Self::now_sec();
+			let block_time = T::ExpectedBlockTimeSec::get().max(1) as u64;
+			if let Some(last) = LastBlockTime::<T, I>::get() {
+				if now.saturating_sub(last) > CHAIN_DOWNTIME_MIN_BLOCKS * block_time {
+					LastChainDowntime::<T, I>::put((last, now));
+					Self::deposit_event(Event::<T, I>::ChainDowntimeDetected(last, now));
+				}
+			}
+			LastBlockTime::<T, I>::put(now);
+		}
+
+		/// Returns the last chain downtime if a gatekeeper report at `report_time` (in sec) can
+		/// be affected by it
+		///
+		/// The workers have no chance to answer the challenges before the chain resumes, so the
+		/// gatekeeper may report them offline until a few heartbeat windows after the downtime.
+		fn is_in_downtime_grace(report_time: u64) -> Option<(u64, u64)> {
+			let (start, end) = LastChainDowntime::<T, I>::get()?;
+			let window = TokenomicParameters::<T, I>::get()?.heartbeat_window as u64;
+			let grace = 2 * window * T::ExpectedBlockTimeSec::get() as u64;
+			if report_time >= start && report_time < end + grace {
+				Some((start, end))
+			} else {
+				None
+			}
+		}
+
+		/// Restarts the trial of a miner if it's in trial mining
+		fn restart_trial(miner: &T::AccountId) {
+			if let Some(config) = TrialMining::<T, I>::get() {
+				let now = frame_system::Pallet::<T>::block_number();
+				TrialMiners::<T, I>::mutate_exists(miner, |ends_at| {
+					if let Some(ends_at) = ends_at {
+						*ends_at = now + config.duration;
+					}
+				});
+			}
+		}
+
+		/// Applies the trial mining rules to a settlement of a miner
+		///
+		/// The payout is capped during the trial. Once the trial has ended without the miner being
+		/// unresponsive, the miner is upgraded to normal mining by raising its Ve to the uncapped
+		/// one, with the same raise to V (also applied by the gatekeeper on `TrialCompleted`).
+		fn settle_trial(
+			miner: &T::AccountId,
+			miner_info: &mut MinerInfo,
+			info: &SettleInfo,
+		) -> SettleInfo {
+			let ends_at = match TrialMiners::<T, I>::get(miner) {
+				Some(ends_at) => ends_at,
+				None => return info.clone(),
+			};
+			let now = frame_system::Pallet::<T>::block_number();
+			if now < ends_at || miner_info.state == MinerState::MiningUnresponsive {
+				return match TrialMining::<T, I>::get() {
+					Some(config) => SettleInfo {
+						payout: info.payout.min(config.payout_cap),
+						..info.clone()
+					},
+					None => info.clone(),
+				};
+			}
+			TrialMiners::<T, I>::remove(miner);
+			let ve = miner_info.ve.to_fixed();
+			let full_ve = Self::uncapped_ve(miner).unwrap_or(ve).max(ve);
+			let v_delta = full_ve - ve;
+			miner_info.ve = full_ve.into();
+			if let Some(worker) = MinerBindings::<T, I>::get(miner) {
+				Self::push_worker_event(
+					worker,
+					WorkerEvent::TrialCompleted {
+						v_delta: v_delta.to_bits(),
+					},
+				);
+			}
+			Self::deposit_event(Event::<T, I>::MinerTrialCompleted(
+				miner.clone(),
+				full_ve.to_bits(),
+			));
+			SettleInfo {
+				v: (info.v_bits().to_fixed() + v_delta).to_bits(),
+				..info.clone()
+			}
+		}
+
+		/// The Ve of a mining miner without the trial cap
+		fn uncapped_ve(miner: &T::AccountId) -> Option<FixedPoint> {
+			let worker = MinerBindings::<T, I>::get(miner)?;
+			let worker_info = T::WorkerInfo::worker_info(&worker)?;
+			let p = worker_info.initial_score?;
+			let stake = Stakes::<T, I>::get(miner)?;
+			let tokenomic = Self::tokenomic();
+			let ve = tokenomic.ve(stake, p, worker_info.confidence_level);
+			Some(ve.min(tokenomic.v_max()))
+		}
+
+		/// The owner of the rewards the miner earns on the worker
+		fn reward_beneficiary(
+			miner: &T::AccountId,
+			worker: &WorkerPublicKey,
+		) -> RewardBeneficiary<T::AccountId> {
+			if Self::is_solo_miner(miner) {
+				return RewardBeneficiary::Owner(miner.clone());
+			}
+			match T::StakePools::pool_of(worker) {
+				Some(pid) => RewardBeneficiary::Pool(pid),
+				None => RewardBeneficiary::Owner(
+					RefundBeneficiaries::<T, I>::get(miner).unwrap_or_else(|| miner.clone()),
+				),
+			}
+		}
+
+		/// Adds a chunk to the locked rewards of `beneficiary`, keeping them ordered by the unlock
+		/// time
+		///
+		/// The chunks with the same unlock time are merged. Once `MAX_LOCKED_REWARDS` is reached,
+		/// the amount is merged into the next chunk to unlock, or the last chunk is postponed to
+		/// `unlock_at`, so that no reward is unlocked earlier than its lock period.
+		fn add_locked_reward(
+			beneficiary: &RewardBeneficiary<T::AccountId>,
+			amount: BalanceOf<T, I>,
+			unlock_at: u64,
+		) {
+			LockedRewards::<T, I>::mutate(beneficiary, |rewards| {
+				let pos = rewards
+					.iter()
+					.take_while(|r| r.unlock_at <= unlock_at)
+					.count();
+				if pos > 0 && rewards[pos - 1].unlock_at == unlock_at {
+					rewards[pos - 1].amount = rewards[pos - 1].amount.saturating_add(amount);
+				} else if rewards.len() < MAX_LOCKED_REWARDS {
+					rewards.insert(pos, LockedReward { amount, unlock_at });
+				} else if let Some(next) = rewards.get_mut(pos) {
+					next.amount = next.amount.saturating_add(amount);
+				} else if let Some(last) = rewards.last_mut() {
+					last.amount = last.amount.saturating_add(amount);
+					last.unlock_at = unlock_at;
+				}
+			});
+		}
+
+		/// Removes the unlocked rewards of `beneficiary`, returning their sum
+		fn take_unlocked_rewards(
+			beneficiary: &RewardBeneficiary<T::AccountId>,
+		) -> Result<BalanceOf<T, I>, DispatchError> {
+			let now = Self::now_sec();
+			let mut locked = LockedRewards::<T, I>::get(beneficiary);
+			let num_unlocked = locked.iter().take_while(|r| r.unlock_at <= now).count();
+			ensure!(num_unlocked > 0, Error::<T, I>::NoUnlockedRewards);
+			let amount = locked
+				.drain(..num_unlocked)
+				.fold(Zero::zero(), |acc: BalanceOf<T, I>, r| {
+					acc.saturating_add(r.amount)
+				});
+			if locked.is_empty() {
+				LockedRewards::<T, I>::remove(beneficiary);
+			} else {
+				LockedRewards::<T, I>::insert(beneficiary, locked);
+			}
+			Ok(amount)
+		}
+
+		/// Locks a part of the payout according to `RewardLockup` for the beneficiary of the
+		/// miner, returning the settlement with the remaining payout
+		fn lock_reward(miner: &T::AccountId, info: &SettleInfo, now: u64) -> SettleInfo {
+			let lockup = match RewardLockup::<T, I>::get() {
+				Some(lockup) if !lockup.percentage.is_zero() => lockup,
+				_ => return info.clone(),
+			};
+			let payout = info.payout_bits().to_fixed();
+			let locked_fixed = payout * FixedPoint::from_num(lockup.percentage.deconstruct())
+				/ FixedPoint::from_num(Permill::ACCURACY);
+			let locked: BalanceOf<T, I> = FixedPointConvert::from_fixed(&locked_fixed);
+			if locked == Zero::zero() {
+				return info.clone();
+			}
+			let unlock_at = now + lockup.duration;
+			let beneficiary = Self::reward_beneficiary(miner, &info.pubkey);
+			Self::add_locked_reward(&beneficiary, locked, unlock_at);
+			Self::deposit_event(Event::<T, I>::RewardLocked(
+				miner.clone(),
+				locked,
+				unlock_at,
+			));
+			SettleInfo {
+				payout: (payout - locked.to_fixed()).to_bits(),
+				..info.clone()
+			}
+		}
+
+		fn lock_solo_stake(miner: &T::AccountId, stake: BalanceOf<T, I>) {
+			T::Currency::set_lock(
+				Self::solo_staking_id(),
+				miner,
+				stake,
+				WithdrawReasons::all(),
+			);
+			SoloMiners::<T, I>::insert(miner, true);
+		}
+
+		/// Sends the payout of a settlement to the `RewardSink`
+		fn dispatch_reward(miner: &T::AccountId, settle: &SettleInfo, now: u64) {
+			let sink = Self::reward_sink();
+			if sink == RewardSink::Normal {
+				Self::deliver_reward(miner, settle, now);
+				return;
+			}
+			let amount: BalanceOf<T, I> =
+				FixedPointConvert::from_fixed(&settle.payout_bits().to_fixed());
+			if amount == Zero::zero() {
+				return;
+			}
+			match sink {
+				RewardSink::ParkInPallet => {
+					ParkedRewards::<T, I>::mutate(miner, |parked| {
+						let total = parked
+							.as_ref()
+							.map(|p| p.amount)
+							.unwrap_or_default()
+							.saturating_add(amount);
+						*parked = Some(ParkedReward {
+							worker: settle.pubkey.clone(),
+							amount: total,
+						});
+					});
+					Self::deposit_event(Event::<T, I>::RewardParked(miner.clone(), amount));
+				}
+				RewardSink::Burn => {
+					let (_burnt, _) = T::Currency::slash(&Self::account_id(), amount);
+					Self::deposit_event(Event::<T, I>::RewardBurnt(miner.clone(), amount));
+				}
+				RewardSink::Normal => (),
+			}
+		}
+
+		/// Pays the solo miners and notifies the `LifecycleObserver` of the payout
+		fn deliver_reward(miner: &T::AccountId, settle: &SettleInfo, now: u64) {
+			if Self::is_solo_miner(miner) {
+				Self::pay_solo_reward(miner, settle, now);
+			}
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Settled { miner, settle });
+		}
+
+		/// Pays the payout of a solo miner to its `LockedRewards`, claimable immediately
+		fn pay_solo_reward(miner: &T::AccountId, info: &SettleInfo, now: u64) {
+			let amount: BalanceOf<T, I> =
+				FixedPointConvert::from_fixed(&info.payout_bits().to_fixed());
+			if amount == Zero::zero() {
+				return;
+			}
+			Self::add_locked_reward(&RewardBeneficiary::Owner(miner.clone()), amount, now);
+		}
+
+		/// Pays a part of the payout in the secondary asset according to `SecondaryPayout`,
+		/// returning the settlement with the remaining payout
+		///
+		/// The asset goes to the beneficiary of the miner rather than the miner, which can be a
+		/// keyless sub-account. The whole payout stays in PHA if the subsidy pool cannot afford
+		/// the asset amount, or if it's earned for a stake pool, which has no account to hold the
+		/// asset.
+		fn pay_secondary(miner: &T::AccountId, info: &SettleInfo) -> SettleInfo {
+			let config = match SecondaryPayout::<T, I>::get() {
+				Some(config) if !config.ratio.is_zero() => config,
+				_ => return info.clone(),
+			};
+			let beneficiary = match Self::reward_beneficiary(miner, &info.pubkey) {
+				RewardBeneficiary::Owner(owner) => owner,
+				RewardBeneficiary::Pool(_) => return info.clone(),
+			};
+			let payout = info.payout_bits().to_fixed();
+			let portion_fixed = payout * FixedPoint::from_num(config.ratio.deconstruct())
+				/ FixedPoint::from_num(Permill::ACCURACY);
+			let portion: BalanceOf<T, I> = FixedPointConvert::from_fixed(&portion_fixed);
+			if portion == Zero::zero() {
+				return info.clone();
+			}
+			let amount = match portion
+				.to_fixed()
+				.checked_mul(FixedPoint::from_bits(config.exchange_rate))
+			{
+				Some(amount) => amount.to_num::<u128>(),
+				None => return info.clone(),
+			};
+			let wallet = Self::account_id();
+			if amount == 0
+				|| T::SecondaryAssets::balance(config.asset, &wallet) < amount
+				|| T::SecondaryAssets::transfer(config.asset, &wallet, &beneficiary, amount)
+					.is_err()
+			{
+				return info.clone();
+			}
+			SecondaryPaidTotal::<T, I>::mutate(config.asset, |total| {
+				*total = total.saturating_add(amount)
+			});
+			Self::deposit_event(Event::<T, I>::SecondaryRewardPaid(
+				beneficiary,
+				config.asset,
+				amount,
+			));
+			SettleInfo {
+				payout: (payout - portion.to_fixed()).to_bits(),
+				..info.clone()
+			}
+		}
+
+		/// Sets or clears the daily payout drip of a miner
+		///
+		/// The reserve left after the smoothing is cleared is released in the next settlement.
+		pub fn set_payout_smoothing(
+			miner: T::AccountId,
+			daily_drip: Option<BalanceOf<T, I>>,
+		) -> DispatchResult {
+			ensure!(
+				daily_drip != Some(Zero::zero()),
+				Error::<T, I>::InvalidParameter
+			);
+			PayoutSmoothing::<T, I>::set(&miner, daily_drip);
+			Self::deposit_event(Event::<T, I>::PayoutSmoothingChanged(miner, daily_drip));
+			Ok(())
+		}
+
+		/// Accrues the payout to the reserve of the miner in the payout smoothing mode, returning
+		/// the settlement with the payout released from the reserve since the last release
+		fn smooth_payout(miner: &T::AccountId, info: &SettleInfo, now: u64) -> SettleInfo {
+			let drip = PayoutSmoothing::<T, I>::get(miner);
+			let mut reserve = match PayoutReserves::<T, I>::get(miner) {
+				Some(reserve) => reserve,
+				None if drip.is_none() => return info.clone(),
+				None => PayoutReserve {
+					amount: Zero::zero(),
+					released_at: now,
+				},
+			};
+			let payout: BalanceOf<T, I> =
+				FixedPointConvert::from_fixed(&info.payout_bits().to_fixed());
+			reserve.amount = reserve.amount.saturating_add(payout);
+			let released = match drip {
+				Some(drip) => {
+					let elapsed = now.saturating_sub(reserve.released_at);
+					let allowance = drip.saturating_mul(elapsed.saturated_into())
+						/ PAYOUT_DRIP_PERIOD_SEC.saturated_into();
+					allowance.min(reserve.amount)
+				}
+				// Flush the reserve left after the smoothing is cleared
+				None => reserve.amount,
+			};
+			reserve.amount -= released;
+			reserve.released_at = now;
+			if drip.is_some() {
+				PayoutReserves::<T, I>::insert(miner, &reserve);
+			} else {
+				PayoutReserves::<T, I>::remove(miner);
+			}
+			Self::deposit_event(Event::<T, I>::PayoutReleased(
+				miner.clone(),
+				released,
+				reserve.amount,
+			));
+			SettleInfo {
+				payout: released.to_fixed().to_bits(),
+				..info.clone()
+			}
+		}
+
+		/// Releases the whole payout reserve of a miner through the worker bound to it
+		///
+		/// The reserve is kept if the miner is no longer bound to any worker.
+		fn flush_payout_reserve(miner: &T::AccountId, miner_info: &MinerInfo, now: u64) {
+			let worker = match MinerBindings::<T, I>::get(miner) {
+				Some(worker) => worker,
+				None => return,
+			};
+			let reserve = match PayoutReserves::<T, I>::take(miner) {
+				Some(reserve) => reserve,
+				None => return,
+			};
+			if reserve.amount == Zero::zero() {
+				return;
+			}
+			let settle = SettleInfo {
+				pubkey: worker,
+				v: miner_info.v.to_bits(),
+				payout: reserve.amount.to_fixed().to_bits(),
+			};
+			Self::dispatch_reward(miner, &settle, now);
+			Self::deposit_event(Event::<T, I>::PayoutReleased(
+				miner.clone(),
+				reserve.amount,
+				Zero::zero(),
+			));
+		}
+
+		/// The balances of the subsidy pool in PHA and in the secondary asset (if enabled)
+		pub fn subsidy_pool_balances() -> (BalanceOf<T, I>, Option<(u32, u128)>) {
+			let wallet = Self::account_id();
+			let secondary = SecondaryPayout::<T, I>::get().map(|config| {
+				(
+					config.asset,
+					T::SecondaryAssets::balance(config.asset, &wallet),
+				)
+			});
+			(T::Currency::free_balance(&wallet), secondary)
+		}
+
+		/// Turns a miner back to Ready state after cooling down and releases its stake
+		fn do_reclaim(miner: T::AccountId) -> DispatchResult {
+			let mut miner_info = Miners::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotFound)?;
+			ensure!(
+				Self::can_reclaim(&miner_info),
+				Error::<T, I>::CoolDownNotReady
+			);
+			Self::apply_pending_settlement(&miner, &mut miner_info, Self::now_sec());
+			Self::set_miner_state(&miner, &mut miner_info, MinerState::Ready);
+			miner_info.cool_down_start = 0u64;
+
+			// Calcualte remaining stake
+			let v = miner_info.v.to_fixed();
+			let ve = miner_info.ve.to_fixed();
+			let mut return_rate = (v / ve).min(FixedPoint::from_num(1));
+			if RevokedSessions::<T, I>::take(&miner) {
+				let ratio = Self::revocation_slash_ratio();
+				let slash_rate = FixedPoint::from_num(ratio.deconstruct())
+					/ FixedPoint::from_num(Permill::ACCURACY);
+				return_rate = return_rate.min(FixedPoint::from_num(1) - slash_rate);
+			}
+			let orig_stake = Stakes::<T, I>::take(&miner).unwrap_or_default();
+			SettlementTraces::<T, I>::remove(&miner);
+			// If we consider kappa as a panelty of frequent exit:
+			// 	let tokenomic = Self::tokenomic();
+			// 	let returned = return_rate * orig_stake.to_fixed() * tokenomic.kappa();
+			let returned = return_rate * orig_stake.to_fixed();
+			// Convert to Balance, leaving the dust in the slash
+			let (returned, _) = balance_convert::floor_with_dust(&returned);
+			// The operator bond of the worker the session started on covers the slash first
+			let slashed = orig_stake - returned;
+			let slashed = match Self::take_bonded_worker(&miner) {
+				Some(worker) => slashed - Self::slash_operator_bond(&worker, slashed),
+				None => slashed,
+			};
+			miner_info.stats.on_slash(slashed.saturated_into());
+			Miners::<T, I>::insert(&miner, &miner_info);
+			if slashed != Zero::zero() {
+				Self::record_settlement(
+					&miner,
+					SettlementRecord {
+						v: miner_info.v.to_bits(),
+						payout: Zero::zero(),
+						slashed,
+					},
+				);
+			}
+			// The worker may have been unbound during cooling down
+			if let Some(worker) = MinerBindings::<T, I>::get(&miner) {
+				Self::push_worker_event(worker, WorkerEvent::CoolDownEnded);
+			}
+
+			if slashed != Zero::zero() {
+				T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Slashed {
+					miner: &miner,
+					slashed,
+				});
+			}
+			Self::flush_payout_reserve(&miner, &miner_info, Self::now_sec());
+			let beneficiary = RefundBeneficiaries::<T, I>::take(&miner);
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Reclaimed {
+				miner: &miner,
+				orig_stake,
+				slashed,
+				beneficiary: beneficiary.as_ref(),
+			});
+			if SoloMiners::<T, I>::take(&miner) {
+				T::Currency::slash(&miner, slashed);
+				T::Currency::remove_lock(Self::solo_staking_id(), &miner);
+			}
+			if let Some(beneficiary) = beneficiary {
+				Self::route_refund(&miner, beneficiary);
+			}
+			Self::deposit_event(Event::<T, I>::MinerWithdrew(
+				miner.clone(),
+				orig_stake - slashed,
+			));
+			if slashed != Zero::zero() {
+				Self::deposit_event(Event::<T, I>::MinerStakeSlashed(miner.clone(), slashed));
+			}
+			Self::deposit_event(Event::<T, I>::MinerReclaimed(miner, orig_stake, slashed));
+			Ok(())
+		}
+
+		/// Ensures the operator bond of the worker can't be charged by a pending reclaim, i.e. no
+		/// session recorded in `BondedSessions` is left, and the miner bound to the worker (if any)
+		/// is `Ready`
+		fn ensure_operator_bond_unslashable(worker: &WorkerPublicKey) -> DispatchResult {
+			ensure!(
+				BondedSessions::<T, I>::iter_prefix(worker).next().is_none(),
+				Error::<T, I>::OperatorBondInUse
+			);
+			if let Some(miner) = WorkerBindings::<T, I>::get(worker) {
+				let state = Miners::<T, I>::get(&miner).map(|info| info.state);
+				ensure!(
+					matches!(state, None | Some(MinerState::Ready)),
+					Error::<T, I>::OperatorBondInUse
+				);
+			}
+			Ok(())
+		}
+
+		/// Releases the bonded session of the miner, returning the worker whose operator bond
+		/// covers its slash
+		///
+		/// The sessions started before `BondedWorkers` fall back to the bound worker.
+		fn take_bonded_worker(miner: &T::AccountId) -> Option<WorkerPublicKey> {
+			match BondedWorkers::<T, I>::take(miner) {
+				Some(worker) => {
+					BondedSessions::<T, I>::remove(&worker, miner);
+					Some(worker)
+				}
+				None => MinerBindings::<T, I>::get(miner),
+			}
+		}
+
+		/// Charges up to `amount` of a slash to the operator bond of `worker`, moving it to the
+		/// subsidy pool. Returns the amount charged.
+		///
+		/// The bonded amount is charged before the unbonding one.
+		fn slash_operator_bond(
+			worker: &WorkerPublicKey,
+			amount: BalanceOf<T, I>,
+		) -> BalanceOf<T, I> {
+			let zero = Zero::zero();
+			if amount.is_zero() {
+				return zero;
+			}
+			let worker = worker.clone();
+			let mut bond = match OperatorBonds::<T, I>::get(&worker) {
+				Some(bond) => bond,
+				None => return zero,
+			};
+			let from_bonded = amount.min(bond.bonded);
+			let from_unbonding = (amount - from_bonded).min(bond.unbonding);
+			let charged = from_bonded + from_unbonding;
+			if charged.is_zero() {
+				return zero;
+			}
+			if T::Currency::transfer(
+				&Self::bond_account_id(),
+				&Self::account_id(),
+				charged,
+				AllowDeath,
+			)
+			.is_err()
+			{
+				return zero;
+			}
+			bond.bonded -= from_bonded;
+			bond.unbonding -= from_unbonding;
+			let operator = bond.operator.clone();
+			if bond.bonded.is_zero() && bond.unbonding.is_zero() {
+				OperatorBonds::<T, I>::remove(&worker);
+			} else {
+				OperatorBonds::<T, I>::insert(&worker, bond);
+			}
+			Self::deposit_event(Event::<T, I>::OperatorBondSlashed(
+				worker, operator, charged,
+			));
+			charged
+		}
+
+		/// Moves the free balance of a reclaimed miner to its refund beneficiary
+		fn route_refund(miner: &T::AccountId, beneficiary: T::AccountId) {
+			let amount = T::Currency::free_balance(miner);
+			if amount.is_zero() {
+				return;
+			}
+			if T::Currency::transfer(miner, &beneficiary, amount, AllowDeath).is_ok() {
+				Self::deposit_event(Event::<T, I>::RefundRouted(
+					miner.clone(),
+					beneficiary,
+					amount,
+				));
+			}
+		}
+
+		fn can_reclaim(miner_info: &MinerInfo) -> bool {
+			if miner_info.state != MinerState::MiningCoolingDown {
+				return false;
+			}
+			let now = Self::now_sec();
+			now - miner_info.cool_down_start >= Self::cool_down_period()
+		}
+
+		/// Returns the condition blocking `miner` from reclaiming its stake, or None if it can
+		/// reclaim
+		pub fn why_cannot_reclaim(miner: &T::AccountId) -> Option<ReclaimBlocker> {
+			let miner_info = match Miners::<T, I>::get(miner) {
+				Some(info) => info,
+				None => return Some(ReclaimBlocker::MinerNotFound),
+			};
+			if miner_info.state != MinerState::MiningCoolingDown {
+				return Some(ReclaimBlocker::NotCoolingDown(miner_info.state));
+			}
+			let elapsed = Self::now_sec().saturating_sub(miner_info.cool_down_start);
+			let remaining = Self::cool_down_period().saturating_sub(elapsed);
+			if remaining > 0 {
+				return Some(ReclaimBlocker::CoolDownRemaining(remaining));
+			}
+			None
+		}
+
+		/// Returns the first condition blocking `miner` from starting mining with `stake`, or None
+		/// if it can start
+		///
+		/// The conditions are checked in the same order as `start_mining`.
+		pub fn why_cannot_start(
+			miner: &T::AccountId,
+			stake: BalanceOf<T, I>,
+		) -> Option<StartBlocker<BalanceOf<T, I>>> {
+			let (worker, miner_info) = match (
+				MinerBindings::<T, I>::get(miner),
+				Miners::<T, I>::get(miner),
+			) {
+				(Some(worker), Some(info)) => (worker, info),
+				_ => return Some(StartBlocker::MinerNotFound),
+			};
+			let now = Self::now_sec();
+			if let Some(last_end) = LastSessionEnd::<T, I>::get(&worker) {
+				let next_start = last_end.saturating_add(MinSessionInterval::<T, I>::get());
+				if now < next_start {
+					return Some(StartBlocker::SessionIntervalRemaining(next_start - now));
+				}
+			}
+			let worker_info = match T::WorkerInfo::worker_info(&worker) {
+				Some(info) => info,
+				None => return Some(StartBlocker::WorkerNotRegistered),
+			};
+			if let Some(config) = MinWorkerVersion::<T, I>::get() {
+				if worker_info.runtime_version < config.version && now >= config.enforced_at {
+					return Some(StartBlocker::WorkerVersionTooOld);
+				}
+			}
+			if miner_info.state != MinerState::Ready {
+				return Some(StartBlocker::NotReady(miner_info.state));
+			}
+			if let Some(max) = MaxOnlineMiners::<T, I>::get() {
+				if Self::online_miners() >= max {
+					return Some(StartBlocker::NetworkAtCapacity);
+				}
+			}
+			let p = match worker_info.initial_score {
+				Some(p) => p,
+				None => return Some(StartBlocker::BenchmarkMissing),
+			};
+			if let Some(period) = BenchmarkValidityPeriod::<T, I>::get() {
+				let expired = worker_info
+					.benchmarked_at
+					.map_or(true, |at| now.saturating_sub(at) > period);
+				if expired {
+					return Some(StartBlocker::BenchmarkExpired);
+				}
+			}
+			let tokenomic = Self::tokenomic();
+			let min_stake = tokenomic.minimal_stake(p);
+			if stake < min_stake {
+				return Some(StartBlocker::InsufficientStake(min_stake - stake));
+			}
+			if let Some(cap) = Self::stake_cap(p) {
+				if stake > cap {
+					return Some(StartBlocker::StakeExceedsCap(stake - cap));
+				}
+			}
+			if tokenomic.ve(stake, p, worker_info.confidence_level) > tokenomic.v_max() {
+				return Some(StartBlocker::TooMuchStake);
+			}
+			None
+		}
+
+		/// Binds a miner to a worker
+		///
+		/// This will bind the miner account to the worker, and then create a `Miners` entry to
+		/// track the mining session in the future. The mining session will exist until ther miner
+		/// and the worker is unbound.
+		///
+		/// Requires:
+		/// 1. The worker is alerady registered
+		/// 2. The worker has an initial benchmark
+		/// 3. Both the worker and the miner are not bound
+		pub fn bind(miner: T::AccountId, pubkey: WorkerPublicKey) -> DispatchResult {
+			Self::do_bind(miner, pubkey, true)
+		}
+
+		/// Changes the state of a miner and moves it in `MinersByState` accordingly
+		fn set_miner_state(miner: &T::AccountId, info: &mut MinerInfo, state: MinerState) {
+			if info.state == state {
+				return;
+			}
+			MinersByState::<T, I>::remove(&info.state, miner);
+			MinersByState::<T, I>::insert(&state, miner, ());
+			Self::count_miner_state(miner, &info.state, false);
+			Self::count_miner_state(miner, &state, true);
+			Self::deposit_event(Event::<T, I>::MinerStateChanged(
+				miner.clone(),
+				info.state.clone(),
+				state.clone(),
+				frame_system::Pallet::<T>::block_number(),
+			));
+			info.state = state;
+		}
+
+		/// Increases or decreases the counter of the online miner state, if any
+		///
+		/// The counters saturate instead of panicking on a drift, which is fixed by the recount.
+		fn count_miner_state(miner: &T::AccountId, state: &MinerState, enter: bool) {
+			let update = |v: &mut u32| {
+				*v = if enter {
+					v.saturating_add(1)
+				} else {
+					v.saturating_sub(1)
+				}
+			};
+			match state {
+				MinerState::MiningIdle => IdleMiners::<T, I>::mutate(update),
+				MinerState::MiningActive => ActiveMiners::<T, I>::mutate(update),
+				MinerState::MiningUnresponsive => UnresponsiveMiners::<T, I>::mutate(update),
+				_ => return,
+			}
+			// Keep the ongoing recount consistent if the miner has been scanned
+			OnlineMinersRecount::<T, I>::mutate(|recount| {
+				if let Some((cursor, counted)) = recount {
+					if Miners::<T, I>::hashed_key_for(miner) <= *cursor {
+						counted.update(state, enter);
+					}
+				}
+			});
+		}
+
+		/// Gets the total number of the online miners, including the unresponsive ones
+		///
+		/// It's the sum of `IdleMiners`, `ActiveMiners` and `UnresponsiveMiners`, replacing the
+		/// retired `OnlineMiners` counter.
+		pub fn online_miners() -> u32 {
+			OnlineMinerCounts::current::<T, I>().total()
+		}
+
+		/// Gets the number of the online miners responding to the heartbeat challenges
+		pub fn responsive_miners() -> u32 {
+			IdleMiners::<T, I>::get().saturating_add(ActiveMiners::<T, I>::get())
+		}
+
+		/// Checks the pRuntime version of a worker against `MinWorkerVersion`, accepting the
+		/// outdated workers in the grace period with a warning event
+		fn ensure_worker_version(
+			pubkey: &WorkerPublicKey,
+			worker: &MiningWorkerInfo<T::AccountId>,
+		) -> DispatchResult {
+			let config = match MinWorkerVersion::<T, I>::get() {
+				Some(config) if worker.runtime_version < config.version => config,
+				_ => return Ok(()),
+			};
+			ensure!(
+				Self::now_sec() < config.enforced_at,
+				Error::<T, I>::WorkerVersionTooOld
+			);
+			Self::deposit_event(Event::<T, I>::OutdatedWorkerInGrace(
+				pubkey.clone(),
+				worker.runtime_version,
+			));
+			Ok(())
+		}
+
+		/// Binds a miner to a worker and starts mining atomically
+		///
+		/// It's the same as `bind` followed by `start_mining`, but no change is made if either
+		/// fails, and the worker is notified by a single `MinerBoundAndStarted` message.
+		#[frame_support::transactional]
+		pub fn bind_and_start(
+			miner: T::AccountId,
+			pubkey: WorkerPublicKey,
+			stake: BalanceOf<T, I>,
+		) -> DispatchResult {
+			Self::do_bind(miner.clone(), pubkey, false)?;
+			Self::do_start_mining(miner, stake, None, true)
+		}
+
+		/// Binds a miner to a worker, notifying the worker if `notify_worker` is set
+		fn do_bind(
+			miner: T::AccountId,
+			pubkey: WorkerPublicKey,
+			notify_worker: bool,
+		) -> DispatchResult {
+			let worker =
+				T::WorkerInfo::worker_info(&pubkey).ok_or(Error::<T, I>::WorkerNotRegistered)?;
+			// Check the worker has finished the benchmark
+			ensure!(
+				worker.initial_score != None,
+				Error::<T, I>::BenchmarkMissing
+			);
+			Self::ensure_worker_version(&pubkey, &worker)?;
+			ensure!(
+				!registry::RevokedAttestations::<T>::get(&pubkey),
+				Error::<T, I>::AttestationRevoked
+			);
+			// Check miner and worker not bound
+			ensure!(
+				Self::ensure_miner_bound(&miner).is_err(),
+				Error::<T, I>::DuplicateBoundMiner
+			);
+			ensure!(
+				!Self::is_bound_in_any_instance(&pubkey),
+				Error::<T, I>::DuplicateBoundMiner
+			);
+
+			let now = Self::now_sec();
+			MinerBindings::<T, I>::insert(&miner, &pubkey);
+			WorkerBindings::<T, I>::insert(&pubkey, &miner);
+			if notify_worker {
+				Self::commit_binding(&pubkey, Some(&miner));
+			} else {
+				Self::update_bindings_commitment(&pubkey, Some(&miner));
+			}
+			// A re-bind resets the mining state
+			if let Some(info) = Miners::<T, I>::get(&miner) {
+				MinersByState::<T, I>::remove(&info.state, &miner);
+				Self::count_miner_state(&miner, &info.state, false);
+				if info.state != MinerState::Ready {
+					Self::deposit_event(Event::<T, I>::MinerStateChanged(
+						miner.clone(),
+						info.state,
+						MinerState::Ready,
+						frame_system::Pallet::<T>::block_number(),
+					));
+				}
+			}
+			MinersByState::<T, I>::insert(&MinerState::Ready, &miner, ());
+			Miners::<T, I>::insert(
+				&miner,
+				MinerInfo {
+					state: MinerState::Ready,
+					ve: FixedBits::default(),
+					v: FixedBits::default(),
+					v_updated_at: now,
+					benchmark: Benchmark {
+						p_instant: 0u32,
+						iterations: 0u64,
+						mining_start_time: now,
+						updated_at: 0u64,
+					},
+					cool_down_start: 0u64,
+					stats: Default::default(),
+					heartbeat_latency: 0,
+					v_balance: 0,
+					session_start: 0,
+					session_payout: 0,
+				},
+			);
+
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Bound {
+				miner: &miner,
+				worker: &pubkey,
+			});
+			Self::deposit_event(Event::<T, I>::MinerBound(miner, pubkey));
+			Ok(())
+		}
+
+		/// Unbinds a miner from a worker
+		///
+		/// - `notify`: whether to notify the observers the unbinding event.
+		///
+		/// Requires:
+		/// 1. The miner is bounded with a worker
+		pub fn unbind_miner(miner: &T::AccountId, notify: bool) -> DispatchResult {
+			let worker = Self::ensure_miner_bound(miner)?;
+			let miner_info = Miners::<T, I>::get(miner)
+				.expect("A bounded miner must has the associated MinerInfo; qed.");
+
+			if Observers::<T, I>::contains_key(miner) {
+				Self::stop_observing(miner.clone())?;
+			}
+			let force = !miner_info.state.can_unbind();
+			if force {
+				// Force unbinding. Stop the miner first.
+				Self::stop_mining(miner.clone())?;
+				// TODO: consider the final state sync (could cause slash) when stopping mining
+			}
+			MinerBindings::<T, I>::remove(miner);
+			WorkerBindings::<T, I>::remove(&worker);
+			Self::commit_binding(&worker, None);
+			Self::deposit_event(Event::<T, I>::MinerUnbound(miner.clone(), worker.clone()));
+			if notify {
+				T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Unbound {
+					miner,
+					worker: &worker,
+					force,
+				});
+			}
+
+			Ok(())
+		}
+
+		/// Rebinds a mining miner to a replacement worker, transferring the mining session
+		///
+		/// The accumulated V is preserved by the gatekeeper, while the benchmark is restarted on the
+		/// new worker.
+		///
+		/// Requires:
+		/// 1. The miner is bound and in mining (not cooling down)
+		/// 2. The new worker is registered, not bound, and has an equal or better benchmark
+		pub fn swap_miner_worker(
+			miner: &T::AccountId,
+			new_worker: WorkerPublicKey,
+		) -> DispatchResult {
+			let old_worker = Self::ensure_miner_bound(miner)?;
+			let mut miner_info = Miners::<T, I>::get(miner).ok_or(Error::<T, I>::MinerNotFound)?;
+			ensure!(
+				miner_info.state.can_settle(),
+				Error::<T, I>::MinerNotMining
+			);
+			ensure!(
+				!Self::is_bound_in_any_instance(&new_worker),
+				Error::<T, I>::DuplicateBoundMiner
+			);
+			let new_score = T::WorkerInfo::worker_info(&new_worker)
+				.ok_or(Error::<T, I>::WorkerNotRegistered)?
+				.initial_score
+				.ok_or(Error::<T, I>::BenchmarkMissing)?;
+			let old_score = T::WorkerInfo::worker_info(&old_worker)
+				.and_then(|w| w.initial_score)
+				.unwrap_or_default();
+			ensure!(new_score >= old_score, Error::<T, I>::InferiorBenchmark);
+			ensure!(
+				!registry::RevokedAttestations::<T>::get(&new_worker),
+				Error::<T, I>::AttestationRevoked
+			);
+
+			let now = Self::now_sec();
+			MinerBindings::<T, I>::insert(miner, &new_worker);
+			WorkerBindings::<T, I>::remove(&old_worker);
+			WorkerBindings::<T, I>::insert(&new_worker, miner);
+			Self::commit_binding(&old_worker, None);
+			Self::commit_binding(&new_worker, Some(miner));
+			// The gatekeeper resets the responsiveness of the new worker
+			Self::set_miner_state(miner, &mut miner_info, MinerState::MiningIdle);
+			miner_info.benchmark = Benchmark {
+				p_instant: 0u32,
+				iterations: 0u64,
+				mining_start_time: now,
+				updated_at: 0u64,
+			};
+			let session_id = NextSessionId::<T, I>::get();
+			NextSessionId::<T, I>::put(session_id + 1);
+			miner_info.stats.on_session(session_id);
+			Miners::<T, I>::insert(miner, &miner_info);
+
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::WorkerSwapped {
+				miner,
+				from: &old_worker,
+				to: &new_worker,
+			});
+			Self::push_worker_event(old_worker.clone(), WorkerEvent::MiningStop);
+			Self::push_worker_event(
+				new_worker.clone(),
+				WorkerEvent::SessionTransferred {
+					session_id,
+					from: old_worker.clone(),
+				},
+			);
+			Self::deposit_event(Event::<T, I>::MinerWorkerSwapped(
+				miner.clone(),
+				old_worker,
+				new_worker,
+			));
+			Ok(())
+		}
+
+		/// Starts mining with the given `stake`, assuming the stake is already locked externally
+		pub fn start_mining(miner: T::AccountId, stake: BalanceOf<T, I>) -> DispatchResult {
+			Self::do_start_mining(miner, stake, None, false)
+		}
+
+		/// Starts mining as `start_mining`, routing the funds left on the miner account to
+		/// `beneficiary` when the miner is reclaimed
+		pub fn start_mining_with_refund(
+			miner: T::AccountId,
+			stake: BalanceOf<T, I>,
+			beneficiary: T::AccountId,
+		) -> DispatchResult {
+			Self::do_start_mining(miner.clone(), stake, None, false)?;
+			RefundBeneficiaries::<T, I>::insert(&miner, beneficiary);
+			Ok(())
+		}
+
+		/// Starts observing the bound worker without stake
+		///
+		/// The worker runs the benchmark and responds to the heartbeat challenges as in mining, so
+		/// that the hardware can be burnt in before any stake. The miner stays in `Ready` state,
+		/// earns nothing and can't be slashed. Starting mining ends the observation.
+		///
+		/// Requires:
+		/// 1. The miner is bound to a worker with the initial benchmark, and is in Ready state
+		pub fn start_observing(miner: T::AccountId) -> DispatchResult {
+			let worker = MinerBindings::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotFound)?;
+			let miner_info = Miners::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotFound)?;
+			ensure!(
+				miner_info.state == MinerState::Ready,
+				Error::<T, I>::MinerNotReady
+			);
+			ensure!(
+				!Observers::<T, I>::contains_key(&miner),
+				Error::<T, I>::AlreadyObserving
+			);
+			let worker_info =
+				T::WorkerInfo::worker_info(&worker).expect("Bounded worker must exist; qed.");
+			ensure!(
+				worker_info.initial_score.is_some(),
+				Error::<T, I>::BenchmarkMissing
+			);
+
+			let session_id = NextSessionId::<T, I>::get();
+			NextSessionId::<T, I>::put(session_id + 1);
+			Observers::<T, I>::insert(&miner, Self::now_sec());
+			ObservingMiners::<T, I>::mutate(|n| *n = n.saturating_add(1));
+			Self::push_worker_event(worker, WorkerEvent::ObservingStart { session_id });
+			Self::deposit_event(Event::<T, I>::MinerObservingStarted(miner));
+			Ok(())
+		}
+
+		/// Stops observing, and stops the benchmark of the worker
+		pub fn stop_observing(miner: T::AccountId) -> DispatchResult {
+			let worker = MinerBindings::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotFound)?;
+			Self::end_observation(&miner).ok_or(Error::<T, I>::MinerNotObserving)?;
+			Self::withdraw_session_backlog(&worker);
+			Self::push_worker_event(worker, WorkerEvent::MiningStop);
+			Ok(())
+		}
+
+		/// Removes the miner from the observers. Returns None if it's not observing.
+		fn end_observation(miner: &T::AccountId) -> Option<()> {
+			Observers::<T, I>::take(miner)?;
+			ObservingMiners::<T, I>::mutate(|n| *n = n.saturating_sub(1));
+			Self::deposit_event(Event::<T, I>::MinerObservingStopped(miner.clone()));
+			Some(())
+		}
+
+		/// Starts trial mining with a reduced stake
+		///
+		/// The minimal stake is reduced, and the initial V and the payouts are capped according
+		/// to `TrialMining`, until the miner has stayed responsive for the trial duration.
+		pub fn start_trial_mining(miner: T::AccountId, stake: BalanceOf<T, I>) -> DispatchResult {
+			let config = TrialMining::<T, I>::get().ok_or(Error::<T, I>::TrialMiningDisabled)?;
+			Self::do_start_mining(miner, stake, Some(config), false)
+		}
+
+		/// Starts mining, optionally in trial mining
+		///
+		/// - `just_bound`: the miner was bound in the same call without notifying the worker, so
+		///   the binding is sent along with the start in `MinerBoundAndStarted`
+		fn do_start_mining(
+			miner: T::AccountId,
+			stake: BalanceOf<T, I>,
+			trial: Option<TrialMiningConfig<T::BlockNumber>>,
+			just_bound: bool,
+		) -> DispatchResult {
+			let worker = MinerBindings::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotFound)?;
+			let now = Self::now_sec();
+			let session_id = NextSessionId::<T, I>::get();
+			// Throttles the workers cycling through the sessions
+			if let Some(last_end) = LastSessionEnd::<T, I>::get(&worker) {
+				ensure!(
+					now >= last_end.saturating_add(MinSessionInterval::<T, I>::get()),
+					Error::<T, I>::SessionIntervalNotElapsed
+				);
+			}
+			// Checked by the binding already
+			if !just_bound {
+				let worker_info = T::WorkerInfo::worker_info(&worker)
+					.ok_or(Error::<T, I>::WorkerNotRegistered)?;
+				Self::ensure_worker_version(&worker, &worker_info)?;
+			}
+
+			let ve =
+				Miners::<T, I>::try_mutate(&miner, |info| -> Result<FixedPoint, DispatchError> {
+					let info = info.as_mut().ok_or(Error::<T, I>::MinerNotFound)?;
+					ensure!(
+						info.state == MinerState::Ready,
+						Error::<T, I>::MinerNotReady
+					);
+					if let Some(max) = MaxOnlineMiners::<T, I>::get() {
+						if Self::online_miners() >= max {
+							Self::deposit_event(Event::<T, I>::MiningStartBlockedByCapacity(
+								miner.clone(),
+								max,
+							));
+							return Err(Error::<T, I>::NetworkAtCapacity.into());
+						}
+					}
+
+					let worker_info = T::WorkerInfo::worker_info(&worker)
+						.expect("Bounded worker must exist; qed.");
+					let p = worker_info
+						.initial_score
+						.ok_or(Error::<T, I>::BenchmarkMissing)?;
+					if let Some(period) = BenchmarkValidityPeriod::<T, I>::get() {
+						let expired = worker_info
+							.benchmarked_at
+							.map_or(true, |at| now.saturating_sub(at) > period);
+						if expired {
+							let duration =
+								registry::BenchmarkDuration::<T>::get().unwrap_or_default();
+							Self::push_worker_event(
+								worker.clone(),
+								WorkerEvent::BenchStart { duration },
+							);
+							return Err(Error::<T, I>::BenchmarkExpired.into());
+						}
+					}
+
+					let tokenomic = Self::tokenomic();
+					let min_stake = match &trial {
+						Some(config) => config.stake_ratio * tokenomic.minimal_stake(p),
+						None => tokenomic.minimal_stake(p),
+					};
+					ensure!(stake >= min_stake, Error::<T, I>::InsufficientStake);
+					if let Some(cap) = Self::stake_cap(p) {
+						ensure!(stake <= cap, Error::<T, I>::StakeExceedsCap);
+					}
+
+					let mut ve = tokenomic.ve(stake, p, worker_info.confidence_level);
+					let v_max = tokenomic.v_max();
+					ensure!(ve <= v_max, Error::<T, I>::TooMuchStake);
+					if let Some(config) = &trial {
+						ve = ve.min(FixedPoint::from_bits(config.ve_cap));
+					}
+
+					Self::set_miner_state(&miner, info, MinerState::MiningIdle);
+					info.ve = ve.into();
+					info.set_v(ve.into());
+					info.v_updated_at = now;
+					info.stats.on_session(session_id);
+					info.session_start = now;
+					info.session_payout = 0;
+					Ok(ve)
+				})?;
+			UptimeStreaks::<T, I>::remove(&miner);
+			EffectiveSlashRates::<T, I>::remove(&miner);
+			#[cfg(any(test, feature = "settlement-audit"))]
+			Self::start_settlement_trace(&miner);
+
+			Stakes::<T, I>::insert(&miner, stake);
+			BondedWorkers::<T, I>::insert(&miner, &worker);
+			BondedSessions::<T, I>::insert(&worker, &miner, ());
+			// Replaced by MiningStart on the worker
+			let _ = Self::end_observation(&miner);
+			OnlineStake::<T, I>::mutate(|total| *total = total.saturating_add(stake));
+
+			NextSessionId::<T, I>::put(session_id + 1);
+			if let Some(config) = trial {
+				let ends_at = frame_system::Pallet::<T>::block_number() + config.duration;
+				TrialMiners::<T, I>::insert(&miner, ends_at);
+				Self::deposit_event(Event::<T, I>::MinerTrialStarted(miner.clone(), ends_at));
+			}
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Started {
+				miner: &miner,
+				worker: &worker,
+				stake,
+			});
+			let event = if just_bound {
+				WorkerEvent::MinerBoundAndStarted {
+					miner: miner.encode(),
+					session_id,
+					init_v: ve.to_bits(),
+				}
+			} else {
+				WorkerEvent::MiningStart {
+					session_id: session_id,
+					init_v: ve.to_bits(),
+				}
+			};
+			Self::push_worker_event(worker.clone(), event);
+			if StakeWeightedSampling::<T, I>::get() {
+				Self::push_worker_event(
+					worker.clone(),
+					WorkerEvent::MiningStakeUpdated {
+						stake: stake.saturated_into(),
+					},
+				);
+			}
+			// The gatekeeper starts the session with the full slash rate
+			let multiplier = Self::slash_multiplier(&miner);
+			if multiplier != Permill::one() {
+				Self::push_slash_multiplier(worker, multiplier);
+			}
+			Self::deposit_event(Event::<T, I>::MinerStaked(miner.clone(), stake));
+			Self::deposit_event(Event::<T, I>::MinerStarted(miner));
+			Ok(())
+		}
+
+		/// Stops mining, enterying cool down state
+		///
+		/// Requires:
+		/// 1. Ther miner is in Idle, MiningActive, or MiningUnresponsive state
+		pub fn stop_mining(miner: T::AccountId) -> DispatchResult {
+			let worker = MinerBindings::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotBound)?;
+			let mut miner_info = Miners::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotFound)?;
+
+			ensure!(
+				miner_info.state != MinerState::Ready
+					&& miner_info.state != MinerState::MiningCoolingDown,
+				Error::<T, I>::MinerNotMining
+			);
+
+			let now = Self::now_sec();
+			Self::set_miner_state(&miner, &mut miner_info, MinerState::MiningCoolingDown);
+			miner_info.cool_down_start = now;
+			Miners::<T, I>::insert(&miner, &miner_info);
+			let stake = Stakes::<T, I>::get(&miner).unwrap_or_default();
+			OnlineStake::<T, I>::mutate(|total| *total = total.saturating_sub(stake));
+			TrialMiners::<T, I>::remove(&miner);
+			LastSessionEnd::<T, I>::insert(&worker, now);
+			Self::schedule_reclaim(&miner, now);
+
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::Stopped {
+				miner: &miner,
+				worker: &worker,
+			});
+			Self::withdraw_session_backlog(&worker);
+			Self::push_worker_event(worker, WorkerEvent::MiningStop);
+			Self::deposit_event(Event::<T, I>::MinerStopped(miner));
+			Ok(())
+		}
+
+		/// Pushes an event to the worker with the current session and the next nonce of the worker
+		fn push_worker_event(worker: WorkerPublicKey, event: WorkerEvent) {
+			let seq = WorkerEventSeqs::<T, I>::mutate(&worker, |seq| {
+				match event {
+					WorkerEvent::MiningStart { session_id, .. }
+					| WorkerEvent::SessionTransferred { session_id, .. }
+					| WorkerEvent::MinerBoundAndStarted { session_id, .. }
+					| WorkerEvent::ObservingStart { session_id } => {
+						seq.session_id = session_id;
+					}
+					_ => {}
+				}
+				seq.nonce += 1;
+				*seq
+			});
+			// The commands within a session are obsolete once the session is terminated
+			let in_session = matches!(
+				event,
+				WorkerEvent::MiningEnterUnresponsive
+					| WorkerEvent::MiningExitUnresponsive
+					| WorkerEvent::TrialCompleted { .. }
+					| WorkerEvent::MiningStakeUpdated { .. }
+			);
+			let tag = (worker.clone(), seq.session_id).encode();
+			let message = SystemEvent::new_sequenced_worker_event(worker, event, seq);
+			if in_session {
+				Self::push_tagged_message(message, tag);
+			} else {
+				Self::push_message(message);
+			}
+		}
+
+		/// Withdraws the commands of the current session of the worker not yet delivered, so that
+		/// the worker doesn't act on them after the session is terminated
+		fn withdraw_session_backlog(worker: &WorkerPublicKey) {
+			let session_id = WorkerEventSeqs::<T, I>::get(worker).session_id;
+			let tag = (worker.clone(), session_id).encode();
+			let withdrawn = mq::Pallet::<T>::withdraw_tagged_messages(|t| t == &tag[..]);
+			if withdrawn > 0 {
+				Self::deposit_event(Event::<T, I>::SessionBacklogWithdrawn(
+					worker.clone(),
+					session_id,
+					withdrawn,
+				));
+			}
+		}
+
+		/// Updates a worker binding in the bindings child trie, refreshes the commitment, and
+		/// notifies the worker
+		fn commit_binding(worker: &WorkerPublicKey, miner: Option<&T::AccountId>) {
+			Self::update_bindings_commitment(worker, miner);
+			let event = match miner {
+				Some(miner) => WorkerEvent::MinerBound {
+					miner: miner.encode(),
+				},
+				None => WorkerEvent::MinerUnbound,
+			};
+			Self::push_worker_event(worker.clone(), event);
+		}
+
+		/// Updates the binding of a worker in `BindingsCommitment` without notifying the worker
+		fn update_bindings_commitment(worker: &WorkerPublicKey, miner: Option<&T::AccountId>) {
+			let child_info = Self::bindings_child_info();
+			let key = worker.encode();
+			match miner {
+				Some(miner) => child::put(&child_info, &key, miner),
+				None => child::kill(&child_info, &key),
+			}
+			let root = child::root(&child_info);
+			let root = T::Hash::decode(&mut &root[..]).expect("Child root is a hash; qed.");
+			BindingsCommitment::<T, I>::put(root);
+		}
+
+		fn bindings_child_info() -> ChildInfo {
+			ChildInfo::new_default(&[BINDINGS_CHILD_KEY, T::TopicPrefix::get()].concat())
+		}
+
+		/// The lock of the stake of the solo miners of this instance, derived from `TopicPrefix`
+		/// for the instances other than the default one
+		pub fn solo_staking_id() -> LockIdentifier {
+			let prefix = T::TopicPrefix::get();
+			if prefix.is_empty() {
+				return SOLO_STAKING_ID;
+			}
+			let hash = crate::hashing::blake2_256(&[&SOLO_STAKING_ID[..], prefix].concat());
+			let mut id = LockIdentifier::default();
+			id.copy_from_slice(&hash[..id.len()]);
+			id
+		}
+
+		/// Returns if the worker is bound in this or any other instance of the pallet
+		fn is_bound_in_any_instance(pubkey: &WorkerPublicKey) -> bool {
+			WorkerBindings::<T, I>::contains_key(pubkey)
+				|| <T::OtherInstances as registry::WorkerBindingInfo>::is_worker_bound(pubkey)
+		}
+
+		/// Returns if the worker is already bounded to a miner
+		pub fn ensure_worker_bound(pubkey: &WorkerPublicKey) -> Result<T::AccountId, Error<T, I>> {
+			WorkerBindings::<T, I>::get(&pubkey).ok_or(Error::<T, I>::WorkerNotBound)
+		}
+
+		/// Returns if the miner is already bounded to a worker
+		pub fn ensure_miner_bound(miner: &T::AccountId) -> Result<WorkerPublicKey, Error<T, I>> {
+			MinerBindings::<T, I>::get(&miner).ok_or(Error::<T, I>::MinerNotBound)
+		}
+
+		fn update_tokenomic_parameters(params: TokenomicParams, proposer: Option<T::AccountId>) {
+			let old_params_hash =
+				TokenomicParameters::<T, I>::get().map(|p| T::Hashing::hash_of(&p));
+			TokenomicHistory::<T, I>::mutate(|history| {
+				if history.len() >= MAX_TOKENOMIC_HISTORY {
+					history.remove(0);
+				}
+				history.push(TokenomicChange {
+					block: frame_system::Pallet::<T>::block_number(),
+					old_params_hash,
+					new_params_hash: T::Hashing::hash_of(&params),
+					proposer,
+				});
+			});
+			TokenomicParameters::<T, I>::put(params.clone());
+			Self::push_message(GatekeeperEvent::TokenomicParametersChanged(params));
+		}
+
+		/// Schedules an operation to be dispatched at the beginning of block `at`
+		fn schedule(at: T::BlockNumber, op: ScheduledOp<T::AccountId>) -> DispatchResult {
+			ensure!(
+				at > frame_system::Pallet::<T>::block_number(),
+				Error::<T, I>::InvalidParameter
+			);
+			ScheduledOps::<T, I>::try_mutate(at, |ops| -> DispatchResult {
+				ensure!(
+					ops.len() < MAX_SCHEDULED_OPS_PER_BLOCK,
+					Error::<T, I>::ScheduleFull
+				);
+				ops.push(op);
+				Ok(())
+			})
+		}
+
+		/// Schedules the reclaim of a miner at the first block after its cool down with a free
+		/// slot
+		///
+		/// The reclaim is left to be triggered manually if no slot is found.
+		fn schedule_reclaim(miner: &T::AccountId, cool_down_start: u64) {
+			let block_time = T::ExpectedBlockTimeSec::get().max(1) as u64;
+			let remaining =
+				(cool_down_start + Self::cool_down_period()).saturating_sub(Self::now_sec());
+			let blocks: T::BlockNumber = (remaining / block_time + 1).saturated_into();
+			let at = frame_system::Pallet::<T>::block_number() + blocks;
+			for i in 0..MAX_SCHEDULE_LOOKAHEAD {
+				let op = ScheduledOp::Reclaim(miner.clone());
+				if Self::schedule(at + i.into(), op).is_ok() {
+					return;
+				}
+			}
+		}
+
+		/// Dispatches the operations scheduled at block `n`
+		fn dispatch_scheduled_ops(n: T::BlockNumber) -> Weight {
+			let ops = ScheduledOps::<T, I>::take(n);
+			let mut weight = T::DbWeight::get().reads_writes(1, 1);
+			for op in ops {
+				let result = match &op {
+					ScheduledOp::UpdateTokenomic(params) => {
+						Self::update_tokenomic_parameters(params.clone(), None);
+						Ok(())
+					}
+					ScheduledOp::Reclaim(miner) => match Miners::<T, I>::get(miner) {
+						// Block time may drift from the expectation
+						Some(info)
+							if info.state == MinerState::MiningCoolingDown
+								&& !Self::can_reclaim(&info) =>
+						{
+							Self::schedule_reclaim(miner, info.cool_down_start);
+							Ok(())
+						}
+						_ => Self::do_reclaim(miner.clone()),
+					},
+				};
+				weight = weight.saturating_add(T::DbWeight::get().reads_writes(4, 4));
+				Self::deposit_event(Event::<T, I>::OperationDispatched(op, result.is_ok()));
+			}
+			weight
+		}
+
+		/// Updates `pha_rate` from the price feed at most once per era, clamped to `PhaRateBounds`
+		fn maybe_feed_pha_rate() {
+			let (min, max) = match PhaRateBounds::<T, I>::get() {
+				Some(bounds) => bounds,
+				None => return,
+			};
+			let now = Self::now_sec();
+			let last = PhaRateUpdatedAt::<T, I>::get();
+			if last != 0 && now < last + PHA_RATE_UPDATE_PERIOD_SEC {
+				return;
+			}
+			let price = match T::PriceFeed::pha_price() {
+				Some(price) => price,
+				None => return,
+			};
+			let mut params = match TokenomicParameters::<T, I>::get() {
+				Some(params) => params,
+				None => return,
+			};
+			PhaRateUpdatedAt::<T, I>::put(now);
+			let pha_rate = price.max(min).min(max);
+			if pha_rate == params.pha_rate {
+				return;
+			}
+			params.pha_rate = pha_rate;
+			Self::update_tokenomic_parameters(params, None);
+			Self::deposit_event(Event::<T, I>::PhaRateUpdated(pha_rate));
+		}
+
+		/// The balance of an account available to stake as a solo miner
+		///
+		/// Locks overlap, so the balance already frozen by the other locks (e.g. vesting,
+		/// democracy, or stake pool contributions) is excluded to keep the solo stake backed by
+		/// otherwise liquid balance.
+		pub fn free_stakable_balance(who: &T::AccountId) -> BalanceOf<T, I> {
+			if Self::is_solo_miner(who) {
+				return Zero::zero();
+			}
+			let free = T::Currency::free_balance(who);
+			let can_withdraw = |amount: BalanceOf<T, I>| {
+				T::Currency::ensure_can_withdraw(who, amount, WithdrawReasons::all(), free - amount)
+					.is_ok()
+			};
+			if can_withdraw(free) {
+				return free;
+			}
+			// The withdrawable amount is monotonic. Bisect the max one allowed by the locks.
+			let one: BalanceOf<T, I> = 1u32.into();
+			let (mut lo, mut hi) = (Zero::zero(), free);
+			while hi - lo > one {
+				let mid = lo + (hi - lo) / 2u32.into();
+				if can_withdraw(mid) {
+					lo = mid;
+				} else {
+					hi = mid;
+				}
+			}
+			lo
+		}
+
+		pub fn withdraw_subsidy_pool(
+			target: &T::AccountId,
+			value: BalanceOf<T, I>,
+		) -> DispatchResult {
+			let wallet = Self::account_id();
+			T::Currency::transfer(&wallet, &target, value, KeepAlive)
+		}
+
+		/// Estimates the V change, payout and slash of a worker that stays online for
+		/// `blocks_online` blocks and then offline for `blocks_offline` blocks
+		///
+		/// It mirrors the gatekeeper tokenomic rules, assuming the worker performs exactly as its
+		/// benchmark, and all the online miners share the budget equally. The simulation starts
+		/// from the current V if the worker is mining, or the estimated initial V with the minimal
+		/// stake otherwise.
+		pub fn simulate_settlement(
+			worker: WorkerPublicKey,
+			blocks_online: u32,
+			blocks_offline: u32,
+		) -> Option<SettlementSimulation> {
+			let worker_info = T::WorkerInfo::worker_info(&worker)?;
+			let p = worker_info.initial_score?;
+			let tokenomic = Self::tokenomic();
+			let miner_info = WorkerBindings::<T, I>::get(&worker).and_then(Self::miners);
+			let v = match miner_info {
+				Some(info) if info.state != MinerState::Ready => info.v.to_fixed(),
+				_ => tokenomic.ve(tokenomic.minimal_stake(p), p, worker_info.confidence_level),
+			};
+			let (v_end, payout, slash) = tokenomic.simulate(
+				v,
+				p,
+				blocks_online,
+				blocks_offline,
+				Self::online_miners().max(1),
+				T::ExpectedBlockTimeSec::get(),
+			);
+			let v_delta = I64F64::from_num(v_end) - I64F64::from_num(v);
+			Some(SettlementSimulation {
+				v_delta: v_delta.to_bits(),
+				payout_estimate: payout.to_bits(),
+				slash_estimate: slash.to_bits(),
+			})
+		}
+
+		/// The current per-block tokenomic parameters in block time independent units
+		pub fn tokenomic_hourly_rates() -> Option<TokenomicHourlyRates> {
+			let params = TokenomicParameters::<T, I>::get()?;
+			Some(TokenomicHourlyRates::from_params(
+				&params,
+				T::ExpectedBlockTimeSec::get(),
+			))
+		}
+
+		fn tokenomic() -> Tokenomic<T, I> {
+			let params =
+				TokenomicParameters::<T, I>::get().expect("TokenomicParameters must exist; qed.");
+			Tokenomic::<T, I>::new(params)
+		}
+
+		/// Gets the stake cap with the given performance score, or None if the cap is disabled
+		fn stake_cap(p: u32) -> Option<BalanceOf<T, I>> {
+			let params = StakeCap::<T, I>::get()?;
+			let p = FixedPoint::from_num(p);
+			let cap = FixedPoint::from_bits(params.k)
+				.saturating_mul(p)
+				.saturating_add(FixedPoint::from_bits(params.b));
+			Some(FixedPointConvert::from_fixed(&cap))
+		}
+
+		/// The min ratio of the stake slashed for a session ended by an attestation revocation
+		pub fn revocation_slash_ratio() -> Permill {
+			RevocationSlashRatio::<T, I>::get().unwrap_or_else(Permill::one)
+		}
+
+		fn now_sec() -> u64 {
+			<T as registry::Config>::UnixTime::now()
+				.as_secs()
+				.saturated_into::<u64>()
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> registry::OnWorkerKeyRotated for Pallet<T, I>
+	where
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		/// Migrates the bindings and the mining session of the rotated worker to the new key
+		fn on_worker_key_rotated(old: &WorkerPublicKey, new: &WorkerPublicKey) {
+			let miner = match WorkerBindings::<T, I>::take(old) {
+				Some(miner) => miner,
+				None => return,
+			};
+			MinerBindings::<T, I>::insert(&miner, new);
+			WorkerBindings::<T, I>::insert(new, &miner);
+			Self::commit_binding(old, None);
+			Self::commit_binding(new, Some(&miner));
+
+			let mut miner_info = match Miners::<T, I>::get(&miner) {
+				Some(info) => info,
+				None => return,
+			};
+			if miner_info.state.can_settle() {
+				// The new pRuntime instance restarts the benchmark counter, so the session is
+				// handed over the same way as `swap_miner_worker`.
+				Self::set_miner_state(&miner, &mut miner_info, MinerState::MiningIdle);
+				miner_info.benchmark = Benchmark {
+					p_instant: 0u32,
+					iterations: 0u64,
+					mining_start_time: Self::now_sec(),
+					updated_at: 0u64,
+				};
+				let session_id = NextSessionId::<T, I>::get();
+				NextSessionId::<T, I>::put(session_id + 1);
+				miner_info.stats.on_session(session_id);
+				Miners::<T, I>::insert(&miner, &miner_info);
+				Self::push_worker_event(old.clone(), WorkerEvent::MiningStop);
+				Self::push_worker_event(
+					new.clone(),
+					WorkerEvent::SessionTransferred {
+						session_id,
+						from: old.clone(),
+					},
+				);
+			}
+			T::LifecycleObserver::on_mining_event(&MiningLifecycleEvent::WorkerKeyRotated {
+				miner: &miner,
+				from: old,
+				to: new,
+			});
+			Self::deposit_event(Event::<T, I>::MinerWorkerSwapped(
+				miner,
+				old.clone(),
+				new.clone(),
+			));
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> registry::WorkerBindingInfo for Pallet<T, I> {
+		fn is_worker_bound(worker: &WorkerPublicKey) -> bool {
+			WorkerBindings::<T, I>::contains_key(worker)
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> registry::OnAttestationRevoked for Pallet<T, I>
+	where
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		/// Ends the mining session of the revoked worker at once and unbinds it, flagging the
+		/// session for the revocation slash
+		fn on_attestation_revoked(worker: &WorkerPublicKey) {
+			let miner = match WorkerBindings::<T, I>::get(worker) {
+				Some(miner) => miner,
+				None => return,
+			};
+			let session_ended =
+				Miners::<T, I>::get(&miner).map_or(false, |info| !info.state.can_unbind());
+			if session_ended {
+				RevokedSessions::<T, I>::insert(&miner, true);
+			}
+			if let Err(err) = Self::unbind_miner(&miner, true) {
+				log::error!("Failed to unbind the revoked worker: {:?}", err);
+			}
+			Self::deposit_event(Event::<T, I>::MinerAttestationRevoked(
+				miner,
+				worker.clone(),
+				session_ended,
+			));
+		}
+	}
+
+	impl<T: Config<I>, I: 'static> MiningInfoProvider<T::AccountId, BalanceOf<T, I>> for Pallet<T, I>
+	where
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		fn miner_state(miner: &T::AccountId) -> Option<MinerState> {
+			Miners::<T, I>::get(miner).map(|info| info.state)
+		}
+		fn miner_stake(miner: &T::AccountId) -> Option<BalanceOf<T, I>> {
+			Stakes::<T, I>::get(miner)
+		}
+		fn miner_ve_v(miner: &T::AccountId) -> Option<(u128, u128)> {
+			Miners::<T, I>::get(miner).map(|info| (info.ve.to_bits(), info.v.to_bits()))
+		}
+		fn can_reclaim(miner: &T::AccountId) -> bool {
+			Miners::<T, I>::get(miner).map_or(false, |info| Self::can_reclaim(&info))
+		}
+		fn is_solo_miner(miner: &T::AccountId) -> bool {
+			SoloMiners::<T, I>::get(miner)
+		}
+	}
+
+	pub(crate) struct Tokenomic<T, I: 'static = ()> {
+		params: TokenomicParams,
+		mark: PhantomData<(T, I)>,
+	}
+
+	impl<T, I: 'static> Tokenomic<T, I>
+	where
+		T: Config<I>,
+		BalanceOf<T, I>: FixedPointConvert,
+	{
+		pub(crate) fn new(params: TokenomicParams) -> Self {
+			Tokenomic {
+				params,
+				mark: Default::default(),
+			}
+		}
+
+		/// Gets the minimal stake with the given performance score
+		pub(crate) fn minimal_stake(&self, p: u32) -> BalanceOf<T, I> {
+			let p = FixedPoint::from_num(p);
+			let k = FixedPoint::from_bits(self.params.k);
+			let min_stake = k * p.sqrt();
+			FixedPointConvert::from_fixed(&min_stake)
+		}
+
+		/// Calcuates the initial Ve
+		pub(crate) fn ve(&self, s: BalanceOf<T, I>, p: u32, confidence_level: u8) -> FixedPoint {
+			let f1 = FixedPoint::from_num(1);
+			let score = Self::confidence_score(confidence_level);
+			let re = FixedPoint::from_bits(self.params.re);
+			let tweaked_re = (re - f1) * score + f1;
+			let s = s.to_fixed();
+			let c = self.rig_cost(p);
+			tweaked_re * (s + c)
+		}
+
+		/// Simulates the gatekeeper V updates for `blocks_online` idle blocks with a final
+		/// heartbeat, followed by `blocks_offline` slashed blocks
+		///
+		/// Returns the final V, the payout, and the slashed V.
+		pub(crate) fn simulate(
+			&self,
+			v: FixedPoint,
+			p: u32,
+			blocks_online: u32,
+			blocks_offline: u32,
+			online_miners: u32,
+			secs_per_block: u32,
+		) -> (FixedPoint, FixedPoint, FixedPoint) {
+			let one = FixedPoint::from_num(1);
+			let budget_per_sec = FixedPoint::from_bits(self.params.budget_per_sec);
+			let slash_rate = FixedPoint::from_bits(self.params.slash_rate);
+			// Idle: v[n+1] = rho * v[n] + cost_idle, capped by v_max
+			let v_online = self.v_after_idle(v, p, blocks_online);
+			// Heartbeat: the payout is bounded by the V increment and the budget share
+			let dt = FixedPoint::from_num(blocks_online) * FixedPoint::from_num(secs_per_block);
+			let budget = budget_per_sec * dt / FixedPoint::from_num(online_miners);
+			let payout = (v_online - v).min(budget);
+			let v_settled = v_online - payout;
+			// Offline: v[n+1] = v[n] * (1 - slash_rate)
+			let v_end = v_settled * fixed_pow(one - slash_rate, blocks_offline);
+			(v_end, payout, v_settled - v_end)
+		}
+
+		/// Grows V as idle for `blocks` blocks: v[n+1] = rho * v[n] + cost_idle, capped by v_max
+		fn v_after_idle(&self, v: FixedPoint, p: u32, blocks: u32) -> FixedPoint {
+			self.v_after_idle_scaled(v, p, FixedPoint::from_num(1), blocks)
+		}
+
+		/// Grows V as idle for `blocks` blocks with the performance multiplier `perf`:
+		/// v[n+1] = v[n] + perf * ((rho - 1) * v[n] + cost_idle), capped by v_max
+		fn v_after_idle_scaled(
+			&self,
+			v: FixedPoint,
+			p: u32,
+			perf: FixedPoint,
+			blocks: u32,
+		) -> FixedPoint {
+			let one = FixedPoint::from_num(1);
+			let rho = FixedPoint::from_bits(self.params.rho);
+			let rho = if rho >= one {
+				one.saturating_add(perf.saturating_mul(rho - one))
+			} else {
+				one.saturating_sub(perf.saturating_mul(one - rho))
+			};
+			let cost_k = FixedPoint::from_bits(self.params.cost_k);
+			let cost_b = FixedPoint::from_bits(self.params.cost_b);
+			let pha_rate = FixedPoint::from_bits(self.params.pha_rate);
+			let cost_idle = perf.saturating_mul(
+				(cost_k * FixedPoint::from_num(p) + cost_b) / pha_rate / FixedPoint::from_num(365),
+			);
+			let rho_n = fixed_pow(rho, blocks);
+			let accrued = if rho > one {
+				cost_idle * (rho_n - one) / (rho - one)
+			} else {
+				cost_idle * FixedPoint::from_num(blocks)
+			};
+			(rho_n * v + accrued).min(self.v_max()).max(v)
+		}
+
+		/// Replays the gatekeeper V updates of the `(blocks, unresponsive)` segments in order,
+		/// before any payout
+		///
+		/// V grows as idle scaled by `perf`, or is slashed at the slash rate scaled by
+		/// `slash_multiplier` while unresponsive.
+		#[cfg(any(test, feature = "settlement-audit"))]
+		fn replay_v(
+			&self,
+			v: FixedPoint,
+			p: u32,
+			perf: FixedPoint,
+			slash_multiplier: Permill,
+			segments: &[(u32, bool)],
+		) -> FixedPoint {
+			let slash_rate = FixedPoint::from_bits(self.params.slash_rate)
+				* FixedPoint::from_num(slash_multiplier.deconstruct())
+				/ FixedPoint::from_num(Permill::ACCURACY);
+			let keep = FixedPoint::from_num(1).saturating_sub(slash_rate);
+			segments.iter().fold(v, |v, &(blocks, unresponsive)| {
+				if unresponsive {
+					v * fixed_pow(keep, blocks)
+				} else {
+					self.v_after_idle_scaled(v, p, perf, blocks)
+				}
+			})
+		}
+
+		/// Gets the max v in fixed point
+		fn v_max(&self) -> FixedPoint {
+			FixedPoint::from_bits(self.params.v_max)
+		}
+
+		/// Gets the estimated rig costs in PHA
+		fn rig_cost(&self, p: u32) -> FixedPoint {
+			let cost_k = FixedPoint::from_bits(self.params.rig_k);
+			let cost_b = FixedPoint::from_bits(self.params.rig_b);
+			let pha_rate = FixedPoint::from_bits(self.params.pha_rate);
+			let p = FixedPoint::from_num(p);
+			(cost_k * p + cost_b) / pha_rate
+		}
 
 		/// Gets the operating cost per sec
 		#[cfg(test)]
@@ -733,352 +6512,4987 @@ pub mod pallet {
 			(cost_k * p + cost_b) / pha_rate
 		}
 
-		/// Converts confidence level to score
-		fn confidence_score(confidence_level: u8) -> FixedPoint {
-			use fixed_macro::types::U64F64 as fp;
-			const SCORES: [FixedPoint; 5] = [fp!(1), fp!(1), fp!(1), fp!(0.8), fp!(0.7)];
-			if 1 <= confidence_level && confidence_level <= 5 {
-				SCORES[confidence_level as usize - 1]
-			} else {
-				SCORES[0]
+		/// Converts confidence level to score, with the `ConfidenceScores` table if set
+		fn confidence_score(confidence_level: u8) -> FixedPoint {
+			use fixed_macro::types::U64F64 as fp;
+			const SCORES: [FixedPoint; 5] = [fp!(1), fp!(1), fp!(1), fp!(0.8), fp!(0.7)];
+			let index = if 1 <= confidence_level && confidence_level <= 5 {
+				confidence_level as usize - 1
+			} else {
+				0
+			};
+			match ConfidenceScores::<T, I>::get() {
+				Some(scores) => FixedPoint::from_bits(scores[index]),
+				None => SCORES[index],
+			}
+		}
+
+		/// Gets kappa in FixedPoint
+		fn _kappa(&self) -> FixedPoint {
+			FixedPoint::from_bits(self.params.kappa)
+		}
+	}
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig {
+		pub cool_down_period_sec: u32,
+		pub tokenomic_parameters: TokenomicParams,
+		/// The SCALE-encoded `MiningStateSnapshot` to import, overriding the parameters above
+		pub mining_state: Option<sp_core::Bytes>,
+	}
+
+	#[cfg(feature = "std")]
+	impl Default for GenesisConfig {
+		/// Default tokenoic parameters for Phala
+		fn default() -> Self {
+			use fixed_macro::types::U64F64 as fp;
+			let pha_rate = fp!(1);
+			let rho = fp!(1.00000099985); // hourly: 1.00020,  1.0002 ** (1/300)
+			let slash_rate = fp!(0.001) / 300; // hourly rate: 0.001, convert to per-block rate
+			let budget_per_sec = fp!(720000) / 24 / 3600;
+			let v_max = fp!(30000);
+			let cost_k = fp!(0.0415625) / 3600 / 24 / 365; // annual 0.0415625, convert to per sec
+			let cost_b = fp!(88.59375) / 3600 / 24 / 365; // annual 88.59375, convert to per sec
+			let heartbeat_window = 10; // 10 blocks
+			let rig_k = fp!(0.3);
+			let rig_b = fp!(0);
+			let re = fp!(1.5);
+			let k = fp!(100);
+			let kappa = fp!(1);
+
+			Self {
+				cool_down_period_sec: 604800, // 7 days
+				tokenomic_parameters: TokenomicParams {
+					pha_rate: pha_rate.to_bits(),
+					rho: rho.to_bits(),
+					budget_per_sec: budget_per_sec.to_bits(),
+					v_max: v_max.to_bits(),
+					cost_k: cost_k.to_bits(),
+					cost_b: cost_b.to_bits(),
+					slash_rate: slash_rate.to_bits(),
+					heartbeat_window: 10,
+					rig_k: rig_k.to_bits(),
+					rig_b: rig_b.to_bits(),
+					re: re.to_bits(),
+					k: k.to_bits(),
+					kappa: kappa.to_bits(),
+				},
+				mining_state: None,
+			}
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config<I>, I: 'static> GenesisBuild<T, I> for GenesisConfig {
+		fn build(&self) {
+			MiningStorageVersion::<T, I>::put(STORAGE_VERSION);
+			CoolDownPeriod::<T, I>::put(self.cool_down_period_sec as u64);
+			TokenomicParameters::<T, I>::put(self.tokenomic_parameters.clone());
+			Pallet::<T, I>::queue_message(GatekeeperEvent::TokenomicParametersChanged(
+				self.tokenomic_parameters.clone(),
+			));
+			if let Some(state) = &self.mining_state {
+				let snapshot = MiningStateSnapshot::decode(&mut &state[..])
+					.expect("Invalid mining state snapshot");
+				Pallet::<T, I>::import_mining_state(snapshot);
+			}
+		}
+	}
+
+	/// Splits the gatekeeper blocks between the settlements at `since` and `until` into the runs
+	/// of the idle and the unresponsive blocks by the status `transitions` of a `SettlementTrace`,
+	/// returning the runs and whether the miner is unresponsive as of `until`
+	///
+	/// The blocks of the settlements (heartbeats) don't update V. A worker reported offline is
+	/// slashed from the block of the report, and a recovered one heartbeats in that block.
+	#[cfg(any(test, feature = "settlement-audit"))]
+	fn trace_segments(
+		since: u32,
+		until: u32,
+		mut unresponsive: bool,
+		transitions: &[(u32, bool)],
+	) -> (Vec<(u32, bool)>, bool) {
+		let mut segments = Vec::new();
+		let mut cursor = since.saturating_add(1);
+		for &(block, to_unresponsive) in transitions.iter().filter(|(block, _)| *block < until) {
+			let block = block.max(cursor);
+			segments.push((block - cursor, unresponsive));
+			unresponsive = to_unresponsive;
+			cursor = if to_unresponsive {
+				block
+			} else {
+				block.saturating_add(1)
+			};
+		}
+		segments.push((until.saturating_sub(cursor), unresponsive));
+		(segments, unresponsive)
+	}
+
+	/// Calculates `x ^ n` by squaring
+	fn fixed_pow(x: FixedPoint, n: u32) -> FixedPoint {
+		let mut i = n;
+		let mut x_pow2 = x;
+		let mut z = FixedPoint::from_num(1);
+		while i > 0 {
+			if i & 1 == 1 {
+				z *= x_pow2;
+			}
+			i >>= 1;
+			if i > 0 {
+				x_pow2 *= x_pow2;
+			}
+		}
+		z
+	}
+
+	/// Calculates the PoW target to sample `num_tx` heartbeats out of `num_workers` in a block
+	///
+	/// It never panics as it's called in `on_finalize`. The target saturates at the full range
+	/// when every worker is sampled.
+	pub(crate) fn pow_target(num_tx: u32, num_workers: u32, secs_per_block: u32) -> U256 {
+		use fixed::types::U32F32;
+		if num_workers == 0 {
+			return U256::zero();
+		}
+		let one = U32F32::from_num(1);
+		let num_workers = U32F32::from_num(num_workers);
+		let num_tx = U32F32::from_num(num_tx);
+		let blocks_per_hour = (3600 / secs_per_block.max(1)).max(1);
+		// Limit tx per block for a single miner
+		//     t <= max_tx_per_hour * N/T (max_tx_per_hour = 2)
+		let max_tx =
+			num_workers.saturating_mul(U32F32::from_num(2)) / U32F32::from_num(blocks_per_hour);
+		let target_tx = cmp::min(num_tx, max_tx);
+		// Convert to U256 target
+		//     target = MAX * tx / num_workers
+		let frac: u32 = cmp::min(target_tx / num_workers, one)
+			.checked_shl(24)
+			.map(|frac| frac.to_num())
+			.unwrap_or(1 << 24);
+		(U256::MAX >> 24).saturating_mul(frac.into())
+	}
+
+	/// Derives the miner sub-account of a stake pool worker
+	///
+	/// It's the canonical derivation shared by the stake pool and the off-chain tools (exposed by
+	/// the `StakePoolApi` runtime API). Note that a pool worker replaced by `swap_worker` keeps
+	/// driving the sub-account derived from the original worker.
+	pub fn pool_sub_account<AccountId>(pid: u64, pubkey: &WorkerPublicKey) -> AccountId
+	where
+		AccountId: Encode + Decode + Default,
+	{
+		let hash = crate::hashing::blake2_256(&(pid, pubkey).encode());
+		// stake pool miner
+		(b"spm/", hash)
+			.using_encoded(|b| AccountId::decode(&mut TrailingZeroInput::new(b)))
+			.unwrap_or_default()
+	}
+
+	impl<T: Config<I>, I: 'static> MessageOriginInfo for Pallet<T, I> {
+		type Config = T;
+
+		fn message_topic(topic: &[u8]) -> Vec<u8> {
+			Self::instance_topic(topic)
+		}
+	}
+
+	#[cfg(test)]
+	mod test {
+		use super::*;
+		use crate::mock::{
+			ecdh_pubkey, elapse_seconds, new_test_ext, set_block_1, setup_workers, take_events,
+			take_messages, worker_pubkey, Event as TestEvent, Origin, Test, DOLLARS,
+		};
+		// Pallets
+		use crate::mock::{PhalaMining, PhalaRegistry, System, Timestamp};
+		use phala_types::WorkerRegistrationInfo;
+
+		use fixed_macro::types::U64F64 as fp;
+		use frame_support::{assert_noop, assert_ok};
+
+		#[test]
+		fn test_mining_wallet_setup() {
+			new_test_ext().execute_with(|| {
+				assert!(System::account_exists(&PhalaMining::account_id()));
+			});
+		}
+
+		#[test]
+		fn test_pow_target() {
+			// No target
+			assert_eq!(pow_target(20, 0, 12), U256::zero());
+			// Capped target (py3: ``)
+			assert_eq!(
+				pow_target(20, 20, 12),
+				U256::from_dec_str(
+					"771946525395830978497002573683960742805751636319313395421818009383503547160"
+				)
+				.unwrap()
+			);
+			// Not capped target (py3: `int(((1 << 256) - 1) * 20 / 200_000)`)
+			assert_eq!(
+				pow_target(20, 200_000, 12),
+				U256::from_dec_str(
+					"11574228623567775471528085581038571683760509746329738253007553123311417715"
+				)
+				.unwrap()
+			);
+		}
+
+		#[test]
+		fn test_heartbeat_challenge() {
+			new_test_ext().execute_with(|| {
+				use phala_types::messaging::{SystemEvent, Topic};
+
+				set_block_1();
+				IdleMiners::<Test>::put(20);
+				Pallet::<Test>::heartbeat_challenge();
+				// Extract messages
+				let msgs = take_messages();
+				let message = match msgs.as_slice() {
+					[m] => m,
+					_ => panic!("Wrong message events"),
+				};
+				// Check the event target
+				assert_eq!(message.destination, Topic::new("phala/system/event"));
+				// Check the oubound message parameters
+				let target = match message.decode_payload::<SystemEvent>() {
+					Some(SystemEvent::HeartbeatChallenge(r)) => r.online_target,
+					_ => panic!("Wrong outbound message"),
+				};
+				assert_eq!(target, U256::from_dec_str(
+					"771946525395830978497002573683960742805751636319313395421818009383503547160"
+				).unwrap());
+			});
+		}
+
+		#[test]
+		fn test_recount_online_miners() {
+			use frame_support::traits::OnIdle;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(3);
+				for i in 1..=3 {
+					assert_ok!(PhalaMining::bind(i, worker_pubkey(i as u8)));
+				}
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				// Drift the counter
+				IdleMiners::<Test>::put(5);
+				assert_eq!(PhalaMining::online_miners(), 5);
+				// Nothing happens out of the recount interval
+				PhalaMining::recount_on_idle(1, Weight::max_value());
+				assert!(!OnlineMinersRecount::<Test>::exists());
+				// A round in chunks of 2
+				OnlineMinersRecount::<Test>::put((Vec::<u8>::new(), OnlineMinerCounts::default()));
+				assert_eq!(PhalaMining::recount_online_miners(2), 2);
+				assert_eq!(PhalaMining::online_miners(), 5);
+				// A miner goes offline in the middle of the round
+				assert_ok!(PhalaMining::stop_mining(1));
+				assert_eq!(PhalaMining::online_miners(), 4);
+				assert_eq!(PhalaMining::recount_online_miners(2), 1);
+				assert_eq!(PhalaMining::online_miners(), 0);
+				assert_eq!(PhalaMining::idle_miners(), 0);
+				assert!(!OnlineMinersRecount::<Test>::exists());
+				let events = take_events();
+				assert_eq!(
+					events.last(),
+					Some(&TestEvent::PhalaMining(Event::CounterReconciled(4, 0)))
+				);
+				// The counter never underflows
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				IdleMiners::<Test>::put(0);
+				assert_ok!(PhalaMining::stop_mining(2));
+				assert_eq!(PhalaMining::idle_miners(), 0);
+				// A full round in a single block
+				ActiveMiners::<Test>::put(7);
+				assert_eq!(PhalaMining::online_miners(), 7);
+				<PhalaMining as OnIdle<u64>>::on_idle(
+					ONLINE_MINERS_RECOUNT_INTERVAL as u64,
+					Weight::max_value(),
+				);
+				assert_eq!(PhalaMining::active_miners(), 0);
+				assert_eq!(PhalaMining::online_miners(), 0);
+			});
+		}
+
+		#[test]
+		fn test_bind_unbind() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				// Bind & unbind normally
+				let _ = take_events();
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::unbind_miner(&1, false));
+				assert_eq!(
+					take_events().as_slice(),
+					[
+						TestEvent::PhalaMining(Event::MinerBound(1, worker_pubkey(1))),
+						TestEvent::PhalaMining(Event::MinerUnbound(1, worker_pubkey(1)))
+					]
+				);
+				// Checks edge cases
+				assert_noop!(
+					PhalaMining::bind(1, worker_pubkey(100)),
+					Error::<Test>::WorkerNotRegistered,
+				);
+				assert_noop!(
+					PhalaMining::unbind_miner(&1, false),
+					Error::<Test>::MinerNotBound,
+				);
+				// No double binding
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_noop!(
+					PhalaMining::bind(2, worker_pubkey(1)),
+					Error::<Test>::DuplicateBoundMiner
+				);
+				assert_noop!(
+					PhalaMining::bind(1, worker_pubkey(2)),
+					Error::<Test>::DuplicateBoundMiner
+				);
+				// Force unbind should be tested via StakePool
+			});
+		}
+
+		#[test]
+		fn test_binding_notification() {
+			use phala_types::messaging::{SystemEvent, WorkerEventWithKey};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				let _ = take_messages();
+				let worker_events = || -> Vec<WorkerEvent> {
+					take_messages()
+						.iter()
+						.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+							Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+								pubkey,
+								event,
+								..
+							})) if pubkey == worker_pubkey(1) => Some(event),
+							_ => None,
+						})
+						.collect()
+				};
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert!(matches!(
+					worker_events().as_slice(),
+					[WorkerEvent::MinerBound { miner }] if miner == &1u64.encode()
+				));
+				assert_ok!(PhalaMining::unbind_miner(&1, false));
+				assert!(matches!(
+					worker_events().as_slice(),
+					[WorkerEvent::MinerUnbound]
+				));
+			});
+		}
+
+		#[test]
+		#[should_panic]
+		fn test_stakepool_callback_panic() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				// Force unbind without StakePool registration will cause a panic
+				let _ = PhalaMining::unbind(Origin::signed(1), 1);
+			});
+		}
+
+		#[test]
+		fn test_tokenomic() {
+			new_test_ext().execute_with(|| {
+				let params = TokenomicParameters::<Test>::get().unwrap();
+				let tokenomic = Tokenomic::<Test>::new(params);
+				fn pow(x: FixedPoint, n: u32) -> FixedPoint {
+					let mut i = n;
+					let mut x_pow2 = x;
+					let mut z = FixedPoint::from_num(1);
+					while i > 0 {
+						if i & 1 == 1 {
+							z *= x_pow2;
+						}
+						x_pow2 *= x_pow2;
+						i >>= 1;
+					}
+					z
+				}
+				// Vmax
+				assert_eq!(tokenomic.v_max(), fp!(30000));
+				// Minimal stake
+				assert_eq!(tokenomic.minimal_stake(1000), 3162_277660146355);
+				// Ve for different confidence level
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 1),
+					fp!(1950.00000000000000001626)
+				);
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 2),
+					fp!(1950.00000000000000001626)
+				);
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 3),
+					fp!(1950.00000000000000001626)
+				);
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 4),
+					fp!(1819.99999999999999998694)
+				);
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 5),
+					fp!(1754.9999999999999999723)
+				);
+				// Rig cost estimation
+				assert_eq!(tokenomic.rig_cost(500), fp!(150.0000000000000000054));
+				assert_eq!(tokenomic.rig_cost(2000), fp!(600.0000000000000000217));
+				assert_eq!(tokenomic.rig_cost(2800), fp!(840.00000000000000003036));
+
+				const BLOCK_SEC: u32 = 12;
+				const HOUR_BLOCKS: u32 = 3600 / BLOCK_SEC;
+				// Slash per hour (around 0.1%)
+				let slash_rate = FixedPoint::from_bits(tokenomic.params.slash_rate);
+				let slash_decay = FixedPoint::from_num(1) - slash_rate;
+				assert_eq!(pow(slash_decay, HOUR_BLOCKS), fp!(0.9990004981683704595));
+				// Budget per day
+				let budger_per_sec = FixedPoint::from_bits(tokenomic.params.budget_per_sec);
+				assert_eq!(budger_per_sec * 3600 * 24, fp!(719999.99999999999999843875));
+				// Cost estimation per year
+				assert_eq!(
+					tokenomic.op_cost(2000) * 3600 * 24 * 365,
+					fp!(171.71874999890369452304)
+				);
+			});
+		}
+
+		#[test]
+		fn test_benchmark_report() {
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				// 100 iters per sec
+				PhalaRegistry::internal_set_benchmark(&worker_pubkey(1), Some(600));
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				// Though only the mining workers can send heartbeat, but we don't enforce it in
+				// the pallet, but just by pRuntime. Therefore we can directly throw a heartbeat
+				// response to test benchmark report.
+
+				// 110% boost
+				elapse_seconds(100);
+				assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+					MiningReportEvent,
+				> {
+					sender: MessageOrigin::Worker(worker_pubkey(1)),
+					destination: Topic::new(*b"phala/mining/report"),
+					payload: MiningReportEvent::Heartbeat {
+						session_id: 0,
+						challenge_block: 0,
+						challenge_time: 0,
+						iterations: 11000,
+						platform: None,
+						restarted: false,
+					},
+				}));
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(
+					miner.benchmark,
+					Benchmark {
+						p_instant: 660,
+						iterations: 11000,
+						mining_start_time: 0,
+						updated_at: 100,
+					}
+				);
+
+				// 150% boost (capped)
+				elapse_seconds(100);
+				assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+					MiningReportEvent,
+				> {
+					sender: MessageOrigin::Worker(worker_pubkey(1)),
+					destination: Topic::new(*b"phala/mining/report"),
+					payload: MiningReportEvent::Heartbeat {
+						session_id: 0,
+						challenge_block: 0,
+						challenge_time: 0,
+						iterations: 11000 + 15000,
+						platform: None,
+						restarted: false,
+					},
+				}));
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(
+					miner.benchmark,
+					Benchmark {
+						p_instant: 720,
+						iterations: 26000,
+						mining_start_time: 0,
+						updated_at: 200,
+					}
+				);
+			});
+		}
+
+		#[test]
+		fn test_heartbeat_latency() {
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let heartbeat = |challenge_block, challenge_time, iterations| {
+					DecodedMessage::<MiningReportEvent> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block,
+							challenge_time,
+							iterations,
+							platform: None,
+							restarted: false,
+						},
+					}
+				};
+				// The first sample initializes the average
+				elapse_seconds(100);
+				PhalaMining::heartbeat_challenge();
+				elapse_seconds(8);
+				assert_ok!(PhalaMining::on_mining_message_received(heartbeat(
+					1,
+					Timestamp::get() - 8000,
+					100
+				)));
+				assert_eq!(PhalaMining::heartbeat_latency(&1), Some(8000));
+				// Then the samples are averaged with a weight of 1/8
+				System::set_block_number(2);
+				PhalaMining::heartbeat_challenge();
+				elapse_seconds(16);
+				assert_ok!(PhalaMining::on_mining_message_received(heartbeat(
+					2,
+					Timestamp::get() - 16000,
+					200
+				)));
+				assert_eq!(PhalaMining::heartbeat_latency(&1), Some(9000));
+				assert_eq!(PhalaMining::heartbeat_latency(&2), None);
+				// A forged challenge time doesn't change the measured latency
+				System::set_block_number(3);
+				PhalaMining::heartbeat_challenge();
+				elapse_seconds(16);
+				assert_ok!(PhalaMining::on_mining_message_received(heartbeat(
+					3,
+					Timestamp::get(),
+					300
+				)));
+				assert_eq!(PhalaMining::heartbeat_latency(&1), Some(9875));
+				// Neither is an unknown challenge block sampled
+				elapse_seconds(100);
+				assert_ok!(PhalaMining::on_mining_message_received(heartbeat(
+					100,
+					Timestamp::get(),
+					400
+				)));
+				assert_eq!(PhalaMining::heartbeat_latency(&1), Some(9875));
+			});
+		}
+
+		#[test]
+		fn test_heartbeat_history() {
+			use frame_support::traits::OnIdle;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				let heartbeat =
+					|worker, challenge_block, iterations| DecodedMessage::<MiningReportEvent> {
+						sender: MessageOrigin::Worker(worker_pubkey(worker)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block,
+							challenge_time: Timestamp::get(),
+							iterations,
+							platform: None,
+							restarted: false,
+						},
+					};
+				// The history is bounded by the capacity
+				for i in 0..(MAX_HEARTBEAT_HISTORY as u32 + 4) {
+					elapse_seconds(10);
+					assert_ok!(PhalaMining::on_mining_message_received(heartbeat(
+						1,
+						i,
+						(i as u64 + 1) * 100
+					)));
+				}
+				let history = PhalaMining::heartbeat_history(1);
+				assert_eq!(history.len(), MAX_HEARTBEAT_HISTORY);
+				assert_eq!(history.iter().next().unwrap().1.challenge_block, 4);
+				assert_eq!(
+					history.latest().unwrap().1,
+					HeartbeatRecord {
+						session_id: 0,
+						challenge_block: MAX_HEARTBEAT_HISTORY as u32 + 3,
+						iterations: (MAX_HEARTBEAT_HISTORY as u64 + 4) * 100,
+					}
+				);
+				// Nothing expired yet
+				elapse_seconds(HEARTBEAT_HISTORY_TTL_SEC - 200);
+				assert_ok!(PhalaMining::on_mining_message_received(heartbeat(
+					2, 0, 100
+				)));
+				<PhalaMining as OnIdle<u64>>::on_idle(1, Weight::max_value());
+				assert!(!HeartbeatHistoryPruneCursor::<Test>::exists());
+				assert_eq!(
+					PhalaMining::heartbeat_history(1).len(),
+					MAX_HEARTBEAT_HISTORY
+				);
+				// The expired heartbeats are pruned, and the emptied history is removed
+				elapse_seconds(100);
+				PhalaMining::prune_heartbeat_history(Weight::max_value());
+				assert_eq!(PhalaMining::heartbeat_history(1).len(), 11);
+				elapse_seconds(1000);
+				<PhalaMining as OnIdle<u64>>::on_idle(1, Weight::max_value());
+				assert!(!HeartbeatHistory::<Test>::contains_key(1));
+				assert_eq!(PhalaMining::heartbeat_history(2).len(), 1);
+			});
+		}
+
+		#[test]
+		fn test_reward_lockup() {
+			use crate::mock::Balances;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				assert_ok!(PhalaMining::set_reward_lockup(
+					Origin::root(),
+					Some(RewardLockupConfig {
+						percentage: Permill::from_percent(30),
+						duration: 100,
+					})
+				));
+				let settle = SettleInfo {
+					pubkey: worker_pubkey(1),
+					v: 0,
+					payout: FixedPoint::from_num(10).to_bits(),
+				};
+				let remaining = PhalaMining::lock_reward(&1, &settle, 0);
+				assert_eq!(FixedPoint::from_bits(remaining.payout), fp!(7));
+				assert_eq!(
+					PhalaMining::locked_rewards(RewardBeneficiary::Owner(1)),
+					vec![LockedReward {
+						amount: 3 * DOLLARS,
+						unlock_at: 100,
+					}]
+				);
+				// Cannot claim before unlocked
+				assert_noop!(
+					PhalaMining::claim_unlocked_rewards(Origin::signed(1)),
+					Error::<Test>::NoUnlockedRewards
+				);
+				elapse_seconds(100);
+				let free = Balances::free_balance(1);
+				assert_ok!(PhalaMining::claim_unlocked_rewards(Origin::signed(1)));
+				assert_eq!(Balances::free_balance(1), free + 3 * DOLLARS);
+				assert!(PhalaMining::locked_rewards(RewardBeneficiary::Owner(1)).is_empty());
+				// No lockup if disabled
+				assert_ok!(PhalaMining::set_reward_lockup(Origin::root(), None));
+				let remaining = PhalaMining::lock_reward(&1, &settle, 0);
+				assert_eq!(remaining, settle);
+			});
+		}
+
+		#[test]
+		fn test_locked_rewards_bounded_and_sorted() {
+			use crate::mock::Balances;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let owner = RewardBeneficiary::Owner(1);
+				let chunk = |amount, unlock_at| LockedReward { amount, unlock_at };
+				PhalaMining::add_locked_reward(&owner, 1, 300);
+				// A shortened lock period unlocks before the earlier chunks
+				PhalaMining::add_locked_reward(&owner, 2, 100);
+				PhalaMining::add_locked_reward(&owner, 3, 100);
+				assert_eq!(
+					PhalaMining::locked_rewards(&owner),
+					vec![chunk(5, 100), chunk(1, 300)]
+				);
+				elapse_seconds(100);
+				let free = Balances::free_balance(1);
+				assert_ok!(PhalaMining::claim_unlocked_rewards(Origin::signed(1)));
+				assert_eq!(Balances::free_balance(1), free + 5);
+				assert_eq!(PhalaMining::locked_rewards(&owner), vec![chunk(1, 300)]);
+				// Bounded by merging into the next chunk to unlock, never unlocking earlier
+				for i in 1..MAX_LOCKED_REWARDS as u64 {
+					PhalaMining::add_locked_reward(&owner, 1, 300 + i * 10);
+				}
+				assert_eq!(
+					PhalaMining::locked_rewards(&owner).len(),
+					MAX_LOCKED_REWARDS
+				);
+				PhalaMining::add_locked_reward(&owner, 10, 305);
+				PhalaMining::add_locked_reward(&owner, 10, 10_000);
+				let locked = PhalaMining::locked_rewards(&owner);
+				assert_eq!(locked.len(), MAX_LOCKED_REWARDS);
+				assert_eq!(locked[1], chunk(11, 310));
+				assert_eq!(locked.last(), Some(&chunk(11, 10_000)));
+			});
+		}
+
+		#[test]
+		fn test_heartbeat_challenge_policy() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				// Challenge every block by default
+				assert!(PhalaMining::should_challenge(1));
+				assert_noop!(
+					PhalaMining::set_heartbeat_challenge_policy(Origin::root(), 0, None),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_heartbeat_challenge_policy(
+					Origin::root(),
+					2,
+					Some(0)
+				));
+				assert!(!PhalaMining::should_challenge(1));
+				assert!(PhalaMining::should_challenge(2));
+				// Skipped when the outbound queue is congested
+				Pallet::<Test>::heartbeat_challenge();
+				let _ = take_events();
+				assert!(!PhalaMining::should_challenge(2));
+				assert_eq!(
+					take_events().as_slice(),
+					[TestEvent::PhalaMining(Event::HeartbeatChallengeSkipped(1))]
+				);
+			});
+		}
+
+		#[test]
+		fn test_pha_rate_feed() {
+			use crate::mock::set_pha_price;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				elapse_seconds(100);
+				let pha_rate = || TokenomicParameters::<Test>::get().unwrap().pha_rate;
+				let orig_rate = pha_rate();
+				set_pha_price(Some(fp!(2).to_bits()));
+				// Disabled by default
+				PhalaMining::maybe_feed_pha_rate();
+				assert_eq!(pha_rate(), orig_rate);
+				assert_noop!(
+					PhalaMining::set_pha_rate_bounds(
+						Origin::root(),
+						Some((fp!(1.5).to_bits(), fp!(0.5).to_bits()))
+					),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_pha_rate_bounds(
+					Origin::root(),
+					Some((fp!(0.5).to_bits(), fp!(1.5).to_bits()))
+				));
+				// Clamped to the bounds and pushed to the gatekeeper
+				let _ = take_events();
+				let _ = take_messages();
+				PhalaMining::maybe_feed_pha_rate();
+				assert_eq!(pha_rate(), fp!(1.5).to_bits());
+				assert_eq!(
+					take_events().as_slice(),
+					[TestEvent::PhalaMining(Event::PhaRateUpdated(
+						fp!(1.5).to_bits()
+					))]
+				);
+				assert_eq!(take_messages().len(), 1);
+				// At most once per era
+				set_pha_price(Some(fp!(1.2).to_bits()));
+				PhalaMining::maybe_feed_pha_rate();
+				assert_eq!(pha_rate(), fp!(1.5).to_bits());
+				elapse_seconds(PHA_RATE_UPDATE_PERIOD_SEC);
+				PhalaMining::maybe_feed_pha_rate();
+				assert_eq!(pha_rate(), fp!(1.2).to_bits());
+			});
+		}
+
+		#[test]
+		fn test_tokenomic_history() {
+			use sp_runtime::traits::BlakeTwo256;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let old_params = TokenomicParameters::<Test>::get().unwrap();
+				let mut new_params = old_params.clone();
+				new_params.kappa = FixedPoint::from_num(2).to_bits();
+				assert_noop!(
+					PhalaMining::update_tokenomic(Origin::signed(1), new_params.clone()),
+					sp_runtime::DispatchError::BadOrigin
+				);
+				assert_ok!(PhalaMining::update_tokenomic(
+					Origin::root(),
+					new_params.clone()
+				));
+				assert_eq!(
+					PhalaMining::tokenomic_history(),
+					vec![TokenomicChange {
+						block: 1,
+						old_params_hash: Some(BlakeTwo256::hash_of(&old_params)),
+						new_params_hash: BlakeTwo256::hash_of(&new_params),
+						proposer: None,
+					}]
+				);
+				// Only the recent changes are kept
+				for _ in 0..MAX_TOKENOMIC_HISTORY {
+					assert_ok!(PhalaMining::update_tokenomic(
+						Origin::root(),
+						new_params.clone()
+					));
+				}
+				let history = PhalaMining::tokenomic_history();
+				assert_eq!(history.len(), MAX_TOKENOMIC_HISTORY);
+				assert_eq!(
+					history[0].old_params_hash,
+					Some(BlakeTwo256::hash_of(&new_params))
+				);
+			});
+		}
+
+		#[test]
+		fn test_simulate_settlement() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_eq!(
+					PhalaMining::simulate_settlement(worker_pubkey(100), 10, 10),
+					None
+				);
+				// Nothing happens without any block
+				assert_eq!(
+					PhalaMining::simulate_settlement(worker_pubkey(1), 0, 0),
+					Some(Default::default())
+				);
+				// Online only: V grows but is paid out within the budget
+				let online = PhalaMining::simulate_settlement(worker_pubkey(1), 300, 0).unwrap();
+				assert!(online.payout_estimate > 0);
+				assert_eq!(online.slash_estimate, 0);
+				assert!(online.v_delta >= 0);
+				// Offline only: V is slashed
+				let offline = PhalaMining::simulate_settlement(worker_pubkey(1), 0, 300).unwrap();
+				assert_eq!(offline.payout_estimate, 0);
+				assert!(offline.slash_estimate > 0);
+				assert_eq!(offline.v_delta, -(offline.slash_estimate as i128));
+			});
+		}
+
+		#[test]
+		fn test_bindings_commitment() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_eq!(PhalaMining::bindings_commitment(), None);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let root1 = PhalaMining::bindings_commitment().unwrap();
+				let child_info = PhalaMining::bindings_child_info();
+				assert_eq!(
+					child::get::<u64>(&child_info, &worker_pubkey(1).encode()),
+					Some(1)
+				);
+				// The root changes with the bindings
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				let root2 = PhalaMining::bindings_commitment().unwrap();
+				assert_ne!(root1, root2);
+				assert_ok!(PhalaMining::unbind_miner(&2, false));
+				assert_eq!(
+					child::get::<u64>(&child_info, &worker_pubkey(2).encode()),
+					None
+				);
+				assert_eq!(PhalaMining::bindings_commitment(), Some(root1));
+			});
+		}
+
+		#[test]
+		fn test_fixed_bits_codec() {
+			let v = fp!(1.5);
+			let bits = FixedBits::from_fixed(v);
+			assert_eq!(bits.encode(), v.to_bits().encode());
+			assert_eq!(
+				FixedBits::decode(&mut &v.to_bits().encode()[..]).unwrap(),
+				bits
+			);
+			assert_eq!(bits.to_fixed(), v);
+			assert_eq!(FixedPoint::from(bits), v);
+		}
+
+		/// Rewrites the miners in the baseline layout, and rolls back the storage version
+		fn downgrade_miners_v0(total_reward: u128) {
+			use frame_support::storage::unhashed;
+			// The v0 encoding has the bare V bits and only the accumulated reward in the stats
+			for (miner, info) in Miners::<Test>::iter().collect::<Vec<_>>() {
+				let raw = (
+					info.state,
+					info.ve.to_bits(),
+					info.v.to_bits(),
+					info.v_updated_at,
+					info.benchmark,
+					info.cool_down_start,
+					total_reward,
+				)
+					.encode();
+				unhashed::put_raw(&Miners::<Test>::hashed_key_for(miner), &raw);
+			}
+			MiningStorageVersion::<Test>::put(0);
+		}
+
+		#[test]
+		fn test_migrate_miners_v1() {
+			use frame_support::storage::migration;
+			use frame_support::traits::OnRuntimeUpgrade;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				for i in 1..=2 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+				}
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let info = PhalaMining::miners(1).unwrap();
+				downgrade_miners_v0(100);
+				migration::remove_storage_prefix(b"PhalaMining", b"MinersByState", &[]);
+				IdleMiners::<Test>::kill();
+				migration::put_storage_value(b"PhalaMining", b"OnlineMiners", &[], 1u32);
+
+				<PhalaMining as OnRuntimeUpgrade>::on_runtime_upgrade();
+				assert_eq!(MiningStorageVersion::<Test>::get(), STORAGE_VERSION);
+				let migrated = PhalaMining::miners(1).unwrap();
+				assert_eq!(migrated.stats.total_reward, 100);
+				assert_eq!(migrated.stats.sessions_count, 0);
+				assert_eq!(migrated.v_balance, info.v_balance);
+				assert_eq!(migrated.session_start, PhalaMining::now_sec());
+				assert_eq!(PhalaMining::miners(2).unwrap().state, MinerState::Ready);
+				assert_eq!(
+					PhalaMining::miners_by_state(MinerState::MiningIdle, 0),
+					vec![1]
+				);
+				assert_eq!(PhalaMining::miners_by_state(MinerState::Ready, 0), vec![2]);
+				assert_eq!(PhalaMining::idle_miners(), 1);
+				assert!(!migration::have_storage_value(b"PhalaMining", b"OnlineMiners", &[]));
+				assert_eq!(PhalaMining::do_try_state(), Ok(()));
+			});
+		}
+
+		#[test]
+		fn test_stake_cap() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				// Disabled by default
+				assert_eq!(PhalaMining::stake_cap(1000), None);
+				// cap = 1 * p + 100
+				let cap = StakeCapParams {
+					k: fp!(1).to_bits(),
+					b: fp!(100).to_bits(),
+				};
+				assert_noop!(
+					PhalaMining::set_stake_cap(Origin::signed(1), Some(cap.clone())),
+					sp_runtime::DispatchError::BadOrigin
+				);
+				assert_ok!(PhalaMining::set_stake_cap(
+					Origin::root(),
+					Some(cap.clone())
+				));
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::StakeCapChanged(Some(cap)))));
+				// The gatekeepers get the parameters in the unchanged layout
+				assert_eq!(
+					TokenomicParameters::<Test>::get().unwrap().encode().len(),
+					16 * 12 + 4
+				);
+				assert_eq!(PhalaMining::stake_cap(1000), Some(1100 * DOLLARS));
+				// The worker has score 1, so the cap is 101 PHA
+				assert_noop!(
+					PhalaMining::start_mining(1, 102 * DOLLARS),
+					Error::<Test>::StakeExceedsCap
+				);
+				assert_ok!(PhalaMining::start_mining(1, 101 * DOLLARS));
+			});
+		}
+
+		#[test]
+		fn test_rotate_worker_key() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let rotation_info = |pubkey, operator| WorkerRegistrationInfo::<u64> {
+					version: 1,
+					machine_id: Default::default(),
+					pubkey,
+					ecdh_pubkey: ecdh_pubkey(10),
+					genesis_block_hash: Default::default(),
+					features: vec![1, 4],
+					operator,
+				};
+				// Only the operator can rotate, and the new key must be fresh
+				assert_noop!(
+					PhalaRegistry::do_rotate_worker_key(
+						&2,
+						worker_pubkey(1),
+						rotation_info(worker_pubkey(10), Some(2)),
+						128,
+						0
+					),
+					registry::Error::<Test>::NotWorkerOperator
+				);
+				assert_noop!(
+					PhalaRegistry::do_rotate_worker_key(
+						&1,
+						worker_pubkey(1),
+						rotation_info(worker_pubkey(2), Some(1)),
+						128,
+						0
+					),
+					registry::Error::<Test>::DuplicateWorker
+				);
+				assert_ok!(PhalaRegistry::do_rotate_worker_key(
+					&1,
+					worker_pubkey(1),
+					rotation_info(worker_pubkey(10), Some(1)),
+					128,
+					0
+				));
+				// The registry entry and the bindings are moved to the new key
+				assert!(registry::Workers::<Test>::get(worker_pubkey(1)).is_none());
+				let info = registry::Workers::<Test>::get(worker_pubkey(10)).unwrap();
+				assert_eq!(info.initial_score, Some(1));
+				assert_eq!(WorkerBindings::<Test>::get(worker_pubkey(1)), None);
+				assert_eq!(WorkerBindings::<Test>::get(worker_pubkey(10)), Some(1));
+				assert_eq!(MinerBindings::<Test>::get(1), Some(worker_pubkey(10)));
+				let child_info = PhalaMining::bindings_child_info();
+				assert_eq!(
+					child::get::<u64>(&child_info, &worker_pubkey(1).encode()),
+					None
+				);
+				assert_eq!(
+					child::get::<u64>(&child_info, &worker_pubkey(10).encode()),
+					Some(1)
+				);
+			});
+		}
+
+		#[test]
+		fn test_gatekeeper_quorum() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(4);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				// Only the registered gatekeepers count
+				assert_noop!(
+					PhalaMining::set_gatekeeper_quorum(Origin::root(), 2),
+					Error::<Test>::InvalidParameter
+				);
+				registry::Gatekeeper::<Test>::put(vec![worker_pubkey(2), worker_pubkey(3)]);
+				assert_ok!(PhalaMining::set_gatekeeper_quorum(Origin::root(), 2));
+				let _ = take_events();
+
+				let update = MiningInfoUpdateEvent::<BlockNumber> {
+					block_number: 1,
+					timestamp_ms: Timestamp::get(),
+					offline: vec![worker_pubkey(1)],
+					recovered_to_online: vec![],
+					settle: vec![],
+					protocol_version: 0,
+					settle_bindings: vec![],
+					continues: false,
+				};
+				let digest = crate::hashing::blake2_256(&update.encode());
+				let confirm = |worker| DecodedMessage::<MiningInfoUpdateConfirm> {
+					sender: MessageOrigin::Worker(worker_pubkey(worker)),
+					destination: Topic::new(*b"^phala/mining/update/confirm"),
+					payload: MiningInfoUpdateConfirm { digest },
+				};
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: update,
+				}));
+				// Not applied until confirmed by two distinct gatekeepers
+				assert_ok!(PhalaMining::on_gk_confirm_received(confirm(2)));
+				assert_ok!(PhalaMining::on_gk_confirm_received(confirm(2)));
+				assert_noop!(
+					PhalaMining::on_gk_confirm_received(confirm(4)),
+					Error::<Test>::BadSender
+				);
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningIdle
+				);
+				assert_ok!(PhalaMining::on_gk_confirm_received(confirm(3)));
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningUnresponsive
+				);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::MiningUpdateConfirmed(digest, 2)
+				)));
+				assert!(!PendingMiningUpdates::<Test>::contains_key(&digest));
+				assert!(MiningUpdateDigests::<Test>::get().is_empty());
+			});
+		}
+
+		#[test]
+		fn test_trial_mining() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, SystemEvent, Topic, WorkerEventWithKey,
+			};
+			fn gk_update(offline: bool, recovered: bool, settle: Option<(FixedPoint, FixedPoint)>) {
+				let worker = || vec![worker_pubkey(1)];
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: System::block_number(),
+						timestamp_ms: Timestamp::get(),
+						offline: if offline { worker() } else { vec![] },
+						recovered_to_online: if recovered { worker() } else { vec![] },
+						settle: settle
+							.into_iter()
+							.map(|(v, payout)| SettleInfo {
+								pubkey: worker_pubkey(1),
+								v: v.to_bits(),
+								payout: payout.to_bits(),
+							})
+							.collect(),
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			fn settled_payout() -> u128 {
+				take_events()
+					.into_iter()
+					.find_map(|e| match e {
+						TestEvent::PhalaMining(Event::MinerSettled(_, _, payout, _, _)) => {
+							Some(payout)
+						}
+						_ => None,
+					})
+					.expect("Settled; qed.")
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let min_stake = PhalaMining::tokenomic().minimal_stake(1);
+				let stake = min_stake / 2 + 1;
+				assert_noop!(
+					PhalaMining::start_trial_mining(1, stake),
+					Error::<Test>::TrialMiningDisabled
+				);
+				assert_ok!(PhalaMining::set_trial_mining(
+					Origin::root(),
+					Some(TrialMiningConfig {
+						duration: 100,
+						stake_ratio: Permill::from_percent(50),
+						ve_cap: fp!(1).to_bits(),
+						payout_cap: fp!(0.5).to_bits(),
+					})
+				));
+				// Reduced stake is only allowed in the trial
+				assert_noop!(
+					PhalaMining::start_mining(1, stake),
+					Error::<Test>::InsufficientStake
+				);
+				assert_ok!(PhalaMining::start_trial_mining(1, stake));
+				assert_eq!(PhalaMining::trial_ends_at(1), Some(101));
+				assert_eq!(PhalaMining::miners(1).unwrap().ve.to_fixed(), fp!(1));
+				let full_ve = PhalaMining::uncapped_ve(&1).unwrap();
+				assert!(full_ve > fp!(1));
+				let _ = take_events();
+				// The payout is capped during the trial
+				gk_update(false, false, Some((fp!(1), fp!(2))));
+				assert_eq!(settled_payout(), fp!(0.5).to_bits());
+				// Being unresponsive restarts the trial
+				System::set_block_number(50);
+				gk_update(true, false, None);
+				gk_update(false, true, None);
+				assert_eq!(PhalaMining::trial_ends_at(1), Some(150));
+				System::set_block_number(120);
+				gk_update(false, false, Some((fp!(1), fp!(2))));
+				assert_eq!(settled_payout(), fp!(0.5).to_bits());
+				// Upgraded to normal mining after the trial
+				let _ = take_messages();
+				System::set_block_number(150);
+				gk_update(false, false, Some((fp!(1), fp!(2))));
+				assert_eq!(PhalaMining::trial_ends_at(1), None);
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(miner.ve.to_fixed(), full_ve);
+				assert_eq!(miner.v.to_fixed(), full_ve);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::MinerTrialCompleted(1, full_ve.to_bits())
+				)));
+				let v_delta = (full_ve - fp!(1)).to_bits();
+				assert!(take_messages().iter().any(|m| matches!(
+					m.decode_payload::<SystemEvent>(),
+					Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+						event: WorkerEvent::TrialCompleted { v_delta: delta },
+						..
+					})) if delta == v_delta
+				)));
+				// The payout is no longer capped
+				gk_update(false, false, Some((full_ve, fp!(2))));
+				assert_eq!(settled_payout(), fp!(2).to_bits());
+			});
+		}
+
+		#[test]
+		fn test_scheduled_ops() {
+			use frame_support::traits::OnInitialize;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let mut params = TokenomicParameters::<Test>::get().unwrap();
+				params.pha_rate = fp!(2).to_bits();
+				assert_noop!(
+					PhalaMining::schedule_tokenomic_update(Origin::root(), 1, params.clone()),
+					Error::<Test>::InvalidParameter
+				);
+				for _ in 0..MAX_SCHEDULED_OPS_PER_BLOCK {
+					assert_ok!(PhalaMining::schedule_tokenomic_update(
+						Origin::root(),
+						10,
+						params.clone()
+					));
+				}
+				assert_noop!(
+					PhalaMining::schedule_tokenomic_update(Origin::root(), 10, params.clone()),
+					Error::<Test>::ScheduleFull
+				);
+				// Dispatched at the scheduled block
+				<PhalaMining as OnInitialize<u64>>::on_initialize(9);
+				assert_ne!(TokenomicParameters::<Test>::get(), Some(params.clone()));
+				<PhalaMining as OnInitialize<u64>>::on_initialize(10);
+				assert_eq!(TokenomicParameters::<Test>::get(), Some(params));
+				assert!(PhalaMining::scheduled_ops(10).is_empty());
+
+				// Stopping a miner schedules its reclaim after the cool down
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::stop_mining(1));
+				let at = 1 + PhalaMining::cool_down_period() / 12 + 1;
+				assert_eq!(
+					PhalaMining::scheduled_ops(at),
+					vec![ScheduledOp::Reclaim(1)]
+				);
+				// Postponed if the cool down is not over yet by the timestamp
+				let _ = take_events();
+				System::set_block_number(at);
+				<PhalaMining as OnInitialize<u64>>::on_initialize(at);
+				assert_eq!(
+					PhalaMining::scheduled_ops(at * 2 - 1),
+					vec![ScheduledOp::Reclaim(1)]
+				);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::OperationDispatched(ScheduledOp::Reclaim(1), true)
+				)));
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningCoolingDown
+				);
+			});
+		}
+
+		#[test]
+		fn test_targeted_heartbeat_challenge() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, SystemEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let gk_update = |offline: Vec<WorkerPublicKey>, recovered: Vec<WorkerPublicKey>| {
+					assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+						MiningInfoUpdateEvent<BlockNumber>,
+					> {
+						sender: MessageOrigin::Gatekeeper,
+						destination: Topic::new(*b"^phala/mining/update"),
+						payload: MiningInfoUpdateEvent::<BlockNumber> {
+							block_number: 1,
+							timestamp_ms: Timestamp::get(),
+							offline,
+							recovered_to_online: recovered,
+							settle: vec![],
+							protocol_version: 0,
+							settle_bindings: vec![],
+							continues: false,
+						},
+					}));
+				};
+				gk_update(vec![worker_pubkey(1)], vec![]);
+				assert!(PhalaMining::heartbeat_targets().is_empty());
+				// The recovered worker is challenged specifically
+				gk_update(vec![], vec![worker_pubkey(1)]);
+				assert_eq!(PhalaMining::heartbeat_targets(), vec![worker_pubkey(1)]);
+				let _ = take_messages();
+				PhalaMining::challenge_heartbeat_targets();
+				assert!(PhalaMining::heartbeat_targets().is_empty());
+				let challenge = match take_messages()
+					.iter()
+					.find_map(|m| m.decode_payload::<SystemEvent>())
+				{
+					Some(SystemEvent::HeartbeatChallenge(challenge)) => challenge,
+					_ => panic!("Missing targeted challenge"),
+				};
+				assert_eq!(challenge.online_target, U256::MAX);
+				let targets = challenge.targets.expect("Targeted; qed.");
+				assert!(targets.contains(&worker_pubkey(1)));
+				assert!(!targets.contains(&worker_pubkey(2)));
+				// Nothing to challenge
+				PhalaMining::challenge_heartbeat_targets();
+				assert!(take_messages().is_empty());
+				// The challenges without targets are still decodable
+				let legacy = (U256::from(1), U256::from(2)).encode();
+				assert_eq!(
+					HeartbeatChallenge::decode(&mut &legacy[..]).unwrap(),
+					HeartbeatChallenge {
+						seed: U256::from(1),
+						online_target: U256::from(2),
+						targets: None,
+						stake_weighting: None,
+					}
+				);
+			});
+		}
+
+		#[test]
+		fn test_set_expected_heartbeat_count() {
+			use phala_types::messaging::SystemEvent;
+			fn challenge_target() -> U256 {
+				let _ = take_messages();
+				PhalaMining::heartbeat_challenge();
+				match take_messages()[0].decode_payload::<SystemEvent>() {
+					Some(SystemEvent::HeartbeatChallenge(r)) => r.online_target,
+					_ => panic!("Wrong outbound message"),
+				}
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				IdleMiners::<Test>::put(200_000);
+				assert_eq!(challenge_target(), pow_target(20, 200_000, 12));
+				assert_noop!(
+					PhalaMining::set_expected_heartbeat_count(Origin::root(), 0),
+					Error::<Test>::InvalidParameter
+				);
+				assert_noop!(
+					PhalaMining::set_expected_heartbeat_count(
+						Origin::root(),
+						MAX_EXPECTED_HEARTBEAT_COUNT + 1
+					),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_expected_heartbeat_count(
+					Origin::root(),
+					40
+				));
+				assert_eq!(ExpectedHeartbeatCount::<Test>::get(), Some(40));
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::ExpectedHeartbeatCountChanged(40)
+				)));
+				// The target is raised for more heartbeats
+				let target = challenge_target();
+				assert_eq!(target, pow_target(40, 200_000, 12));
+				assert!(target > pow_target(20, 200_000, 12));
+			});
+		}
+
+		#[test]
+		fn test_secondary_payout() {
+			use crate::mock::TestSecondaryAssets;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let settle = SettleInfo {
+					pubkey: worker_pubkey(1),
+					v: 0,
+					payout: FixedPoint::from_num(10).to_bits(),
+				};
+				// Disabled by default
+				assert_eq!(PhalaMining::pay_secondary(&1, &settle), settle);
+				let config = SecondaryPayoutConfig {
+					asset: 7,
+					ratio: Permill::from_percent(20),
+					exchange_rate: FixedPoint::from_num(5).to_bits(),
+				};
+				assert_ok!(PhalaMining::set_secondary_payout(
+					Origin::root(),
+					Some(config.clone())
+				));
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::SecondaryPayoutChanged(Some(config))
+				)));
+				// Falls back to PHA if the subsidy pool runs out of the asset
+				assert_eq!(PhalaMining::pay_secondary(&1, &settle), settle);
+				TestSecondaryAssets::mint(7, PhalaMining::account_id(), 15);
+				assert_eq!(PhalaMining::subsidy_pool_balances().1, Some((7, 15)));
+				// 20% of the 10 PHA payout is paid as 2 * 5 units of the asset
+				let remaining = PhalaMining::pay_secondary(&1, &settle);
+				assert_eq!(FixedPoint::from_bits(remaining.payout), fp!(8));
+				assert_eq!(
+					<TestSecondaryAssets as SecondaryAssets<u64>>::balance(7, &1),
+					10
+				);
+				assert_eq!(PhalaMining::secondary_paid_total(7), 10);
+				assert_eq!(PhalaMining::subsidy_pool_balances().1, Some((7, 5)));
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::SecondaryRewardPaid(1, 7, 10))]
+				);
+				assert_eq!(PhalaMining::pay_secondary(&1, &settle), settle);
+				// A keyless miner is paid to its refund beneficiary
+				TestSecondaryAssets::mint(7, PhalaMining::account_id(), 10);
+				RefundBeneficiaries::<Test>::insert(2, 99);
+				let remaining = PhalaMining::pay_secondary(&2, &settle);
+				assert_eq!(FixedPoint::from_bits(remaining.payout), fp!(8));
+				assert_eq!(
+					<TestSecondaryAssets as SecondaryAssets<u64>>::balance(7, &2),
+					0
+				);
+				assert_eq!(
+					<TestSecondaryAssets as SecondaryAssets<u64>>::balance(7, &99),
+					10
+				);
+			});
+		}
+
+		#[test]
+		fn test_solo_mining() {
+			use crate::mock::{
+				elapse_cool_down, setup_workers_linked_operators, Balances, BlockNumber,
+			};
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers_linked_operators(2);
+				// Only the operator can bind the worker
+				assert_noop!(
+					PhalaMining::bind_solo(Origin::signed(2), worker_pubkey(1)),
+					Error::<Test>::BadSender
+				);
+				assert_ok!(PhalaMining::bind_solo(Origin::signed(1), worker_pubkey(1)));
+				// The balance frozen by the other locks cannot be staked
+				Balances::set_lock(*b"vesting ", &1, 400 * DOLLARS, WithdrawReasons::all());
+				assert_eq!(PhalaMining::free_stakable_balance(&1), 600 * DOLLARS);
+				assert_noop!(
+					PhalaMining::start_solo_mining(Origin::signed(1), 601 * DOLLARS),
+					Error::<Test>::InsufficientStakableBalance
+				);
+				assert_ok!(PhalaMining::start_solo_mining(
+					Origin::signed(1),
+					500 * DOLLARS
+				));
+				assert!(PhalaMining::is_solo_miner(1));
+				assert_eq!(PhalaMining::stakes(1), Some(500 * DOLLARS));
+				assert_eq!(PhalaMining::free_stakable_balance(&1), 0);
+				assert!(Balances::locks(&1)
+					.iter()
+					.any(|l| l.id == SOLO_STAKING_ID && l.amount == 500 * DOLLARS));
+				// The rewards are paid to the miner directly
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: 1,
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v: PhalaMining::miners(1).unwrap().v.to_bits(),
+							payout: fp!(2).to_bits(),
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+				assert_eq!(
+					PhalaMining::locked_rewards(RewardBeneficiary::Owner(1)),
+					vec![LockedReward {
+						amount: 2 * DOLLARS,
+						unlock_at: PhalaMining::now_sec(),
+					}]
+				);
+				// The stake is released after the cool down
+				assert_noop!(
+					PhalaMining::stop_solo_mining(Origin::signed(2)),
+					Error::<Test>::NotSoloMiner
+				);
+				assert_ok!(PhalaMining::stop_solo_mining(Origin::signed(1)));
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(2), 1));
+				assert!(!PhalaMining::is_solo_miner(1));
+				assert!(Balances::locks(&1).iter().all(|l| l.id != SOLO_STAKING_ID));
+				assert_eq!(PhalaMining::free_stakable_balance(&1), 600 * DOLLARS);
+			});
+		}
+
+		#[test]
+		fn test_tokenomic_hourly_rates() {
+			use phala_types::messaging::GatekeeperEvent;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let _ = take_messages();
+				let rates = TokenomicHourlyRates {
+					rho: fp!(1.0003).to_bits(),
+					slash_rate: fp!(0.001).to_bits(),
+					heartbeat_window_sec: 125,
+				};
+				assert_ok!(PhalaMining::update_tokenomic_rates(
+					Origin::root(),
+					rates.clone()
+				));
+				// Converted with the 12s blocks of the mock
+				let params = TokenomicParameters::<Test>::get().unwrap();
+				assert_eq!(params.slash_rate, (fp!(0.001) / 300).to_bits());
+				assert_eq!(params.rho, (fp!(1) + fp!(0.0003) / 300).to_bits());
+				assert_eq!(params.heartbeat_window, 11);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::TokenomicRatesApplied(rates.clone(), 12)
+				)));
+				assert!(take_messages().iter().any(|m| matches!(
+					m.decode_payload::<GatekeeperEvent>(),
+					Some(GatekeeperEvent::TokenomicParametersChanged(p)) if p == params
+				)));
+				// The rates can be read back
+				let read = PhalaMining::tokenomic_hourly_rates().unwrap();
+				assert_eq!(read.heartbeat_window_sec, 132);
+				let slash_rate = FixedPoint::from_bits(read.slash_rate);
+				assert!(slash_rate <= fp!(0.001) && fp!(0.001) - slash_rate < fp!(0.0000001));
+				// Nothing to apply the rates to before the parameters are set
+				TokenomicParameters::<Test>::kill();
+				assert_noop!(
+					PhalaMining::update_tokenomic_rates(Origin::root(), rates),
+					Error::<Test>::TokenomicParametersNotSet
+				);
+			});
+		}
+
+		#[test]
+		fn test_heartbeat_platform_status() {
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, MiningReportEvent, PlatformStatus, Topic,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				registry::Workers::<Test>::mutate(worker_pubkey(1), |w| {
+					w.as_mut().unwrap().confidence_level = 2;
+				});
+				let heartbeat = |iterations, confidence_level| {
+					assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+						MiningReportEvent,
+					> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block: 0,
+							challenge_time: Timestamp::get(),
+							iterations,
+							platform: Some(PlatformStatus {
+								confidence_level,
+								uptime_sec: 3600,
+							}),
+							restarted: false,
+						},
+					}));
+				};
+				let reattestation_events = || {
+					take_events()
+						.into_iter()
+						.filter(|e| {
+							matches!(
+								e,
+								TestEvent::PhalaRegistry(
+									registry::Event::WorkerNeedsReattestation(_, _)
+								)
+							)
+						})
+						.count()
+				};
+				heartbeat(100, 2);
+				assert!(!PhalaRegistry::needs_reattestation(&worker_pubkey(1)));
+				assert_eq!(reattestation_events(), 0);
+				// Flagged once when the platform falls behind the attested level
+				heartbeat(200, 5);
+				assert!(PhalaRegistry::needs_reattestation(&worker_pubkey(1)));
+				assert_eq!(
+					PhalaRegistry::platform_status(&worker_pubkey(1)).map(|s| s.uptime_sec),
+					Some(3600)
+				);
+				assert_eq!(reattestation_events(), 1);
+				heartbeat(300, 5);
+				assert_eq!(reattestation_events(), 0);
+			});
+		}
+
+		#[test]
+		fn test_decode_heartbeat_without_platform_status() {
+			use phala_types::messaging::MiningReportEvent;
+			let encoded = (0u8, 1u32, 2u32, 3u64, 4u64).encode();
+			assert!(matches!(
+				MiningReportEvent::decode(&mut &encoded[..]),
+				Ok(MiningReportEvent::Heartbeat {
+					session_id: 1,
+					iterations: 4,
+					platform: None,
+					restarted: false,
+					..
+				})
+			));
+		}
+
+		#[test]
+		fn test_bind_and_start() {
+			use phala_types::messaging::{SystemEvent, WorkerEventWithKey};
+			fn worker_events() -> Vec<WorkerEvent> {
+				take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey { event, .. })) => {
+							Some(event)
+						}
+						_ => None,
+					})
+					.collect()
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				let _ = take_events();
+				let _ = take_messages();
+				// Nothing is bound if the start fails
+				assert_noop!(
+					PhalaMining::bind_and_start(1, worker_pubkey(1), 0),
+					Error::<Test>::InsufficientStake
+				);
+				assert_ok!(PhalaMining::bind_and_start(
+					1,
+					worker_pubkey(1),
+					1000 * DOLLARS
+				));
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningIdle
+				);
+				assert_eq!(
+					take_events(),
+					vec![
+						TestEvent::PhalaMining(Event::MinerBound(1, worker_pubkey(1))),
+						TestEvent::PhalaMining(Event::MinerStateChanged(
+							1,
+							MinerState::Ready,
+							MinerState::MiningIdle,
+							1
+						)),
+						TestEvent::PhalaMining(Event::MinerStaked(1, 1000 * DOLLARS)),
+						TestEvent::PhalaMining(Event::MinerStarted(1)),
+					]
+				);
+				// A single message carries both the binding and the start
+				assert!(matches!(
+					worker_events().as_slice(),
+					[WorkerEvent::MinerBoundAndStarted { miner, .. }] if miner == &1u64.encode()
+				));
+			});
+		}
+
+		#[test]
+		fn test_chain_downtime_grace() {
+			use crate::mock::BlockNumber;
+			use frame_support::traits::OnFinalize;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn report_offline() {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: System::block_number(),
+						timestamp_ms: Timestamp::get(),
+						offline: vec![worker_pubkey(1)],
+						recovered_to_online: vec![],
+						settle: vec![],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				<PhalaMining as OnFinalize<u64>>::on_finalize(1);
+				// A normal block gap
+				elapse_seconds(12);
+				<PhalaMining as OnFinalize<u64>>::on_finalize(2);
+				assert_eq!(PhalaMining::last_chain_downtime(), None);
+				// The chain halted for an hour
+				let start = Timestamp::get() / 1000;
+				elapse_seconds(3600);
+				<PhalaMining as OnFinalize<u64>>::on_finalize(3);
+				let end = start + 3600;
+				assert_eq!(PhalaMining::last_chain_downtime(), Some((start, end)));
+				let _ = take_events();
+				// Suppressed within the grace period (2 * 10 blocks)
+				elapse_seconds(120);
+				report_offline();
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningIdle
+				);
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::OfflineReportsSuppressed(
+						start, end, 1
+					))]
+				);
+				// Applied after the grace period
+				elapse_seconds(120);
+				report_offline();
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningUnresponsive
+				);
+			});
+		}
+
+		#[test]
+		fn test_worker_event_seq() {
+			use phala_types::messaging::{SystemEvent, WorkerEventWithKey};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				let _ = take_messages();
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::stop_mining(1));
+				let seqs: Vec<_> = take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+							pubkey, seq, ..
+						})) if pubkey == worker_pubkey(1) => seq,
+						_ => None,
+					})
+					.collect();
+				let session_id = PhalaMining::miners(1).unwrap().stats.last_session_id;
+				assert_eq!(
+					seqs.iter()
+						.map(|seq| (seq.session_id, seq.nonce))
+						.collect::<Vec<_>>(),
+					vec![(0, 1), (session_id, 2), (session_id, 3)]
+				);
+				// The last nonce is tracked on-chain
+				assert_eq!(
+					PhalaMining::worker_event_seq(worker_pubkey(1)),
+					WorkerEventSeq {
+						session_id,
+						nonce: 3
+					}
+				);
+			});
+		}
+
+		#[test]
+		fn test_decode_worker_event_without_seq() {
+			use phala_types::messaging::WorkerEventWithKey;
+			let encoded = (worker_pubkey(1), WorkerEvent::MiningStop).encode();
+			assert!(matches!(
+				WorkerEventWithKey::decode(&mut &encoded[..]),
+				Ok(WorkerEventWithKey {
+					event: WorkerEvent::MiningStop,
+					seq: None,
+					..
+				})
+			));
+		}
+
+		#[test]
+		fn test_deterministic_challenge_seed() {
+			use crate::mock::{challenge_seed_at, challenged_workers, take_heartbeat_challenges};
+			use frame_support::traits::OnFinalize;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				IdleMiners::<Test>::put(4);
+				let mut challenges = Vec::new();
+				for block in 1..=3 {
+					System::set_block_number(block);
+					PhalaMining::on_finalize(block);
+					let challenge = take_heartbeat_challenges().pop().unwrap();
+					assert_eq!(challenge.seed, challenge_seed_at(block));
+					challenges.push(challenge);
+				}
+				// A new seed each block
+				assert_ne!(challenges[0].seed, challenges[1].seed);
+				assert_ne!(challenges[1].seed, challenges[2].seed);
+				// The targeted challenges only hit the targets
+				PhalaMining::add_heartbeat_target(worker_pubkey(3));
+				PhalaMining::challenge_heartbeat_targets();
+				let challenge = take_heartbeat_challenges().pop().unwrap();
+				assert_eq!(challenge.seed, challenge_seed_at(3));
+				assert_eq!(challenged_workers(&challenge, 4), vec![3]);
+				// Reproducible, and varied by the base seed
+				let seed = PhalaMining::challenge_seed();
+				assert_eq!(seed, challenges[2].seed);
+				DeterministicRandomness::<Test>::set_base_seed(1);
+				assert_ne!(PhalaMining::challenge_seed(), seed);
+				assert_eq!(PhalaMining::challenge_seed(), challenge_seed_at(3));
+			});
+		}
+
+		#[test]
+		fn test_mining_info_provider() {
+			use crate::mock::elapse_cool_down;
+			// Qualified to skip the inherent functions of the same names
+			type Info = <Test as crate::stakepool::Config>::MiningInfo;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_eq!(Info::miner_state(&1), None);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_eq!(Info::miner_state(&1), Some(MinerState::Ready));
+				assert_eq!(Info::miner_stake(&1), None);
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(Info::miner_state(&1), Some(MinerState::MiningIdle));
+				assert_eq!(Info::miner_stake(&1), Some(1000 * DOLLARS));
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(
+					Info::miner_ve_v(&1),
+					Some((miner.ve.to_bits(), miner.v.to_bits()))
+				);
+				assert!(!<Info as MiningInfoProvider<_, _>>::is_solo_miner(&1));
+				// Reclaimable after cooling down
+				assert_ok!(PhalaMining::stop_mining(1));
+				assert!(!<Info as MiningInfoProvider<_, _>>::can_reclaim(&1));
+				elapse_cool_down();
+				assert!(<Info as MiningInfoProvider<_, _>>::can_reclaim(&1));
+			});
+		}
+
+		#[test]
+		fn test_max_online_miners() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_ok!(PhalaMining::set_max_online_miners(Origin::root(), Some(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let _ = take_events();
+				// Blocked at the cap
+				assert_eq!(
+					PhalaMining::start_mining(2, 1000 * DOLLARS),
+					Err(Error::<Test>::NetworkAtCapacity.into())
+				);
+				assert_eq!(PhalaMining::miners(2).unwrap().state, MinerState::Ready);
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::MiningStartBlockedByCapacity(
+						2, 1
+					))]
+				);
+				// Cleared by the governance
+				assert_ok!(PhalaMining::set_max_online_miners(Origin::root(), None));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				assert_eq!(PhalaMining::online_miners(), 2);
+			});
+		}
+
+		#[test]
+		fn test_settlement_history() {
+			use crate::mock::{elapse_cool_down, BlockNumber};
+			use frame_support::traits::OnIdle;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn settle(v: u128, payout: FixedPoint) {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: System::block_number(),
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v,
+							payout: payout.to_bits(),
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let ve = PhalaMining::miners(1).unwrap().ve.to_bits();
+				System::set_block_number(2);
+				settle(ve, fp!(2));
+				System::set_block_number(5);
+				settle(ve, fp!(3));
+				assert_eq!(
+					PhalaMining::settlements_in_range(&1, 1, 3),
+					vec![(
+						2,
+						SettlementRecord {
+							v: ve,
+							payout: 2 * DOLLARS,
+							slashed: 0,
+						}
+					)]
+				);
+				assert_eq!(PhalaMining::settlements_in_range(&1, 1, 5).len(), 2);
+				// The slash at the reclaim is recorded
+				assert_ok!(PhalaMining::stop_mining(1));
+				settle(ve / 2, fp!(0));
+				elapse_cool_down();
+				System::set_block_number(6);
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				let records = PhalaMining::settlements_in_range(&1, 6, 6);
+				assert_eq!(records.len(), 2);
+				// The settlement of the cool down applied before the slash
+				assert_eq!(records[0].1.v, ve / 2);
+				assert!(records[1].1.slashed > 0);
+				assert_eq!(records[1].1.payout, 0);
+				// Pruned after expired
+				let ttl_blocks = SETTLEMENT_HISTORY_TTL_SEC / 12;
+				System::set_block_number(6 + ttl_blocks);
+				<PhalaMining as OnIdle<u64>>::on_idle(6 + ttl_blocks, Weight::max_value());
+				assert!(PhalaMining::settlement_history(1).is_empty());
+			});
+		}
+
+		#[test]
+		fn test_tokenomic_freeze() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn settle(payout: FixedPoint) {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: 1,
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v: PhalaMining::miners(1).unwrap().v.to_bits(),
+							payout: payout.to_bits(),
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_noop!(
+					PhalaMining::resume_settlement(Origin::root()),
+					Error::<Test>::SettlementNotFrozen
+				);
+				let freeze = |sender| {
+					PhalaMining::on_gk_emergency_received(DecodedMessage {
+						sender,
+						destination: Topic::new(*b"^phala/mining/emergency"),
+						payload: MiningEmergencyEvent::TokenomicFreeze {
+							reason: b"mismatch".to_vec(),
+						},
+					})
+				};
+				assert_noop!(
+					freeze(MessageOrigin::Worker(worker_pubkey(1))),
+					Error::<Test>::BadSender
+				);
+				assert_ok!(freeze(MessageOrigin::Gatekeeper));
+				assert_eq!(PhalaMining::settlement_frozen(), Some(b"mismatch".to_vec()));
+				let _ = take_events();
+				// Buffered and merged by worker
+				settle(fp!(1));
+				settle(fp!(2));
+				assert!(take_events().is_empty());
+				let buffered = PhalaMining::frozen_settlements();
+				assert_eq!(buffered.len(), 1);
+				assert_eq!(buffered[0].payout, fp!(3).to_bits());
+				// Applied at the resume
+				assert_ok!(PhalaMining::resume_settlement(Origin::root()));
+				assert_eq!(PhalaMining::settlement_frozen(), None);
+				assert!(PhalaMining::frozen_settlements().is_empty());
+				let events = take_events();
+				assert!(events.iter().any(|e| matches!(
+					e,
+					TestEvent::PhalaMining(Event::MinerSettled(1, _, payout_bits, _, payout))
+						if *payout_bits == fp!(3).to_bits() && *payout == 3 * DOLLARS
+				)));
+				assert!(events.contains(&TestEvent::PhalaMining(Event::SettlementResumed(1))));
+			});
+		}
+
+		#[test]
+		fn test_mock_worker_info() {
+			use crate::mock::TestWorkerInfo;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				// A worker unknown to the registry
+				let worker = worker_pubkey(9);
+				assert_noop!(
+					PhalaMining::bind(1, worker.clone()),
+					Error::<Test>::WorkerNotRegistered
+				);
+				TestWorkerInfo::set_mock_worker(
+					worker.clone(),
+					MiningWorkerInfo {
+						operator: Some(1),
+						confidence_level: 1,
+						initial_score: Some(100),
+						runtime_version: 0,
+						benchmarked_at: None,
+					},
+				);
+				assert!(registry::Workers::<Test>::get(&worker).is_none());
+				assert_ok!(PhalaMining::bind(1, worker.clone()));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningIdle
+				);
+			});
+		}
+
+		#[test]
+		fn test_dynamic_heartbeat_count() {
+			use frame_support::weights::DispatchClass;
+			fn run_block(fullness: Perbill) {
+				let max_weight = <Test as frame_system::Config>::BlockWeights::get().max_block;
+				System::register_extra_weight_unchecked(
+					fullness * max_weight,
+					DispatchClass::Mandatory,
+				);
+				PhalaMining::adjust_heartbeat_count();
+				System::reset_events();
+				// Clear `frame_system::BlockWeight` for the next block
+				let key = [
+					sp_core::hashing::twox_128(b"System"),
+					sp_core::hashing::twox_128(b"BlockWeight"),
+				]
+				.concat();
+				sp_io::storage::clear(&key);
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				// Static without the bounds
+				run_block(Perbill::zero());
+				assert_eq!(PhalaMining::heartbeat_count(), 20);
+				assert_eq!(PhalaMining::effective_heartbeat_count(), None);
+				assert_noop!(
+					PhalaMining::set_heartbeat_count_bounds(Origin::root(), Some((30, 10))),
+					Error::<Test>::InvalidParameter
+				);
+				assert_noop!(
+					PhalaMining::set_heartbeat_count_bounds(Origin::root(), Some((0, 10))),
+					Error::<Test>::InvalidParameter
+				);
+				// The effective count starts from the static one clamped to the bounds
+				assert_ok!(PhalaMining::set_heartbeat_count_bounds(
+					Origin::root(),
+					Some((10, 40))
+				));
+				assert_eq!(PhalaMining::effective_heartbeat_count(), Some(20));
+				// Empty blocks raise the count up to the max
+				for _ in 0..50 {
+					run_block(Perbill::zero());
+				}
+				assert_eq!(PhalaMining::heartbeat_count(), 40);
+				// Full blocks lower the count down to the min, once the average is high enough
+				run_block(Perbill::one());
+				assert_eq!(PhalaMining::heartbeat_count(), 40);
+				for _ in 0..50 {
+					run_block(Perbill::one());
+				}
+				assert!(PhalaMining::block_utilization() > HEARTBEAT_HIGH_UTILIZATION);
+				assert_eq!(PhalaMining::heartbeat_count(), 10);
+				// Half full blocks keep the count
+				for _ in 0..50 {
+					run_block(Perbill::from_percent(50));
+				}
+				let count = PhalaMining::heartbeat_count();
+				run_block(Perbill::from_percent(50));
+				assert_eq!(PhalaMining::heartbeat_count(), count);
+				// The challenge follows the effective count
+				IdleMiners::<Test>::put(200_000);
+				let _ = take_messages();
+				PhalaMining::heartbeat_challenge();
+				let challenge = crate::mock::take_heartbeat_challenges();
+				assert_eq!(challenge[0].online_target, pow_target(count, 200_000, 12));
+				// Back to static
+				assert_ok!(PhalaMining::set_heartbeat_count_bounds(
+					Origin::root(),
+					None
+				));
+				assert_eq!(PhalaMining::effective_heartbeat_count(), None);
+				assert_eq!(PhalaMining::heartbeat_count(), 20);
+			});
+		}
+
+		#[test]
+		fn test_gk_protocol_version() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, MiningInfoUpdateEvent, SettleInfo, Topic,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				update.settle.push(SettleInfo {
+					pubkey: worker_pubkey(1),
+					v: 1,
+					payout: 0,
+				});
+				// The legacy layout is kept for version 0, and decoded as version 0
+				let legacy = update.encode();
+				update.protocol_version = GK_PROTOCOL_VERSION;
+				let versioned = update.encode();
+				assert_eq!(versioned.len(), legacy.len() + 4);
+				let decoded =
+					MiningInfoUpdateEvent::<BlockNumber>::decode(&mut &legacy[..]).unwrap();
+				assert_eq!(decoded.protocol_version, 0);
+				let decoded =
+					MiningInfoUpdateEvent::<BlockNumber>::decode(&mut &versioned[..]).unwrap();
+				assert_eq!(decoded, update);
+				// The audit data of the version 2 gatekeepers is skipped
+				let mut audited = legacy.clone();
+				2u32.encode_to(&mut audited);
+				vec![(worker_pubkey(1), 10u32, 5u32)].encode_to(&mut audited);
+				let decoded =
+					MiningInfoUpdateEvent::<BlockNumber>::decode(&mut &audited[..]).unwrap();
+				assert_eq!(decoded.protocol_version, 2);
+				assert_eq!(decoded.settle, update.settle);
+				// Both versions are accepted by default
+				let send = |version: u32| {
+					let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+					update.protocol_version = version;
+					PhalaMining::on_gk_message_received(DecodedMessage {
+						sender: MessageOrigin::Gatekeeper,
+						destination: Topic::new(*b"^phala/mining/update"),
+						payload: update,
+					})
+				};
+				assert_ok!(send(0));
+				assert_ok!(send(GK_PROTOCOL_VERSION));
+				assert_noop!(
+					send(GK_PROTOCOL_VERSION + 1),
+					Error::<Test>::UnsupportedGkProtocol
+				);
+				// Drop the legacy gatekeepers after the upgrade
+				assert_noop!(
+					PhalaMining::set_supported_gk_protocol(
+						Origin::root(),
+						0,
+						GK_PROTOCOL_VERSION + 1
+					),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_supported_gk_protocol(
+					Origin::root(),
+					1,
+					GK_PROTOCOL_VERSION
+				));
+				assert_noop!(send(0), Error::<Test>::UnsupportedGkProtocol);
+				assert_ok!(send(GK_PROTOCOL_VERSION));
+			});
+		}
+
+		#[test]
+		fn test_stake_weighted_sampling() {
+			use crate::mock::take_heartbeat_challenges;
+			use phala_types::messaging::{SystemEvent, WorkerEventWithKey};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(3);
+				for i in 1..=3 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+				}
+				// The stake sum is maintained with the sampling disabled
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(PhalaMining::online_stake(), 1000 * DOLLARS);
+				let _ = take_messages();
+				PhalaMining::heartbeat_challenge();
+				assert_eq!(take_heartbeat_challenges()[0].stake_weighting, None);
+
+				assert_ok!(PhalaMining::set_stake_weighted_sampling(
+					Origin::root(),
+					true
+				));
+				assert_ok!(PhalaMining::start_mining(2, 3000 * DOLLARS));
+				assert_ok!(PhalaMining::start_mining(3, 2000 * DOLLARS));
+				assert_eq!(PhalaMining::online_stake(), 6000 * DOLLARS);
+				// The workers started after enabling are told their stake
+				let stakes: Vec<_> = take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+							pubkey,
+							event: WorkerEvent::MiningStakeUpdated { stake },
+							..
+						})) => Some((pubkey, stake)),
+						_ => None,
+					})
+					.collect();
+				assert_eq!(
+					stakes,
+					vec![
+						(worker_pubkey(2), 3000 * DOLLARS),
+						(worker_pubkey(3), 2000 * DOLLARS)
+					]
+				);
+				// Scaled by the stake relative to the average stake
+				PhalaMining::heartbeat_challenge();
+				let challenge = take_heartbeat_challenges().remove(0);
+				let weighting = challenge.stake_weighting.expect("Stake weighted; qed.");
+				assert_eq!(
+					weighting,
+					StakeWeighting {
+						online_stake: 6000 * DOLLARS,
+						online_miners: 3,
+					}
+				);
+				let target = U256::MAX / 1000;
+				let avg = weighting.target_of(target, 2000 * DOLLARS);
+				let high = weighting.target_of(target, 3000 * DOLLARS);
+				let low = weighting.target_of(target, 1000 * DOLLARS);
+				assert!(low < avg && avg < high);
+				assert!(avg <= target && target - avg < U256::from(6000 * DOLLARS));
+				// The chain load is kept: the scaled targets sum up to the unscaled ones
+				let sum = low + avg + high;
+				assert!(sum <= target * 3 && target * 3 - sum < U256::from(3 * 6000 * DOLLARS));
+				// Saturated instead of overflowing
+				assert_eq!(weighting.target_of(U256::MAX, 6000 * DOLLARS), U256::MAX);
+				// Removed at stop
+				assert_ok!(PhalaMining::stop_mining(2));
+				assert_eq!(PhalaMining::online_stake(), 3000 * DOLLARS);
+			});
+		}
+
+		#[test]
+		fn test_observation_mode() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, MiningInfoUpdateEvent, SettleInfo, SystemEvent,
+				Topic, WorkerEventWithKey,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				assert_noop!(
+					PhalaMining::start_observing(2),
+					Error::<Test>::MinerNotReady
+				);
+				let _ = take_messages();
+				assert_ok!(PhalaMining::start_observing(1));
+				assert_noop!(
+					PhalaMining::start_observing(1),
+					Error::<Test>::AlreadyObserving
+				);
+				assert_eq!(PhalaMining::observing_miners(), 1);
+				assert_eq!(PhalaMining::online_miners(), 1);
+				assert_eq!(
+					PhalaMining::observing_since(1),
+					Some(PhalaMining::now_sec())
+				);
+				// The worker is told to run as in mining
+				let events: Vec<_> = take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey { event, .. })) => {
+							Some(event)
+						}
+						_ => None,
+					})
+					.collect();
+				assert!(matches!(events[..], [WorkerEvent::ObservingStart { .. }]));
+				// Never earns or gets slashed
+				elapse_seconds(100);
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: 1,
+						timestamp_ms: Timestamp::get(),
+						offline: vec![worker_pubkey(1)],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v: fp!(1).to_bits(),
+							payout: fp!(1).to_bits(),
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(miner.state, MinerState::Ready);
+				assert_eq!(miner.v.to_bits(), 0);
+				assert!(PhalaMining::settlement_history(1).is_empty());
+				// Starting mining ends the observation
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(PhalaMining::observing_miners(), 0);
+				assert_noop!(
+					PhalaMining::stop_observing(1),
+					Error::<Test>::MinerNotObserving
+				);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::MinerObservingStopped(1))));
+				// Unbinding ends the observation
+				assert_ok!(PhalaMining::stop_mining(2));
+				crate::mock::elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 2));
+				assert_ok!(PhalaMining::start_observing(2));
+				assert_ok!(PhalaMining::unbind_miner(&2, false));
+				assert_eq!(PhalaMining::observing_miners(), 0);
+				assert_eq!(PhalaMining::observing_since(2), None);
+			});
+		}
+
+		#[test]
+		fn test_withdraw_session_backlog() {
+			use phala_types::messaging::{SystemEvent, WorkerEventWithKey};
+			fn worker_events() -> Vec<WorkerEvent> {
+				take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey { event, .. })) => {
+							Some(event)
+						}
+						_ => None,
+					})
+					.collect()
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_ok!(PhalaMining::set_stake_weighted_sampling(
+					Origin::root(),
+					true
+				));
+				let _ = take_messages();
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				// Force stopped in the same block
+				assert_ok!(PhalaMining::force_stop_mining(Origin::root(), 1));
+				let session_id = PhalaMining::worker_event_seq(worker_pubkey(1)).session_id;
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::SessionBacklogWithdrawn(worker_pubkey(1), session_id, 1)
+				)));
+				// The stake update of worker 1 is withdrawn, while the session boundaries and
+				// the other worker are untouched
+				let events = worker_events();
+				assert_eq!(events.len(), 4);
+				assert!(matches!(
+					events[..],
+					[
+						WorkerEvent::MiningStart { .. },
+						WorkerEvent::MiningStart { .. },
+						WorkerEvent::MiningStakeUpdated { .. },
+						WorkerEvent::MiningStop
+					]
+				));
+				// Nothing to withdraw once delivered
+				assert_ok!(PhalaMining::force_stop_mining(Origin::root(), 2));
+				assert_eq!(worker_events().len(), 1);
+				assert!(!take_events().iter().any(|e| matches!(
+					e,
+					TestEvent::PhalaMining(Event::SessionBacklogWithdrawn(..))
+				)));
+			});
+		}
+
+		#[test]
+		fn test_settlement_audit() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, MiningInfoUpdateEvent, SettleInfo, Topic,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::set_settlement_audit_tolerance(
+					Origin::root(),
+					Some(Permill::from_parts(1000))
+				));
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let v = PhalaMining::miners(1).unwrap().v.to_bits();
+				let send = |block: BlockNumber, offline: bool, settle: Option<(u128, u128)>| {
+					let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(block, 0);
+					update.protocol_version = 2;
+					if offline {
+						update.offline.push(worker_pubkey(1));
+					}
+					if let Some((v, payout)) = settle {
+						update.settle.push(SettleInfo {
+							pubkey: worker_pubkey(1),
+							v,
+							payout,
+						});
+					}
+					assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage {
+						sender: MessageOrigin::Gatekeeper,
+						destination: Topic::new(*b"^phala/mining/update"),
+						payload: update,
+					}));
+				};
+				let rejected = || {
+					take_events().into_iter().any(|e| {
+						matches!(
+							e,
+							TestEvent::PhalaMining(Event::SettlementAuditFailed(1, _, _, _, _))
+						)
+					})
+				};
+				take_events();
+				// The payout is counted in the replayed V
+				send(1, false, Some((v / 2, v - v / 2)));
+				assert!(!rejected());
+				assert_eq!(PhalaMining::miners(1).unwrap().v.to_bits(), v / 2);
+				// Reported offline on chain and slashed for 1000 blocks (0.33%), but the V is
+				// reported unchanged. The payout is dropped, and the V resynced.
+				let v = v / 2;
+				send(2, true, None);
+				send(1002, false, Some((v / 2, v / 2)));
+				assert!(rejected());
+				assert_eq!(PhalaMining::miners(1).unwrap().v.to_bits(), v / 2);
+				// Audited again from the resynced V
+				let v = v / 2;
+				send(1003, false, Some((v, 0)));
+				assert!(!rejected());
+				assert_eq!(PhalaMining::miners(1).unwrap().v.to_bits(), v);
+				// Not audited once disabled
+				assert_ok!(PhalaMining::set_settlement_audit_tolerance(
+					Origin::root(),
+					None
+				));
+				take_events();
+				send(1004, false, Some((v / 2, 0)));
+				assert!(!rejected());
+				assert_eq!(PhalaMining::miners(1).unwrap().v.to_bits(), v / 2);
+				assert_eq!(PhalaMining::settlement_trace(1), None);
+			});
+		}
+
+		#[test]
+		fn test_trace_segments() {
+			// Idle since the last settlement, the settlement blocks excluded
+			assert_eq!(trace_segments(10, 20, false, &[]), (vec![(9, false)], false));
+			// Slashed from the offline report, and heartbeating in the block of the recovery
+			assert_eq!(
+				trace_segments(10, 20, false, &[(13, true), (16, false), (25, true)]),
+				(vec![(2, false), (3, true), (3, false)], false)
+			);
+			// Still unresponsive at the settlement
+			assert_eq!(
+				trace_segments(10, 20, false, &[(13, true)]),
+				(vec![(2, false), (7, true)], true)
+			);
+		}
+
+		#[test]
+		fn test_miner_type_info() {
+			use scale_info::{Type, TypeDef};
+			fn field_names(ty: Type) -> Vec<&'static str> {
+				match ty.type_def() {
+					TypeDef::Composite(c) => c
+						.fields()
+						.iter()
+						.filter_map(|f| f.name().copied())
+						.collect(),
+					_ => panic!("Not a struct"),
+				}
+			}
+			// The storage can be decoded by the field names without the custom type bundles
+			assert_eq!(
+				field_names(MinerInfo::type_info()),
+				vec![
+					"state",
+					"ve",
+					"v",
+					"v_updated_at",
+					"benchmark",
+					"cool_down_start",
+					"stats",
+					"heartbeat_latency",
+					"v_balance",
+					"session_start",
+					"session_payout"
+				]
+			);
+			assert_eq!(
+				field_names(Benchmark::type_info()),
+				vec!["p_instant", "iterations", "mining_start_time", "updated_at"]
+			);
+			assert!(field_names(TokenomicParams::type_info()).contains(&"slash_rate"));
+			// Including the types holding the primitives without TypeInfo
+			assert_eq!(
+				field_names(TierRule::type_info()),
+				vec![
+					"min_liveness",
+					"min_sessions",
+					"slash_multiplier",
+					"target_interval"
+				]
+			);
+			assert_eq!(
+				field_names(QueuedSettlement::<u64>::type_info()),
+				vec!["info", "block"]
+			);
+			match MinerState::type_info().type_def() {
+				TypeDef::Variant(v) => assert_eq!(v.variants().len(), 5),
+				_ => panic!("Not an enum"),
+			}
+		}
+
+		#[test]
+		fn test_min_session_interval() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::set_min_session_interval(
+					Origin::root(),
+					PhalaMining::cool_down_period() + 3600
+				));
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::stop_mining(1));
+				let stopped_at = PhalaMining::last_session_end(worker_pubkey(1)).unwrap();
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				// Rebound to another miner, but the worker is still throttled
+				assert_ok!(PhalaMining::unbind_miner(&1, false));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(1)));
+				assert_noop!(
+					PhalaMining::start_mining(2, 1000 * DOLLARS),
+					Error::<Test>::SessionIntervalNotElapsed
+				);
+				elapse_seconds(3600);
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				assert_eq!(
+					PhalaMining::last_session_end(worker_pubkey(1)),
+					Some(stopped_at)
+				);
+			});
+		}
+
+		#[test]
+		fn test_settlement_queue() {
+			use crate::mock::gk_settle;
+			use frame_support::traits::OnInitialize;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(3);
+				for i in 1..=3 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+					assert_ok!(PhalaMining::start_mining(i as u64, 1000 * DOLLARS));
+				}
+				let v = PhalaMining::miners(1).unwrap().v.to_bits();
+				let send = |workers: &[u8], v: u128| gk_settle(workers, v, 0);
+				let v_of = |miner: u64| PhalaMining::miners(miner).unwrap().v.to_bits();
+				// Two settlements per block
+				assert_ok!(PhalaMining::set_settlement_weight_budget(
+					Origin::root(),
+					Some(SETTLEMENT_BASE_WEIGHT * 5 / 2)
+				));
+				take_events();
+				send(&[1, 2, 3], v / 2);
+				send(&[1], v / 4);
+				assert_eq!(
+					take_events().as_slice(),
+					[
+						TestEvent::PhalaMining(Event::SettlementsQueued(3, 3)),
+						TestEvent::PhalaMining(Event::SettlementsQueued(1, 4))
+					]
+				);
+				assert_eq!(v_of(1), v);
+				<PhalaMining as OnInitialize<u64>>::on_initialize(2);
+				// Any two of the shuffled settlements of the first message
+				let settled = [v_of(1), v_of(2), v_of(3)]
+					.iter()
+					.filter(|&&v_i| v_i == v / 2)
+					.count();
+				assert_eq!(settled, 2);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::QueuedSettlementsApplied(2, 2)
+				)));
+				// Queued behind the earlier ones even if the budget is removed
+				assert_ok!(PhalaMining::set_settlement_weight_budget(
+					Origin::root(),
+					None
+				));
+				send(&[3], v / 8);
+				assert_eq!(v_of(3), v);
+				<PhalaMining as OnInitialize<u64>>::on_initialize(3);
+				assert_eq!((v_of(1), v_of(2), v_of(3)), (v / 4, v / 2, v / 8));
+				assert_eq!(PhalaMining::settlement_queue_range(), (0, 0));
+				// Applied in place with an empty queue
+				send(&[2], v / 8);
+				assert_eq!(v_of(2), v / 8);
+			});
+		}
+
+		#[test]
+		fn test_payout_smoothing() {
+			use crate::mock::{
+				elapse_cool_down, elapse_seconds, gk_settle, setup_workers_linked_operators,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers_linked_operators(1);
+				assert_ok!(PhalaMining::bind_solo(Origin::signed(1), worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_solo_mining(
+					Origin::signed(1),
+					500 * DOLLARS
+				));
+				let settle = |payout: FixedPoint| {
+					gk_settle(&[1], PhalaMining::miners(1).unwrap().v.to_bits(), payout.to_bits())
+				};
+				let rewarded = || -> u128 {
+					PhalaMining::locked_rewards(RewardBeneficiary::Owner(1))
+						.iter()
+						.map(|r| r.amount)
+						.sum()
+				};
+				assert_noop!(
+					PhalaMining::set_solo_payout_smoothing(Origin::signed(2), Some(DOLLARS)),
+					Error::<Test>::NotSoloMiner
+				);
+				assert_noop!(
+					PhalaMining::set_solo_payout_smoothing(Origin::signed(1), Some(0)),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_solo_payout_smoothing(
+					Origin::signed(1),
+					Some(24 * DOLLARS)
+				));
+				// The first payout is held in the reserve
+				settle(fp!(10));
+				assert_eq!(rewarded(), 0);
+				assert_eq!(
+					PhalaMining::payout_reserve(1),
+					Some(PayoutReserve {
+						amount: 10 * DOLLARS,
+						released_at: PhalaMining::now_sec(),
+					})
+				);
+				// One dollar per hour is dripped
+				elapse_seconds(3 * 3600);
+				settle(fp!(1));
+				assert_eq!(rewarded(), 3 * DOLLARS);
+				assert_eq!(PhalaMining::payout_reserve(1).unwrap().amount, 8 * DOLLARS);
+				// The drip is capped by the reserve
+				elapse_seconds(24 * 3600);
+				settle(fp!(0));
+				assert_eq!(rewarded(), 11 * DOLLARS);
+				assert_eq!(PhalaMining::payout_reserve(1).unwrap().amount, 0);
+				// The reserve is flushed on reclaim
+				settle(fp!(5));
+				assert_eq!(rewarded(), 11 * DOLLARS);
+				assert_ok!(PhalaMining::stop_solo_mining(Origin::signed(1)));
+				elapse_cool_down();
+				take_events();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(rewarded(), 16 * DOLLARS);
+				assert_eq!(PhalaMining::payout_reserve(1), None);
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::PayoutReleased(
+						1,
+						5 * DOLLARS,
+						0
+					)))
+				);
+			});
+		}
+
+		#[test]
+		fn test_export_import_mining_state() {
+			use sp_runtime::BuildStorage;
+			let (snapshot, commitment) = new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				for i in 1..=2 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+				}
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				(
+					PhalaMining::export_mining_state(),
+					PhalaMining::bindings_commitment(),
+				)
+			});
+			assert_eq!(snapshot.miners.len(), 2);
+			assert_eq!(snapshot.bindings.len(), 2);
+			assert_eq!(snapshot.stakes, vec![(1, 1000 * DOLLARS)]);
+			assert_eq!(snapshot.online_miners, 1);
+			// Restored by the genesis builder
+			let mut t = frame_system::GenesisConfig::default()
+				.build_storage::<Test>()
+				.unwrap();
+			GenesisBuild::<Test>::assimilate_storage(
+				&GenesisConfig {
+					mining_state: Some(snapshot.encode().into()),
+					..Default::default()
+				},
+				&mut t,
+			)
+			.unwrap();
+			sp_io::TestExternalities::new(t).execute_with(|| {
+				assert_eq!(PhalaMining::export_mining_state(), snapshot);
+				assert_eq!(PhalaMining::bindings_commitment(), commitment);
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningIdle
+				);
+				assert_eq!(WorkerBindings::<Test>::get(&worker_pubkey(2)), Some(2));
+			});
+		}
+
+		#[test]
+		fn test_clock_skew() {
+			use frame_support::traits::OnFinalize;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let heartbeat = |challenge_time, iterations| {
+					elapse_seconds(10);
+					assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+						MiningReportEvent,
+					> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block: 1,
+							challenge_time,
+							iterations,
+							platform: None,
+							restarted: false,
+						},
+					}));
+				};
+				// Not tracked without the challenge emission time
+				heartbeat(Timestamp::get(), 100);
+				assert_eq!(PhalaMining::clock_skew(worker_pubkey(1)), None);
+				PhalaMining::heartbeat_challenge();
+				let emitted_at = Timestamp::get();
+				// Tracked but not flagged without the threshold
+				heartbeat(emitted_at - 3000, 200);
+				assert_eq!(PhalaMining::clock_skew(worker_pubkey(1)), Some(-3000));
+				assert!(!PhalaRegistry::is_clock_skewed(worker_pubkey(1)));
+				assert_ok!(PhalaMining::set_max_clock_skew(Origin::root(), Some(2000)));
+				take_events();
+				heartbeat(emitted_at + 5000, 300);
+				assert!(PhalaRegistry::is_clock_skewed(worker_pubkey(1)));
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::WorkerClockSkewed(
+						worker_pubkey(1),
+						5000
+					))]
+				);
+				// Cleared once the skew is back within the threshold
+				heartbeat(emitted_at + 1000, 400);
+				assert!(!PhalaRegistry::is_clock_skewed(worker_pubkey(1)));
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::WorkerClockSkewCleared(
+						worker_pubkey(1)
+					))]
+				);
+				// The emission time expires
+				System::set_block_number(1 + CHALLENGE_TIME_TTL_BLOCKS as u64);
+				<PhalaMining as OnFinalize<u64>>::on_finalize(1 + CHALLENGE_TIME_TTL_BLOCKS as u64);
+				assert_eq!(ChallengeTimes::<Test>::get(1), None);
+			});
+		}
+
+		#[test]
+		fn test_benchmark_reset() {
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				PhalaRegistry::internal_set_benchmark(&worker_pubkey(1), Some(600));
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let heartbeat = |session_id, iterations, restarted| {
+					elapse_seconds(100);
+					PhalaMining::on_mining_message_received(DecodedMessage::<MiningReportEvent> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id,
+							challenge_block: 0,
+							challenge_time: 0,
+							iterations,
+							platform: None,
+							restarted,
+						},
+					})
+				};
+				let benchmark = || PhalaMining::miners(1).unwrap().benchmark;
+				assert_ok!(heartbeat(0, 10000, false));
+				assert_eq!(benchmark().p_instant, 600);
+				assert_ok!(PhalaMining::set_benchmark_reset_allowed(
+					Origin::root(),
+					true
+				));
+				take_events();
+				// The restarted worker reports from a new baseline
+				assert_ok!(heartbeat(0, 500, true));
+				assert_eq!(benchmark().iterations, 500);
+				assert_eq!(benchmark().p_instant, 600);
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::BenchmarkBaselineReset(
+						1, 500
+					))]
+				);
+				assert_ok!(heartbeat(0, 9500, false));
+				assert_eq!(benchmark().p_instant, 540);
+				// So does a new session
+				assert_ok!(heartbeat(1, 100, false));
+				assert_eq!(benchmark().iterations, 100);
+				assert_ok!(heartbeat(1, 10100, false));
+				assert_eq!(benchmark().p_instant, 600);
+			});
+		}
+
+		#[test]
+		#[should_panic]
+		fn test_benchmark_reset_disallowed() {
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				for iterations in vec![10000, 500] {
+					elapse_seconds(100);
+					let _ = PhalaMining::on_mining_message_received(DecodedMessage::<
+						MiningReportEvent,
+					> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block: 0,
+							challenge_time: 0,
+							iterations,
+							platform: None,
+							restarted: true,
+						},
+					});
+				}
+			});
+		}
+
+		#[test]
+		fn test_min_worker_version() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(3);
+				PhalaRegistry::internal_set_runtime_version(&worker_pubkey(3), 2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::set_min_worker_version(
+					Origin::root(),
+					Some(2),
+					3600
+				));
+				take_events();
+				// Accepted with a warning in the grace period
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::OutdatedWorkerInGrace(worker_pubkey(2), 0)
+				)));
+				// Rejected after the grace period
+				elapse_seconds(3600);
+				assert_noop!(
+					PhalaMining::start_mining(1, 1000 * DOLLARS),
+					Error::<Test>::WorkerVersionTooOld
+				);
+				assert_ok!(PhalaMining::unbind_miner(&2, false));
+				assert_noop!(
+					PhalaMining::bind(2, worker_pubkey(2)),
+					Error::<Test>::WorkerVersionTooOld
+				);
+				// The up-to-date workers are not affected
+				assert_ok!(PhalaMining::bind(3, worker_pubkey(3)));
+				assert_ok!(PhalaMining::start_mining(3, 1000 * DOLLARS));
+				// Lifted
+				assert_ok!(PhalaMining::set_min_worker_version(Origin::root(), None, 0));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+			});
+		}
+
+		#[test]
+		fn test_miners_by_state() {
+			use crate::mock::elapse_cool_down;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(3);
+				for i in 1..=3 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+				}
+				let sorted = |state| {
+					let mut miners = PhalaMining::miners_by_state(state, 0);
+					miners.sort();
+					miners
+				};
+				assert_eq!(sorted(MinerState::Ready), vec![1, 2, 3]);
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::stop_mining(2));
+				assert_eq!(sorted(MinerState::Ready), vec![3]);
+				assert_eq!(sorted(MinerState::MiningIdle), vec![1]);
+				assert_eq!(sorted(MinerState::MiningCoolingDown), vec![2]);
+				assert!(PhalaMining::miners_by_state(MinerState::MiningIdle, 1).is_empty());
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 2));
+				assert_eq!(sorted(MinerState::Ready), vec![2, 3]);
+				assert!(sorted(MinerState::MiningCoolingDown).is_empty());
+			});
+		}
+
+		#[test]
+		fn test_settle_binding_dispute() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, MiningInfoUpdateEvent, SettleBinding, SettleInfo,
+				Topic,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				for i in 1..=2 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+					assert_ok!(PhalaMining::start_mining(i as u64, 1000 * DOLLARS));
+				}
+				let v = PhalaMining::miners(1).unwrap().v.to_bits();
+				let hash_of = |miner: u64| Some(crate::hashing::blake2_256(&miner.encode()));
+				let send = |bindings: Vec<(u8, Option<[u8; 32]>)>| {
+					let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+					update.protocol_version = 3;
+					for i in 1..=2 {
+						update.settle.push(SettleInfo {
+							pubkey: worker_pubkey(i),
+							v: v / 2,
+							payout: 0,
+						});
+					}
+					for (i, binding_hash) in bindings {
+						update.settle_bindings.push(SettleBinding {
+							pubkey: worker_pubkey(i),
+							binding_hash,
+						});
+					}
+					assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage {
+						sender: MessageOrigin::Gatekeeper,
+						destination: Topic::new(*b"^phala/mining/update"),
+						payload: update,
+					}));
+				};
+				let v_of = |miner: u64| PhalaMining::miners(miner).unwrap().v.to_bits();
+				take_events();
+				// Worker 2 is settled under the wrong miner, worker 1 is fine
+				send(vec![(1, hash_of(1)), (2, hash_of(1))]);
+				assert_eq!((v_of(1), v_of(2)), (v / 2, v));
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::SettlementDisputed(0, worker_pubkey(2))
+				)));
+				assert_eq!(
+					PhalaMining::settlement_disputes(0).map(|d| d.binding_hash),
+					Some(hash_of(1))
+				);
+				// So is a settlement without the binding
+				send(vec![(1, hash_of(1))]);
+				assert!(PhalaMining::settlement_disputes(1).is_some());
+				assert_eq!(v_of(2), v);
+				// Resolved by the governance
+				assert_ok!(PhalaMining::resolve_settlement_dispute(
+					Origin::root(),
+					0,
+					true
+				));
+				assert_eq!(v_of(2), v / 2);
+				assert_ok!(PhalaMining::resolve_settlement_dispute(
+					Origin::root(),
+					1,
+					false
+				));
+				assert_noop!(
+					PhalaMining::resolve_settlement_dispute(Origin::root(), 1, true),
+					Error::<Test>::SettlementDisputeNotFound
+				);
+				// The encoded bindings survive the round trip
+				let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				update.protocol_version = 3;
+				update.settle_bindings.push(SettleBinding {
+					pubkey: worker_pubkey(1),
+					binding_hash: None,
+				});
+				let encoded = update.encode();
+				assert_eq!(
+					MiningInfoUpdateEvent::<BlockNumber>::decode(&mut &encoded[..]).unwrap(),
+					update
+				);
+			});
+		}
+
+		#[test]
+		fn test_try_state() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(PhalaMining::do_try_state(), Ok(()));
+				// Drifted counters
+				IdleMiners::<Test>::put(2);
+				assert!(PhalaMining::do_try_state().is_err());
+				IdleMiners::<Test>::put(1);
+				ActiveMiners::<Test>::put(1);
+				assert!(PhalaMining::do_try_state().is_err());
+				ActiveMiners::<Test>::kill();
+				// Missing stake
+				Stakes::<Test>::remove(1);
+				assert!(PhalaMining::do_try_state().is_err());
+				Stakes::<Test>::insert(1, 1000 * DOLLARS);
+				// Asymmetric bindings
+				WorkerBindings::<Test>::remove(worker_pubkey(2));
+				assert!(PhalaMining::do_try_state().is_err());
+				WorkerBindings::<Test>::insert(worker_pubkey(2), 2);
+				assert_eq!(PhalaMining::do_try_state(), Ok(()));
+			});
+		}
+
+		#[test]
+		fn test_gk_block_number_wire_type() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{GkBlockNumber, MiningInfoUpdateEvent, SettleInfo};
+			let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(100, 1000);
+			update.protocol_version = GK_PROTOCOL_VERSION;
+			update.settle.push(SettleInfo {
+				pubkey: worker_pubkey(1),
+				v: 1,
+				payout: 0,
+			});
+			// The u64 runtime speaks the same layout as the u32 gatekeepers
+			let encoded = update.encode();
+			let gk_update =
+				MiningInfoUpdateEvent::<GkBlockNumber>::decode(&mut &encoded[..]).unwrap();
+			assert_eq!(gk_update.block_number, 100);
+			assert_eq!(gk_update.encode(), encoded);
+			assert_eq!(
+				MiningInfoUpdateEvent::<BlockNumber>::decode(&mut &gk_update.encode()[..]).unwrap(),
+				update
+			);
+			assert_eq!(&encoded[..4], &100u32.to_le_bytes()[..]);
+			// Out of range block numbers saturate
+			let update = MiningInfoUpdateEvent::<BlockNumber>::new(u64::MAX, 0);
+			let decoded =
+				MiningInfoUpdateEvent::<GkBlockNumber>::decode(&mut &update.encode()[..]).unwrap();
+			assert_eq!(decoded.block_number, GkBlockNumber::MAX);
+		}
+
+		#[test]
+		fn test_gatekeeper_telemetry() {
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				registry::Gatekeeper::<Test>::put(vec![worker_pubkey(2)]);
+				let telemetry = |sender, block_number| DecodedMessage::<GatekeeperTelemetry> {
+					sender,
+					destination: Topic::new(*b"^phala/mining/telemetry"),
+					payload: GatekeeperTelemetry {
+						block_number,
+						workers_tracked: 10,
+						events_emitted: 3,
+					},
+				};
+				// Only accepted from the gatekeepers
+				assert_noop!(
+					PhalaMining::on_gk_telemetry_received(telemetry(
+						MessageOrigin::Worker(worker_pubkey(1)),
+						1
+					)),
+					Error::<Test>::BadSender
+				);
+				assert_noop!(
+					PhalaMining::on_gk_telemetry_received(telemetry(MessageOrigin::Gatekeeper, 1)),
+					Error::<Test>::BadSender
+				);
+				System::set_block_number(5);
+				assert_ok!(PhalaMining::on_gk_telemetry_received(telemetry(
+					MessageOrigin::Worker(worker_pubkey(2)),
+					2
+				)));
+				assert_eq!(
+					PhalaMining::gatekeeper_telemetry_log(),
+					vec![GatekeeperTelemetryRecord {
+						gatekeeper: worker_pubkey(2),
+						received_at: 5,
+						workers_tracked: 10,
+						events_emitted: 3,
+						processing_lag: 3,
+					}]
+				);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::GatekeeperTelemetryReported(worker_pubkey(2), 10, 3, 3)
+				)));
+				// A ring buffer of the latest reports
+				for _ in 0..MAX_GATEKEEPER_TELEMETRY {
+					assert_ok!(PhalaMining::on_gk_telemetry_received(telemetry(
+						MessageOrigin::Worker(worker_pubkey(2)),
+						5
+					)));
+				}
+				let log = PhalaMining::gatekeeper_telemetry_log();
+				assert_eq!(log.len(), MAX_GATEKEEPER_TELEMETRY);
+				assert!(log.iter().all(|record| record.processing_lag == 0));
+			});
+		}
+
+		#[test]
+		fn test_confidence_scores() {
+			use phala_types::messaging::GatekeeperEvent;
+			new_test_ext().execute_with(|| {
+				let tokenomic = PhalaMining::tokenomic();
+				let default_ve5 = tokenomic.ve(1000 * DOLLARS, 1000, 5);
+				let default_ve4 = tokenomic.ve(1000 * DOLLARS, 1000, 4);
+				assert_eq!(default_ve5, fp!(1754.9999999999999999723));
+				// Each score must be in [0, 1]
+				let one = fp!(1).to_bits();
+				assert_noop!(
+					PhalaMining::set_confidence_scores(
+						Origin::root(),
+						Some([one, one + 1, one, one, one])
+					),
+					Error::<Test>::InvalidParameter
+				);
+				let scores = [fp!(0.7).to_bits(), one, one, one, fp!(0.8).to_bits()];
+				assert_ok!(PhalaMining::set_confidence_scores(
+					Origin::root(),
+					Some(scores)
+				));
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::ConfidenceScoresChanged(Some(scores))
+				)));
+				assert!(take_messages().iter().any(|m| matches!(
+					m.decode_payload::<GatekeeperEvent>(),
+					Some(GatekeeperEvent::ConfidenceScoresChanged(Some(s))) if s == scores
+				)));
+				// The boundary levels
+				assert_eq!(tokenomic.ve(1000 * DOLLARS, 1000, 1), default_ve5);
+				assert_eq!(tokenomic.ve(1000 * DOLLARS, 1000, 5), default_ve4);
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 4),
+					tokenomic.ve(1000 * DOLLARS, 1000, 2)
+				);
+				// Out of range levels fall back to the first score
+				assert_eq!(tokenomic.ve(1000 * DOLLARS, 1000, 0), default_ve5);
+				assert_eq!(tokenomic.ve(1000 * DOLLARS, 1000, 6), default_ve5);
+				// Restores the builtin table
+				assert_ok!(PhalaMining::set_confidence_scores(Origin::root(), None));
+				assert_eq!(tokenomic.ve(1000 * DOLLARS, 1000, 5), default_ve5);
+				assert_eq!(
+					tokenomic.ve(1000 * DOLLARS, 1000, 0),
+					fp!(1950.00000000000000001626)
+				);
+			});
+		}
+
+		#[test]
+		fn test_pow_target_extreme() {
+			let full = (U256::MAX >> 24) * U256::from(1u32 << 24);
+			// Saturated instead of panicking
+			assert!(pow_target(u32::MAX, u32::MAX, 1) > U256::zero());
+			assert_eq!(pow_target(u32::MAX, 1, u32::MAX), pow_target(1, 1, 3600));
+			assert_eq!(pow_target(20, 20, 0), pow_target(20, 20, 1));
+			assert_eq!(pow_target(20, 20, 7200), full);
+			// Tens of millions of workers are still sampled
+			assert!(pow_target(20, 50_000_000, 1) > U256::zero());
+			// Properties over random inputs
+			let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+			let mut next = |n: u64| {
+				seed ^= seed << 13;
+				seed ^= seed >> 7;
+				seed ^= seed << 17;
+				seed % n
+			};
+			for _ in 0..10_000 {
+				let num_workers = next(100_000_000) as u32 + 1;
+				let secs_per_block = next(7200) as u32;
+				let num_tx = next(1000) as u32;
+				let target = pow_target(num_tx, num_workers, secs_per_block);
+				assert!(target <= full);
+				// More heartbeats never lower the target
+				assert!(pow_target(num_tx + 1, num_workers, secs_per_block) >= target);
+				// More workers never raise the per worker target of the same heartbeats
+				if num_workers < u32::MAX {
+					let blocks_per_hour = (3600 / secs_per_block.max(1)).max(1);
+					if num_tx * blocks_per_hour <= num_workers * 2 {
+						assert!(pow_target(num_tx, num_workers + 1, secs_per_block) <= target);
+					}
+				}
+			}
+		}
+
+		#[test]
+		fn test_refund_beneficiary() {
+			use crate::mock::{elapse_cool_down, Balances};
+			use frame_support::traits::Currency;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining_with_refund(1, 1000 * DOLLARS, 99));
+				assert_eq!(PhalaMining::refund_beneficiary(1), Some(99));
+				assert_ok!(PhalaMining::stop_mining(1));
+				// Some funds land on the miner account
+				let _ = Balances::deposit_creating(&1, 10 * DOLLARS);
+				let left = Balances::free_balance(1);
+				let before = Balances::free_balance(99);
+				let _ = take_events();
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(Balances::free_balance(1), 0);
+				assert_eq!(Balances::free_balance(99), before + left);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::RefundRouted(1, 99, left))));
+				assert_eq!(PhalaMining::refund_beneficiary(1), None);
+			});
+		}
+
+		#[test]
+		fn test_settlement_pause_on_upgrade() {
+			use crate::mock::BlockNumber;
+			use frame_support::traits::{OnInitialize, OnRuntimeUpgrade};
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(3);
+				for i in 1..=3 {
+					assert_ok!(PhalaMining::bind(i, worker_pubkey(i as u8)));
+					assert_ok!(PhalaMining::start_mining(i, 1000 * DOLLARS));
+				}
+				let offline = |worker, protocol_version| {
+					let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+					update.protocol_version = protocol_version;
+					update.offline.push(worker_pubkey(worker));
+					PhalaMining::on_gk_message_received(DecodedMessage {
+						sender: MessageOrigin::Gatekeeper,
+						destination: Topic::new(*b"^phala/mining/update"),
+						payload: update,
+					})
+				};
+				// Not paused without a pending migration
+				let _ = take_events();
+				<PhalaMining as OnRuntimeUpgrade>::on_runtime_upgrade();
+				assert_eq!(PhalaMining::settlement_paused(), None);
+				assert!(take_events().is_empty());
+				downgrade_miners_v0(0);
+				<PhalaMining as OnRuntimeUpgrade>::on_runtime_upgrade();
+				assert_eq!(PhalaMining::settlement_paused(), Some(1));
+				assert!(take_events().contains(&TestEvent::PhalaMining(Event::SettlementPaused(1))));
+				// Buffered during the upgrade block, the legacy layout without the trailing fields
+				// followed by a versioned one
+				assert_ok!(offline(1, 0));
+				assert_ok!(offline(2, 2));
+				// Rejected with an event beyond the cap, for the gatekeeper to resend
+				let buffered = PausedMiningUpdates::<Test>::get();
+				let full = vec![Vec::<u8>::new(); MAX_PENDING_MINING_UPDATES];
+				PausedMiningUpdates::<Test>::put(full);
+				assert_eq!(
+					offline(3, 0),
+					Err(Error::<Test>::PausedMiningUpdatesFull.into())
+				);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::PausedMiningUpdateDropped(1))));
+				PausedMiningUpdates::<Test>::put(buffered);
+				PhalaMining::on_initialize(1);
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningIdle
+				);
+				assert_eq!(
+					PhalaMining::miners(2).unwrap().state,
+					MinerState::MiningIdle
+				);
+				// Lifted in the next block as the migrations are done
+				System::set_block_number(2);
+				PhalaMining::on_initialize(2);
+				assert_eq!(PhalaMining::settlement_paused(), None);
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningUnresponsive
+				);
+				assert_eq!(
+					PhalaMining::miners(2).unwrap().state,
+					MinerState::MiningUnresponsive
+				);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::SettlementPauseLifted(2))));
+				// Lifted after UPGRADE_PAUSE_MAX_BLOCKS if the migrations never complete
+				downgrade_miners_v0(0);
+				<PhalaMining as OnRuntimeUpgrade>::on_runtime_upgrade();
+				MiningStorageVersion::<Test>::put(STORAGE_VERSION - 1);
+				assert_ok!(offline(3, 0));
+				System::set_block_number(3);
+				PhalaMining::on_initialize(3);
+				assert_eq!(PhalaMining::settlement_paused(), Some(2));
+				let lifted_at = 2 + UPGRADE_PAUSE_MAX_BLOCKS as BlockNumber;
+				System::set_block_number(lifted_at);
+				PhalaMining::on_initialize(lifted_at);
+				assert_eq!(PhalaMining::settlement_paused(), None);
+				assert_eq!(
+					PhalaMining::miners(3).unwrap().state,
+					MinerState::MiningUnresponsive
+				);
+			});
+		}
+
+		#[test]
+		fn test_miner_state_changed() {
+			use crate::mock::{elapse_cool_down, BlockNumber};
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn gk_update(offline: Vec<WorkerPublicKey>, recovered: Vec<WorkerPublicKey>) {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: System::block_number(),
+						timestamp_ms: Timestamp::get(),
+						offline,
+						recovered_to_online: recovered,
+						settle: vec![],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			fn state_changes() -> Vec<(u64, MinerState, MinerState, u64)> {
+				take_events()
+					.into_iter()
+					.filter_map(|e| match e {
+						TestEvent::PhalaMining(Event::MinerStateChanged(m, old, new, block)) => {
+							Some((m, old, new, block))
+						}
+						_ => None,
+					})
+					.collect()
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				let _ = take_events();
+				// Nothing to report at the creation of the miner
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_eq!(state_changes(), vec![]);
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				// Driven by the gatekeeper
+				gk_update(vec![worker_pubkey(1)], vec![]);
+				gk_update(vec![], vec![worker_pubkey(1)]);
+				assert_ok!(PhalaMining::stop_mining(1));
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(
+					state_changes(),
+					vec![
+						(1, MinerState::Ready, MinerState::MiningIdle, 1),
+						(1, MinerState::MiningIdle, MinerState::MiningUnresponsive, 1),
+						(1, MinerState::MiningUnresponsive, MinerState::MiningIdle, 1),
+						(1, MinerState::MiningIdle, MinerState::MiningCoolingDown, 1),
+						(1, MinerState::MiningCoolingDown, MinerState::Ready, 1),
+					]
+				);
+			});
+		}
+
+		#[test]
+		fn test_benchmark_validity_period() {
+			use crate::mock::elapse_cool_down;
+			use phala_types::messaging::{SystemEvent, WorkerEventWithKey};
+			fn bench_requested() -> bool {
+				take_messages().iter().any(|m| {
+					matches!(
+						m.decode_payload::<SystemEvent>(),
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+							event: WorkerEvent::BenchStart { .. },
+							..
+						}))
+					)
+				})
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_noop!(
+					PhalaMining::set_benchmark_validity_period(Origin::signed(1), Some(3600)),
+					sp_runtime::DispatchError::BadOrigin
+				);
+				assert_ok!(PhalaMining::set_benchmark_validity_period(
+					Origin::root(),
+					Some(3600)
+				));
+				// Still valid
+				elapse_seconds(3600);
+				let _ = take_messages();
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert!(!bench_requested());
+				// Expired, and the worker is asked to re-benchmark
+				elapse_seconds(1);
+				assert_eq!(
+					PhalaMining::start_mining(2, 1000 * DOLLARS),
+					Err(Error::<Test>::BenchmarkExpired.into())
+				);
+				assert_eq!(PhalaMining::miners(2).unwrap().state, MinerState::Ready);
+				assert!(bench_requested());
+				// Accepted after the new benchmark
+				PhalaRegistry::internal_set_benchmark(&worker_pubkey(2), Some(1));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				// An unknown benchmark time counts as expired
+				assert_ok!(PhalaMining::stop_mining(2));
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(2), 2));
+				registry::BenchmarkedAt::<Test>::remove(worker_pubkey(2));
+				assert_eq!(
+					PhalaMining::start_mining(2, 1000 * DOLLARS),
+					Err(Error::<Test>::BenchmarkExpired.into())
+				);
+				// Disabled
+				assert_ok!(PhalaMining::set_benchmark_validity_period(
+					Origin::root(),
+					None
+				));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+			});
+		}
+
+		#[test]
+		fn test_v_balance() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, SettleInfo, Topic};
+			fn settle(v: FixedPoint) {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: System::block_number(),
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v: v.to_bits(),
+							payout: 0,
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_eq!(PhalaMining::miner_v(&1), Some((0, 0)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let ve = PhalaMining::miners(1).unwrap().ve.to_fixed();
+				let ve_balance: u128 = FixedPointConvert::from_fixed(&ve);
+				assert_eq!(PhalaMining::miner_v(&1), Some((ve.to_bits(), ve_balance)));
+				// Updated at each settlement, the same as the settlement event
+				let _ = take_events();
+				let v = fp!(1234.5678);
+				settle(v);
+				let v_balance = PhalaMining::miners(1).unwrap().v_balance;
+				let expected: u128 = FixedPointConvert::from_fixed(&v);
+				assert_eq!(v_balance, expected);
+				assert_eq!(PhalaMining::miner_v(&1), Some((v.to_bits(), v_balance)));
+				assert!(take_events().iter().any(|e| matches!(
+					e,
+					TestEvent::PhalaMining(Event::MinerSettled(1, v_bits, _, v_event, _))
+						if *v_bits == v.to_bits() && *v_event == v_balance
+				)));
+			});
+		}
+
+		#[test]
+		fn test_relayer_tip() {
+			use crate::mock::Balances;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				// Operated by 1, mined by 2
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(1)));
+				let mut iterations = 0;
+				let mut heartbeat_by = |relayer: Option<u64>| {
+					match relayer {
+						Some(relayer) => mq::CurrentRelayer::<Test>::put(relayer),
+						None => mq::CurrentRelayer::<Test>::kill(),
+					}
+					elapse_seconds(10);
+					iterations += 100;
+					assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+						MiningReportEvent,
+					> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block: 0,
+							challenge_time: Timestamp::get(),
+							iterations,
+							platform: None,
+							restarted: false,
+						},
+					}));
+					mq::CurrentRelayer::<Test>::kill();
+				};
+				let tipped = || {
+					take_events()
+						.into_iter()
+						.filter(|e| matches!(e, TestEvent::PhalaMining(Event::RelayerTipped(..))))
+						.count()
+				};
+				// Disabled by default
+				let _ = take_events();
+				heartbeat_by(Some(99));
+				assert_eq!(tipped(), 0);
+				assert_ok!(PhalaMining::set_relayer_tip(
+					Origin::root(),
+					Some(RelayerTipConfig {
+						amount: 1 * DOLLARS,
+						max_per_block: 2,
+					})
+				));
+				// Not to the operator, the miner, or without a relayer
+				heartbeat_by(Some(1));
+				heartbeat_by(Some(2));
+				heartbeat_by(None);
+				assert_eq!(tipped(), 0);
+				// Paid to the third party, capped per block
+				heartbeat_by(Some(99));
+				assert_eq!(
+					take_events(),
+					vec![TestEvent::PhalaMining(Event::RelayerTipped(
+						99,
+						worker_pubkey(1),
+						1 * DOLLARS
+					))]
+				);
+				assert_eq!(Balances::free_balance(99), 1 * DOLLARS);
+				heartbeat_by(Some(99));
+				heartbeat_by(Some(99));
+				assert_eq!(tipped(), 1);
+				assert_eq!(Balances::free_balance(99), 2 * DOLLARS);
+				System::set_block_number(2);
+				heartbeat_by(Some(99));
+				assert_eq!(tipped(), 1);
+				assert_eq!(Balances::free_balance(99), 3 * DOLLARS);
+			});
+		}
+
+		#[test]
+		fn test_import_worker() {
+			use registry::ImportedRegistration;
+			use sp_core::{sr25519, Pair};
+			fn authority(seed: u8) -> sr25519::Pair {
+				sr25519::Pair::from_seed(&[seed; 32])
+			}
+			fn sign(
+				registration: &ImportedRegistration<u64>,
+				signers: &[u8],
+			) -> Vec<(sr25519::Public, sr25519::Signature)> {
+				let data = PhalaRegistry::import_signing_data(registration);
+				signers
+					.iter()
+					.map(|seed| (authority(*seed).public(), authority(*seed).sign(&data)))
+					.collect()
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				let mut registration = ImportedRegistration {
+					chain_id: 2,
+					pubkey: worker_pubkey(10),
+					ecdh_pubkey: crate::mock::ecdh_pubkey(1),
+					runtime_version: 0,
+					operator: Some(1),
+					confidence_level: 1,
+					initial_score: Some(100),
+					registered_at: 1000,
+				};
+				let import = |registration: &ImportedRegistration<u64>, signers: &[u8]| {
+					PhalaRegistry::import_worker(
+						Origin::signed(99),
+						registration.clone(),
+						sign(registration, signers),
+					)
+				};
+				assert_noop!(
+					import(&registration, &[1, 2]),
+					registry::Error::<Test>::ImportDisabled
+				);
+				assert_noop!(
+					PhalaRegistry::set_import_authorities(
+						Origin::root(),
+						vec![authority(1).public()],
+						2
+					),
+					registry::Error::<Test>::InvalidInput
+				);
+				assert_ok!(PhalaRegistry::set_import_authorities(
+					Origin::root(),
+					vec![
+						authority(1).public(),
+						authority(2).public(),
+						authority(3).public()
+					],
+					2
+				));
+				// Below the threshold, counting the distinct authorities only
+				assert_noop!(
+					import(&registration, &[1, 1, 4]),
+					registry::Error::<Test>::InsufficientImportSignatures
+				);
+				// A bad signature
+				let mut signatures = sign(&registration, &[1, 2]);
+				signatures[1].1 = authority(2).sign(b"something else");
+				assert_noop!(
+					PhalaRegistry::import_worker(
+						Origin::signed(99),
+						registration.clone(),
+						signatures
+					),
+					registry::Error::<Test>::InvalidSignature
+				);
+				// Never overrides the workers registered here
+				let mut local = registration.clone();
+				local.pubkey = worker_pubkey(1);
+				assert_noop!(
+					import(&local, &[1, 2]),
+					registry::Error::<Test>::DuplicateWorker
+				);
+				// The signatures are bounded by the authorities
+				assert_noop!(
+					import(&registration, &[1, 2, 3, 1]),
+					registry::Error::<Test>::TooManyImportSignatures
+				);
+				// Imported and announced to the gatekeepers with the imported score
+				let _ = take_messages();
+				assert_ok!(import(&registration, &[1, 3]));
+				assert!(take_events().contains(&TestEvent::PhalaRegistry(
+					registry::Event::WorkerImported(2, worker_pubkey(10))
+				)));
+				let worker_events: Vec<_> = take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(e)) if e.pubkey == worker_pubkey(10) => {
+							Some(e.event)
+						}
+						_ => None,
+					})
+					.collect();
+				assert!(matches!(
+					worker_events.as_slice(),
+					[
+						WorkerEvent::Registered(phala_types::messaging::WorkerInfo {
+							confidence_level: 1
+						}),
+						WorkerEvent::BenchScore(100),
+					]
+				));
+				// Replays are rejected, but a newer registration updates the entry
+				assert_noop!(
+					import(&registration, &[1, 2]),
+					registry::Error::<Test>::StaleImport
+				);
+				registration.registered_at = 2000;
+				registration.confidence_level = 2;
+				assert_ok!(import(&registration, &[2, 3]));
+				let worker = registry::Workers::<Test>::get(worker_pubkey(10)).unwrap();
+				assert_eq!(worker.confidence_level, 2);
+				// Eligible for binding, and never replaced once bound
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(10)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				registration.registered_at = 3000;
+				registration.operator = Some(2);
+				assert_noop!(
+					import(&registration, &[1, 2]),
+					registry::Error::<Test>::WorkerBound
+				);
+				// The same worker from another chain is a different worker
+				registration.chain_id = 3;
+				assert_noop!(
+					import(&registration, &[1, 2]),
+					registry::Error::<Test>::DuplicateWorker
+				);
+			});
+		}
+
+		#[test]
+		fn test_status_update_pagination() {
+			use crate::mock::BlockNumber;
+			use frame_support::traits::OnInitialize;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn gk_update(payload: MiningInfoUpdateEvent<BlockNumber>) {
+				// Over the wire
+				let payload = Decode::decode(&mut &payload.encode()[..]).unwrap();
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload,
+				}));
 			}
+			fn state(miner: u64) -> MinerState {
+				PhalaMining::miners(miner).unwrap().state
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(6);
+				for i in 1..=6 {
+					assert_ok!(PhalaMining::bind(i as u64, worker_pubkey(i)));
+					assert_ok!(PhalaMining::start_mining(i as u64, 1000 * DOLLARS));
+				}
+				take_events();
+				// The gatekeeper splits the 6 offline workers into 2 pages
+				let mut event = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				event.protocol_version = GK_PROTOCOL_VERSION;
+				event.offline = (1..=6).map(worker_pubkey).collect();
+				let pages = event.paginate(4);
+				assert_eq!(pages.len(), 2);
+				assert!(pages[0].continues);
+				assert!(!pages[1].continues);
+				let mut pages = pages.into_iter();
+				gk_update(pages.next().unwrap());
+				assert_eq!(PhalaMining::status_continuation(), Some((1, 1)));
+				gk_update(pages.next().unwrap());
+				assert_eq!(PhalaMining::status_continuation(), None);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::StatusUpdatesPaginated(1, 2))));
+				for i in 1..=6 {
+					assert_eq!(state(i), MinerState::MiningUnresponsive);
+				}
+				// An oversized message is applied across the blocks
+				let mut event = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				event.recovered_to_online = (1..=6).map(worker_pubkey).collect();
+				gk_update(event);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::StatusUpdatesDeferred(2, 2))));
+				assert_eq!(state(4), MinerState::MiningIdle);
+				assert_eq!(state(5), MinerState::MiningUnresponsive);
+				// Queued behind the backlog
+				let mut event = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				event.offline = vec![worker_pubkey(6)];
+				gk_update(event);
+				assert_eq!(PhalaMining::deferred_status_updates().len(), 3);
+				System::set_block_number(2);
+				PhalaMining::on_initialize(2);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::DeferredStatusUpdatesApplied(3, 0)
+				)));
+				assert_eq!(state(5), MinerState::MiningIdle);
+				assert_eq!(state(6), MinerState::MiningUnresponsive);
+				// The final page never arrives
+				let mut event = MiningInfoUpdateEvent::<BlockNumber>::new(2, 0);
+				event.offline = vec![worker_pubkey(1)];
+				event.continues = true;
+				gk_update(event);
+				gk_update(MiningInfoUpdateEvent::<BlockNumber>::new(3, 0));
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::StatusPagesIncomplete(2, 1))));
+				assert_eq!(PhalaMining::status_continuation(), None);
+			});
 		}
 
-		/// Gets kappa in FixedPoint
-		fn _kappa(&self) -> FixedPoint {
-			FixedPoint::from_bits(self.params.kappa)
+		#[test]
+		fn test_reputation_tiers() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{
+				DecodedMessage, MessageOrigin, MiningReportEvent, SystemEvent, Topic,
+				WorkerEventWithKey,
+			};
+			fn gk_update(offline: bool) {
+				let mut event = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				if offline {
+					event.offline.push(worker_pubkey(1));
+				} else {
+					event.recovered_to_online.push(worker_pubkey(1));
+				}
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: event,
+				}));
+			}
+			fn slash_multipliers() -> Vec<u32> {
+				take_messages()
+					.iter()
+					.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+						Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+							event: WorkerEvent::TierChanged { slash_multiplier },
+							..
+						})) => Some(slash_multiplier),
+						_ => None,
+					})
+					.collect()
+			}
+			let rule = |min_liveness, slash_multiplier| TierRule {
+				min_liveness,
+				min_sessions: 1,
+				slash_multiplier: Permill::from_percent(slash_multiplier),
+				target_interval: 100,
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let mut iterations = 0;
+				let mut heartbeat = || {
+					elapse_seconds(10);
+					iterations += 100;
+					assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+						MiningReportEvent,
+					> {
+						sender: MessageOrigin::Worker(worker_pubkey(1)),
+						destination: Topic::new(*b"phala/mining/report"),
+						payload: MiningReportEvent::Heartbeat {
+							session_id: 0,
+							challenge_block: 0,
+							challenge_time: Timestamp::get(),
+							iterations,
+							platform: None,
+							restarted: false,
+						},
+					}));
+				};
+				assert_noop!(
+					PhalaMining::set_reputation_tiers(
+						Origin::root(),
+						Some(ReputationTierConfig {
+							silver: rule(4, 50),
+							gold: rule(2, 20),
+						})
+					),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_reputation_tiers(
+					Origin::root(),
+					Some(ReputationTierConfig {
+						silver: rule(2, 50),
+						gold: rule(4, 20),
+					})
+				));
+				let _ = take_events();
+				let _ = take_messages();
+				// Promoted by the heartbeats
+				heartbeat();
+				heartbeat();
+				assert_eq!(
+					PhalaMining::reputations(1),
+					MinerReputation {
+						tier: MinerTier::Silver,
+						liveness: 2,
+					}
+				);
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::MinerTierChanged(
+						1,
+						MinerTier::Bronze,
+						MinerTier::Silver
+					)))
+				);
+				assert_eq!(slash_multipliers(), vec![500_000]);
+				heartbeat();
+				heartbeat();
+				assert_eq!(PhalaMining::reputations(1).tier, MinerTier::Gold);
+				assert_eq!(PhalaMining::slash_multiplier(&1), Permill::from_percent(20));
+				assert_eq!(slash_multipliers(), vec![200_000]);
+				// Demoted by going offline, but targeted once in the interval of the tier
+				gk_update(true);
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::MinerTierChanged(
+						1,
+						MinerTier::Gold,
+						MinerTier::Silver
+					)))
+				);
+				gk_update(false);
+				assert_eq!(PhalaMining::heartbeat_targets(), vec![worker_pubkey(1)]);
+				HeartbeatTargets::<Test>::kill();
+				heartbeat();
+				heartbeat();
+				gk_update(true);
+				gk_update(false);
+				assert_eq!(PhalaMining::heartbeat_targets(), vec![]);
+				// Bronze is always targeted
+				gk_update(true);
+				assert_eq!(PhalaMining::reputations(1).tier, MinerTier::Bronze);
+				assert_eq!(slash_multipliers().last(), Some(&1_000_000));
+				gk_update(false);
+				assert_eq!(PhalaMining::heartbeat_targets(), vec![worker_pubkey(1)]);
+			});
 		}
-	}
-
-	#[pallet::genesis_config]
-	pub struct GenesisConfig {
-		pub cool_down_period_sec: u32,
-		pub tokenomic_parameters: TokenomicParams,
-	}
-
-	#[cfg(feature = "std")]
-	impl Default for GenesisConfig {
-		/// Default tokenoic parameters for Phala
-		fn default() -> Self {
-			use fixed_macro::types::U64F64 as fp;
-			let pha_rate = fp!(1);
-			let rho = fp!(1.00000099985); // hourly: 1.00020,  1.0002 ** (1/300)
-			let slash_rate = fp!(0.001) / 300; // hourly rate: 0.001, convert to per-block rate
-			let budget_per_sec = fp!(720000) / 24 / 3600;
-			let v_max = fp!(30000);
-			let cost_k = fp!(0.0415625) / 3600 / 24 / 365; // annual 0.0415625, convert to per sec
-			let cost_b = fp!(88.59375) / 3600 / 24 / 365; // annual 88.59375, convert to per sec
-			let heartbeat_window = 10; // 10 blocks
-			let rig_k = fp!(0.3);
-			let rig_b = fp!(0);
-			let re = fp!(1.5);
-			let k = fp!(100);
-			let kappa = fp!(1);
 
-			Self {
-				cool_down_period_sec: 604800, // 7 days
-				tokenomic_parameters: TokenomicParams {
-					pha_rate: pha_rate.to_bits(),
-					rho: rho.to_bits(),
-					budget_per_sec: budget_per_sec.to_bits(),
-					v_max: v_max.to_bits(),
-					cost_k: cost_k.to_bits(),
-					cost_b: cost_b.to_bits(),
-					slash_rate: slash_rate.to_bits(),
-					heartbeat_window: 10,
-					rig_k: rig_k.to_bits(),
-					rig_b: rig_b.to_bits(),
-					re: re.to_bits(),
-					k: k.to_bits(),
-					kappa: kappa.to_bits(),
-				},
-			}
+		#[test]
+		fn test_miner_state_counters() {
+			use crate::mock::{take_heartbeat_challenges, BlockNumber};
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				for i in 1..=2 {
+					assert_ok!(PhalaMining::bind(i, worker_pubkey(i as u8)));
+					assert_ok!(PhalaMining::start_mining(i, 1000 * DOLLARS));
+				}
+				assert_eq!(PhalaMining::idle_miners(), 2);
+				// Worker 1 goes offline
+				let mut event = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				event.offline.push(worker_pubkey(1));
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: event,
+				}));
+				assert_eq!(PhalaMining::idle_miners(), 1);
+				assert_eq!(PhalaMining::unresponsive_miners(), 1);
+				assert_eq!(PhalaMining::online_miners(), 2);
+				assert_eq!(PhalaMining::responsive_miners(), 1);
+				// Only the responsive miners are sampled
+				let _ = take_messages();
+				PhalaMining::heartbeat_challenge();
+				assert_eq!(
+					take_heartbeat_challenges()[0].online_target,
+					pow_target(PhalaMining::heartbeat_count(), 1, 12)
+				);
+				// Out of the online states
+				assert_ok!(PhalaMining::stop_mining(2));
+				assert_eq!(PhalaMining::idle_miners(), 0);
+				assert_eq!(PhalaMining::unresponsive_miners(), 1);
+				assert_eq!(PhalaMining::do_try_state(), Ok(()));
+				// Rebuilt by the recount
+				UnresponsiveMiners::<Test>::kill();
+				assert!(PhalaMining::do_try_state().is_err());
+				PhalaMining::recount_miner_states();
+				assert_eq!(PhalaMining::unresponsive_miners(), 1);
+				assert_eq!(PhalaMining::do_try_state(), Ok(()));
+			});
 		}
-	}
 
-	#[pallet::genesis_build]
-	impl<T: Config> GenesisBuild<T> for GenesisConfig {
-		fn build(&self) {
-			CoolDownPeriod::<T>::put(self.cool_down_period_sec as u64);
-			TokenomicParameters::<T>::put(self.tokenomic_parameters.clone());
-			Pallet::<T>::queue_message(GatekeeperEvent::TokenomicParametersChanged(
-				self.tokenomic_parameters.clone(),
-			));
+		#[test]
+		fn test_reward_dust() {
+			use crate::mock::BlockNumber;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let ve = PhalaMining::miners(1).unwrap().ve.to_bits();
+				// 2.5 units of the balance, rounded down to 2
+				let payout = FixedPoint::from_num(25) / FixedPoint::from_num(10_000_000_000_000u64);
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: 1,
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v: ve,
+							payout: payout.to_bits(),
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+				assert_eq!(PhalaMining::miners(1).unwrap().stats.total_reward, 2);
+				let dust = FixedPoint::from_bits(PhalaMining::reward_dust(1));
+				assert!(dust > 0);
+				assert_eq!(
+					balance_convert::floor_with_dust::<u128>(&(payout - dust)),
+					(2, FixedPoint::from_num(0))
+				);
+				// The whole units are flushed once they reach the threshold
+				let unit = FixedPoint::from_num(1) / FixedPoint::from_num(1_000_000_000_000u64);
+				PhalaMining::accrue_dust(&1, unit * FixedPoint::from_num(DUST_FLUSH_THRESHOLD - 2));
+				assert_eq!(PhalaMining::flushed_dust(), 0);
+				take_events();
+				PhalaMining::accrue_dust(&1, unit * FixedPoint::from_num(2));
+				assert_eq!(PhalaMining::flushed_dust(), DUST_FLUSH_THRESHOLD);
+				assert!(FixedPoint::from_bits(PhalaMining::reward_dust(1)) < unit);
+				assert_eq!(
+					take_events().as_slice(),
+					[TestEvent::PhalaMining(Event::RewardDustFlushed(
+						1,
+						DUST_FLUSH_THRESHOLD
+					))]
+				);
+			});
 		}
-	}
 
-	fn pow_target(num_tx: u32, num_workers: u32, secs_per_block: u32) -> U256 {
-		use fixed::types::U32F32;
-		if num_workers == 0 {
-			return U256::zero();
+		#[test]
+		fn test_reclaim_conserves_stake() {
+			use crate::mock::{elapse_cool_down, gk_settle};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				// A third of Ve leaves a fraction of the stake to round
+				let ve = PhalaMining::miners(1).unwrap().ve.to_fixed();
+				gk_settle(&[1], (ve / FixedPoint::from_num(3)).to_bits(), 0);
+				assert_ok!(PhalaMining::stop_mining(1));
+				elapse_cool_down();
+				take_events();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				let (mut withdrawn, mut reclaimed) = (None, None);
+				for event in take_events() {
+					match event {
+						TestEvent::PhalaMining(Event::MinerWithdrew(1, amount)) => {
+							withdrawn = Some(amount)
+						}
+						TestEvent::PhalaMining(Event::MinerReclaimed(1, orig_stake, slashed)) => {
+							reclaimed = Some((orig_stake, slashed))
+						}
+						_ => {}
+					}
+				}
+				let (orig_stake, slashed) = reclaimed.expect("Reclaimed");
+				assert_eq!(orig_stake, 1000 * DOLLARS);
+				assert!(slashed > 0 && slashed < orig_stake);
+				assert_eq!(withdrawn.unwrap() + slashed, orig_stake);
+				// The rounding dust stays in the slash and isn't accrued again
+				assert_eq!(PhalaMining::reward_dust(1), 0);
+				assert_eq!(PhalaMining::flushed_dust(), 0);
+				assert_eq!(PhalaMining::miners(1).unwrap().stats.total_slashed, slashed);
+			});
 		}
-		let num_workers = U32F32::from_num(num_workers);
-		let num_tx = U32F32::from_num(num_tx);
-		// Limit tx per block for a single miner
-		//     t <= max_tx_per_hour * N/T (max_tx_per_hour = 2)
-		let max_tx = num_workers * U32F32::from_num(2) / U32F32::from_num(3600 / secs_per_block);
-		let target_tx = cmp::min(num_tx, max_tx);
-		// Convert to U256 target
-		//     target = MAX * tx / num_workers
-		let frac: u32 = (target_tx / num_workers)
-			.checked_shl(24)
-			.expect("No overflow; qed.")
-			.to_num();
-		(U256::MAX >> 24) * frac
-	}
 
-	impl<T: Config> MessageOriginInfo for Pallet<T> {
-		type Config = T;
-	}
+		#[test]
+		fn test_why_cannot_start_or_reclaim() {
+			use crate::mock::elapse_cool_down;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_eq!(
+					PhalaMining::why_cannot_start(&1, 1000 * DOLLARS),
+					Some(StartBlocker::MinerNotFound)
+				);
+				assert_eq!(
+					PhalaMining::why_cannot_reclaim(&1),
+					Some(ReclaimBlocker::MinerNotFound)
+				);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				let min_stake = PhalaMining::tokenomic().minimal_stake(1);
+				assert!(min_stake > 0);
+				assert_eq!(
+					PhalaMining::why_cannot_start(&1, min_stake - 1),
+					Some(StartBlocker::InsufficientStake(1))
+				);
+				assert_eq!(PhalaMining::why_cannot_start(&1, min_stake), None);
+				assert_eq!(
+					PhalaMining::why_cannot_reclaim(&1),
+					Some(ReclaimBlocker::NotCoolingDown(MinerState::Ready))
+				);
+				assert_ok!(PhalaMining::start_mining(1, min_stake));
+				assert_eq!(
+					PhalaMining::why_cannot_start(&1, min_stake),
+					Some(StartBlocker::NotReady(MinerState::MiningIdle))
+				);
+				assert_ok!(PhalaMining::stop_mining(1));
+				assert_eq!(
+					PhalaMining::why_cannot_reclaim(&1),
+					Some(ReclaimBlocker::CoolDownRemaining(
+						PhalaMining::cool_down_period()
+					))
+				);
+				elapse_cool_down();
+				assert_eq!(PhalaMining::why_cannot_reclaim(&1), None);
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(PhalaMining::why_cannot_start(&1, min_stake), None);
+			});
+		}
 
-	#[cfg(test)]
-	mod test {
-		use super::*;
-		use crate::mock::{
-			elapse_seconds, new_test_ext, set_block_1, setup_workers, take_events, take_messages,
-			worker_pubkey, Event as TestEvent, Origin, Test, DOLLARS,
-		};
-		// Pallets
-		use crate::mock::{PhalaMining, PhalaRegistry, System};
+		#[test]
+		fn test_instance_topic() {
+			new_test_ext().execute_with(|| {
+				// The default instance keeps the original topics
+				assert_eq!(
+					PhalaMining::instance_topic(b"^phala/mining/update"),
+					b"^phala/mining/update".to_vec()
+				);
+				assert_eq!(
+					PhalaMining::instance_topic(b"phala/mining/report"),
+					b"phala/mining/report".to_vec()
+				);
+				// So are the produced topics, the stake lock and the bindings trie
+				assert!(PhalaMining::topics()
+					.iter()
+					.any(|t| t.topic == b"phala/system/event".to_vec()
+						&& t.direction == TopicDirection::Produced));
+				assert_eq!(PhalaMining::solo_staking_id(), SOLO_STAKING_ID);
+				assert_eq!(
+					PhalaMining::bindings_child_info(),
+					ChildInfo::new_default(BINDINGS_CHILD_KEY)
+				);
+			});
+		}
 
-		use fixed_macro::types::U64F64 as fp;
-		use frame_support::{assert_noop, assert_ok};
+		#[test]
+		fn test_operator_bond() {
+			use crate::mock::{elapse_cool_down, Balances, BlockNumber};
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn settle(v: u128) {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: System::block_number(),
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v,
+							payout: 0,
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+			}
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				// Only the operator can bond
+				assert_noop!(
+					PhalaMining::operator_bond(Origin::signed(2), worker_pubkey(1), 100 * DOLLARS),
+					Error::<Test>::BadSender
+				);
+				assert_ok!(PhalaMining::operator_bond(
+					Origin::signed(1),
+					worker_pubkey(1),
+					100 * DOLLARS
+				));
+				let bond_account = PhalaMining::bond_account_id();
+				assert_eq!(Balances::free_balance(&bond_account), 100 * DOLLARS);
+				// The unbonding amount is locked for the unbonding period
+				assert_ok!(PhalaMining::set_operator_unbonding_period(
+					Origin::root(),
+					100
+				));
+				assert_ok!(PhalaMining::operator_unbond(
+					Origin::signed(1),
+					worker_pubkey(1),
+					40 * DOLLARS
+				));
+				assert_noop!(
+					PhalaMining::withdraw_operator_bond(Origin::signed(1), worker_pubkey(1)),
+					Error::<Test>::OperatorBondLocked
+				);
+				elapse_seconds(100);
+				let free = Balances::free_balance(&1);
+				assert_ok!(PhalaMining::withdraw_operator_bond(
+					Origin::signed(1),
+					worker_pubkey(1)
+				));
+				assert_eq!(Balances::free_balance(&1), free + 40 * DOLLARS);
+				assert_eq!(
+					PhalaMining::operator_bonds(worker_pubkey(1)).map(|b| b.bonded),
+					Some(60 * DOLLARS)
+				);
+				// The slash at the reclaim is charged to the bond first
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let ve = PhalaMining::miners(1).unwrap().ve.to_bits();
+				assert_ok!(PhalaMining::stop_mining(1));
+				settle(ve / 2);
+				// Can't escape the slash while mining or cooling down
+				assert_noop!(
+					PhalaMining::operator_unbond(Origin::signed(1), worker_pubkey(1), 60 * DOLLARS),
+					Error::<Test>::OperatorBondInUse
+				);
+				// Nor by unbinding the miner during the cool down
+				assert_ok!(PhalaMining::unbind(Origin::signed(1), 1));
+				assert_noop!(
+					PhalaMining::operator_unbond(Origin::signed(1), worker_pubkey(1), 60 * DOLLARS),
+					Error::<Test>::OperatorBondInUse
+				);
+				elapse_cool_down();
+				let pool = Balances::free_balance(&PhalaMining::account_id());
+				take_events();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(PhalaMining::operator_bonds(worker_pubkey(1)), None);
+				assert_eq!(Balances::free_balance(&bond_account), 0);
+				assert_eq!(
+					Balances::free_balance(&PhalaMining::account_id()),
+					pool + 60 * DOLLARS
+				);
+				let rate = FixedPoint::from_bits(ve / 2) / FixedPoint::from_bits(ve);
+				let returned: u128 =
+					FixedPointConvert::from_fixed(&(rate * (1000 * DOLLARS).to_fixed()));
+				let events = take_events();
+				assert!(
+					events.contains(&TestEvent::PhalaMining(Event::OperatorBondSlashed(
+						worker_pubkey(1),
+						1,
+						60 * DOLLARS
+					)))
+				);
+				assert!(
+					events.contains(&TestEvent::PhalaMining(Event::MinerReclaimed(
+						1,
+						1000 * DOLLARS,
+						1000 * DOLLARS - returned - 60 * DOLLARS
+					)))
+				);
+			});
+		}
 
 		#[test]
-		fn test_mining_wallet_setup() {
+		fn test_mining_digest() {
+			use crate::mock::BlockNumber;
+			use frame_support::traits::OnFinalize;
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
 			new_test_ext().execute_with(|| {
-				assert!(System::account_exists(&PhalaMining::account_id()));
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let ve = PhalaMining::miners(1).unwrap().ve.to_bits();
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+					MiningInfoUpdateEvent<BlockNumber>,
+				> {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: MiningInfoUpdateEvent::<BlockNumber> {
+						block_number: 1,
+						timestamp_ms: Timestamp::get(),
+						offline: vec![],
+						recovered_to_online: vec![],
+						settle: vec![SettleInfo {
+							pubkey: worker_pubkey(1),
+							v: ve / 2,
+							payout: fp!(2).to_bits(),
+						}],
+						protocol_version: 0,
+						settle_bindings: vec![],
+						continues: false,
+					},
+				}));
+				<PhalaMining as OnFinalize<u64>>::on_finalize(1);
+				let digest = PhalaMining::mining_digest();
+				assert_eq!(digest.block, 1);
+				assert_eq!(digest.settled_at, 1);
+				assert_eq!(digest.settled_miners, 1);
+				assert_eq!(digest.settled_payout, 2 * DOLLARS);
+				assert!(digest.settled_v_slash > 0);
+				assert_eq!(digest.online_miners, 1);
+				assert_eq!(digest.idle_miners, 1);
+				// The settlement totals are kept until the next settlement
+				System::set_block_number(2);
+				<PhalaMining as OnFinalize<u64>>::on_finalize(2);
+				let digest = PhalaMining::mining_digest();
+				assert_eq!(digest.block, 2);
+				assert_eq!(digest.settled_at, 1);
+				assert_eq!(digest.settled_miners, 1);
 			});
 		}
 
 		#[test]
-		fn test_pow_target() {
-			// No target
-			assert_eq!(pow_target(20, 0, 12), U256::zero());
-			// Capped target (py3: ``)
-			assert_eq!(
-				pow_target(20, 20, 12),
-				U256::from_dec_str(
-					"771946525395830978497002573683960742805751636319313395421818009383503547160"
-				)
-				.unwrap()
-			);
-			// Not capped target (py3: `int(((1 << 256) - 1) * 20 / 200_000)`)
-			assert_eq!(
-				pow_target(20, 200_000, 12),
-				U256::from_dec_str(
-					"11574228623567775471528085581038571683760509746329738253007553123311417715"
-				)
-				.unwrap()
-			);
+		fn test_payout_cap() {
+			use crate::mock::{elapse_cool_down, gk_settle};
+			let settle = |payout: FixedPoint| {
+				gk_settle(&[1], PhalaMining::miners(1).unwrap().v.to_bits(), payout.to_bits())
+			};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let ve = PhalaMining::miners(1).unwrap().ve.to_fixed();
+				// Only Ve can be paid right after the session started
+				take_events();
+				settle(ve * 2);
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::PayoutCapped(
+						1,
+						(ve * 2).to_bits(),
+						ve.to_bits()
+					)))
+				);
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(miner.session_payout, ve.to_bits());
+				assert_eq!(miner.stats.total_reward, FixedPointConvert::from_fixed(&ve));
+				// The cap grows with the budget share over time
+				elapse_seconds(3600);
+				let miner = PhalaMining::miners(1).unwrap();
+				let room = PhalaMining::payout_cap(&miner, PhalaMining::now_sec()) - ve;
+				assert!(room > 0);
+				settle(room);
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().session_payout,
+					(ve + room).to_bits()
+				);
+				settle(fp!(1));
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().session_payout,
+					(ve + room).to_bits()
+				);
+				// Reset in a new session
+				assert_ok!(PhalaMining::stop_mining(1));
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				let miner = PhalaMining::miners(1).unwrap();
+				assert_eq!(miner.session_payout, 0);
+				assert_eq!(miner.session_start, PhalaMining::now_sec());
+			});
 		}
 
 		#[test]
-		fn test_heartbeat_challenge() {
+		fn test_stake_change_events() {
+			use crate::mock::elapse_cool_down;
 			new_test_ext().execute_with(|| {
-				use phala_types::messaging::{SystemEvent, Topic};
+				set_block_1();
+				setup_workers(2);
+				// Kept at the indices of the former placeholders
+				let index = |event: Event<Test>| event.encode()[0];
+				assert_eq!(index(Event::MinerStaked(1, 0)), 9);
+				assert_eq!(index(Event::MinerWithdrew(1, 0)), 10);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				take_events();
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				let events = take_events();
+				assert!(events.contains(&TestEvent::PhalaMining(Event::MinerStaked(
+					1,
+					1000 * DOLLARS
+				))));
+				assert!(events.contains(&TestEvent::PhalaMining(Event::MinerStaked(
+					2,
+					1000 * DOLLARS
+				))));
+				// Miner 2 loses half of its V
+				Miners::<Test>::mutate(2, |info| {
+					let info = info.as_mut().unwrap();
+					info.set_v((info.ve.to_fixed() / 2).into());
+				});
+				assert_ok!(PhalaMining::stop_mining(1));
+				assert_ok!(PhalaMining::stop_mining(2));
+				elapse_cool_down();
+				take_events();
+				// Fully released without a slash
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				let events = take_events();
+				assert!(
+					events.contains(&TestEvent::PhalaMining(Event::MinerWithdrew(
+						1,
+						1000 * DOLLARS
+					)))
+				);
+				assert!(!events
+					.iter()
+					.any(|e| matches!(e, TestEvent::PhalaMining(Event::MinerStakeSlashed(..)))));
+				// Released and slashed
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 2));
+				let events = take_events();
+				let slashed = PhalaMining::miners(2).unwrap().stats.total_slashed;
+				assert!(slashed > 0);
+				assert!(
+					events.contains(&TestEvent::PhalaMining(Event::MinerWithdrew(
+						2,
+						1000 * DOLLARS - slashed
+					)))
+				);
+				assert!(
+					events.contains(&TestEvent::PhalaMining(Event::MinerStakeSlashed(
+						2, slashed
+					)))
+				);
+			});
+		}
 
+		#[test]
+		fn test_topics() {
+			use phala_types::messaging::BindTopic;
+			fn find(topic: &[u8]) -> TopicInfo {
+				PhalaMining::topics()
+					.into_iter()
+					.find(|t| t.topic == topic)
+					.expect("Topic must be listed")
+			}
+			new_test_ext().execute_with(|| {
 				set_block_1();
-				OnlineMiners::<Test>::put(20);
-				Pallet::<Test>::heartbeat_challenge();
-				// Extract messages
-				let msgs = take_messages();
-				let message = match msgs.as_slice() {
-					[m] => m,
-					_ => panic!("Wrong message events"),
-				};
-				// Check the event target
-				assert_eq!(message.destination, Topic::new("phala/system/event"));
-				// Check the oubound message parameters
-				let target = match message.decode_payload::<SystemEvent>() {
-					Some(SystemEvent::HeartbeatChallenge(r)) => r.online_target,
-					_ => panic!("Wrong outbound message"),
-				};
-				assert_eq!(target, U256::from_dec_str(
-					"771946525395830978497002573683960742805751636319313395421818009383503547160"
-				).unwrap());
+				let update = find(b"^phala/mining/update");
+				assert_eq!(update.direction, TopicDirection::Consumed);
+				assert_eq!(
+					update.type_name,
+					b"MiningInfoUpdateEvent<BlockNumber>".to_vec()
+				);
+				assert_eq!(update.version, GK_PROTOCOL_VERSION);
+				assert_eq!(find(SystemEvent::TOPIC).direction, TopicDirection::Produced);
+				assert_eq!(
+					find(MiningReportEvent::TOPIC).direction,
+					TopicDirection::Consumed
+				);
+				// No topic is listed twice
+				let topics = PhalaMining::topics();
+				for (i, t) in topics.iter().enumerate() {
+					assert!(!topics[i + 1..].iter().any(|other| other.topic == t.topic));
+				}
+				// Follows the accepted protocol versions
+				assert_ok!(PhalaMining::set_supported_gk_protocol(Origin::root(), 0, 0));
+				assert_eq!(find(b"^phala/mining/update").version, 0);
 			});
 		}
 
 		#[test]
-		fn test_bind_unbind() {
+		fn test_attestation_revoked() {
+			use crate::mock::elapse_cool_down;
 			new_test_ext().execute_with(|| {
 				set_block_1();
-				setup_workers(2);
-				// Bind & unbind normally
-				let _ = take_events();
+				setup_workers(3);
 				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
-				assert_ok!(PhalaMining::unbind_miner(&1, false));
+				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
+				assert_ok!(PhalaMining::bind(3, worker_pubkey(3)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::start_mining(2, 1000 * DOLLARS));
+				assert_noop!(
+					PhalaRegistry::revoke_attestation(Origin::signed(1), worker_pubkey(1)),
+					sp_runtime::DispatchError::BadOrigin
+				);
+				// The session is stopped and the worker is unbound at once
+				take_events();
+				assert_ok!(PhalaRegistry::revoke_attestation(
+					Origin::root(),
+					worker_pubkey(1)
+				));
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::MinerAttestationRevoked(1, worker_pubkey(1), true)
+				)));
 				assert_eq!(
-					take_events().as_slice(),
-					[
-						TestEvent::PhalaMining(Event::MinerBound(1, worker_pubkey(1))),
-						TestEvent::PhalaMining(Event::MinerUnbound(1, worker_pubkey(1)))
-					]
+					PhalaMining::miners(1).unwrap().state,
+					MinerState::MiningCoolingDown
 				);
-				// Checks edge cases
+				assert_eq!(MinerBindings::<Test>::get(1), None);
+				assert!(PhalaMining::is_session_revoked(1));
 				assert_noop!(
-					PhalaMining::bind(1, worker_pubkey(100)),
-					Error::<Test>::WorkerNotRegistered,
+					PhalaRegistry::revoke_attestation(Origin::root(), worker_pubkey(1)),
+					registry::Error::<Test>::AttestationAlreadyRevoked
 				);
+				// Not bound again until attested again
 				assert_noop!(
-					PhalaMining::unbind_miner(&1, false),
-					Error::<Test>::MinerNotBound,
+					PhalaMining::bind(1, worker_pubkey(1)),
+					Error::<Test>::AttestationRevoked
 				);
-				// No double binding
-				assert_ok!(PhalaMining::bind(2, worker_pubkey(2)));
-				assert_noop!(
-					PhalaMining::bind(2, worker_pubkey(1)),
-					Error::<Test>::DuplicateBoundMiner
+				// An idle miner is only unbound
+				assert_ok!(PhalaRegistry::revoke_attestation(
+					Origin::root(),
+					worker_pubkey(3)
+				));
+				assert_eq!(MinerBindings::<Test>::get(3), None);
+				assert!(!PhalaMining::is_session_revoked(3));
+				// The whole stake is slashed by default
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(
+					PhalaMining::miners(1).unwrap().stats.total_slashed,
+					1000 * DOLLARS
 				);
-				assert_noop!(
-					PhalaMining::bind(1, worker_pubkey(2)),
-					Error::<Test>::DuplicateBoundMiner
+				assert!(!PhalaMining::is_session_revoked(1));
+				// Or by the configured ratio
+				assert_ok!(PhalaMining::set_revocation_slash_ratio(
+					Origin::root(),
+					Some(Permill::from_percent(50))
+				));
+				assert_ok!(PhalaRegistry::revoke_attestation(
+					Origin::root(),
+					worker_pubkey(2)
+				));
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 2));
+				assert_eq!(
+					PhalaMining::miners(2).unwrap().stats.total_slashed,
+					500 * DOLLARS
 				);
-				// Force unbind should be tested via StakePool
 			});
 		}
 
 		#[test]
-		#[should_panic]
-		fn test_stakepool_callback_panic() {
+		fn test_shuffle_settlements() {
+			use phala_types::messaging::SettleInfo;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				let settle = |worker: u8, v: u128| SettleInfo {
+					pubkey: worker_pubkey(worker),
+					v,
+					payout: 0,
+				};
+				let items: Vec<_> = (1..=20).map(|i| settle(i, 0)).collect();
+				let shuffled = PhalaMining::shuffle_settlements(items.clone());
+				// A permutation, deterministic in the block
+				assert_ne!(shuffled, items);
+				assert_eq!(PhalaMining::shuffle_settlements(items.clone()), shuffled);
+				let mut sorted = shuffled.clone();
+				sorted.sort_by_key(|s| s.pubkey.clone());
+				assert_eq!(sorted, items);
+				// Varies by block
+				System::set_block_number(2);
+				assert_ne!(PhalaMining::shuffle_settlements(items.clone()), shuffled);
+				// The settlements of a worker stay in order
+				let mut items = items;
+				items.extend((1..=20).map(|i| settle(i, 1)));
+				let shuffled = PhalaMining::shuffle_settlements(items);
+				for i in 1..=20 {
+					let versions: Vec<_> = shuffled
+						.iter()
+						.filter(|s| s.pubkey == worker_pubkey(i))
+						.map(|s| s.v)
+						.collect();
+					assert_eq!(versions, vec![0, 1]);
+				}
+			});
+		}
+
+		#[test]
+		fn test_uptime_streak_bonus() {
+			use crate::mock::{gk_settle, BlockNumber};
+			use phala_types::messaging::{DecodedMessage, MessageOrigin, Topic};
+			fn send(update: MiningInfoUpdateEvent<BlockNumber>) {
+				assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage {
+					sender: MessageOrigin::Gatekeeper,
+					destination: Topic::new(*b"^phala/mining/update"),
+					payload: update,
+				}));
+			}
+			let settle = |payout: FixedPoint| {
+				gk_settle(&[1], PhalaMining::miners(1).unwrap().v.to_bits(), payout.to_bits())
+			};
+			let tier = |min_streak, bonus| StreakBonusTier {
+				min_streak,
+				bonus: Permill::from_percent(bonus),
+			};
 			new_test_ext().execute_with(|| {
 				set_block_1();
 				setup_workers(1);
 				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
 				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
-				// Force unbind without StakePool registration will cause a panic
-				let _ = PhalaMining::unbind(Origin::signed(1), 1);
+				assert_noop!(
+					PhalaMining::set_uptime_streak_bonuses(
+						Origin::root(),
+						Some(vec![tier(3, 10), tier(2, 20)])
+					),
+					Error::<Test>::InvalidParameter
+				);
+				assert_noop!(
+					PhalaMining::set_uptime_streak_bonuses(Origin::root(), Some(vec![tier(2, 50)])),
+					Error::<Test>::InvalidParameter
+				);
+				assert_ok!(PhalaMining::set_uptime_streak_bonuses(
+					Origin::root(),
+					Some(vec![tier(2, 10), tier(3, 20)])
+				));
+				let reward = || PhalaMining::miners(1).unwrap().stats.total_reward;
+				// No bonus below the first tier
+				take_events();
+				settle(fp!(1));
+				assert_eq!(reward(), 1 * DOLLARS);
+				assert!(!take_events()
+					.iter()
+					.any(|e| matches!(e, TestEvent::PhalaMining(Event::UptimeStreakBonus(..)))));
+				// Raised by the highest tier reached
+				settle(fp!(1));
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::UptimeStreakBonus(
+						1,
+						2,
+						(fp!(1) / 10).to_bits()
+					)))
+				);
+				settle(fp!(1));
+				assert_eq!(PhalaMining::uptime_streak(1), 3);
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::UptimeStreakBonus(
+						1,
+						3,
+						(fp!(1) / 5).to_bits()
+					)))
+				);
+				assert!(reward() > 33 * DOLLARS / 10 - 10 && reward() <= 33 * DOLLARS / 10);
+				// Reset by going unresponsive
+				let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+				update.offline.push(worker_pubkey(1));
+				send(update);
+				assert_eq!(PhalaMining::uptime_streak(1), 0);
 			});
 		}
 
 		#[test]
-		fn test_tokenomic() {
+		fn test_topic_key_provisioning() {
+			use phala_types::messaging::Topic;
+			use registry::{RegistryEvent, TopicKey, TopicKeyAnchor};
+			fn provision(worker: u8, topic: &[u8], pubkey: &[u8]) -> DispatchResult {
+				PhalaRegistry::on_message_received(DecodedMessage {
+					sender: MessageOrigin::Worker(worker_pubkey(worker)),
+					destination: Topic::new(*b"^phala/registry/event"),
+					payload: RegistryEvent::TopicKey {
+						topic: topic.to_vec(),
+						pubkey: pubkey.to_vec(),
+					},
+				})
+			}
 			new_test_ext().execute_with(|| {
-				let params = TokenomicParameters::<Test>::get().unwrap();
-				let tokenomic = Tokenomic::<Test>::new(params);
-				fn pow(x: FixedPoint, n: u32) -> FixedPoint {
-					let mut i = n;
-					let mut x_pow2 = x;
-					let mut z = FixedPoint::from_num(1);
-					while i > 0 {
-						if i & 1 == 1 {
-							z *= x_pow2;
-						}
-						x_pow2 *= x_pow2;
-						i >>= 1;
-					}
-					z
-				}
-				// Vmax
-				assert_eq!(tokenomic.v_max(), fp!(30000));
-				// Minimal stake
-				assert_eq!(tokenomic.minimal_stake(1000), 3162_277660146355);
-				// Ve for different confidence level
+				set_block_1();
+				setup_workers(2);
+				// Only the registered workers can provision
+				assert_noop!(
+					provision(3, b"contract/1", &[1; 32]),
+					registry::Error::<Test>::WorkerNotFound
+				);
+				assert_noop!(
+					provision(1, b"contract/1", &[]),
+					registry::Error::<Test>::InvalidTopicKey
+				);
+				// The first provisioner anchors the topic
+				take_events();
+				assert_ok!(provision(1, b"contract/1", &[1; 32]));
 				assert_eq!(
-					tokenomic.ve(1000 * DOLLARS, 1000, 1),
-					fp!(1950.00000000000000001626)
+					TopicKey::<Test>::get(b"contract/1".to_vec()),
+					Some(vec![1; 32])
 				);
 				assert_eq!(
-					tokenomic.ve(1000 * DOLLARS, 1000, 2),
-					fp!(1950.00000000000000001626)
+					PhalaRegistry::topic_key_anchor(b"contract/1".to_vec()),
+					Some(TopicKeyAnchor {
+						provisioner: worker_pubkey(1),
+						epoch: 1,
+					})
+				);
+				assert!(take_events().contains(&TestEvent::PhalaRegistry(
+					registry::Event::TopicKeyProvisioned(
+						b"contract/1".to_vec(),
+						worker_pubkey(1),
+						1
+					)
+				)));
+				// Rotated by the provisioner only
+				assert_noop!(
+					provision(2, b"contract/1", &[2; 32]),
+					registry::Error::<Test>::TopicKeyNotOwned
 				);
+				assert_ok!(provision(1, b"contract/1", &[3; 32]));
 				assert_eq!(
-					tokenomic.ve(1000 * DOLLARS, 1000, 3),
-					fp!(1950.00000000000000001626)
+					TopicKey::<Test>::get(b"contract/1".to_vec()),
+					Some(vec![3; 32])
 				);
 				assert_eq!(
-					tokenomic.ve(1000 * DOLLARS, 1000, 4),
-					fp!(1819.99999999999999998694)
+					PhalaRegistry::topic_key_anchor(b"contract/1".to_vec()).map(|a| a.epoch),
+					Some(2)
 				);
+				// The revoked workers can't rotate the keys
+				assert_ok!(PhalaRegistry::revoke_attestation(
+					Origin::root(),
+					worker_pubkey(1)
+				));
+				assert_noop!(
+					provision(1, b"contract/1", &[4; 32]),
+					registry::Error::<Test>::AttestationAlreadyRevoked
+				);
+				// Root hands the squatted topic over to another worker
+				assert_ok!(PhalaRegistry::force_register_topic_pubkey(
+					Origin::root(),
+					b"contract/1".to_vec(),
+					vec![5; 32],
+					Some(worker_pubkey(2))
+				));
 				assert_eq!(
-					tokenomic.ve(1000 * DOLLARS, 1000, 5),
-					fp!(1754.9999999999999999723)
+					PhalaRegistry::topic_key_anchor(b"contract/1".to_vec()),
+					Some(TopicKeyAnchor {
+						provisioner: worker_pubkey(2),
+						epoch: 3,
+					})
 				);
-				// Rig cost estimation
-				assert_eq!(tokenomic.rig_cost(500), fp!(150.0000000000000000054));
-				assert_eq!(tokenomic.rig_cost(2000), fp!(600.0000000000000000217));
-				assert_eq!(tokenomic.rig_cost(2800), fp!(840.00000000000000003036));
-
-				const BLOCK_SEC: u32 = 12;
-				const HOUR_BLOCKS: u32 = 3600 / BLOCK_SEC;
-				// Slash per hour (around 0.1%)
-				let slash_rate = FixedPoint::from_bits(tokenomic.params.slash_rate);
-				let slash_decay = FixedPoint::from_num(1) - slash_rate;
-				assert_eq!(pow(slash_decay, HOUR_BLOCKS), fp!(0.9990004981683704595));
-				// Budget per day
-				let budger_per_sec = FixedPoint::from_bits(tokenomic.params.budget_per_sec);
-				assert_eq!(budger_per_sec * 3600 * 24, fp!(719999.99999999999999843875));
-				// Cost estimation per year
+				assert_ok!(provision(2, b"contract/1", &[6; 32]));
 				assert_eq!(
-					tokenomic.op_cost(2000) * 3600 * 24 * 365,
-					fp!(171.71874999890369452304)
+					TopicKey::<Test>::get(b"contract/1".to_vec()),
+					Some(vec![6; 32])
+				);
+				// Never taken over by the workers if forced without a provisioner
+				assert_ok!(PhalaRegistry::force_register_topic_pubkey(
+					Origin::root(),
+					b"contract/1".to_vec(),
+					vec![5; 32],
+					None
+				));
+				assert_eq!(
+					PhalaRegistry::topic_key_anchor(b"contract/1".to_vec()),
+					None
+				);
+				assert_noop!(
+					provision(2, b"contract/1", &[2; 32]),
+					registry::Error::<Test>::TopicKeyNotOwned
 				);
 			});
 		}
 
 		#[test]
-		fn test_benchmark_report() {
-			use phala_types::messaging::{DecodedMessage, MessageOrigin, MiningReportEvent, Topic};
+		fn test_reward_sink() {
+			use crate::mock::{gk_settle, Balances};
+			let settle = |payout: FixedPoint| {
+				gk_settle(&[1], PhalaMining::miners(1).unwrap().v.to_bits(), payout.to_bits())
+			};
 			new_test_ext().execute_with(|| {
 				set_block_1();
 				setup_workers(1);
-				// 100 iters per sec
-				PhalaRegistry::internal_set_benchmark(&worker_pubkey(1), Some(600));
 				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
-				// Though only the mining workers can send heartbeat, but we don't enforce it in
-				// the pallet, but just by pRuntime. Therefore we can directly throw a heartbeat
-				// response to test benchmark report.
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				// Solo miner, so the dispatched payouts are visible in the locked rewards
+				SoloMiners::<Test>::insert(1, true);
+				assert_eq!(PhalaMining::reward_sink(), RewardSink::Normal);
+				assert_noop!(
+					PhalaMining::set_reward_sink(Origin::signed(1), RewardSink::Burn),
+					sp_runtime::DispatchError::BadOrigin
+				);
+				// Parked instead of dispatched
+				assert_ok!(PhalaMining::set_reward_sink(
+					Origin::root(),
+					RewardSink::ParkInPallet
+				));
+				take_events();
+				settle(fp!(1));
+				settle(fp!(1));
+				assert!(PhalaMining::locked_rewards(RewardBeneficiary::Owner(1)).is_empty());
+				assert_eq!(
+					PhalaMining::parked_reward(1),
+					Some(ParkedReward {
+						worker: worker_pubkey(1),
+						amount: 2 * DOLLARS,
+					})
+				);
+				assert!(take_events()
+					.contains(&TestEvent::PhalaMining(Event::RewardParked(1, DOLLARS))));
+				// Drained only after the sink is back to normal
+				assert_noop!(
+					PhalaMining::drain_parked_rewards(Origin::root(), vec![1]),
+					Error::<Test>::RewardSinkNotNormal
+				);
+				assert_ok!(PhalaMining::set_reward_sink(
+					Origin::root(),
+					RewardSink::Normal
+				));
+				assert_ok!(PhalaMining::drain_parked_rewards(
+					Origin::root(),
+					vec![1, 2]
+				));
+				assert_eq!(PhalaMining::parked_reward(1), None);
+				assert_eq!(
+					PhalaMining::locked_rewards(RewardBeneficiary::Owner(1))
+						.iter()
+						.map(|r| r.amount)
+						.sum::<u128>(),
+					2 * DOLLARS
+				);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::ParkedRewardDrained(1, 2 * DOLLARS)
+				)));
+				// Burnt from the subsidy pool
+				assert_ok!(PhalaMining::set_reward_sink(
+					Origin::root(),
+					RewardSink::Burn
+				));
+				let pool = Balances::free_balance(&PhalaMining::account_id());
+				let issuance = Balances::total_issuance();
+				settle(fp!(1));
+				assert_eq!(
+					Balances::free_balance(&PhalaMining::account_id()),
+					pool - DOLLARS
+				);
+				assert_eq!(Balances::total_issuance(), issuance - DOLLARS);
+				assert_eq!(PhalaMining::parked_reward(1), None);
+				assert!(
+					take_events().contains(&TestEvent::PhalaMining(Event::RewardBurnt(1, DOLLARS)))
+				);
+			});
+		}
 
-				// 110% boost
-				elapse_seconds(100);
-				assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
-					MiningReportEvent,
-				> {
-					sender: MessageOrigin::Worker(worker_pubkey(1)),
-					destination: Topic::new(*b"phala/mining/report"),
-					payload: MiningReportEvent::Heartbeat {
-						session_id: 0,
-						challenge_block: 0,
-						challenge_time: 0,
-						iterations: 11000,
-					},
-				}));
-				let miner = PhalaMining::miners(1).unwrap();
+		#[test]
+		fn test_effective_slash_rate() {
+			use crate::mock::{elapse_cool_down, gk_settle};
+			let settle = |v: FixedPoint| gk_settle(&[1], v.to_bits(), 0);
+			let v = || PhalaMining::miners(1).unwrap().v.to_fixed();
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(PhalaMining::effective_slash_rate(1), Permill::zero());
+				// 0.01% a day, annualized to ~3.65%, and averaged with a weight of 1/8
+				elapse_seconds(24 * 3600);
+				settle(v() - v() / 10000);
+				let rate = PhalaMining::effective_slash_rate(1).deconstruct();
+				assert!((4500..=4570).contains(&rate), "rate = {}", rate);
+				// Capped at 100% a year
+				elapse_seconds(3600);
+				settle(v() / 2);
 				assert_eq!(
-					miner.benchmark,
-					Benchmark {
-						p_instant: 660,
-						iterations: 11000,
-						mining_start_time: 0,
-						updated_at: 100,
-					}
+					PhalaMining::effective_slash_rate(1).deconstruct(),
+					rate * 7 / 8 + 125_000
+				);
+				// Growing V lowers the average
+				let rate = PhalaMining::effective_slash_rate(1).deconstruct();
+				elapse_seconds(3600);
+				settle(v() * 2);
+				assert_eq!(
+					PhalaMining::effective_slash_rate(1).deconstruct(),
+					rate * 7 / 8
 				);
+				// Reset by a new session
+				assert_ok!(PhalaMining::stop_mining(1));
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_eq!(PhalaMining::effective_slash_rate(1), Permill::zero());
+			});
+		}
 
-				// 150% boost (capped)
-				elapse_seconds(100);
-				assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
-					MiningReportEvent,
-				> {
-					sender: MessageOrigin::Worker(worker_pubkey(1)),
-					destination: Topic::new(*b"phala/mining/report"),
-					payload: MiningReportEvent::Heartbeat {
-						session_id: 0,
-						challenge_block: 0,
-						challenge_time: 0,
-						iterations: 11000 + 15000,
-					},
-				}));
-				let miner = PhalaMining::miners(1).unwrap();
+		#[test]
+		fn test_wrong_state_settle_policy() {
+			use crate::mock::{elapse_cool_down, gk_settle};
+			let settle = |payout: FixedPoint| {
+				gk_settle(&[1], PhalaMining::miners(1).unwrap().v.to_bits(), payout.to_bits())
+			};
+			let reward = || PhalaMining::miners(1).unwrap().stats.total_reward;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				assert_ok!(PhalaMining::bind(1, worker_pubkey(1)));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_noop!(
+					PhalaMining::set_wrong_state_settle_policy(
+						Origin::signed(1),
+						WrongStateSettlePolicy::Buffer
+					),
+					sp_runtime::DispatchError::BadOrigin
+				);
+				// Buffered in the cool down by default, and applied to the session at the reclaim
+				assert_ok!(PhalaMining::stop_mining(1));
+				take_events();
+				settle(fp!(1));
+				settle(fp!(2));
+				assert_eq!(reward(), 0);
+				assert!(take_events().contains(&TestEvent::PhalaMining(Event::SettleBuffered(
+					1,
+					worker_pubkey(1)
+				))));
 				assert_eq!(
-					miner.benchmark,
-					Benchmark {
-						p_instant: 720,
-						iterations: 26000,
-						mining_start_time: 0,
-						updated_at: 200,
-					}
+					PhalaMining::pending_settlement(1).map(|s| s.payout),
+					Some(fp!(3).to_bits())
 				);
+				elapse_cool_down();
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), 1));
+				assert_eq!(PhalaMining::pending_settlement(1), None);
+				let settled = reward();
+				assert!(settled > 0);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::PendingSettlementApplied(1, fp!(3).to_bits())
+				)));
+				// Ignored after the reclaim, never carried into the next session
+				settle(fp!(1));
+				assert_eq!(reward(), settled);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::SettleIgnoredWrongState(1, worker_pubkey(1), MinerState::Ready)
+				)));
+				assert_eq!(PhalaMining::pending_settlement(1), None);
+				// Ignored in the cool down too by the policy
+				assert_ok!(PhalaMining::set_wrong_state_settle_policy(
+					Origin::root(),
+					WrongStateSettlePolicy::Ignore
+				));
+				assert_ok!(PhalaMining::start_mining(1, 1000 * DOLLARS));
+				assert_ok!(PhalaMining::stop_mining(1));
+				let settled = reward();
+				take_events();
+				settle(fp!(1));
+				assert_eq!(reward(), settled);
+				assert!(take_events().contains(&TestEvent::PhalaMining(
+					Event::SettleIgnoredWrongState(
+						1,
+						worker_pubkey(1),
+						MinerState::MiningCoolingDown
+					)
+				)));
+				assert_eq!(PhalaMining::pending_settlement(1), None);
 			});
 		}
 	}
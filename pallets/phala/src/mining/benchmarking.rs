@@ -0,0 +1,74 @@
+//! Benchmarks of the mining hot paths
+//!
+//! The results include the storage read / write counts of the extrinsics.
+
+use super::*;
+use crate::balance_convert::FixedPointConvert;
+use crate::registry;
+
+use fixed::types::U64F64 as FixedPoint;
+use frame_benchmarking::{benchmarks, impl_benchmark_test_suite, whitelisted_caller};
+use frame_support::traits::Currency;
+use frame_system::RawOrigin;
+use phala_types::{EcdhPublicKey, WorkerPublicKey};
+
+type BalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+/// Registers a benchmarked worker and binds it to a miner
+fn setup_bound_miner<T: Config>() -> (T::AccountId, WorkerPublicKey)
+where
+	BalanceOf<T>: FixedPointConvert,
+{
+	let miner: T::AccountId = whitelisted_caller();
+	let worker = WorkerPublicKey::from_raw([1u8; 32]);
+	registry::Pallet::<T>::force_register_worker(
+		RawOrigin::Root.into(),
+		worker.clone(),
+		EcdhPublicKey([1u8; 32]),
+		None,
+	)
+	.expect("Root can register workers; qed.");
+	registry::Pallet::<T>::internal_set_benchmark(&worker, Some(1));
+	Pallet::<T>::bind(miner.clone(), worker.clone()).expect("Fresh miner can bind; qed.");
+	(miner, worker)
+}
+
+fn mining_stake<T: Config>() -> BalanceOf<T>
+where
+	BalanceOf<T>: FixedPointConvert,
+{
+	FixedPointConvert::from_fixed(&FixedPoint::from_num(1000))
+}
+
+benchmarks! {
+	where_clause {
+		where BalanceOf<T>: FixedPointConvert
+	}
+
+	start_mining {
+		let (miner, _) = setup_bound_miner::<T>();
+		let stake = mining_stake::<T>();
+	}: force_start_mining(RawOrigin::Root, miner.clone(), stake)
+	verify {
+		assert_eq!(Miners::<T>::get(&miner).unwrap().state, MinerState::MiningIdle);
+	}
+
+	stop_mining {
+		let (miner, _) = setup_bound_miner::<T>();
+		Pallet::<T>::start_mining(miner.clone(), mining_stake::<T>())?;
+	}: force_stop_mining(RawOrigin::Root, miner.clone())
+	verify {
+		assert_eq!(Miners::<T>::get(&miner).unwrap().state, MinerState::MiningCoolingDown);
+	}
+
+	// The heartbeat challenge target in `on_finalize`, up to tens of millions of miners
+	pow_target {
+		let n in 1 .. 50_000_000;
+		let s in 1 .. 12;
+	}: {
+		let _ = pow_target(20, n, s);
+	}
+}
+
+impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
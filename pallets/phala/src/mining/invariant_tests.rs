@@ -0,0 +1,297 @@
+//! Randomized invariant tests of the mining state machine
+//!
+//! Each run drives a random sequence of start / stop / heartbeat / settle / offline / recover /
+//! reclaim operations through the stake pool, with a minimal gatekeeper model fed by the pallet's
+//! outbound messages. Each step ends its block with `on_idle`, and the invariants are checked after
+//! every step.
+
+use super::pallet::{Miners, Stakes};
+use super::*;
+use crate::mock::{
+	elapse_cool_down, elapse_seconds, execute_with_invariants, set_block_1, setup_workers,
+	take_events, take_messages, worker_pubkey, BlockNumber, Event as TestEvent, Origin, Test,
+	DOLLARS,
+};
+// Pallets
+use crate::mock::{PhalaMining, PhalaStakePool, System, Timestamp};
+
+use fixed::types::U64F64 as FixedPoint;
+use frame_support::{assert_ok, traits::OnIdle, weights::Weight};
+use phala_types::messaging::{
+	DecodedMessage, MessageOrigin, MiningInfoUpdateEvent, MiningReportEvent, SettleInfo,
+	SystemEvent, Topic, WorkerEvent, WorkerEventWithKey,
+};
+use phala_types::WorkerPublicKey;
+use sp_std::collections::btree_map::BTreeMap;
+
+const NUM_WORKERS: u8 = 4;
+const NUM_RUNS: u64 = 20;
+const NUM_STEPS: usize = 150;
+const POOL_OWNER: u64 = 1;
+const CONTRIBUTOR: u64 = 99;
+
+/// A xorshift64 generator, good enough to pick the operations
+struct Rng(u64);
+
+impl Rng {
+	fn new(seed: u64) -> Self {
+		Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+	}
+	fn next(&mut self) -> u64 {
+		let mut x = self.0;
+		x ^= x << 13;
+		x ^= x >> 7;
+		x ^= x << 17;
+		self.0 = x;
+		x
+	}
+	fn below(&mut self, n: u64) -> u64 {
+		self.next() % n
+	}
+	fn worker(&mut self) -> u8 {
+		self.below(NUM_WORKERS as u64) as u8 + 1
+	}
+}
+
+/// The gatekeeper view and the expected stakes of the workers
+#[derive(Default)]
+struct Model {
+	/// Workers in a mining session, with their unresponsive flag
+	mining: BTreeMap<u8, bool>,
+	/// The expected stake of the miner of each worker
+	stakes: BTreeMap<u8, u128>,
+	/// The last reported iterations of each worker
+	iterations: BTreeMap<u8, u64>,
+}
+
+fn miner_of(worker: u8) -> u64 {
+	pool_sub_account(0, &worker_pubkey(worker))
+}
+
+fn worker_index(pubkey: &WorkerPublicKey) -> Option<u8> {
+	(1..=NUM_WORKERS).find(|i| &worker_pubkey(*i) == pubkey)
+}
+
+fn send_gk_update(offline: Vec<u8>, recovered: Vec<u8>, settle: Vec<SettleInfo>) {
+	assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage::<
+		MiningInfoUpdateEvent<BlockNumber>,
+	> {
+		sender: MessageOrigin::Gatekeeper,
+		destination: Topic::new(*b"^phala/mining/update"),
+		payload: MiningInfoUpdateEvent::<BlockNumber> {
+			block_number: System::block_number(),
+			timestamp_ms: Timestamp::get(),
+			offline: offline.into_iter().map(worker_pubkey).collect(),
+			recovered_to_online: recovered.into_iter().map(worker_pubkey).collect(),
+			settle,
+			protocol_version: 0,
+			settle_bindings: vec![],
+			continues: false,
+		},
+	}));
+}
+
+/// Settles a worker with a random V under `v_max` and a small payout
+fn random_settle(rng: &mut Rng, worker: u8) -> SettleInfo {
+	let v_max = TokenomicParameters::<Test>::get().unwrap().v_max;
+	let payout = FixedPoint::from_num(rng.below(10)) / FixedPoint::from_num(10);
+	SettleInfo {
+		pubkey: worker_pubkey(worker),
+		v: v_max / 100 * rng.below(101) as u128,
+		payout: payout.to_bits(),
+	}
+}
+
+/// Replays the outbound worker events on the gatekeeper model. A stopped worker gets its final
+/// settlement immediately, as the gatekeeper does.
+fn sync_gatekeeper(rng: &mut Rng, model: &mut Model) {
+	let mut final_settle = Vec::new();
+	for message in take_messages() {
+		if message.destination != Topic::new(*b"phala/system/event") {
+			continue;
+		}
+		let (pubkey, event) = match message.decode_payload::<SystemEvent>() {
+			Some(SystemEvent::WorkerEvent(WorkerEventWithKey { pubkey, event, .. })) => {
+				(pubkey, event)
+			}
+			_ => continue,
+		};
+		let worker = match worker_index(&pubkey) {
+			Some(worker) => worker,
+			None => continue,
+		};
+		match event {
+			WorkerEvent::MiningStart { .. } => {
+				model.mining.insert(worker, false);
+			}
+			WorkerEvent::MiningStop => {
+				model.mining.remove(&worker);
+				final_settle.push(random_settle(rng, worker));
+			}
+			_ => {}
+		}
+	}
+	if !final_settle.is_empty() {
+		send_gk_update(vec![], vec![], final_settle);
+	}
+}
+
+/// Checks the events of the last step against the miner states and updates the expected stakes
+fn check_events(model: &mut Model) {
+	for event in take_events() {
+		match event {
+			TestEvent::PhalaMining(Event::MinerSettled(miner, ..)) => {
+				let state = Miners::<Test>::get(miner).unwrap().state;
+				assert_ne!(state, MinerState::Ready, "Settled a Ready miner");
+			}
+			TestEvent::PhalaMining(Event::MinerReclaimed(miner, orig_stake, slashed)) => {
+				assert!(slashed <= orig_stake, "Slashed more than the stake");
+				let worker = (1..=NUM_WORKERS)
+					.find(|i| miner_of(*i) == miner)
+					.expect("Only pool miners exist");
+				assert_eq!(model.stakes.remove(&worker), Some(orig_stake));
+			}
+			_ => {}
+		}
+	}
+}
+
+fn check_invariants(model: &Model) {
+	let v_max = TokenomicParameters::<Test>::get().unwrap().v_max;
+	let mut online = 0u32;
+	let mut total_stake = 0u128;
+	for worker in 1..=NUM_WORKERS {
+		let miner = miner_of(worker);
+		let info = Miners::<Test>::get(miner).expect("Pool workers are bound");
+		let is_online = matches!(
+			info.state,
+			MinerState::MiningIdle | MinerState::MiningActive | MinerState::MiningUnresponsive
+		);
+		if is_online {
+			online += 1;
+		}
+		// The chain and the gatekeeper agree on the mining workers
+		assert_eq!(model.mining.get(&worker).is_some(), is_online);
+		assert_eq!(
+			model.mining.get(&worker).copied().unwrap_or(false),
+			info.state == MinerState::MiningUnresponsive
+		);
+		// Stake is only held by mining and cooling down miners, and never leaks
+		let stake = Stakes::<Test>::get(miner);
+		assert_eq!(stake.is_some(), info.state != MinerState::Ready);
+		assert_eq!(stake, model.stakes.get(&worker).copied());
+		total_stake += stake.unwrap_or_default();
+		assert!(info.v.to_bits() <= v_max, "V exceeds v_max");
+	}
+	assert_eq!(PhalaMining::online_miners(), online);
+	assert_eq!(total_stake, model.stakes.values().sum::<u128>());
+	assert_eq!(PhalaMining::do_try_state(), Ok(()));
+}
+
+fn step(rng: &mut Rng, model: &mut Model) {
+	let worker = rng.worker();
+	let miner = miner_of(worker);
+	match rng.below(8) {
+		0 => {
+			let stake = [100, 500, 1000][rng.below(3) as usize] * DOLLARS;
+			let r = PhalaStakePool::start_mining(
+				Origin::signed(POOL_OWNER),
+				0,
+				worker_pubkey(worker),
+				stake,
+			);
+			if r.is_ok() {
+				model.stakes.insert(worker, stake);
+			}
+		}
+		1 => {
+			let _ =
+				PhalaStakePool::stop_mining(Origin::signed(POOL_OWNER), 0, worker_pubkey(worker));
+		}
+		2 => {
+			elapse_seconds(1 + rng.below(10));
+			let iterations = model.iterations.entry(worker).or_default();
+			*iterations += 1 + rng.below(1000);
+			assert_ok!(PhalaMining::on_mining_message_received(DecodedMessage::<
+				MiningReportEvent,
+			> {
+				sender: MessageOrigin::Worker(worker_pubkey(worker)),
+				destination: Topic::new(*b"phala/mining/report"),
+				payload: MiningReportEvent::Heartbeat {
+					session_id: 0,
+					challenge_block: 0,
+					challenge_time: Timestamp::get(),
+					iterations: *iterations,
+					platform: None,
+					restarted: false,
+				},
+			}));
+		}
+		3 => {
+			if model.mining.contains_key(&worker) {
+				let settle = random_settle(rng, worker);
+				send_gk_update(vec![], vec![], vec![settle]);
+			}
+		}
+		4 => {
+			if model.mining.get(&worker) == Some(&false) {
+				model.mining.insert(worker, true);
+				send_gk_update(vec![worker], vec![], vec![]);
+			}
+		}
+		5 => {
+			if model.mining.get(&worker) == Some(&true) {
+				model.mining.insert(worker, false);
+				send_gk_update(vec![], vec![worker], vec![]);
+			}
+		}
+		6 => {
+			let _ = PhalaMining::reclaim(Origin::signed(POOL_OWNER), miner);
+		}
+		_ => {
+			if rng.below(4) == 0 {
+				elapse_cool_down();
+			} else {
+				elapse_seconds(1 + rng.below(3600));
+			}
+		}
+	}
+}
+
+#[test]
+fn test_mining_invariants() {
+	for seed in 0..NUM_RUNS {
+		execute_with_invariants(|| {
+			set_block_1();
+			setup_workers(NUM_WORKERS);
+			assert_ok!(PhalaStakePool::create(Origin::signed(POOL_OWNER)));
+			for worker in 1..=NUM_WORKERS {
+				assert_ok!(PhalaStakePool::add_worker(
+					Origin::signed(POOL_OWNER),
+					0,
+					worker_pubkey(worker)
+				));
+			}
+			assert_ok!(PhalaStakePool::contribute(
+				Origin::signed(CONTRIBUTOR),
+				0,
+				100_000 * DOLLARS
+			));
+			let _ = take_events();
+			let _ = take_messages();
+
+			let mut rng = Rng::new(seed);
+			let mut model = Model::default();
+			for _ in 0..NUM_STEPS {
+				step(&mut rng, &mut model);
+				sync_gatekeeper(&mut rng, &mut model);
+				// Ends the block
+				let n = System::block_number();
+				<PhalaMining as OnIdle<BlockNumber>>::on_idle(n, Weight::max_value());
+				System::set_block_number(n + 1);
+				check_events(&mut model);
+				check_invariants(&model);
+			}
+		});
+	}
+}
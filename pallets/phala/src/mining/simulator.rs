@@ -0,0 +1,199 @@
+//! Multi-day reward and slash simulations of the tokenomic, for the governance to evaluate the
+//! proposed parameter changes
+//!
+//! The simulations go through the same fixed-point code paths as the on-chain settlement. They
+//! must run inside the externalities of a chain (e.g. a `TestExternalities` built from a live
+//! state), where the confidence scores are read from.
+
+use super::pallet::{BalanceOf, Config, Tokenomic};
+use crate::balance_convert::FixedPointConvert;
+
+use fixed::types::U64F64 as FixedPoint;
+use phala_types::messaging::TokenomicParameters as TokenomicParams;
+use sp_runtime::Permill;
+use sp_std::fmt::Write;
+
+/// A group of identical workers in a scenario
+#[derive(Clone, Debug)]
+pub struct WorkerProfile<Balance> {
+	/// Number of the workers in this group
+	pub count: u32,
+	/// The performance score
+	pub p: u32,
+	/// The confidence level of the attestation
+	pub confidence_level: u8,
+	/// The stake of each worker, or the minimal stake if `None`
+	pub stake: Option<Balance>,
+	/// The fraction of the blocks the workers are online
+	pub uptime: Permill,
+}
+
+/// A simulation scenario
+#[derive(Clone, Debug)]
+pub struct Scenario<Balance> {
+	/// The tokenomic parameters to evaluate
+	pub params: TokenomicParams,
+	/// The worker population
+	pub workers: Vec<WorkerProfile<Balance>>,
+	/// Number of days to simulate
+	pub days: u32,
+	/// Number of blocks between two heartbeats of a worker
+	pub round_blocks: u32,
+	/// The expected block time
+	pub secs_per_block: u32,
+}
+
+/// The outcome of a worker profile on a simulated day
+///
+/// All the amounts are per worker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DayReport {
+	/// The day, starting from 1
+	pub day: u32,
+	/// The index of the profile in `Scenario::workers`
+	pub profile: usize,
+	/// V at the end of the day
+	pub v: FixedPoint,
+	/// The total payout of the day
+	pub payout: FixedPoint,
+	/// The total slashed V of the day
+	pub slash: FixedPoint,
+}
+
+/// Runs the scenario, returning the reports ordered by day and then by profile
+///
+/// The budget of each round is shared by all the workers in the scenario, as the on-chain
+/// simulation does with the online miners.
+pub fn run<T>(scenario: &Scenario<BalanceOf<T>>) -> Vec<DayReport>
+where
+	T: Config,
+	BalanceOf<T>: FixedPointConvert,
+{
+	let tokenomic = Tokenomic::<T>::new(scenario.params.clone());
+	let online_miners = scenario.workers.iter().map(|w| w.count).sum::<u32>().max(1);
+	let round_blocks = scenario.round_blocks.max(1);
+	let blocks_per_day = 86400 / scenario.secs_per_block.max(1);
+	let rounds_per_day = (blocks_per_day / round_blocks).max(1);
+
+	let mut vs: Vec<FixedPoint> = scenario
+		.workers
+		.iter()
+		.map(|w| {
+			let stake = w.stake.unwrap_or_else(|| tokenomic.minimal_stake(w.p));
+			tokenomic.ve(stake, w.p, w.confidence_level)
+		})
+		.collect();
+
+	let mut reports = Vec::new();
+	for day in 1..=scenario.days {
+		for (profile, (w, v)) in scenario.workers.iter().zip(vs.iter_mut()).enumerate() {
+			let blocks_online = w.uptime.mul_floor(round_blocks);
+			let blocks_offline = round_blocks - blocks_online;
+			let mut payout = FixedPoint::from_num(0);
+			let mut slash = FixedPoint::from_num(0);
+			for _ in 0..rounds_per_day {
+				let (v_end, round_payout, round_slash) = tokenomic.simulate(
+					*v,
+					w.p,
+					blocks_online,
+					blocks_offline,
+					online_miners,
+					scenario.secs_per_block,
+				);
+				*v = v_end;
+				payout += round_payout;
+				slash += round_slash;
+			}
+			reports.push(DayReport {
+				day,
+				profile,
+				v: *v,
+				payout,
+				slash,
+			});
+		}
+	}
+	reports
+}
+
+/// Renders the reports as CSV with a header line
+pub fn to_csv(reports: &[DayReport]) -> String {
+	let mut csv = String::from("day,profile,v,payout,slash\n");
+	for r in reports {
+		let _ = writeln!(
+			csv,
+			"{},{},{},{},{}",
+			r.day, r.profile, r.v, r.payout, r.slash
+		);
+	}
+	csv
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::mining::pallet::TokenomicParameters;
+	use crate::mock::{new_test_ext, Test, DOLLARS};
+
+	fn scenario(workers: Vec<WorkerProfile<u128>>) -> Scenario<u128> {
+		Scenario {
+			params: TokenomicParameters::<Test>::get().unwrap(),
+			workers,
+			days: 3,
+			round_blocks: 10,
+			secs_per_block: 12,
+		}
+	}
+
+	#[test]
+	fn test_simulate_population() {
+		new_test_ext().execute_with(|| {
+			let full = WorkerProfile {
+				count: 10,
+				p: 1000,
+				confidence_level: 1,
+				stake: None,
+				uptime: Permill::one(),
+			};
+			let flaky = WorkerProfile {
+				uptime: Permill::from_percent(50),
+				..full.clone()
+			};
+			let reports = run::<Test>(&scenario(vec![full, flaky]));
+			assert_eq!(reports.len(), 6);
+			for day in reports.chunks(2) {
+				let (full, flaky) = (&day[0], &day[1]);
+				assert!(full.payout > 0);
+				assert_eq!(full.slash, 0);
+				assert!(flaky.slash > 0);
+				assert!(flaky.v < full.v);
+			}
+			// A stake above the minimal one never lowers V
+			let staked = WorkerProfile {
+				count: 1,
+				p: 1000,
+				confidence_level: 1,
+				stake: Some(10000 * DOLLARS),
+				uptime: Permill::one(),
+			};
+			let minimal = WorkerProfile {
+				stake: None,
+				..staked.clone()
+			};
+			let reports = run::<Test>(&scenario(vec![staked, minimal]));
+			assert!(reports[0].v >= reports[1].v);
+		});
+	}
+
+	#[test]
+	fn test_to_csv() {
+		let reports = vec![DayReport {
+			day: 1,
+			profile: 0,
+			v: FixedPoint::from_num(2),
+			payout: FixedPoint::from_num(1),
+			slash: FixedPoint::from_num(0),
+		}];
+		assert_eq!(to_csv(&reports), "day,profile,v,payout,slash\n1,0,2,1,0\n");
+	}
+}
@@ -1,10 +1,12 @@
 use crate::{mining, mq, registry, stakepool};
 
-use frame_support::{parameter_types, traits::GenesisBuild};
-use frame_support_test::TestRandomness;
+use frame_support::{parameter_types, traits::GenesisBuild, PalletId};
 use frame_system as system;
-use phala_types::messaging::Message;
-use sp_core::H256;
+use phala_types::messaging::{
+	DecodedMessage, HeartbeatChallenge, Message, MessageOrigin, MiningInfoUpdateEvent,
+	SettleInfo, SystemEvent, Topic,
+};
+use sp_core::{H256, U256};
 use sp_runtime::{
 	testing::Header,
 	traits::{BlakeTwo256, IdentityLookup},
@@ -39,6 +41,10 @@ parameter_types! {
 	pub const SS58Prefix: u8 = 20;
 	pub const MinimumPeriod: u64 = 1;
 	pub const ExpectedBlockTimeSec: u32 = 12;
+	pub const MaxStatusUpdatesPerMessage: u32 = 4;
+	pub const MiningRandomnessSubject: &'static [u8] = crate::constants::RANDOMNESS_SUBJECT;
+	pub const MiningPalletId: PalletId = PalletId(*b"phala/pp");
+	pub const MiningTopicPrefix: &'static [u8] = b"";
 	pub const MinMiningStaking: Balance = 1 * DOLLARS;
 	pub const MinContribution: Balance = 1 * CENTS;
 	pub const MiningInsurancePeriod: u64 = 3 * DAYS;
@@ -100,16 +106,101 @@ impl mq::Config for Test {
 impl registry::Config for Test {
 	type Event = Event;
 	type UnixTime = Timestamp;
+	type OnWorkerKeyRotated = PhalaMining;
+	type OnAttestationRevoked = PhalaMining;
+	type WorkerBindingInfo = PhalaMining;
 }
 
 impl mining::Config for Test {
 	type Event = Event;
 	type ExpectedBlockTimeSec = ExpectedBlockTimeSec;
+	type MaxStatusUpdatesPerMessage = MaxStatusUpdatesPerMessage;
+	type RandomnessSubject = MiningRandomnessSubject;
+	type PalletId = MiningPalletId;
+	type TopicPrefix = MiningTopicPrefix;
+	type OtherInstances = ();
 	type Currency = Balances;
-	type Randomness = TestRandomness<Self>;
-	type OnReward = PhalaStakePool;
-	type OnUnbound = PhalaStakePool;
-	type OnReclaim = PhalaStakePool;
+	type Randomness = mining::DeterministicRandomness<Self>;
+	type LifecycleObserver = PhalaStakePool;
+	type PriceFeed = TestPriceFeed;
+	type SecondaryAssets = TestSecondaryAssets;
+	type WorkerInfo = TestWorkerInfo;
+	type GovernanceOrigin = system::EnsureRoot<Self::AccountId>;
+	type TestingOrigin = system::EnsureRoot<Self::AccountId>;
+	type StakePools = PhalaStakePool;
+}
+
+thread_local! {
+	static PHA_PRICE: std::cell::RefCell<Option<u128>> = std::cell::RefCell::new(None);
+}
+
+/// A price feed returning the price set by `set_pha_price`
+pub struct TestPriceFeed;
+impl mining::PriceFeed for TestPriceFeed {
+	fn pha_price() -> Option<u128> {
+		PHA_PRICE.with(|p| *p.borrow())
+	}
+}
+
+pub fn set_pha_price(price: Option<u128>) {
+	PHA_PRICE.with(|p| *p.borrow_mut() = price);
+}
+
+thread_local! {
+	static SECONDARY_BALANCES: std::cell::RefCell<std::collections::BTreeMap<(u32, u64), u128>> =
+		Default::default();
+}
+
+/// A minimal assets implementation for the secondary payout
+pub struct TestSecondaryAssets;
+impl TestSecondaryAssets {
+	pub fn mint(asset: u32, who: u64, amount: u128) {
+		SECONDARY_BALANCES.with(|b| *b.borrow_mut().entry((asset, who)).or_default() += amount);
+	}
+}
+impl mining::SecondaryAssets<u64> for TestSecondaryAssets {
+	fn balance(asset: u32, who: &u64) -> u128 {
+		SECONDARY_BALANCES.with(|b| b.borrow().get(&(asset, *who)).copied().unwrap_or(0))
+	}
+	fn transfer(
+		asset: u32,
+		source: &u64,
+		dest: &u64,
+		amount: u128,
+	) -> frame_support::dispatch::DispatchResult {
+		let balance = Self::balance(asset, source);
+		frame_support::ensure!(balance >= amount, "Insufficient balance");
+		SECONDARY_BALANCES.with(|b| {
+			let mut b = b.borrow_mut();
+			b.insert((asset, *source), balance - amount);
+			*b.entry((asset, *dest)).or_default() += amount;
+		});
+		Ok(())
+	}
+}
+
+thread_local! {
+	static MOCK_WORKERS: std::cell::RefCell<
+		std::collections::BTreeMap<WorkerPublicKey, mining::MiningWorkerInfo<u64>>,
+	> = Default::default();
+}
+
+/// Serves the workers set by `set_mock_worker`, and then the workers in the registry
+pub struct TestWorkerInfo;
+impl TestWorkerInfo {
+	pub fn set_mock_worker(pubkey: WorkerPublicKey, info: mining::MiningWorkerInfo<u64>) {
+		MOCK_WORKERS.with(|w| w.borrow_mut().insert(pubkey, info));
+	}
+}
+impl mining::WorkerInfoProvider<u64> for TestWorkerInfo {
+	fn worker_info(pubkey: &WorkerPublicKey) -> Option<mining::MiningWorkerInfo<u64>> {
+		MOCK_WORKERS
+			.with(|w| w.borrow().get(pubkey).cloned())
+			.or_else(|| <PhalaRegistry as mining::WorkerInfoProvider<u64>>::worker_info(pubkey))
+	}
+	fn is_operator_or_delegate(worker: &mining::MiningWorkerInfo<u64>, who: &u64) -> bool {
+		<PhalaRegistry as mining::WorkerInfoProvider<u64>>::is_operator_or_delegate(worker, who)
+	}
 }
 
 impl stakepool::Config for Test {
@@ -117,6 +208,8 @@ impl stakepool::Config for Test {
 	type Currency = Balances;
 	type MinContribution = MinContribution;
 	type InsurancePeriod = MiningInsurancePeriod;
+	type MiningInfo = PhalaMining;
+	type XcmStakeOrigin = system::EnsureSigned<u64>;
 }
 
 // This function basically just builds a genesis storage key/value store according to
@@ -151,6 +244,14 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 	sp_io::TestExternalities::new(t)
 }
 
+/// Runs a test in `new_test_ext`, and checks the mining invariants after it
+pub fn execute_with_invariants(test: impl FnOnce()) {
+	new_test_ext().execute_with(|| {
+		test();
+		assert_eq!(PhalaMining::do_try_state(), Ok(()));
+	})
+}
+
 pub fn set_block_1() {
 	System::set_block_number(1);
 }
@@ -169,6 +270,7 @@ pub fn take_messages() -> Vec<Message> {
 	let messages = PhalaMq::messages();
 	println!("messages(): {:?}", messages);
 	mq::OutboundMessages::<Test>::kill();
+	mq::OutboundMessageTags::<Test>::kill();
 	messages
 }
 
@@ -218,6 +320,59 @@ pub fn setup_workers_linked_operators(n: u8) {
 	}
 }
 
+/// Returns the heartbeat challenge seed of `block`, as emitted by the mining pallet
+pub fn challenge_seed_at(block: BlockNumber) -> U256 {
+	let hash =
+		mining::DeterministicRandomness::<Test>::random_at(MiningRandomnessSubject::get(), block);
+	U256::from(AsRef::<[u8]>::as_ref(&hash))
+}
+
+/// Takes the heartbeat challenges in the outbound messages. The other messages are dropped.
+pub fn take_heartbeat_challenges() -> Vec<HeartbeatChallenge> {
+	take_messages()
+		.iter()
+		.filter_map(|m| match m.decode_payload::<SystemEvent>() {
+			Some(SystemEvent::HeartbeatChallenge(challenge)) => Some(challenge),
+			_ => None,
+		})
+		.collect()
+}
+
+/// Sends a gatekeeper mining update of block 1 settling `v` and `payout` (in U64F64 bits) to
+/// each of the workers
+pub fn gk_settle(workers: &[u8], v: u128, payout: u128) {
+	use frame_support::assert_ok;
+	let mut update = MiningInfoUpdateEvent::<BlockNumber>::new(1, 0);
+	for i in workers {
+		update.settle.push(SettleInfo {
+			pubkey: worker_pubkey(*i),
+			v,
+			payout,
+		});
+	}
+	assert_ok!(PhalaMining::on_gk_message_received(DecodedMessage {
+		sender: MessageOrigin::Gatekeeper,
+		destination: Topic::new(*b"^phala/mining/update"),
+		payload: update,
+	}));
+}
+
+/// Returns the workers among the first `n` hit by the challenge, as checked by the workers
+pub fn challenged_workers(challenge: &HeartbeatChallenge, n: u8) -> Vec<u8> {
+	(1..=n)
+		.filter(|i| {
+			let worker = worker_pubkey(*i);
+			if let Some(targets) = &challenge.targets {
+				if !targets.contains(&worker) {
+					return false;
+				}
+			}
+			let hashed_id = U256::from(sp_core::hashing::blake2_256(worker.as_ref()));
+			hashed_id ^ challenge.seed <= challenge.online_target
+		})
+		.collect()
+}
+
 pub fn elapse_seconds(sec: u64) {
 	let now = Timestamp::get();
 	Timestamp::set_timestamp(now + sec * 1000);
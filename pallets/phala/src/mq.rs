@@ -43,6 +43,19 @@ pub mod pallet {
 	#[pallet::getter(fn messages)]
 	pub type OutboundMessages<T> = StorageValue<_, Vec<Message>, ValueQuery>;
 
+	/// The tags of the messages pushed by `push_tagged_message` at the current block, with their
+	/// indices in `OutboundMessages`
+	///
+	/// It will be cleared at the beginning of every block.
+	#[pallet::storage]
+	pub type OutboundMessageTags<T> = StorageValue<_, Vec<(u32, Vec<u8>)>, ValueQuery>;
+
+	/// The account submitting the offchain message being dispatched
+	///
+	/// Only set during the dispatch in `sync_offchain_message`.
+	#[pallet::storage]
+	pub type CurrentRelayer<T: Config> = StorageValue<_, T::AccountId>;
+
 	#[pallet::error]
 	pub enum Error<T> {
 		BadSender,
@@ -62,7 +75,7 @@ pub mod pallet {
 			origin: OriginFor<T>,
 			signed_message: SignedMessage,
 		) -> DispatchResult {
-			ensure_signed(origin)?;
+			let relayer = ensure_signed(origin)?;
 
 			// Check sender
 			let sender = &signed_message.message.sender;
@@ -85,7 +98,9 @@ pub mod pallet {
 			// Update ingress
 			OffchainIngress::<T>::insert(sender.clone(), expected_seq + 1);
 			// Call dispatch_message
+			CurrentRelayer::<T>::put(relayer);
 			Self::dispatch_message(signed_message.message);
+			CurrentRelayer::<T>::kill();
 			Ok(())
 		}
 
@@ -106,6 +121,11 @@ pub mod pallet {
 	}
 
 	impl<T: Config> Pallet<T> {
+		/// The account submitting the offchain message being dispatched, if any
+		pub fn current_relayer() -> Option<T::AccountId> {
+			CurrentRelayer::<T>::get()
+		}
+
 		/// Push a validated message to the queue
 		pub fn dispatch_message(message: Message) {
 			// Notify subcribers
@@ -119,12 +139,83 @@ pub mod pallet {
 		}
 
 		pub fn push_bound_message<M: Encode + BindTopic>(sender: MessageOrigin, payload: M) {
-			let message = Message::new(sender, M::TOPIC, payload.encode());
+			Self::push_topic_message(sender, M::TOPIC.to_vec(), payload);
+		}
+
+		/// Pushes a message to `topic` rather than the topic bound to the payload, e.g. the topic
+		/// of a pallet instance
+		pub fn push_topic_message(sender: MessageOrigin, topic: Vec<u8>, payload: impl Encode) {
+			let message = Message::new(sender, topic, payload.encode());
 			Self::dispatch_message(message);
 		}
 
+		/// Pushes a message with a tag, so that it can be withdrawn by `withdraw_tagged_messages`
+		/// before it leaves the block
+		pub fn push_tagged_message<M: Encode + BindTopic>(
+			sender: MessageOrigin,
+			payload: M,
+			tag: Vec<u8>,
+		) {
+			Self::push_tagged_topic_message(sender, M::TOPIC.to_vec(), payload, tag);
+		}
+
+		/// Pushes a tagged message to `topic`, see `push_tagged_message`
+		pub fn push_tagged_topic_message(
+			sender: MessageOrigin,
+			topic: Vec<u8>,
+			payload: impl Encode,
+			tag: Vec<u8>,
+		) {
+			let index = OutboundMessages::<T>::decode_len().unwrap_or(0) as u32;
+			Self::push_topic_message(sender, topic, payload);
+			if OutboundMessages::<T>::decode_len().unwrap_or(0) as u32 > index {
+				OutboundMessageTags::<T>::append((index, tag));
+			}
+		}
+
+		/// Removes the messages pushed at the current block with the tags matching `pred`, e.g.
+		/// the commands of a terminated session. Returns the number of removed messages.
+		pub fn withdraw_tagged_messages(pred: impl Fn(&[u8]) -> bool) -> u32 {
+			let tags = OutboundMessageTags::<T>::get();
+			if !tags.iter().any(|(_, tag)| pred(tag)) {
+				return 0;
+			}
+			let mut messages = OutboundMessages::<T>::get();
+			let mut withdrawn = Vec::new();
+			let mut kept_tags = Vec::new();
+			for (index, tag) in tags {
+				if (index as usize) >= messages.len() {
+					// The messages have been cleared
+					continue;
+				}
+				if pred(&tag) {
+					withdrawn.push(index);
+				} else {
+					kept_tags.push((index, tag));
+				}
+			}
+			let mut i = 0u32;
+			messages.retain(|_| {
+				let keep = !withdrawn.contains(&i);
+				i += 1;
+				keep
+			});
+			// Shift the indices of the tags after the removed messages
+			for (index, _) in kept_tags.iter_mut() {
+				*index -= withdrawn.iter().filter(|w| **w < *index).count() as u32;
+			}
+			OutboundMessages::<T>::put(messages);
+			OutboundMessageTags::<T>::put(kept_tags);
+			withdrawn.len() as u32
+		}
+
 		pub fn queue_bound_message<M: Encode + BindTopic>(sender: MessageOrigin, payload: M) {
-			let message = Message::new(sender, M::TOPIC, payload.encode());
+			Self::queue_topic_message(sender, M::TOPIC.to_vec(), payload);
+		}
+
+		/// Enqueues a message to `topic`, see `queue_bound_message`
+		pub fn queue_topic_message(sender: MessageOrigin, topic: Vec<u8>, payload: impl Encode) {
+			let message = Message::new(sender, topic, payload.encode());
 			QueuedOutboundMessage::<T>::append(message);
 		}
 	}
@@ -134,6 +225,7 @@ pub mod pallet {
 		fn on_initialize(_now: BlockNumberFor<T>) -> Weight {
 			// Clear the previously pushed offchain messages
 			OutboundMessages::<T>::kill();
+			OutboundMessageTags::<T>::kill();
 
 			// Send out queued message from the previous block
 			if let Some(msgs) = QueuedOutboundMessage::<T>::take() {
@@ -193,13 +285,36 @@ pub mod pallet {
 			MessageOrigin::Pallet(name.as_bytes().to_vec())
 		}
 
-		fn push_message(payload: impl Encode + BindTopic) {
-			Pallet::<Self::Config>::push_bound_message(Self::message_origin(), payload);
+		/// The topic the messages bound to `topic` are sent to, e.g. namespaced by a pallet
+		/// instance
+		fn message_topic(topic: &[u8]) -> Vec<u8> {
+			topic.to_vec()
+		}
+
+		fn push_message<M: Encode + BindTopic>(payload: M) {
+			Pallet::<Self::Config>::push_topic_message(
+				Self::message_origin(),
+				Self::message_topic(M::TOPIC),
+				payload,
+			);
+		}
+
+		fn push_tagged_message<M: Encode + BindTopic>(payload: M, tag: Vec<u8>) {
+			Pallet::<Self::Config>::push_tagged_topic_message(
+				Self::message_origin(),
+				Self::message_topic(M::TOPIC),
+				payload,
+				tag,
+			);
 		}
 
 		/// Enqueues a message to push in the beginning of the next block
-		fn queue_message(payload: impl Encode + BindTopic) {
-			Pallet::<Self::Config>::queue_bound_message(Self::message_origin(), payload);
+		fn queue_message<M: Encode + BindTopic>(payload: M) {
+			Pallet::<Self::Config>::queue_topic_message(
+				Self::message_origin(),
+				Self::message_topic(M::TOPIC),
+				payload,
+			);
 		}
 	}
 }
@@ -6,7 +6,7 @@ pub mod pallet {
 	use codec::Encode;
 	use frame_support::{dispatch::DispatchResult, pallet_prelude::*, traits::UnixTime};
 	use frame_system::pallet_prelude::*;
-	use sp_core::H256;
+	use sp_core::{sr25519, H256};
 	use sp_runtime::SaturatedConversion;
 	use sp_std::prelude::*;
 	use sp_std::{
@@ -15,12 +15,13 @@ pub mod pallet {
 	};
 
 	use crate::attestation::{validate_ias_report, Error as AttestationError};
+	use crate::mining::{MiningWorkerInfo, WorkerInfoProvider};
 	use crate::mq::MessageOriginInfo;
 
 	use phala_types::{
 		messaging::{
-			self, bind_topic, DecodedMessage, GatekeeperEvent, MessageOrigin, SignedMessage,
-			SystemEvent, WorkerEvent,
+			self, bind_topic, DecodedMessage, GatekeeperEvent, MessageOrigin, PlatformStatus,
+			SignedMessage, SystemEvent, WorkerEvent,
 		},
 		ContractPublicKey, EcdhPublicKey, MasterPublicKey, WorkerPublicKey, WorkerRegistrationInfo,
 	};
@@ -28,8 +29,34 @@ pub mod pallet {
 	bind_topic!(RegistryEvent, b"^phala/registry/event");
 	#[derive(Encode, Decode, Clone, Debug)]
 	pub enum RegistryEvent {
-		BenchReport { start_time: u64, iterations: u64 },
-		MasterPubkey { master_pubkey: MasterPublicKey },
+		BenchReport {
+			start_time: u64,
+			iterations: u64,
+		},
+		MasterPubkey {
+			master_pubkey: MasterPublicKey,
+		},
+		/// Provisions a new `TopicKey` record of a contract hosted by the worker
+		TopicKey {
+			topic: Vec<u8>,
+			pubkey: Vec<u8>,
+		},
+	}
+
+	/// The max length of a `TopicKey` record provisioned by a worker
+	const MAX_TOPIC_KEY_LEN: usize = 128;
+	/// The max number of `ImportAuthorities`, which bounds the signatures `import_worker` checks
+	const MAX_IMPORT_AUTHORITIES: usize = 16;
+	/// The weight of verifying one sr25519 signature
+	const SR25519_VERIFY_WEIGHT: Weight = 50_000_000;
+
+	/// The provisioner of a `TopicKey` record and its rotation epoch
+	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+	pub struct TopicKeyAnchor {
+		/// The worker allowed to rotate the key
+		pub provisioner: WorkerPublicKey,
+		/// Starts from 1 at the first provisioning, and increased by each rotation
+		pub epoch: u32,
 	}
 
 	#[pallet::config]
@@ -37,6 +64,43 @@ pub mod pallet {
 		type Event: From<Event> + IsType<<Self as frame_system::Config>::Event>;
 
 		type UnixTime: UnixTime;
+
+		/// Notified when a worker identity key is rotated
+		type OnWorkerKeyRotated: OnWorkerKeyRotated;
+
+		/// Notified when the attestation of a worker is revoked
+		type OnAttestationRevoked: OnAttestationRevoked;
+
+		/// Tells if a worker is bound to a miner, which `import_worker` must not override
+		type WorkerBindingInfo: WorkerBindingInfo;
+	}
+
+	/// Handler of the worker identity key rollover
+	#[impl_trait_for_tuples::impl_for_tuples(10)]
+	pub trait OnWorkerKeyRotated {
+		/// Called after the registry has moved the `WorkerInfo` from `old` to `new`
+		fn on_worker_key_rotated(old: &WorkerPublicKey, new: &WorkerPublicKey);
+	}
+
+	/// Handler of the attestation revocation of a worker
+	#[impl_trait_for_tuples::impl_for_tuples(10)]
+	pub trait OnAttestationRevoked {
+		/// Called after the registry has flagged `worker` in `RevokedAttestations`
+		fn on_attestation_revoked(worker: &WorkerPublicKey);
+	}
+
+	/// The binding status of the workers, kept by the pallets binding the workers to miners
+	pub trait WorkerBindingInfo {
+		/// Returns true if `worker` is bound to a miner
+		fn is_worker_bound(worker: &WorkerPublicKey) -> bool;
+	}
+
+	#[impl_trait_for_tuples::impl_for_tuples(10)]
+	impl WorkerBindingInfo for Tuple {
+		fn is_worker_bound(worker: &WorkerPublicKey) -> bool {
+			for_tuples!( #( if Tuple::is_worker_bound(worker) { return true; } )* );
+			false
+		}
 	}
 
 	#[pallet::pallet]
@@ -61,16 +125,89 @@ pub mod pallet {
 	pub type ContractKey<T> = StorageMap<_, Twox64Concat, H256, ContractPublicKey>;
 
 	/// Pubkey for secret topics.
+	///
+	/// The value is either the raw 32 bytes ecdh pubkey (implying the default cipher suite), or
+	/// the SCALE encoded `(cipher_suite, pubkey)` record used by pRuntime to negotiate the suite,
+	/// whose pubkey length is fixed by the suite.
 	#[pallet::storage]
 	pub type TopicKey<T> = StorageMap<_, Blake2_128Concat, Vec<u8>, Vec<u8>>;
 
+	/// The anchors of the `TopicKey` records provisioned by the workers
+	///
+	/// Absent for the records registered by `force_register_topic_pubkey` without a provisioner.
+	#[pallet::storage]
+	#[pallet::getter(fn topic_key_anchor)]
+	pub type TopicKeyAnchors<T> = StorageMap<_, Blake2_128Concat, Vec<u8>, TopicKeyAnchor>;
+
+	/// The authorities attesting the worker registrations on the other chains, and the number of
+	/// their signatures required to import a worker. Importing is disabled with a zero threshold.
+	#[pallet::storage]
+	#[pallet::getter(fn import_authorities)]
+	pub type ImportAuthorities<T> = StorageValue<_, (Vec<sr25519::Public>, u32), ValueQuery>;
+
+	/// The registration time (on the source chain) of the last imported registration, by the
+	/// source chain and the worker
+	#[pallet::storage]
+	pub type ImportedWorkers<T> =
+		StorageDoubleMap<_, Twox64Concat, u32, Twox64Concat, WorkerPublicKey, u64>;
+
 	#[pallet::storage]
 	pub type BenchmarkDuration<T: Config> = StorageValue<_, u32>;
 
+	/// The hot key allowed to do the routine operations on behalf of a worker operator
+	///
+	/// It allows the operator key to stay in cold storage. Currently the delegate can only unbind
+	/// the miners of the operator's workers.
+	#[pallet::storage]
+	#[pallet::getter(fn operator_delegate)]
+	pub type OperatorDelegate<T: Config> = StorageMap<_, Twox64Concat, T::AccountId, T::AccountId>;
+
+	/// The latest platform status reported by each worker along with its heartbeats
+	///
+	/// Cleared when the worker is attested again.
+	#[pallet::storage]
+	#[pallet::getter(fn platform_status)]
+	pub type PlatformStatuses<T> = StorageMap<_, Twox64Concat, WorkerPublicKey, PlatformStatus>;
+
+	/// The workers flagged by the mining pallet for a clock skew beyond `MaxClockSkew`
+	///
+	/// Cleared when the worker is attested again.
+	#[pallet::storage]
+	#[pallet::getter(fn is_clock_skewed)]
+	pub type ClockSkewedWorkers<T> = StorageMap<_, Twox64Concat, WorkerPublicKey, bool, ValueQuery>;
+
+	/// The time (unix timestamp in seconds) of the last benchmark report of the workers
+	///
+	/// Absent for the workers benchmarked before it was tracked.
+	#[pallet::storage]
+	#[pallet::getter(fn benchmarked_at)]
+	pub type BenchmarkedAt<T> = StorageMap<_, Twox64Concat, WorkerPublicKey, u64>;
+
+	/// The workers whose attestation is revoked, e.g. the platform is listed in the SGX
+	/// attestation revocation lists
+	///
+	/// Cleared when the worker is attested again.
+	#[pallet::storage]
+	#[pallet::getter(fn is_attestation_revoked)]
+	pub type RevokedAttestations<T> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, bool, ValueQuery>;
+
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	pub enum Event {
 		GatekeeperAdded(WorkerPublicKey),
+		/// [old_pubkey, new_pubkey]
+		WorkerKeyRotated(WorkerPublicKey, WorkerPublicKey),
+		/// [pubkey, confidence_level]
+		WorkerNeedsReattestation(WorkerPublicKey, u8),
+		/// [num_authorities, threshold]
+		ImportAuthoritiesChanged(u32, u32),
+		/// [chain_id, pubkey]
+		WorkerImported(u32, WorkerPublicKey),
+		/// [pubkey]
+		AttestationRevoked(WorkerPublicKey),
+		/// [topic, provisioner, epoch]
+		TopicKeyProvisioned(Vec<u8>, WorkerPublicKey, u32),
 	}
 
 	#[pallet::error]
@@ -95,11 +232,28 @@ pub mod pallet {
 		InvalidInput,
 		InvalidBenchReport,
 		WorkerNotFound,
+		// Key rotation related
+		DuplicateWorker,
+		NotWorkerOperator,
+		CannotRotateGatekeeper,
 		// Gatekeeper related
 		InvalidGatekeeper,
 		InvalidMasterPubkey,
 		MasterKeyMismatch,
 		MasterKeyUninitialized,
+		// Import related
+		ImportDisabled,
+		InsufficientImportSignatures,
+		StaleImport,
+		// Revocation related
+		AttestationAlreadyRevoked,
+		// Topic key provisioning related
+		InvalidTopicKey,
+		TopicKeyNotOwned,
+		/// More signatures than the import authorities
+		TooManyImportSignatures,
+		/// The worker is bound to a miner, so its registration can't be replaced
+		WorkerBound,
 	}
 
 	#[pallet::call]
@@ -156,13 +310,26 @@ pub mod pallet {
 		}
 
 		/// Force register a topic pubkey
-		#[pallet::weight(10_000 + T::DbWeight::get().writes(1))]
+		///
+		/// Overwrites the anchor of the topic, e.g. to recover a topic squatted by a worker. The
+		/// record is rotated by the `provisioner` afterwards, or by no worker if it's `None`.
+		#[pallet::weight(10_000 + T::DbWeight::get().reads_writes(1, 2))]
 		pub fn force_register_topic_pubkey(
 			origin: OriginFor<T>,
 			topic: Vec<u8>,
 			pubkey: Vec<u8>,
+			provisioner: Option<WorkerPublicKey>,
 		) -> DispatchResult {
 			ensure_root(origin)?;
+			let epoch = TopicKeyAnchors::<T>::get(&topic)
+				.map(|anchor| anchor.epoch.saturating_add(1))
+				.unwrap_or(1);
+			match provisioner {
+				Some(provisioner) => {
+					TopicKeyAnchors::<T>::insert(&topic, TopicKeyAnchor { provisioner, epoch })
+				}
+				None => TopicKeyAnchors::<T>::remove(&topic),
+			}
 			TopicKey::<T>::insert(topic, pubkey);
 			Ok(())
 		}
@@ -253,6 +420,8 @@ pub mod pallet {
 					Some(worker_info) => {
 						// Case 1 - Refresh the RA report and redo benchmark
 						worker_info.last_updated = now;
+						worker_info.confidence_level = fields.confidence_level;
+						worker_info.runtime_version = pruntime_info.version;
 						Self::push_message(SystemEvent::new_worker_event(
 							pubkey.clone(),
 							WorkerEvent::Registered(messaging::WorkerInfo {
@@ -281,6 +450,9 @@ pub mod pallet {
 					}
 				}
 			});
+			PlatformStatuses::<T>::remove(&pubkey);
+			ClockSkewedWorkers::<T>::remove(&pubkey);
+			RevokedAttestations::<T>::remove(&pubkey);
 			// Trigger benchmark anyway
 			let duration = BenchmarkDuration::<T>::get().unwrap_or_default();
 			Self::push_message(SystemEvent::new_worker_event(
@@ -289,6 +461,212 @@ pub mod pallet {
 			));
 			Ok(())
 		}
+
+		/// Rotates the identity key of a registered worker (called by the worker operator)
+		///
+		/// The new key must be attested the same way as `register_worker`, where `pruntime_info`
+		/// carries the new identity. The registry entry (including the benchmark score) is moved
+		/// to the new key, and the mining bindings are migrated atomically by
+		/// `Config::OnWorkerKeyRotated`.
+		///
+		/// Requirements:
+		///  1. `old` is registered and isn't a gatekeeper
+		///  2. The signer is the operator of `old`, and `pruntime_info` keeps the same operator
+		///  3. The new key is not registered yet
+		#[pallet::weight(0)]
+		pub fn rotate_worker_key(
+			origin: OriginFor<T>,
+			old: WorkerPublicKey,
+			pruntime_info: WorkerRegistrationInfo<T::AccountId>,
+			attestation: Attestation,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			// Validate RA report
+			let now = T::UnixTime::now().as_secs().saturated_into::<u64>();
+			let fields = match attestation {
+				Attestation::SgxIas {
+					ra_report,
+					signature,
+					raw_signing_cert,
+				} => validate_ias_report(&ra_report, &signature, &raw_signing_cert, now)
+					.map_err(Into::<Error<T>>::into)?,
+			};
+			// Validate pruntime_info
+			let runtime_info_hash = crate::hashing::blake2_256(&Encode::encode(&pruntime_info));
+			let commit = &fields.report_data[..32];
+			ensure!(
+				&runtime_info_hash == commit,
+				Error::<T>::InvalidRuntimeInfoHash
+			);
+			Self::do_rotate_worker_key(&who, old, pruntime_info, fields.confidence_level, now)
+		}
+
+		/// Sets (or removes with `None`) the delegate key of the sender as a worker operator
+		#[pallet::weight(0)]
+		pub fn set_operator_delegate(
+			origin: OriginFor<T>,
+			delegate: Option<T::AccountId>,
+		) -> DispatchResult {
+			let who = ensure_signed(origin)?;
+			match delegate {
+				Some(delegate) => OperatorDelegate::<T>::insert(&who, delegate),
+				None => OperatorDelegate::<T>::remove(&who),
+			}
+			Ok(())
+		}
+
+		/// Sets the authorities attesting the worker registrations on the other chains
+		///
+		/// `threshold` signatures of the distinct authorities are required to import a worker.
+		/// A zero threshold disables importing. At most `MAX_IMPORT_AUTHORITIES` authorities.
+		#[pallet::weight(0)]
+		pub fn set_import_authorities(
+			origin: OriginFor<T>,
+			authorities: Vec<sr25519::Public>,
+			threshold: u32,
+		) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(
+				threshold as usize <= authorities.len()
+					&& authorities.len() <= MAX_IMPORT_AUTHORITIES,
+				Error::<T>::InvalidInput
+			);
+			let num_authorities = authorities.len() as u32;
+			ImportAuthorities::<T>::put((authorities, threshold));
+			Self::deposit_event(Event::ImportAuthoritiesChanged(num_authorities, threshold));
+			Ok(())
+		}
+
+		/// Imports a worker registered on another chain (called by anyone on behalf of a worker)
+		///
+		/// The worker becomes eligible for binding as if registered here, and is announced to the
+		/// gatekeepers with its imported score like a benchmarked worker.
+		///
+		/// Requirements:
+		///  1. `registration` is signed by at least `threshold` of `ImportAuthorities`, with no
+		///     more signatures than the authorities
+		///  2. It's newer than the last registration imported from the same chain
+		///  3. The worker is not registered on this chain, except by a previous import
+		///  4. The worker is not bound to a miner
+		#[pallet::weight(
+			10_000
+				+ T::DbWeight::get().reads_writes(4, 2)
+				+ SR25519_VERIFY_WEIGHT * signatures.len().min(MAX_IMPORT_AUTHORITIES) as Weight
+		)]
+		pub fn import_worker(
+			origin: OriginFor<T>,
+			registration: ImportedRegistration<T::AccountId>,
+			signatures: Vec<(sr25519::Public, sr25519::Signature)>,
+		) -> DispatchResult {
+			ensure_signed(origin)?;
+			let (authorities, threshold) = ImportAuthorities::<T>::get();
+			ensure!(threshold > 0, Error::<T>::ImportDisabled);
+			ensure!(
+				signatures.len() <= authorities.len(),
+				Error::<T>::TooManyImportSignatures
+			);
+			let data = Self::import_signing_data(&registration);
+			let mut signers: Vec<&sr25519::Public> = Vec::new();
+			for (signer, signature) in signatures.iter() {
+				if !authorities.contains(signer) || signers.contains(&signer) {
+					continue;
+				}
+				ensure!(
+					sp_io::crypto::sr25519_verify(signature, &data, signer),
+					Error::<T>::InvalidSignature
+				);
+				signers.push(signer);
+			}
+			ensure!(
+				signers.len() >= threshold as usize,
+				Error::<T>::InsufficientImportSignatures
+			);
+
+			let chain_id = registration.chain_id;
+			let pubkey = registration.pubkey.clone();
+			match ImportedWorkers::<T>::get(chain_id, &pubkey) {
+				Some(last) => ensure!(registration.registered_at > last, Error::<T>::StaleImport),
+				None => ensure!(
+					!Workers::<T>::contains_key(&pubkey),
+					Error::<T>::DuplicateWorker
+				),
+			}
+			ensure!(
+				!T::WorkerBindingInfo::is_worker_bound(&pubkey),
+				Error::<T>::WorkerBound
+			);
+			let now = T::UnixTime::now().as_millis().saturated_into::<u64>();
+			Workers::<T>::insert(
+				&pubkey,
+				WorkerInfo {
+					pubkey: pubkey.clone(),
+					ecdh_pubkey: registration.ecdh_pubkey,
+					runtime_version: registration.runtime_version,
+					last_updated: now,
+					operator: registration.operator,
+					confidence_level: registration.confidence_level,
+					initial_score: registration.initial_score,
+					features: vec![],
+				},
+			);
+			ImportedWorkers::<T>::insert(chain_id, &pubkey, registration.registered_at);
+			Self::push_message(SystemEvent::new_worker_event(
+				pubkey.clone(),
+				WorkerEvent::Registered(messaging::WorkerInfo {
+					confidence_level: registration.confidence_level,
+				}),
+			));
+			// The score benchmarked on the source chain, or a benchmark here
+			match registration.initial_score {
+				Some(score) => {
+					BenchmarkedAt::<T>::insert(&pubkey, now / 1000);
+					Self::push_message(SystemEvent::new_worker_event(
+						pubkey.clone(),
+						WorkerEvent::BenchScore(score),
+					));
+				}
+				None => {
+					let duration = BenchmarkDuration::<T>::get().unwrap_or_default();
+					Self::push_message(SystemEvent::new_worker_event(
+						pubkey.clone(),
+						WorkerEvent::BenchStart { duration },
+					));
+				}
+			}
+			Self::deposit_event(Event::WorkerImported(chain_id, pubkey));
+			Ok(())
+		}
+
+		/// Revokes the attestation of a worker, e.g. its platform is marked compromised by the
+		/// attestation revocation lists
+		///
+		/// The mining session of the worker is ended at once by `Config::OnAttestationRevoked`,
+		/// and the worker can't be bound again until it's attested again by `register_worker`.
+		#[pallet::weight(0)]
+		pub fn revoke_attestation(origin: OriginFor<T>, worker: WorkerPublicKey) -> DispatchResult {
+			ensure_root(origin)?;
+			ensure!(
+				Workers::<T>::contains_key(&worker),
+				Error::<T>::WorkerNotFound
+			);
+			ensure!(
+				!RevokedAttestations::<T>::get(&worker),
+				Error::<T>::AttestationAlreadyRevoked
+			);
+			RevokedAttestations::<T>::insert(&worker, true);
+			T::OnAttestationRevoked::on_attestation_revoked(&worker);
+			Self::deposit_event(Event::AttestationRevoked(worker));
+			Ok(())
+		}
+	}
+
+	impl<T: Config> Pallet<T> {
+		/// The data signed by the import authorities to attest a registration, bound to the
+		/// genesis of this chain
+		pub fn import_signing_data(registration: &ImportedRegistration<T::AccountId>) -> Vec<u8> {
+			let genesis_hash = frame_system::Pallet::<T>::block_hash(T::BlockNumber::from(0u32));
+			(IMPORT_SIGNING_CONTEXT, genesis_hash, registration).encode()
+		}
 	}
 
 	// TODO.kevin: Move it to mq
@@ -310,6 +688,13 @@ pub mod pallet {
 						.ok_or(Error::<T>::MasterKeyUninitialized)?;
 					&pubkey_copy
 				}
+				MessageOrigin::HostedContract { worker, .. } => {
+					ensure!(
+						Workers::<T>::contains_key(worker),
+						Error::<T>::WorkerNotFound
+					);
+					worker
+				}
 				_ => return Err(Error::<T>::CannotHandleUnknownMessage.into()),
 			};
 			Self::verify_signature(pubkey, message)
@@ -356,12 +741,16 @@ pub mod pallet {
 							val.last_updated = now;
 						}
 					});
+					BenchmarkedAt::<T>::insert(worker_pubkey, now / 1000);
 
 					Self::push_message(SystemEvent::new_worker_event(
 						worker_pubkey.clone(),
 						WorkerEvent::BenchScore(score),
 					));
 				}
+				RegistryEvent::TopicKey { topic, pubkey } => {
+					Self::provision_topic_key(worker_pubkey, topic, pubkey)?;
+				}
 				RegistryEvent::MasterPubkey { master_pubkey } => {
 					let gatekeepers = Gatekeeper::<T>::get();
 					if !gatekeepers.contains(&worker_pubkey) {
@@ -384,13 +773,192 @@ pub mod pallet {
 			Ok(())
 		}
 
-		#[cfg(test)]
+		/// Stores the `TopicKey` record provisioned by a worker, anchoring the topic to the worker
+		///
+		/// The first worker provisioning a topic becomes its provisioner, and is the only one
+		/// allowed to rotate the key later.
+		fn provision_topic_key(
+			worker: &WorkerPublicKey,
+			topic: Vec<u8>,
+			pubkey: Vec<u8>,
+		) -> DispatchResult {
+			ensure!(
+				Workers::<T>::contains_key(worker),
+				Error::<T>::WorkerNotFound
+			);
+			ensure!(
+				!RevokedAttestations::<T>::get(worker),
+				Error::<T>::AttestationAlreadyRevoked
+			);
+			ensure!(
+				!pubkey.is_empty() && pubkey.len() <= MAX_TOPIC_KEY_LEN,
+				Error::<T>::InvalidTopicKey
+			);
+			let epoch = match TopicKeyAnchors::<T>::get(&topic) {
+				Some(anchor) => {
+					ensure!(&anchor.provisioner == worker, Error::<T>::TopicKeyNotOwned);
+					anchor.epoch.saturating_add(1)
+				}
+				None => {
+					ensure!(
+						!TopicKey::<T>::contains_key(&topic),
+						Error::<T>::TopicKeyNotOwned
+					);
+					1
+				}
+			};
+			TopicKeyAnchors::<T>::insert(
+				&topic,
+				TopicKeyAnchor {
+					provisioner: worker.clone(),
+					epoch,
+				},
+			);
+			TopicKey::<T>::insert(&topic, pubkey);
+			Self::deposit_event(Event::TopicKeyProvisioned(topic, worker.clone(), epoch));
+			Ok(())
+		}
+
+		/// Checks if `who` is the operator of the worker, or the delegate of the operator
+		pub fn is_operator_or_delegate(
+			worker: &WorkerInfo<T::AccountId>,
+			who: &T::AccountId,
+		) -> bool {
+			Self::is_operator_or_delegate_of(worker.operator.as_ref(), who)
+		}
+
+		fn is_operator_or_delegate_of(operator: Option<&T::AccountId>, who: &T::AccountId) -> bool {
+			match operator {
+				Some(operator) => {
+					operator == who || OperatorDelegate::<T>::get(operator).as_ref() == Some(who)
+				}
+				None => false,
+			}
+		}
+
+		/// Records the platform status piggybacked on a heartbeat of a worker
+		///
+		/// Emits `WorkerNeedsReattestation` once the reported confidence level falls behind the
+		/// attested one, e.g. the TCB of the platform is out of date.
+		pub fn on_platform_status(worker: &WorkerPublicKey, status: PlatformStatus) {
+			if !Workers::<T>::contains_key(worker) {
+				return;
+			}
+			let was_flagged = Self::needs_reattestation(worker);
+			let confidence_level = status.confidence_level;
+			PlatformStatuses::<T>::insert(worker, status);
+			if !was_flagged && Self::needs_reattestation(worker) {
+				Self::deposit_event(Event::WorkerNeedsReattestation(
+					worker.clone(),
+					confidence_level,
+				));
+			}
+		}
+
+		/// Checks if the platform of a worker reported a worse confidence level than attested
+		pub fn needs_reattestation(worker: &WorkerPublicKey) -> bool {
+			match (
+				Workers::<T>::get(worker),
+				PlatformStatuses::<T>::get(worker),
+			) {
+				(Some(info), Some(status)) => status.confidence_level > info.confidence_level,
+				_ => false,
+			}
+		}
+
+		/// Flags or unflags a worker for its clock skew
+		pub fn set_clock_skewed(worker: &WorkerPublicKey, skewed: bool) {
+			if skewed {
+				ClockSkewedWorkers::<T>::insert(worker, true);
+			} else {
+				ClockSkewedWorkers::<T>::remove(worker);
+			}
+		}
+
+		/// Moves the worker info from `old` to the attested new identity in `pruntime_info`
+		pub(crate) fn do_rotate_worker_key(
+			operator: &T::AccountId,
+			old: WorkerPublicKey,
+			pruntime_info: WorkerRegistrationInfo<T::AccountId>,
+			confidence_level: u8,
+			now: u64,
+		) -> DispatchResult {
+			let new = pruntime_info.pubkey.clone();
+			let old_info = Workers::<T>::get(&old).ok_or(Error::<T>::WorkerNotFound)?;
+			ensure!(
+				old_info.operator.as_ref() == Some(operator),
+				Error::<T>::NotWorkerOperator
+			);
+			ensure!(
+				pruntime_info.operator.as_ref() == Some(operator),
+				Error::<T>::InvalidRuntimeInfo
+			);
+			ensure!(
+				!Workers::<T>::contains_key(&new),
+				Error::<T>::DuplicateWorker
+			);
+			ensure!(
+				!Gatekeeper::<T>::get().contains(&old),
+				Error::<T>::CannotRotateGatekeeper
+			);
+
+			let initial_score = old_info.initial_score;
+			Workers::<T>::remove(&old);
+			PlatformStatuses::<T>::remove(&old);
+			ClockSkewedWorkers::<T>::remove(&old);
+			RevokedAttestations::<T>::remove(&old);
+			if let Some(benchmarked_at) = BenchmarkedAt::<T>::take(&old) {
+				BenchmarkedAt::<T>::insert(&new, benchmarked_at);
+			}
+			Workers::<T>::insert(
+				&new,
+				WorkerInfo {
+					pubkey: new.clone(),
+					ecdh_pubkey: pruntime_info.ecdh_pubkey,
+					runtime_version: pruntime_info.version,
+					last_updated: now,
+					operator: old_info.operator,
+					confidence_level,
+					initial_score,
+					features: pruntime_info.features,
+				},
+			);
+			Self::push_message(SystemEvent::new_worker_event(
+				new.clone(),
+				WorkerEvent::Registered(messaging::WorkerInfo { confidence_level }),
+			));
+			// The hardware is unchanged, so the benchmark score is carried over
+			if let Some(score) = initial_score {
+				Self::push_message(SystemEvent::new_worker_event(
+					new.clone(),
+					WorkerEvent::BenchScore(score),
+				));
+			}
+			T::OnWorkerKeyRotated::on_worker_key_rotated(&old, &new);
+			Self::deposit_event(Event::WorkerKeyRotated(old, new));
+			Ok(())
+		}
+
+		#[cfg(any(test, feature = "runtime-benchmarks"))]
 		pub(crate) fn internal_set_benchmark(worker: &WorkerPublicKey, score: Option<u32>) {
 			Workers::<T>::mutate(worker, |w| {
 				if let Some(w) = w {
 					w.initial_score = score;
 				}
 			});
+			match score {
+				Some(_) => BenchmarkedAt::<T>::insert(worker, T::UnixTime::now().as_secs()),
+				None => BenchmarkedAt::<T>::remove(worker),
+			}
+		}
+
+		#[cfg(test)]
+		pub(crate) fn internal_set_runtime_version(worker: &WorkerPublicKey, version: u32) {
+			Workers::<T>::mutate(worker, |w| {
+				if let Some(w) = w {
+					w.runtime_version = version;
+				}
+			});
 		}
 	}
 
@@ -474,6 +1042,24 @@ pub mod pallet {
 		type Config = T;
 	}
 
+	/// The signing context of the imported registrations
+	const IMPORT_SIGNING_CONTEXT: &[u8] = b"phala/registry/import_worker";
+
+	/// A worker registration on another chain, to be imported by `import_worker`
+	#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+	pub struct ImportedRegistration<AccountId> {
+		/// The source chain the worker is registered on
+		pub chain_id: u32,
+		pub pubkey: WorkerPublicKey,
+		pub ecdh_pubkey: EcdhPublicKey,
+		pub runtime_version: u32,
+		pub operator: Option<AccountId>,
+		pub confidence_level: u8,
+		pub initial_score: Option<u32>,
+		/// The time (in secs) the worker was registered or refreshed on the source chain
+		pub registered_at: u64,
+	}
+
 	#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
 	pub enum Attestation {
 		SgxIas {
@@ -499,6 +1085,24 @@ pub mod pallet {
 		features: Vec<u32>,
 	}
 
+	impl<T: Config> WorkerInfoProvider<T::AccountId> for Pallet<T> {
+		fn worker_info(pubkey: &WorkerPublicKey) -> Option<MiningWorkerInfo<T::AccountId>> {
+			Workers::<T>::get(pubkey).map(|worker| MiningWorkerInfo {
+				operator: worker.operator,
+				confidence_level: worker.confidence_level,
+				initial_score: worker.initial_score,
+				runtime_version: worker.runtime_version,
+				benchmarked_at: BenchmarkedAt::<T>::get(pubkey),
+			})
+		}
+		fn is_operator_or_delegate(
+			worker: &MiningWorkerInfo<T::AccountId>,
+			who: &T::AccountId,
+		) -> bool {
+			Self::is_operator_or_delegate_of(worker.operator.as_ref(), who)
+		}
+	}
+
 	impl<T: Config> From<AttestationError> for Error<T> {
 		fn from(err: AttestationError) -> Self {
 			match err {
@@ -0,0 +1,68 @@
+//! Runtime APIs exposed by the Phala pallets
+
+use codec::Codec;
+use phala_types::WorkerPublicKey;
+use sp_std::vec::Vec;
+
+use crate::mining::{
+	MinerState, MiningStateSnapshot, ReclaimBlocker, SettlementRecord, SettlementSimulation,
+	StartBlocker, TopicInfo,
+};
+
+sp_api::decl_runtime_apis! {
+	/// Queries of the mining pallet for the off-chain tools
+	pub trait MiningApi<AccountId, Balance, BlockNumber>
+	where
+		AccountId: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		/// Estimates the V change, payout and slash of a worker with the given online and offline
+		/// blocks. Returns None if the worker is not registered or benchmarked.
+		fn simulate_settlement(
+			worker: WorkerPublicKey,
+			blocks_online: u32,
+			blocks_offline: u32,
+		) -> Option<SettlementSimulation>;
+
+		/// The balance of an account available to stake as a solo miner, excluding the balance
+		/// frozen by the other locks
+		fn free_stakable_balance(account: AccountId) -> Balance;
+
+		/// The payouts and slashes of a miner from `from_block` to `to_block` (inclusive), as
+		/// `(block, record)` pairs. Only the records still in the pruned history are returned.
+		fn settlements_in_range(
+			miner: AccountId,
+			from_block: BlockNumber,
+			to_block: BlockNumber,
+		) -> Vec<(BlockNumber, SettlementRecord<Balance>)>;
+
+		/// The snapshot of the mining state at the block, to be imported to the genesis of
+		/// another chain with the `mining_state` genesis config
+		fn export_mining_state() -> MiningStateSnapshot<AccountId, Balance>;
+
+		/// The `page`-th page of the miners in `state`
+		fn miners_by_state(state: MinerState, page: u32) -> Vec<AccountId>;
+
+		/// The last updated V of a miner, in the raw U64F64 bits and in the balance scale. The
+		/// clients should display the balance scaled value instead of converting the bits.
+		fn miner_v(miner: AccountId) -> Option<(u128, Balance)>;
+
+		/// The condition blocking a miner from reclaiming its stake, or None if it can reclaim
+		fn why_cannot_reclaim(miner: AccountId) -> Option<ReclaimBlocker>;
+
+		/// The first condition blocking a miner from starting mining with `stake`, or None if it
+		/// can start
+		fn why_cannot_start(miner: AccountId, stake: Balance) -> Option<StartBlocker<Balance>>;
+
+		/// The message queue topics the mining pallet produces or consumes, with the type names
+		/// and protocol versions of their payloads
+		fn topics() -> Vec<TopicInfo>;
+	}
+
+	/// Queries of the stake pool pallet for the off-chain tools
+	pub trait StakePoolApi<AccountId> where AccountId: Codec {
+		/// Derives the miner sub-account of the worker `worker` added to the pool `pid`
+		fn pool_sub_account(pid: u64, worker: WorkerPublicKey) -> AccountId;
+	}
+}
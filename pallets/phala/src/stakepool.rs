@@ -6,7 +6,10 @@ pub mod pallet {
 	use crate::accumulator::Accumulator;
 	use crate::balance_convert::{div as bdiv, mul as bmul, FixedPointConvert};
 	use crate::fixed_point::CodecFixedPoint;
-	use crate::mining;
+	use crate::mining::{
+		self, pool_sub_account, MiningInfoProvider, MiningLifecycleEvent, SettleInfoExt,
+		WorkerInfoProvider,
+	};
 	use crate::registry;
 
 	use fixed::types::U64F64 as FixedPoint;
@@ -19,7 +22,7 @@ pub mod pallet {
 	};
 	use frame_system::pallet_prelude::*;
 	use sp_runtime::{
-		traits::{Saturating, TrailingZeroInput, Zero},
+		traits::{Saturating, Zero},
 		Permill, SaturatedConversion,
 	};
 	use sp_std::{collections::vec_deque::VecDeque, fmt::Display, prelude::*};
@@ -48,6 +51,14 @@ pub mod pallet {
 		type Currency: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
 		type MinContribution: Get<BalanceOf<Self>>;
 		type InsurancePeriod: Get<Self::BlockNumber>;
+		/// The mining status of the pool workers, usually the mining pallet
+		type MiningInfo: MiningInfoProvider<Self::AccountId, BalanceOf<Self>>;
+		/// Converts the origin of an XCM `Transact` to the local account of the beneficiary
+		///
+		/// The reserve-transferred PHA must be deposited to the same account before the
+		/// `stake_via_xcm` call, e.g. by converting the origin with the same `LocationToAccountId`
+		/// as the asset transactor.
+		type XcmStakeOrigin: EnsureOrigin<Self::Origin, Success = Self::AccountId>;
 	}
 
 	#[pallet::pallet]
@@ -85,6 +96,23 @@ pub mod pallet {
 	pub(super) type SubAccountAssignments<T: Config> =
 		StorageMap<_, Twox64Concat, T::AccountId, u64>;
 
+	/// Mapping from the swapped-in workers to the sub-account they took over
+	///
+	/// The sub-account of a worker is derived from its pubkey unless it replaced another worker
+	/// by `swap_worker()`.
+	#[pallet::storage]
+	pub(super) type SwappedWorkerSubAccounts<T: Config> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, T::AccountId>;
+
+	/// The replacement workers approved by the pool owners, keyed by the pool worker to replace
+	///
+	/// The worker operator completes the swap by `mining::swap_worker()`, which consumes the
+	/// approval.
+	#[pallet::storage]
+	#[pallet::getter(fn approved_worker_swap)]
+	pub type ApprovedWorkerSwaps<T: Config> =
+		StorageMap<_, Twox64Concat, WorkerPublicKey, WorkerPublicKey>;
+
 	/// Mapping staker to it's the balance locked in all pools
 	#[pallet::storage]
 	#[pallet::getter(fn stake_ledger)]
@@ -113,6 +141,8 @@ pub mod pallet {
 		PoolCapacitySet(u64, BalanceOf<T>),
 		/// [pid, worker]
 		PoolWorkerAdded(u64, WorkerPublicKey),
+		/// [pid, old_worker, new_worker]
+		PoolWorkerSwapped(u64, WorkerPublicKey, WorkerPublicKey),
 		/// [pid, user, amount]
 		Contribution(u64, T::AccountId, BalanceOf<T>),
 		/// [pid, user, amount]
@@ -123,6 +153,8 @@ pub mod pallet {
 		PoolSlashed(u64, BalanceOf<T>),
 		/// [pid, account, amount]
 		SlashSettled(u64, T::AccountId, BalanceOf<T>),
+		/// [pid, worker, new_worker]. The approval is revoked if `new_worker` is None.
+		PoolWorkerSwapApproved(u64, WorkerPublicKey, Option<WorkerPublicKey>),
 	}
 
 	#[pallet::error]
@@ -190,12 +222,14 @@ pub mod pallet {
 								if (now - info.start_time)
 									> T::InsurancePeriod::get().saturated_into::<u64>()
 								{
-									// stop all worker in this pool
-									// TODO: only stop running workers?
+									// stop all the mining workers in this pool
 									for worker in pool_info.workers {
-										let miner: T::AccountId = pool_sub_account(pid, &worker);
-										// TODO: avoid stop mining multiple times
-										let _ = <mining::pallet::Pallet<T>>::stop_mining(miner);
+										let miner = Self::worker_sub_account(pid, &worker);
+										let is_mining = T::MiningInfo::miner_state(&miner)
+											.map_or(false, |state| state.is_online());
+										if is_mining {
+											let _ = <mining::pallet::Pallet<T>>::stop_mining(miner);
+										}
 									}
 								}
 							}
@@ -259,8 +293,8 @@ pub mod pallet {
 			pubkey: WorkerPublicKey,
 		) -> DispatchResult {
 			let owner = ensure_signed(origin)?;
-			let worker_info =
-				registry::Workers::<T>::get(&pubkey).ok_or(Error::<T>::WorkerNotRegistered)?;
+			let worker_info = <T as mining::Config>::WorkerInfo::worker_info(&pubkey)
+				.ok_or(Error::<T>::WorkerNotRegistered)?;
 
 			// check wheather the owner was bound as operator
 			ensure!(
@@ -322,7 +356,7 @@ pub mod pallet {
 				WorkerAssignments::<T>::get(worker).ok_or(Error::<T>::WorkerDoesNotExist)?;
 			ensure!(pid == lookup_pid, Error::<T>::WorerInAnotherPool);
 			// Remove the worker from the pool (notification suspended)
-			let sub_account: T::AccountId = pool_sub_account(pid, &worker);
+			let sub_account = Self::worker_sub_account(pid, &worker);
 			mining::pallet::Pallet::<T>::unbind_miner(&sub_account, false)?;
 			// Manually clean up the worker, including the pool worker list, and the assignment
 			// indices. (Theoritically we can enable the unbinding notification, and follow the
@@ -331,6 +365,72 @@ pub mod pallet {
 			Ok(())
 		}
 
+		/// Replaces a mining worker in a pool with another worker without interrupting the mining
+		///
+		/// Requires:
+		/// 1. The sender is the pool owner and the operator of the new worker
+		/// 2. The worker is mining in this pool
+		/// 3. The new worker is not bound, and has an equal or better benchmark
+		#[pallet::weight(0)]
+		pub fn swap_worker(
+			origin: OriginFor<T>,
+			pid: u64,
+			worker: WorkerPublicKey,
+			new_worker: WorkerPublicKey,
+		) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			let pool_info = Self::ensure_pool(pid)?;
+			ensure!(pool_info.owner == owner, Error::<T>::UnauthorizedPoolOwner);
+			ensure!(
+				pool_info.workers.contains(&worker),
+				Error::<T>::WorkerDoesNotExist
+			);
+			let new_worker_info = <T as mining::Config>::WorkerInfo::worker_info(&new_worker)
+				.ok_or(Error::<T>::WorkerNotRegistered)?;
+			ensure!(
+				new_worker_info.operator == Some(owner),
+				Error::<T>::UnauthorizedOperator
+			);
+			ensure!(
+				!WorkerAssignments::<T>::contains_key(&new_worker),
+				Error::<T>::WorkerExists
+			);
+			let miner = Self::worker_sub_account(pid, &worker);
+			// The pool bookkeeping is updated by the lifecycle notification
+			mining::pallet::Pallet::<T>::swap_miner_worker(&miner, new_worker)
+		}
+
+		/// Approves the operator of a pool worker to swap it to `new_worker` by
+		/// `mining::swap_worker()`, or revokes the approval if `new_worker` is None
+		///
+		/// Requires:
+		/// 1. The sender is the pool owner
+		/// 2. The worker is in this pool, and the new worker is in no pool
+		#[pallet::weight(0)]
+		pub fn approve_worker_swap(
+			origin: OriginFor<T>,
+			pid: u64,
+			worker: WorkerPublicKey,
+			new_worker: Option<WorkerPublicKey>,
+		) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			let pool_info = Self::ensure_pool(pid)?;
+			ensure!(pool_info.owner == owner, Error::<T>::UnauthorizedPoolOwner);
+			ensure!(
+				pool_info.workers.contains(&worker),
+				Error::<T>::WorkerDoesNotExist
+			);
+			if let Some(new_worker) = &new_worker {
+				ensure!(
+					!WorkerAssignments::<T>::contains_key(new_worker),
+					Error::<T>::WorkerExists
+				);
+			}
+			ApprovedWorkerSwaps::<T>::set(&worker, new_worker.clone());
+			Self::deposit_event(Event::<T>::PoolWorkerSwapApproved(pid, worker, new_worker));
+			Ok(())
+		}
+
 		/// Destroies a stake pool
 		///
 		/// Requires:
@@ -425,61 +525,23 @@ pub mod pallet {
 		#[pallet::weight(0)]
 		pub fn contribute(origin: OriginFor<T>, pid: u64, amount: BalanceOf<T>) -> DispatchResult {
 			let who = ensure_signed(origin)?;
-			let a = amount; // Alias to reduce confusion in the code below
-
-			ensure!(
-				a >= T::MinContribution::get(),
-				Error::<T>::InsufficientContribution
-			);
-			ensure!(
-				<T as Config>::Currency::free_balance(&who) >= a,
-				Error::<T>::InsufficientBalance
-			);
-
-			let mut pool_info = Self::ensure_pool(pid)?;
-			if let Some(cap) = pool_info.cap {
-				ensure!(
-					cap.saturating_sub(pool_info.total_stake) >= a,
-					Error::<T>::StakeExceedsCapacity
-				);
-			}
-			// We don't really want to allow to contribute to a bankrupt StakePool. It can avoid
-			// a lot of weird edge cases when dealing with pending slash.
-			ensure!(
-				pool_info.total_shares == Zero::zero() || pool_info.total_stake > Zero::zero(),
-				Error::<T>::PoolBankrupt
-			);
-
-			let info_key = (pid.clone(), who.clone());
-			// Clear the pending reward before adding stake, if applies
-			let mut user_info = match Self::pool_stakers(&info_key) {
-				Some(mut user_info) => {
-					pool_info.settle_user_pending_reward(&mut user_info);
-					Self::maybe_settle_slash(&pool_info, &mut user_info);
-					user_info
-				}
-				None => UserStakeInfo {
-					user: who.clone(),
-					locked: Zero::zero(),
-					shares: Zero::zero(),
-					available_rewards: Zero::zero(),
-					reward_debt: Zero::zero(),
-				},
-			};
-			pool_info.add_stake(&mut user_info, a);
-
-			// Persist
-			PoolStakers::<T>::insert(&info_key, &user_info);
-			// Lock the funds
-			Self::ledger_accrue(&who, a);
-
-			// We have new free stake now, try handle the waitting withdraw queue
-			Self::try_process_withdraw_queue(&mut pool_info);
+			Self::do_contribute(who, pid, amount)
+		}
 
-			// Persist
-			StakePools::<T>::insert(&pid, &pool_info);
-			Self::deposit_event(Event::<T>::Contribution(pid, who, a));
-			Ok(())
+		/// Contributes the PHA reserve-transferred from another chain to a pool
+		///
+		/// Dispatched by an XCM `Transact` following the deposit of the assets. The origin is
+		/// converted to the beneficiary account by `XcmStakeOrigin`. If the stake can't be
+		/// contributed, the error is returned to the XCM executor, so that the sender can trap or
+		/// refund the deposited assets.
+		#[pallet::weight(0)]
+		pub fn stake_via_xcm(
+			origin: OriginFor<T>,
+			pid: u64,
+			amount: BalanceOf<T>,
+		) -> DispatchResult {
+			let who = T::XcmStakeOrigin::ensure_origin(origin)?;
+			Self::do_contribute(who, pid, amount)
 		}
 
 		/// Demands the return of some stake from a pool.
@@ -538,24 +600,22 @@ pub mod pallet {
 			stake: BalanceOf<T>,
 		) -> DispatchResult {
 			let owner = ensure_signed(origin)?;
-			let mut pool_info = Self::ensure_pool(pid)?;
-			// origin must be owner of pool
-			ensure!(pool_info.owner == owner, Error::<T>::UnauthorizedPoolOwner);
-			// check free stake
-			ensure!(
-				pool_info.free_stake >= stake,
-				Error::<T>::InsufficientFreeStake
-			);
-			// check wheather we have add this worker
-			ensure!(
-				pool_info.workers.contains(&worker),
-				Error::<T>::WorkerDoesNotExist
-			);
-			let miner: T::AccountId = pool_sub_account(pid, &worker);
-			mining::pallet::Pallet::<T>::start_mining(miner.clone(), stake)?;
-			pool_info.free_stake = pool_info.free_stake.saturating_sub(stake);
-			StakePools::<T>::insert(&pid, &pool_info);
-			Ok(())
+			Self::do_start_mining(owner, pid, worker, stake, false)
+		}
+
+		/// Starts a miner in trial mining on behalf of the stake pool
+		///
+		/// Same as `start_mining`, but with the reduced minimal stake and the capped Ve and payouts
+		/// of `mining::TrialMining` until the trial is passed.
+		#[pallet::weight(0)]
+		pub fn start_trial_mining(
+			origin: OriginFor<T>,
+			pid: u64,
+			worker: WorkerPublicKey,
+			stake: BalanceOf<T>,
+		) -> DispatchResult {
+			let owner = ensure_signed(origin)?;
+			Self::do_start_mining(owner, pid, worker, stake, true)
 		}
 
 		/// Stops a miner on behalf of the stake pool
@@ -578,7 +638,7 @@ pub mod pallet {
 				pool_info.workers.contains(&worker),
 				Error::<T>::WorkerDoesNotExist
 			);
-			let miner: T::AccountId = pool_sub_account(pid, &worker);
+			let miner = Self::worker_sub_account(pid, &worker);
 			// TODO: handle slashed amount
 			<mining::pallet::Pallet<T>>::stop_mining(miner)?;
 
@@ -590,6 +650,66 @@ pub mod pallet {
 	where
 		BalanceOf<T>: FixedPointConvert + Display,
 	{
+		/// Contributes `amount` from the free balance of `who` to a pool
+		///
+		/// Nothing is changed if it fails.
+		fn do_contribute(who: T::AccountId, pid: u64, amount: BalanceOf<T>) -> DispatchResult {
+			let a = amount; // Alias to reduce confusion in the code below
+			ensure!(
+				a >= T::MinContribution::get(),
+				Error::<T>::InsufficientContribution
+			);
+			ensure!(
+				<T as Config>::Currency::free_balance(&who) >= a,
+				Error::<T>::InsufficientBalance
+			);
+
+			let mut pool_info = Self::ensure_pool(pid)?;
+			if let Some(cap) = pool_info.cap {
+				ensure!(
+					cap.saturating_sub(pool_info.total_stake) >= a,
+					Error::<T>::StakeExceedsCapacity
+				);
+			}
+			// We don't really want to allow to contribute to a bankrupt StakePool. It can avoid
+			// a lot of weird edge cases when dealing with pending slash.
+			ensure!(
+				pool_info.total_shares == Zero::zero() || pool_info.total_stake > Zero::zero(),
+				Error::<T>::PoolBankrupt
+			);
+
+			let info_key = (pid.clone(), who.clone());
+			// Clear the pending reward before adding stake, if applies
+			let mut user_info = match Self::pool_stakers(&info_key) {
+				Some(mut user_info) => {
+					pool_info.settle_user_pending_reward(&mut user_info);
+					Self::maybe_settle_slash(&pool_info, &mut user_info);
+					user_info
+				}
+				None => UserStakeInfo {
+					user: who.clone(),
+					locked: Zero::zero(),
+					shares: Zero::zero(),
+					available_rewards: Zero::zero(),
+					reward_debt: Zero::zero(),
+				},
+			};
+			pool_info.add_stake(&mut user_info, a);
+
+			// Persist
+			PoolStakers::<T>::insert(&info_key, &user_info);
+			// Lock the funds
+			Self::ledger_accrue(&who, a);
+
+			// We have new free stake now, try handle the waitting withdraw queue
+			Self::try_process_withdraw_queue(&mut pool_info);
+
+			// Persist
+			StakePools::<T>::insert(&pid, &pool_info);
+			Self::deposit_event(Event::<T>::Contribution(pid, who, a));
+			Ok(())
+		}
+
 		/// Adds up the newly received reward to `reward_acc`
 		fn handle_pool_new_reward(
 			pool_info: &mut PoolInfo<T::AccountId, BalanceOf<T>>,
@@ -717,6 +837,42 @@ pub mod pallet {
 			}
 		}
 
+		/// Starts a miner of the pool, optionally in trial mining
+		fn do_start_mining(
+			owner: T::AccountId,
+			pid: u64,
+			worker: WorkerPublicKey,
+			stake: BalanceOf<T>,
+			trial: bool,
+		) -> DispatchResult {
+			let mut pool_info = Self::ensure_pool(pid)?;
+			// origin must be owner of pool
+			ensure!(pool_info.owner == owner, Error::<T>::UnauthorizedPoolOwner);
+			// check free stake
+			ensure!(
+				pool_info.free_stake >= stake,
+				Error::<T>::InsufficientFreeStake
+			);
+			// check wheather we have add this worker
+			ensure!(
+				pool_info.workers.contains(&worker),
+				Error::<T>::WorkerDoesNotExist
+			);
+			let miner = Self::worker_sub_account(pid, &worker);
+			if trial {
+				mining::pallet::Pallet::<T>::start_trial_mining(miner.clone(), stake)?;
+			} else {
+				mining::pallet::Pallet::<T>::start_mining_with_refund(
+					miner.clone(),
+					stake,
+					pool_info.owner.clone(),
+				)?;
+			}
+			pool_info.free_stake = pool_info.free_stake.saturating_sub(stake);
+			StakePools::<T>::insert(&pid, &pool_info);
+			Ok(())
+		}
+
 		/// Gets the pool record by `pid`. Returns error if not exist
 		fn ensure_pool(pid: u64) -> Result<PoolInfo<T::AccountId, BalanceOf<T>>, Error<T>> {
 			Self::stake_pools(&pid).ok_or(Error::<T>::PoolDoesNotExist)
@@ -751,12 +907,40 @@ pub mod pallet {
 			}
 		}
 
+		/// Gets the miner sub-account currently driven by `worker` in the pool
+		fn worker_sub_account(pid: u64, worker: &WorkerPublicKey) -> T::AccountId {
+			SwappedWorkerSubAccounts::<T>::get(worker)
+				.unwrap_or_else(|| pool_sub_account(pid, worker))
+		}
+
+		/// Moves the pool worker entry and its sub-account to the replacement worker
+		///
+		/// It assumes the old worker is in a pool.
+		fn swap_worker_in_pool(from: &WorkerPublicKey, to: &WorkerPublicKey) {
+			let pid = WorkerAssignments::<T>::take(from).expect("Worker must be in a pool; qed.");
+			let sub_account = Self::worker_sub_account(pid, from);
+			ApprovedWorkerSwaps::<T>::remove(from);
+			SwappedWorkerSubAccounts::<T>::remove(from);
+			SwappedWorkerSubAccounts::<T>::insert(to, sub_account);
+			WorkerAssignments::<T>::insert(to, pid);
+			StakePools::<T>::mutate(pid, |value| {
+				if let Some(pool) = value {
+					for w in pool.workers.iter_mut().filter(|w| *w == from) {
+						*w = to.clone();
+					}
+				}
+			});
+			Self::deposit_event(Event::<T>::PoolWorkerSwapped(pid, from.clone(), to.clone()));
+		}
+
 		/// Removes a worker from a pool, either intentially or unintentially.
 		///
 		/// It assumes the worker is already in a pool.
 		fn remove_worker_from_pool(worker: &WorkerPublicKey) {
 			let pid = WorkerAssignments::<T>::take(worker).expect("Worker must be in a pool; qed.");
-			let sub_account: T::AccountId = pool_sub_account(pid, worker);
+			let sub_account = Self::worker_sub_account(pid, worker);
+			ApprovedWorkerSwaps::<T>::remove(worker);
+			SwappedWorkerSubAccounts::<T>::remove(worker);
 			SubAccountAssignments::<T>::remove(sub_account);
 			StakePools::<T>::mutate(pid, |value| {
 				if let Some(pool) = value {
@@ -784,48 +968,104 @@ pub mod pallet {
 		}
 	}
 
-	impl<T: Config> mining::OnReward for Pallet<T>
+	impl<T: Config> mining::MiningLifecycleObserver<T::AccountId, BalanceOf<T>> for Pallet<T>
+	where
+		T: mining::Config,
+		BalanceOf<T>: FixedPointConvert + Display,
+	{
+		fn on_mining_event(event: &MiningLifecycleEvent<T::AccountId, BalanceOf<T>>) {
+			// The solo miners of the mining pallet are not in any pool
+			let is_solo = |miner: &T::AccountId| T::MiningInfo::is_solo_miner(miner);
+			match event {
+				MiningLifecycleEvent::Settled { settle, .. } => {
+					Self::on_reward(sp_std::slice::from_ref(*settle))
+				}
+				MiningLifecycleEvent::WorkerSwapped { miner, from, to }
+				| MiningLifecycleEvent::WorkerKeyRotated { miner, from, to } => {
+					if !is_solo(miner) {
+						Self::swap_worker_in_pool(from, to)
+					}
+				}
+				MiningLifecycleEvent::Unbound {
+					miner,
+					worker,
+					force,
+				} => {
+					if !is_solo(miner) {
+						Self::on_unbound(worker, *force)
+					}
+				}
+				MiningLifecycleEvent::Reclaimed {
+					miner,
+					orig_stake,
+					slashed,
+					..
+				} => {
+					if !is_solo(miner) {
+						Self::on_reclaim(miner, *orig_stake, *slashed)
+					}
+				}
+				_ => (),
+			}
+		}
+	}
+
+	impl<T: Config> mining::StakePoolRouting<BalanceOf<T>> for Pallet<T>
+	where
+		T: mining::Config,
+		BalanceOf<T>: FixedPointConvert + Display,
+	{
+		fn pool_of(worker: &WorkerPublicKey) -> Option<u64> {
+			WorkerAssignments::<T>::get(worker)
+		}
+
+		fn is_swap_approved(worker: &WorkerPublicKey, new_worker: &WorkerPublicKey) -> bool {
+			ApprovedWorkerSwaps::<T>::get(worker).as_ref() == Some(new_worker)
+		}
+
+		fn credit_pool_reward(pid: u64, reward: BalanceOf<T>) {
+			// The pool may be gone. The reward stays in the subsidy pool then.
+			if let Some(mut pool_info) = Self::stake_pools(pid) {
+				Self::handle_pool_new_reward(&mut pool_info, reward);
+				StakePools::<T>::insert(&pid, &pool_info);
+			}
+		}
+	}
+
+	impl<T: Config> Pallet<T>
 	where
+		T: mining::Config,
 		BalanceOf<T>: FixedPointConvert + Display,
 	{
 		/// Called when gk send new payout information.
 		/// Append specific miner's reward balance of current round,
 		/// would be clear once pool was updated
-		fn on_reward(settle: &Vec<SettleInfo>) {
+		fn on_reward(settle: &[SettleInfo]) {
 			for info in settle {
-				let pid = WorkerAssignments::<T>::get(&info.pubkey)
-					.expect("Mining workers must be in the pool; qed.");
+				// Not a pool worker (e.g. a solo miner paid by the mining pallet)
+				let pid = match WorkerAssignments::<T>::get(&info.pubkey) {
+					Some(pid) => pid,
+					None => continue,
+				};
 				let mut pool_info = Self::ensure_pool(pid).expect("Stake pool must exist; qed.");
 
-				let payout_fixed = FixedPoint::from_bits(info.payout);
+				let payout_fixed = info.payout_bits().to_fixed();
 				let reward = BalanceOf::<T>::from_fixed(&payout_fixed);
 				Self::handle_pool_new_reward(&mut pool_info, reward);
 				StakePools::<T>::insert(&pid, &pool_info);
 			}
 		}
-	}
 
-	impl<T: Config> mining::OnUnbound for Pallet<T>
-	where
-		T: mining::Config,
-		BalanceOf<T>: FixedPointConvert + Display,
-	{
 		fn on_unbound(worker: &WorkerPublicKey, _force: bool) {
 			// Usually called on worker force unbinding (force == true), but it's also possible
 			// that the user unbind from the mining pallet directly.
 
 			// Warning: when using Mining & StakePool pallets together, here we assume all the
-			// miners are only registered by StakePool. So we don't bother to double check if the
-			// worker exists.
+			// miners except the solo miners are registered by StakePool. So we don't bother to
+			// double check if the worker exists.
 			Self::remove_worker_from_pool(worker);
 		}
-	}
 
-	impl<T: Config> mining::OnReclaim<T::AccountId, BalanceOf<T>> for Pallet<T>
-	where
-		T: mining::Config,
-		BalanceOf<T>: FixedPointConvert + Display,
-	{
 		/// Called when worker was cleanuped.
 		///
 		/// After the cool down ends, worker was cleaned up, whose contributed balance would be
@@ -875,17 +1115,6 @@ pub mod pallet {
 		}
 	}
 
-	fn pool_sub_account<T>(pid: u64, pubkey: &WorkerPublicKey) -> T
-	where
-		T: Encode + Decode + Default,
-	{
-		let hash = crate::hashing::blake2_256(&(pid, pubkey).encode());
-		// stake pool miner
-		(b"spm/", hash)
-			.using_encoded(|b| T::decode(&mut TrailingZeroInput::new(b)))
-			.unwrap_or_default()
-	}
-
 	#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
 	pub struct PoolInfo<AccountId: Default, Balance> {
 		/// Pool ID
@@ -1096,9 +1325,9 @@ pub mod pallet {
 
 		use super::*;
 		use crate::mock::{
-			ecdh_pubkey, elapse_cool_down, new_test_ext, set_block_1, setup_workers,
-			setup_workers_linked_operators, take_events, worker_pubkey, Balance, BlockNumber,
-			Event as TestEvent, Origin, Test, DOLLARS,
+			ecdh_pubkey, elapse_cool_down, execute_with_invariants, new_test_ext, set_block_1,
+			setup_workers, setup_workers_linked_operators, take_events, worker_pubkey, Balance,
+			BlockNumber, Event as TestEvent, Origin, Test, DOLLARS,
 		};
 		// Pallets
 		use crate::mock::{
@@ -1215,7 +1444,7 @@ pub mod pallet {
 
 		#[test]
 		fn test_start_mining() {
-			new_test_ext().execute_with(|| {
+			execute_with_invariants(|| {
 				set_block_1();
 				assert_ok!(PhalaStakePool::create(Origin::signed(1)));
 				// Cannot start mining wihtout a bound worker
@@ -1268,7 +1497,7 @@ pub mod pallet {
 
 		#[test]
 		fn test_force_unbind() {
-			new_test_ext().execute_with(|| {
+			execute_with_invariants(|| {
 				set_block_1();
 				setup_workers_linked_operators(2);
 				setup_pool_with_workers(1, &[1]); // pid = 0
@@ -1340,6 +1569,38 @@ pub mod pallet {
 			});
 		}
 
+		#[test]
+		fn test_unbind_by_operator_delegate() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers_linked_operators(1);
+				setup_pool_with_workers(1, &[1]); // pid = 0
+				let sub_account = pool_sub_account(0, &worker_pubkey(1));
+				// Unknown keys can't unbind
+				assert_noop!(
+					PhalaMining::unbind(Origin::signed(201), sub_account),
+					mining::Error::<Test>::BadSender
+				);
+				// The operator (account1) delegates the routine operations to account201
+				assert_ok!(PhalaRegistry::set_operator_delegate(
+					Origin::signed(1),
+					Some(201)
+				));
+				assert_eq!(PhalaRegistry::operator_delegate(1), Some(201));
+				assert_ok!(PhalaMining::unbind(Origin::signed(201), sub_account));
+				assert_eq!(
+					WorkerAssignments::<Test>::contains_key(&worker_pubkey(1)),
+					false
+				);
+				// The delegate can be revoked by the operator
+				assert_ok!(PhalaRegistry::set_operator_delegate(
+					Origin::signed(1),
+					None
+				));
+				assert_eq!(PhalaRegistry::operator_delegate(1), None);
+			});
+		}
+
 		#[test]
 		fn test_pool_cap() {
 			new_test_ext().execute_with(|| {
@@ -1494,7 +1755,7 @@ pub mod pallet {
 				));
 				let sub_account1: u64 = pool_sub_account(0, &worker_pubkey(1));
 				let miner = PhalaMining::miners(sub_account1).unwrap();
-				let ve = FixedPoint::from_bits(miner.ve);
+				let ve = miner.ve.to_fixed();
 				assert_eq!(ve, fp!(750.45));
 				// Simulate a slash of 50%
 				let _ = take_events();
@@ -1511,9 +1772,23 @@ pub mod pallet {
 				assert_matches!(
 					ev.as_slice(),
 					[
-						TestEvent::PhalaMining(mining::Event::MinerSettled(_, v, 0)),
+						TestEvent::PhalaMining(mining::Event::MinerSettled(_, v, 0, _, _)),
+						TestEvent::PhalaMining(mining::Event::MinerStateChanged(
+							_,
+							mining::MinerState::MiningIdle,
+							mining::MinerState::MiningCoolingDown,
+							_
+						)),
 						TestEvent::PhalaMining(mining::Event::MinerStopped(_)),
+						TestEvent::PhalaMining(mining::Event::MinerStateChanged(
+							_,
+							mining::MinerState::MiningCoolingDown,
+							mining::MinerState::Ready,
+							_
+						)),
 						TestEvent::PhalaStakePool(Event::PoolSlashed(0, slashed)),
+						TestEvent::PhalaMining(mining::Event::MinerWithdrew(_, _)),
+						TestEvent::PhalaMining(mining::Event::MinerStakeSlashed(_, _)),
 						TestEvent::PhalaMining(mining::Event::MinerReclaimed(_, _, _))
 					]
 					if FixedPoint::from_bits(*v) == ve / 2
@@ -1559,7 +1834,7 @@ pub mod pallet {
 					500 * DOLLARS
 				));
 				let miner = PhalaMining::miners(sub_account1).unwrap();
-				let ve = FixedPoint::from_bits(miner.ve);
+				let ve = miner.ve.to_fixed();
 				let _ = take_events();
 				simulate_v_update(1, (ve / 2).to_bits());
 				// Full stop & settle
@@ -1574,9 +1849,26 @@ pub mod pallet {
 				assert_matches!(
 					ev.as_slice(),
 					[
-						TestEvent::PhalaMining(mining::Event::MinerSettled(_, _, 0)),
+						TestEvent::PhalaMining(mining::Event::MinerSettled(_, _, 0, _, _)),
+						TestEvent::PhalaMining(mining::Event::MinerStateChanged(
+							_,
+							mining::MinerState::MiningIdle,
+							mining::MinerState::MiningCoolingDown,
+							_
+						)),
 						TestEvent::PhalaMining(mining::Event::MinerStopped(_)),
+						TestEvent::PhalaMining(mining::Event::MinerStateChanged(
+							_,
+							mining::MinerState::MiningCoolingDown,
+							mining::MinerState::Ready,
+							_
+						)),
 						TestEvent::PhalaStakePool(Event::PoolSlashed(0, 250000000000001)),
+						TestEvent::PhalaMining(mining::Event::MinerWithdrew(_, 249999999999999)),
+						TestEvent::PhalaMining(mining::Event::MinerStakeSlashed(
+							_,
+							250000000000001
+						)),
 						TestEvent::PhalaMining(mining::Event::MinerReclaimed(
 							_,
 							500000000000000,
@@ -1657,9 +1949,38 @@ pub mod pallet {
 			});
 		}
 
+		#[test]
+		fn test_stake_via_xcm() {
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				assert_ok!(PhalaStakePool::create(Origin::signed(1))); // pid = 0
+				// Contributed as the converted account
+				assert_ok!(PhalaStakePool::stake_via_xcm(
+					Origin::signed(2),
+					0,
+					10 * DOLLARS
+				));
+				assert_eq!(
+					PhalaStakePool::stake_pools(0).unwrap().total_stake,
+					10 * DOLLARS
+				);
+				assert_eq!(PhalaStakePool::stake_ledger(2), Some(10 * DOLLARS));
+				// The failure is returned to the XCM executor, leaving the stake untouched
+				assert_noop!(
+					PhalaStakePool::stake_via_xcm(Origin::signed(2), 1, 10 * DOLLARS),
+					Error::<Test>::PoolDoesNotExist
+				);
+				assert_eq!(PhalaStakePool::stake_ledger(2), Some(10 * DOLLARS));
+				// Not converted to an account
+				assert_noop!(
+					PhalaStakePool::stake_via_xcm(Origin::root(), 0, 10 * DOLLARS),
+					sp_runtime::DispatchError::BadOrigin
+				);
+			});
+		}
+
 		#[test]
 		fn test_reward_management() {
-			use crate::mining::pallet::OnReward;
 			new_test_ext().execute_with(|| {
 				set_block_1();
 				setup_workers(1);
@@ -1784,7 +2105,6 @@ pub mod pallet {
 
 		#[test]
 		fn test_drained_subsidy_pool_noop() {
-			use crate::mining::pallet::OnReward;
 			new_test_ext().execute_with(|| {
 				set_block_1();
 				setup_workers(1);
@@ -1814,7 +2134,6 @@ pub mod pallet {
 
 		#[test]
 		fn test_withdraw() {
-			use crate::mining::pallet::OnReclaim;
 			new_test_ext().execute_with(|| {
 				set_block_1();
 				setup_workers(2);
@@ -1988,7 +2307,7 @@ pub mod pallet {
 
 		#[test]
 		fn test_full_procedure() {
-			new_test_ext().execute_with(|| {
+			execute_with_invariants(|| {
 				set_block_1();
 				let worker1 = worker_pubkey(1);
 				let worker2 = worker_pubkey(2);
@@ -2124,7 +2443,7 @@ pub mod pallet {
 
 				// Slash pool 0 to 90%
 				let miner0 = PhalaMining::miners(sub_account1).unwrap();
-				let ve = FixedPoint::from_bits(miner0.ve);
+				let ve = miner0.ve.to_fixed();
 				simulate_v_update(1, (ve * fp!(0.9)).to_bits());
 
 				// Stop mining
@@ -2187,6 +2506,261 @@ pub mod pallet {
 		/// Sets up a stakepool with the given workers added.
 		///
 		/// Returns the pool id.
+		#[test]
+		fn test_cool_down_ended_notification() {
+			use crate::mock::take_messages;
+			use phala_types::messaging::{SystemEvent, WorkerEvent, WorkerEventWithKey};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				setup_pool_with_workers(1, &[1]); // pid = 0
+				assert_ok!(PhalaStakePool::contribute(
+					Origin::signed(1),
+					0,
+					100 * DOLLARS
+				));
+				assert_ok!(PhalaStakePool::start_mining(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1),
+					100 * DOLLARS
+				));
+				assert_ok!(PhalaStakePool::stop_mining(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1)
+				));
+				elapse_cool_down();
+				let _ = take_messages();
+				let sub_account1: u64 = pool_sub_account(0, &worker_pubkey(1));
+				assert_ok!(PhalaMining::reclaim(Origin::signed(1), sub_account1));
+				// The worker is told it can mine again
+				let messages = take_messages();
+				assert!(messages.iter().any(|m| matches!(
+					m.decode_payload::<SystemEvent>(),
+					Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+						pubkey,
+						event: WorkerEvent::CoolDownEnded,
+						..
+					})) if pubkey == worker_pubkey(1)
+				)));
+			});
+		}
+
+		#[test]
+		fn test_release_locked_pool_rewards() {
+			use crate::mock::elapse_seconds;
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(1);
+				setup_pool_with_workers(1, &[1]); // pid = 0
+				assert_ok!(PhalaStakePool::contribute(
+					Origin::signed(1),
+					0,
+					100 * DOLLARS
+				));
+				assert_ok!(PhalaStakePool::start_mining(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1),
+					100 * DOLLARS
+				));
+				assert_ok!(PhalaMining::set_reward_lockup(
+					Origin::root(),
+					Some(mining::RewardLockupConfig {
+						percentage: Permill::from_percent(50),
+						duration: 100,
+					})
+				));
+				simulate_settlement(1, fp!(1).to_bits(), fp!(200).to_bits());
+				// The locked part belongs to the pool rather than the keyless sub-account
+				let sub_account1: u64 = pool_sub_account(0, &worker_pubkey(1));
+				assert!(
+					PhalaMining::locked_rewards(mining::RewardBeneficiary::Owner(sub_account1))
+						.is_empty()
+				);
+				assert_eq!(
+					PhalaMining::locked_rewards(mining::RewardBeneficiary::Pool(0)).len(),
+					1
+				);
+				let pool = PhalaStakePool::stake_pools(0).unwrap();
+				assert_eq!(pool.reward_acc.get(), fp!(1));
+				assert_noop!(
+					PhalaMining::release_pool_rewards(Origin::signed(2), 0),
+					mining::Error::<Test>::NoUnlockedRewards
+				);
+				// Anyone can release the unlocked rewards to the stakers
+				elapse_seconds(100);
+				assert_ok!(PhalaMining::release_pool_rewards(Origin::signed(2), 0));
+				assert!(PhalaMining::locked_rewards(mining::RewardBeneficiary::Pool(0)).is_empty());
+				let pool = PhalaStakePool::stake_pools(0).unwrap();
+				assert_eq!(pool.reward_acc.get(), fp!(2));
+				let staker1 = PhalaStakePool::pool_stakers((0, 1)).unwrap();
+				assert_eq!(pool.pending_reward(&staker1), 200 * DOLLARS);
+			});
+		}
+
+		#[test]
+		fn test_swap_worker() {
+			use crate::mock::take_messages;
+			use phala_types::messaging::{SystemEvent, WorkerEvent, WorkerEventWithKey};
+			new_test_ext().execute_with(|| {
+				set_block_1();
+				setup_workers(2);
+				setup_pool_with_workers(1, &[1]); // pid = 0
+				let sub_account1: u64 = pool_sub_account(0, &worker_pubkey(1));
+				assert_ok!(PhalaStakePool::contribute(
+					Origin::signed(1),
+					0,
+					500 * DOLLARS
+				));
+				assert_ok!(PhalaStakePool::start_mining(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1),
+					100 * DOLLARS
+				));
+				// Only the pool owner can swap
+				assert_noop!(
+					PhalaStakePool::swap_worker(
+						Origin::signed(2),
+						0,
+						worker_pubkey(1),
+						worker_pubkey(2)
+					),
+					Error::<Test>::UnauthorizedPoolOwner
+				);
+				// Nor can the operator bypass the pool without the approval
+				assert_noop!(
+					PhalaMining::swap_worker(Origin::signed(1), sub_account1, worker_pubkey(2)),
+					mining::Error::<Test>::PoolMinerNotSwappable
+				);
+				// Cannot swap to a worker with a lower benchmark
+				PhalaRegistry::internal_set_benchmark(&worker_pubkey(2), Some(0));
+				assert_noop!(
+					PhalaStakePool::swap_worker(
+						Origin::signed(1),
+						0,
+						worker_pubkey(1),
+						worker_pubkey(2)
+					),
+					mining::Error::<Test>::InferiorBenchmark
+				);
+				PhalaRegistry::internal_set_benchmark(&worker_pubkey(2), Some(1));
+				assert_ok!(PhalaStakePool::swap_worker(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1),
+					worker_pubkey(2)
+				));
+				// The sub-account keeps mining with the new worker
+				assert_eq!(
+					PhalaMining::ensure_worker_bound(&worker_pubkey(2)).ok(),
+					Some(sub_account1)
+				);
+				assert_eq!(
+					PhalaMining::miners(&sub_account1).unwrap().state,
+					mining::MinerState::MiningIdle
+				);
+				assert_eq!(WorkerAssignments::<Test>::get(&worker_pubkey(1)), None);
+				assert_eq!(WorkerAssignments::<Test>::get(&worker_pubkey(2)), Some(0));
+				let pool = PhalaStakePool::stake_pools(0).unwrap();
+				assert_eq!(pool.workers, vec![worker_pubkey(2)]);
+				// The session is transferred to the new worker
+				let messages = take_messages();
+				assert!(messages.iter().any(|m| matches!(
+					m.decode_payload::<SystemEvent>(),
+					Some(SystemEvent::WorkerEvent(WorkerEventWithKey {
+						pubkey,
+						event: WorkerEvent::SessionTransferred { from, .. },
+						..
+					})) if pubkey == worker_pubkey(2) && from == worker_pubkey(1)
+				)));
+				// Settlement and stopping follow the new worker
+				simulate_v_update(2, fp!(1).to_bits());
+				assert_ok!(PhalaStakePool::stop_mining(
+					Origin::signed(1),
+					0,
+					worker_pubkey(2)
+				));
+				assert_eq!(
+					PhalaMining::miners(&sub_account1).unwrap().state,
+					mining::MinerState::MiningCoolingDown
+				);
+			});
+		}
+
+		#[test]
+		fn test_approved_worker_swap() {
+			execute_with_invariants(|| {
+				set_block_1();
+				setup_workers(2);
+				setup_pool_with_workers(1, &[1]); // pid = 0
+				let sub_account1: u64 = pool_sub_account(0, &worker_pubkey(1));
+				assert_ok!(PhalaStakePool::contribute(
+					Origin::signed(1),
+					0,
+					500 * DOLLARS
+				));
+				assert_ok!(PhalaStakePool::start_mining(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1),
+					100 * DOLLARS
+				));
+				// The workers are handed over to another operator
+				for i in 1..=2 {
+					assert_ok!(PhalaRegistry::force_register_worker(
+						Origin::root(),
+						worker_pubkey(i),
+						ecdh_pubkey(1),
+						Some(2)
+					));
+					PhalaRegistry::internal_set_benchmark(&worker_pubkey(i), Some(1));
+				}
+				let swap = || {
+					PhalaMining::swap_worker(Origin::signed(2), sub_account1, worker_pubkey(2))
+				};
+				assert_noop!(swap(), mining::Error::<Test>::PoolMinerNotSwappable);
+				// Only the pool owner can approve
+				assert_noop!(
+					PhalaStakePool::approve_worker_swap(
+						Origin::signed(2),
+						0,
+						worker_pubkey(1),
+						Some(worker_pubkey(2))
+					),
+					Error::<Test>::UnauthorizedPoolOwner
+				);
+				assert_ok!(PhalaStakePool::approve_worker_swap(
+					Origin::signed(1),
+					0,
+					worker_pubkey(1),
+					Some(worker_pubkey(2))
+				));
+				assert!(take_events().contains(&TestEvent::PhalaStakePool(
+					Event::PoolWorkerSwapApproved(0, worker_pubkey(1), Some(worker_pubkey(2)))
+				)));
+				// The operator of both workers completes the swap
+				assert_noop!(
+					PhalaMining::swap_worker(Origin::signed(1), sub_account1, worker_pubkey(2)),
+					mining::Error::<Test>::BadSender
+				);
+				assert_ok!(swap());
+				assert_eq!(
+					PhalaMining::ensure_worker_bound(&worker_pubkey(2)).ok(),
+					Some(sub_account1)
+				);
+				assert_eq!(
+					PhalaMining::miners(&sub_account1).unwrap().state,
+					mining::MinerState::MiningIdle
+				);
+				assert_eq!(WorkerAssignments::<Test>::get(&worker_pubkey(2)), Some(0));
+				// The approval is consumed
+				assert_eq!(PhalaStakePool::approved_worker_swap(&worker_pubkey(1)), None);
+			});
+		}
+
 		fn setup_pool_with_workers(owner: u64, workers: &[u8]) -> u64 {
 			let pid = PhalaStakePool::pool_count();
 			assert_ok!(PhalaStakePool::create(Origin::signed(owner)));
@@ -2201,6 +2775,10 @@ pub mod pallet {
 		}
 
 		fn simulate_v_update(worker: u8, v_bits: u128) {
+			simulate_settlement(worker, v_bits, 0);
+		}
+
+		fn simulate_settlement(worker: u8, v_bits: u128, payout_bits: u128) {
 			use phala_types::messaging::{
 				DecodedMessage, MessageOrigin, MiningInfoUpdateEvent, SettleInfo, Topic,
 			};
@@ -2219,8 +2797,11 @@ pub mod pallet {
 					settle: vec![SettleInfo {
 						pubkey: worker_pubkey(worker),
 						v: v_bits,
-						payout: 0,
+						payout: payout_bits,
 					}],
+					protocol_version: 0,
+					settle_bindings: vec![],
+					continues: false,
 				},
 			}));
 		}
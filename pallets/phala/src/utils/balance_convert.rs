@@ -31,6 +31,19 @@ where
 	FixedPointConvert::from_fixed(&(x.to_fixed() * y))
 }
 
+/// Converts `v` to the balance rounding down, returning the sub-unit dust dropped by the rounding
+///
+/// The rounding policy of the payouts and the returned stakes is floor on all the paths, so that
+/// no more than the fixed point amount is ever paid. The callers keep track of the dust.
+pub fn floor_with_dust<B>(v: &FixedPoint) -> (B, FixedPoint)
+where
+	B: FixedPointConvert,
+{
+	let balance = B::from_fixed(v);
+	let dust = v.saturating_sub(balance.to_fixed());
+	(balance, dust)
+}
+
 pub fn div<B>(x: B, y: &FixedPoint) -> B
 where
 	B: sp_runtime::traits::AtLeast32BitUnsigned + Copy + FixedPointConvert,
@@ -0,0 +1,102 @@
+use codec::{Decode, Encode, FullCodec, FullEncode};
+use frame_support::storage::{types::PrefixIterator, IterableStorageMap, StorageMap};
+use sp_runtime::RuntimeDebug;
+use sp_std::vec::Vec;
+
+/// A time-ordered list of records with the oldest first
+///
+/// The list is bounded by the capacity given when pushing, and the expired records can be pruned
+/// by `prune`, or by `prune_map` incrementally for all the histories in a storage map.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug)]
+pub struct BoundedHistory<V> {
+	/// The records with the time (in sec) they are added
+	records: Vec<(u64, V)>,
+}
+
+impl<V> Default for BoundedHistory<V> {
+	fn default() -> Self {
+		Self {
+			records: Vec::new(),
+		}
+	}
+}
+
+impl<V> BoundedHistory<V> {
+	/// Appends a record, dropping the oldest ones to keep at most `capacity` records
+	pub fn push(&mut self, time: u64, value: V, capacity: usize) {
+		self.records.push((time, value));
+		if self.records.len() > capacity {
+			let excess = self.records.len() - capacity;
+			self.records.drain(..excess);
+		}
+	}
+
+	/// Removes the records added before `expire_before`. Returns the number of removed records.
+	pub fn prune(&mut self, expire_before: u64) -> usize {
+		let num_expired = self
+			.records
+			.iter()
+			.take_while(|(time, _)| *time < expire_before)
+			.count();
+		self.records.drain(..num_expired);
+		num_expired
+	}
+
+	/// Iterates over the records with their time, oldest first
+	pub fn iter(&self) -> impl Iterator<Item = &(u64, V)> {
+		self.records.iter()
+	}
+
+	/// Returns the latest record
+	pub fn latest(&self) -> Option<&(u64, V)> {
+		self.records.last()
+	}
+
+	pub fn len(&self) -> usize {
+		self.records.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.records.is_empty()
+	}
+}
+
+/// Prunes the expired records of the histories in the storage map `M`, scanning at most
+/// `max_items` entries after the raw key `cursor` (or from the beginning if None)
+///
+/// The entries left empty are removed. Returns the cursor to continue with (None if all the
+/// entries have been scanned) and the number of scanned entries.
+pub fn prune_map<M, K, V>(
+	cursor: Option<Vec<u8>>,
+	expire_before: u64,
+	max_items: u32,
+) -> (Option<Vec<u8>>, u32)
+where
+	K: FullEncode,
+	V: FullCodec,
+	M: IterableStorageMap<K, BoundedHistory<V>, Iterator = PrefixIterator<(K, BoundedHistory<V>)>>
+		+ StorageMap<K, BoundedHistory<V>>,
+{
+	let mut iter = match cursor {
+		Some(cursor) => M::iter_from(cursor),
+		None => M::iter(),
+	};
+	let mut scanned = 0u32;
+	while scanned < max_items {
+		match iter.next() {
+			Some((key, mut history)) => {
+				scanned += 1;
+				if history.prune(expire_before) == 0 {
+					continue;
+				}
+				if history.is_empty() {
+					<M as StorageMap<K, _>>::remove(key);
+				} else {
+					<M as StorageMap<K, _>>::insert(key, history);
+				}
+			}
+			None => return (None, scanned),
+		}
+	}
+	(Some(iter.last_raw_key().to_vec()), scanned)
+}
@@ -1,5 +1,7 @@
 use codec::{Decode, Encode, EncodeLike, Input, Output};
 use fixed::types::U64F64 as FixedPoint;
+use scale_info::TypeInfo;
+use sp_runtime::RuntimeDebug;
 
 /// Wrapped FixedPoint (U64F64) to make scale-codec happy
 #[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
@@ -46,3 +48,43 @@ impl Decode for CodecFixedPoint {
 		Ok(CodecFixedPoint(FixedPoint::from_bits(bits)))
 	}
 }
+
+/// The raw bits of a U64F64 fixed point number
+///
+/// It's encoded exactly as the bare `u128` bits, so it can replace the bits in the existing
+/// storage without a migration.
+#[repr(transparent)]
+#[derive(
+	Encode, Decode, TypeInfo, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, RuntimeDebug,
+)]
+pub struct FixedBits(u128);
+
+impl FixedBits {
+	pub const fn from_bits(bits: u128) -> Self {
+		Self(bits)
+	}
+
+	pub const fn to_bits(self) -> u128 {
+		self.0
+	}
+
+	pub fn from_fixed(v: FixedPoint) -> Self {
+		Self(v.to_bits())
+	}
+
+	pub fn to_fixed(self) -> FixedPoint {
+		FixedPoint::from_bits(self.0)
+	}
+}
+
+impl From<FixedPoint> for FixedBits {
+	fn from(v: FixedPoint) -> Self {
+		Self::from_fixed(v)
+	}
+}
+
+impl From<FixedBits> for FixedPoint {
+	fn from(v: FixedBits) -> Self {
+		v.to_fixed()
+	}
+}
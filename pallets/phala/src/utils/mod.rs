@@ -1,5 +1,7 @@
 pub(crate) mod accumulator;
 pub(crate) mod attestation;
 pub(crate) mod balance_convert;
+pub mod bounded_history;
 pub mod constants;
 pub(crate) mod fixed_point;
+pub(crate) mod type_info;
@@ -0,0 +1,166 @@
+//! `TypeInfo` of the mining types holding the primitives without `TypeInfo` in this FRAME
+//! version (`Permill` and the worker pubkeys)
+//!
+//! Such fields are described by their SCALE encoding, so that the storage and the events can be
+//! decoded without the custom type bundles.
+
+use crate::mining::{
+	GatekeeperTelemetryRecord, MinerInfo, MiningStateSnapshot, ParkedReward, QueuedSettlement,
+	RewardLockupConfig, SecondaryPayoutConfig, SettlementDispute, StatusUpdate, StreakBonusTier,
+	TierRule, TrialMiningConfig,
+};
+use phala_types::messaging::TokenomicParameters as TokenomicParams;
+use scale_info::{
+	build::{Fields, Variants},
+	Path, Type, TypeInfo,
+};
+use sp_std::vec::Vec;
+
+/// `Permill`, encoded as the parts per million
+pub struct PermillInfo;
+
+impl TypeInfo for PermillInfo {
+	type Identity = Self;
+
+	fn type_info() -> Type {
+		Type::builder()
+			.path(Path::new("Permill", "sp_arithmetic::per_things"))
+			.composite(Fields::unnamed().field(|f| f.ty::<u32>().type_name("u32")))
+	}
+}
+
+/// `WorkerPublicKey`, encoded as the raw sr25519 pubkey
+pub struct WorkerPublicKeyInfo;
+
+impl TypeInfo for WorkerPublicKeyInfo {
+	type Identity = Self;
+
+	fn type_info() -> Type {
+		Type::builder()
+			.path(Path::new("Public", "sp_core::sr25519"))
+			.composite(Fields::unnamed().field(|f| f.ty::<[u8; 32]>()))
+	}
+}
+
+/// `SettleInfo` of the gatekeeper, holding a worker pubkey
+pub struct SettleInfoInfo;
+
+impl TypeInfo for SettleInfoInfo {
+	type Identity = Self;
+
+	fn type_info() -> Type {
+		Type::builder()
+			.path(Path::new("SettleInfo", "phala_types::messaging"))
+			.composite(
+				Fields::named()
+					.field(|f| {
+						f.ty::<WorkerPublicKeyInfo>()
+							.name("pubkey")
+							.type_name("WorkerPublicKey")
+					})
+					.field(|f| f.ty::<u128>().name("v").type_name("U64F64Bits"))
+					.field(|f| f.ty::<u128>().name("payout").type_name("U64F64Bits")),
+			)
+	}
+}
+
+/// Implements `TypeInfo` for a mining struct, describing its named fields in order by the given
+/// types, which must encode the same as the fields
+macro_rules! impl_mining_type_info {
+	($name:ident $(<$($param:ident),+>)? { $($field:ident: $ty:ty),+ $(,)? }) => {
+		impl$(<$($param: TypeInfo + 'static),+>)? TypeInfo for $name$(<$($param),+>)? {
+			type Identity = Self;
+
+			fn type_info() -> Type {
+				Type::builder()
+					.path(Path::new(stringify!($name), "phala_pallets::mining::pallet"))
+					.type_params(scale_info::type_params!($($($param),+)?))
+					.composite(
+						Fields::named()$(.field(|f| f.ty::<$ty>().name(stringify!($field))))+,
+					)
+			}
+		}
+	};
+}
+
+impl_mining_type_info!(RewardLockupConfig {
+	percentage: PermillInfo,
+	duration: u64,
+});
+
+impl_mining_type_info!(TierRule {
+	min_liveness: u32,
+	min_sessions: u32,
+	slash_multiplier: PermillInfo,
+	target_interval: u32,
+});
+
+impl_mining_type_info!(StreakBonusTier {
+	min_streak: u32,
+	bonus: PermillInfo,
+});
+
+impl_mining_type_info!(TrialMiningConfig<BlockNumber> {
+	duration: BlockNumber,
+	stake_ratio: PermillInfo,
+	ve_cap: u128,
+	payout_cap: u128,
+});
+
+impl_mining_type_info!(SecondaryPayoutConfig {
+	asset: u32,
+	ratio: PermillInfo,
+	exchange_rate: u128,
+});
+
+impl_mining_type_info!(GatekeeperTelemetryRecord<BlockNumber> {
+	gatekeeper: WorkerPublicKeyInfo,
+	received_at: BlockNumber,
+	workers_tracked: u32,
+	events_emitted: u32,
+	processing_lag: u32,
+});
+
+impl_mining_type_info!(ParkedReward<Balance> {
+	worker: WorkerPublicKeyInfo,
+	amount: Balance,
+});
+
+impl_mining_type_info!(SettlementDispute<BlockNumber> {
+	info: SettleInfoInfo,
+	binding_hash: Option<[u8; 32]>,
+	block_number: BlockNumber,
+});
+
+impl_mining_type_info!(QueuedSettlement<BlockNumber> {
+	info: SettleInfoInfo,
+	block: Option<BlockNumber>,
+});
+
+impl_mining_type_info!(MiningStateSnapshot<AccountId, Balance> {
+	miners: Vec<(AccountId, MinerInfo)>,
+	bindings: Vec<(AccountId, WorkerPublicKeyInfo)>,
+	stakes: Vec<(AccountId, Balance)>,
+	solo_miners: Vec<AccountId>,
+	online_miners: u32,
+	observing_miners: u32,
+	online_stake: Balance,
+	next_session_id: u32,
+	cool_down_period: u64,
+	tokenomic_parameters: Option<TokenomicParams>,
+});
+
+impl TypeInfo for StatusUpdate {
+	type Identity = Self;
+
+	fn type_info() -> Type {
+		let pubkey = || Fields::unnamed().field(|f| f.ty::<WorkerPublicKeyInfo>());
+		Type::builder()
+			.path(Path::new("StatusUpdate", "phala_pallets::mining::pallet"))
+			.variant(
+				Variants::new()
+					.variant("Offline", |v| v.index(0).fields(pubkey()))
+					.variant("Recovered", |v| v.index(1).fields(pubkey())),
+			)
+	}
+}
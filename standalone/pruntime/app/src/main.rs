@@ -420,6 +420,7 @@ proxy!("/dump_states", dump_states, actions::ACTION_DUMP_STATES);
 proxy!("/load_states", load_states, actions::ACTION_LOAD_STATES);
 proxy!("/query", query, actions::ACTION_QUERY);
 proxy!("/get_egress_messages", get_egress_messages, actions::ACTION_GET_EGRESS_MESSAGES);
+proxy!("/set_osp_log_level", set_osp_log_level, actions::ACTION_SET_OSP_LOG_LEVEL);
 proxy!("/test_ink", test_ink, actions::ACTION_TEST_INK);
 
 proxy_bin!("/bin_api/sync_header", sync_header, actions::BIN_ACTION_SYNC_HEADER);
@@ -23,6 +23,7 @@ pub mod actions {
     // Reserved: 8, 9
     pub const ACTION_GET_RUNTIME_INFO: u8 = 10;
     pub const ACTION_GET_EGRESS_MESSAGES: u8 = 23;
+    pub const ACTION_SET_OSP_LOG_LEVEL: u8 = 24;
     pub const ACTION_TEST_INK: u8 = 100;
 
     pub const BIN_ACTION_START: u8 = 128;
@@ -1,7 +1,6 @@
 use crate::error_msg;
-use crate::msg_channel::osp::{
-    storage_prefix_for_topic_pubkey, KeyPair, OspMq, Peeler, PeelingReceiver,
-};
+use crate::msg_channel::osp::{KeyPair, OspMq, Peeler, PeelingReceiver};
+use crate::msg_channel::storage_prefix_for_topic_pubkey;
 use crate::std::fmt::Debug;
 use crate::std::string::String;
 use crate::system::System;
@@ -162,6 +161,9 @@ impl<'de> Visitor<'de> for AcidVisitor {
     }
 }
 
+/// The max number of pending egress messages of a contract before it's considered congested
+pub const EGRESS_QUEUE_CAPACITY: usize = 1024;
+
 pub use support::*;
 mod support {
     use super::*;
@@ -182,10 +184,19 @@ mod support {
         pub fn mq(&self) -> &MessageChannel {
             self.mq
         }
+
+        /// The back-pressure signal of the egress queue.
+        ///
+        /// Contracts should defer the non-critical messages while it's congested.
+        pub fn is_congested(&self) -> bool {
+            self.mq.is_congested()
+        }
     }
 
     pub trait Contract {
         fn id(&self) -> ContractId;
+        /// The topic the contract receives the commands from
+        fn command_topic(&self) -> Vec<u8>;
         fn handle_query(
             &mut self,
             origin: Option<&chain::AccountId>,
@@ -301,6 +312,10 @@ mod support {
             self.contract.id()
         }
 
+        fn command_topic(&self) -> Vec<u8> {
+            <PushCommand<Cmd> as BindTopic>::TOPIC.to_vec()
+        }
+
         fn handle_query(
             &mut self,
             origin: Option<&runtime::AccountId>,
@@ -322,6 +337,8 @@ mod support {
             let key_map =
                 |topic: &phala_mq::Path| storage.get(&storage_prefix_for_topic_pubkey(topic));
             let osp_mq = OspMq::new(&self.ecdh_key, &self.send_mq, &key_map);
+            self.cmd_rcv_mq.set_now_ms(env.block.now_ms);
+            self.event_rcv_mq.set_now_ms(env.block.now_ms);
             let context = NativeContext {
                 block: env.block,
                 mq: &self.send_mq,
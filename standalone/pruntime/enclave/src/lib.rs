@@ -718,6 +718,7 @@ fn handle_json_api(action: u8, input: &[u8], output_buf_len: usize) -> Result<Va
                 ACTION_GET_RUNTIME_INFO => get_runtime_info(payload),
                 ACTION_TEST_INK => test_ink(payload),
                 ACTION_GET_EGRESS_MESSAGES => get_egress_messages(output_buf_len),
+                ACTION_SET_OSP_LOG_LEVEL => set_osp_log_level(payload),
                 _ => unknown(),
             }
         }
@@ -1144,9 +1145,32 @@ fn get_info_json() -> Result<Value, Value> {
         "pending_messages": info.pending_messages,
         "score": info.score,
         "machine_id": machine_id,
+        "mq_topics": get_mq_topics_json(),
     }))
 }
 
+/// The receiving progress of each subscribed mq topic, for lagging diagnosis
+fn get_mq_topics_json() -> Value {
+    let state = STATE.lock().unwrap();
+    let topics = match state.as_ref() {
+        Some(state) => state.recv_mq.topic_status(),
+        None => Default::default(),
+    };
+    let topics: Map<String, Value> = topics
+        .into_iter()
+        .map(|(path, status)| {
+            let topic = String::from_utf8_lossy(&path).into_owned();
+            let status = json!({
+                "dispatched_seq": status.dispatched_seq,
+                "last_seq": status.last_seq,
+                "pending": status.pending,
+            });
+            (topic, status)
+        })
+        .collect();
+    Value::Object(topics)
+}
+
 fn convert_runtime_info(info: InitRuntimeResponse) -> Result<InitRuntimeResp, Value> {
     let genesis_block_hash = info.genesis_block_hash_decoded().map_err(display)?;
     let public_key = info.public_key_decoded().map_err(display)?;
@@ -1245,6 +1269,31 @@ fn get_egress_messages(output_buf_len: usize) -> Result<Value, Value> {
     }))
 }
 
+/// Adjusts the verbosity of the OSP decision log
+///
+/// Input: `{"level": "off" | "error" | "warn" | "info" | "debug" | "trace", "topic": <optional>}`.
+/// Sets the default verbosity if `topic` is absent, or resets the topic to the default if `level`
+/// is absent.
+fn set_osp_log_level(input: &Map<String, Value>) -> Result<Value, Value> {
+    use crate::msg_channel::osp::decision_log;
+
+    let topic = input
+        .get("topic")
+        .and_then(|t| t.as_str())
+        .map(|t| t.as_bytes().to_vec());
+    let level = input.get("level").and_then(|l| l.as_str());
+    match (topic, level) {
+        (topic, Some(level)) => {
+            let level: log::LevelFilter =
+                level.parse().map_err(|_| error_msg("Invalid log level"))?;
+            decision_log::set_verbosity(topic, level);
+        }
+        (Some(topic), None) => decision_log::reset_verbosity(&topic),
+        (None, None) => return Err(error_msg("Missing log level")),
+    }
+    Ok(json!({}))
+}
+
 fn query(q: types::SignedQuery) -> Result<Value, Value> {
     let payload_data = q.query_payload.as_bytes();
     // Validate signature
@@ -1335,6 +1384,52 @@ mod gatekeeper {
         gatekeepers.contains(pubkey)
     }
 
+    /// Reads the miner bound to `worker`, verified by a read proof of the mining bindings child
+    /// trie against the `BindingsCommitment` on chain.
+    ///
+    /// Returns None if the proof shows the worker is not bound.
+    #[allow(dead_code)]
+    pub fn verify_mining_binding(
+        worker: &WorkerPublicKey,
+        proof: Vec<Vec<u8>>,
+        chain_storage: &Storage,
+    ) -> Result<Option<chain::AccountId>> {
+        use crate::light_validation::storage_proof::StorageProofChecker;
+
+        let key = storage_prefix("PhalaMining", "BindingsCommitment");
+        let root: H256 = chain_storage
+            .get_decoded(&key)
+            .ok_or_else(|| anyhow!("No mining bindings commitment"))?;
+        let checker = StorageProofChecker::<RuntimeHasher>::new(root, proof)?;
+        match checker.read_value(&worker.encode())? {
+            Some(raw) => Ok(Some(Decode::decode(&mut &raw[..])?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the gatekeeper protocol version to speak to the chain, negotiated with the
+    /// `SupportedGkProtocol` range on chain
+    ///
+    /// Falls back to the legacy version 0 if the range is not set, which is accepted by both the
+    /// legacy chains and the upgraded ones.
+    pub fn negotiate_gk_protocol(chain_storage: &Storage) -> u32 {
+        use phala_types::messaging::GK_PROTOCOL_VERSION;
+
+        let key = storage_prefix("PhalaMining", "SupportedGkProtocol");
+        let (min_version, max_version): (u32, u32) = match chain_storage.get_decoded(&key) {
+            Some(range) => range,
+            None => return 0,
+        };
+        let version = max_version.min(GK_PROTOCOL_VERSION);
+        if version < min_version {
+            warn!(
+                "Gatekeeper protocol v{} is not supported by the chain (v{}..=v{})",
+                GK_PROTOCOL_VERSION, min_version, max_version
+            );
+        }
+        version
+    }
+
     #[allow(dead_code)]
     pub fn read_master_pubkey(chain_storage: &Storage) -> Option<MasterPublicKey> {
         let key = storage_prefix("PhalaRegistry", "GatekeeperMasterPubkey");
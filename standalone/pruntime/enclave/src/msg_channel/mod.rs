@@ -0,0 +1,72 @@
+// TODO: Remove thie attribute once we use OSP.
+#[allow(dead_code)]
+pub mod osp;
+
+use crate::light_validation::utils::storage_map_prefix_blake2_128_concat;
+use crate::std::vec::Vec;
+
+use phala_crypto::{ecdh::EcdhKey, sr25519::KDF};
+use phala_mq::{MessageOrigin, MessageSendQueue, Sr25519MessageChannel};
+use sp_core::{crypto::Pair, sr25519, H256};
+
+/// The key to sign the egress messages of a contract channel with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractSigner {
+    /// The identity key of the worker. Sent as `MessageOrigin::HostedContract`.
+    Worker,
+    /// The sr25519 key derived from the identity key for the contract. Sent as
+    /// `MessageOrigin::Contract`, so the pubkey must be registered for the contract on chain.
+    Derived,
+}
+
+/// Derives the signing key of a contract from the worker identity key
+pub fn derive_contract_signing_key(identity_key: &sr25519::Pair, id: &H256) -> sr25519::Pair {
+    identity_key
+        .derive_sr25519_pair(&[b"contract_signer", id.as_bytes()])
+        .expect("should not fail with valid info")
+}
+
+/// Derives the ecdh key of a contract from the worker identity key
+///
+/// A new `salt` rotates the key, which must be provisioned on chain again.
+pub fn derive_contract_ecdh_key(identity_key: &sr25519::Pair, id: &H256, salt: &[u8]) -> EcdhKey {
+    identity_key
+        .derive_sr25519_pair(&[b"contract_ecdh", id.as_bytes(), salt])
+        .and_then(|pair| pair.derive_ecdh_key())
+        .expect("should not fail with valid info")
+}
+
+/// Creates the egress channel of the contract `id`, signed by the selected `signer`
+pub fn contract_channel(
+    send_mq: &MessageSendQueue,
+    id: H256,
+    identity_key: &sr25519::Pair,
+    signer: ContractSigner,
+) -> Sr25519MessageChannel {
+    let (sender, pair) = match signer {
+        ContractSigner::Worker => (
+            MessageOrigin::HostedContract {
+                id,
+                worker: identity_key.public(),
+            },
+            identity_key.clone(),
+        ),
+        ContractSigner::Derived => (
+            MessageOrigin::Contract(id),
+            derive_contract_signing_key(identity_key, &id),
+        ),
+    };
+    send_mq.channel(sender, pair)
+}
+
+/// Calculates the Substrate storage key prefix for a StorageMap
+pub fn storage_prefix_for_topic_pubkey(topic: &phala_mq::Path) -> Vec<u8> {
+    use phala_pallets::pallet_mq::StorageMapTrait as _;
+
+    type TopicKey = phala_pallets::pallet_registry::TopicKey<chain::Runtime>;
+
+    let module_prefix = TopicKey::module_prefix();
+    let storage_prefix = TopicKey::storage_prefix();
+
+    storage_map_prefix_blake2_128_concat(module_prefix, storage_prefix, &topic)
+}
@@ -0,0 +1,862 @@
+//! OSP (Optional Secret Protocol): A topic using OSP means it accepting either Payload::Plain or
+//! Payload::Encrypted Message.
+//!
+//! The payloads larger than the chunk size of the sender are split into `OspPayload::Chunk`s,
+//! reassembled by `PeelingReceiver`.
+//!
+//! This module only depends on the `std` facade, `generate_random_iv` and `lazy_static` from the
+//! crate root, so that it can be tested outside the enclave (see `pruntime/osp-tests`).
+pub use decrypt::*;
+pub use encrypt::*;
+
+use crate::std::vec::Vec;
+
+use parity_scale_codec::{Decode, Encode};
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct AeadCipher {
+    pub iv: Vec<u8>,
+    pub cipher: Vec<u8>,
+    pub pubkey: Vec<u8>,
+}
+
+#[derive(Encode, Decode, Debug)]
+pub enum OspPayload<T> {
+    Plain(T),
+    /// Encrypted with the legacy suite (`CipherSuite::EcdhAes256Gcm`)
+    Encrypted(AeadCipher),
+    /// Encrypted with the suite negotiated from the topic key record
+    SuiteEncrypted {
+        suite: CipherSuite,
+        cipher: AeadCipher,
+    },
+    /// A SCALE encoded inner payload (never `Stamped` itself) with its validity period
+    ///
+    /// The stamp is covered by the signature of the message channel, so it can't be altered
+    /// by the relayers to replay a message after it expired.
+    Stamped {
+        stamp: OspStamp,
+        payload: Vec<u8>,
+    },
+    /// A piece of a SCALE encoded payload (never `Chunk` itself) too large for a single message
+    ///
+    /// The chunks of a payload share a random `id`, and are reassembled by the receiver once
+    /// all the `total` chunks arrived.
+    Chunk {
+        id: u64,
+        index: u32,
+        total: u32,
+        data: Vec<u8>,
+    },
+}
+
+/// The default max size of the data sent in a single message, beyond which the payload is split
+/// into chunks
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// The max number of the chunks of a payload
+pub const MAX_CHUNKS: u32 = 4096;
+/// The max size of a chunked payload
+pub const MAX_CHUNKED_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+/// How long (in the receiver's clock) the chunks of an incomplete payload are kept
+pub const CHUNK_TIMEOUT_MS: u64 = 120_000;
+/// The max number of the incomplete payloads buffered by a receiver
+pub const MAX_PENDING_CHUNKED_PAYLOADS: usize = 16;
+
+/// The validity period of a stamped OSP message, in the sender's clock (block time, in ms)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct OspStamp {
+    pub sent_at_ms: u64,
+    pub expires_at_ms: u64,
+}
+
+impl OspStamp {
+    pub fn new(now_ms: u64, ttl_ms: u64) -> Self {
+        OspStamp {
+            sent_at_ms: now_ms,
+            expires_at_ms: now_ms.saturating_add(ttl_ms),
+        }
+    }
+
+    /// Checks the stamp against the receiver's clock, tolerating `max_skew_ms` of clock skew
+    /// in both directions
+    pub fn check(&self, now_ms: u64, max_skew_ms: u64) -> Result<(), anyhow::Error> {
+        if self.expires_at_ms < self.sent_at_ms {
+            return Err(anyhow::anyhow!("Malformed Osp stamp {:?}", self));
+        }
+        if self.sent_at_ms > now_ms.saturating_add(max_skew_ms) {
+            return Err(anyhow::anyhow!(
+                "Osp message from the future, sent_at={} now={}",
+                self.sent_at_ms,
+                now_ms
+            ));
+        }
+        if self.expires_at_ms.saturating_add(max_skew_ms) < now_ms {
+            return Err(anyhow::anyhow!(
+                "Osp message expired, expires_at={} now={}",
+                self.expires_at_ms,
+                now_ms
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The cipher suite (ECDH key agreement + AEAD) to protect an OSP payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum CipherSuite {
+    /// ECDH on the sr25519 derived keys + AES-256-GCM. The original OSP suite.
+    EcdhAes256Gcm,
+}
+
+impl Default for CipherSuite {
+    fn default() -> Self {
+        CipherSuite::EcdhAes256Gcm
+    }
+}
+
+impl CipherSuite {
+    /// The suites this enclave can handle, in the order of preference
+    pub const SUPPORTED: &'static [CipherSuite] = &[CipherSuite::EcdhAes256Gcm];
+
+    pub fn is_supported(&self) -> bool {
+        Self::SUPPORTED.contains(self)
+    }
+
+    /// The length of the remote pubkey of the suite
+    pub fn pubkey_len(&self) -> usize {
+        match self {
+            CipherSuite::EcdhAes256Gcm => 32,
+        }
+    }
+}
+
+/// The pubkey record of a topic in the registry (`TopicKey`)
+///
+/// A legacy record is the raw 32 bytes ecdh pubkey, which implies the default suite. New
+/// records are the SCALE encoded `TopicKeyRecord`. Their pubkey length is validated against the
+/// suite, so a valid new record (34 bytes for the default suite) is never taken as a legacy one.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct TopicKeyRecord {
+    pub suite: CipherSuite,
+    pub pubkey: Vec<u8>,
+}
+
+const LEGACY_TOPIC_KEY_LEN: usize = 32;
+
+impl TopicKeyRecord {
+    pub fn decode_raw(raw: &[u8]) -> Option<Self> {
+        if raw.len() == LEGACY_TOPIC_KEY_LEN {
+            return Some(TopicKeyRecord {
+                suite: Default::default(),
+                pubkey: raw.to_vec(),
+            });
+        }
+        let record: Self = Decode::decode(&mut &raw[..]).ok()?;
+        if record.pubkey.len() != record.suite.pubkey_len() {
+            return None;
+        }
+        Some(record)
+    }
+
+    /// Returns the suite to talk to the topic, or None if we don't speak its suite
+    pub fn negotiate(&self) -> Option<CipherSuite> {
+        if self.suite.is_supported() {
+            Some(self.suite)
+        } else {
+            None
+        }
+    }
+}
+
+mod suite {
+    use super::{AeadCipher, CipherSuite};
+    use crate::std::vec::Vec;
+    use phala_crypto::{aead, ecdh};
+
+    pub fn seal(
+        suite: CipherSuite,
+        key: &ecdh::EcdhKey,
+        remote_pubkey: &[u8],
+        mut data: Vec<u8>,
+    ) -> Result<AeadCipher, anyhow::Error> {
+        match suite {
+            CipherSuite::EcdhAes256Gcm => {
+                let iv = crate::generate_random_iv();
+                let sk = ecdh::agree(key, remote_pubkey)
+                    .map_err(|e| anyhow::anyhow!("ecdh agree failed: {:?}", e))?;
+                aead::encrypt(&iv, &sk, &mut data)
+                    .map_err(|e| anyhow::anyhow!("aead encrypt failed: {:?}", e))?;
+                Ok(AeadCipher {
+                    iv: iv.into(),
+                    cipher: data,
+                    pubkey: key.public().to_vec(),
+                })
+            }
+        }
+    }
+
+    pub fn open(
+        suite: CipherSuite,
+        key: &ecdh::EcdhKey,
+        cipher: &mut AeadCipher,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        match suite {
+            CipherSuite::EcdhAes256Gcm => {
+                let sk = ecdh::agree(key, &cipher.pubkey)
+                    .map_err(|e| anyhow::anyhow!("ecdh agree failed: {:?}", e))?;
+                let msg = aead::decrypt(&cipher.iv, &sk, &mut cipher.cipher)
+                    .map_err(|e| anyhow::anyhow!("aead decrypt failed: {:?}", e))?;
+                Ok(msg.to_vec())
+            }
+        }
+    }
+}
+
+pub mod decision_log {
+    //! Structured log of the encryption decisions made by `OspMq::osp_sendto`
+    //!
+    //! Each decision is logged to the `osp` target with the level of its policy (plaintext:
+    //! info, encrypted: debug, rejected: warn), if allowed by the verbosity of the topic.
+    use super::CipherSuite;
+    use crate::std::collections::BTreeMap;
+    use crate::std::string::String;
+    use crate::std::sync::SgxMutex;
+    use log::{Level, LevelFilter};
+    use phala_mq::Path;
+
+    struct Verbosity {
+        default: LevelFilter,
+        topics: BTreeMap<Path, LevelFilter>,
+    }
+
+    lazy_static! {
+        static ref VERBOSITY: SgxMutex<Verbosity> = SgxMutex::new(Verbosity {
+            default: LevelFilter::Warn,
+            topics: Default::default(),
+        });
+    }
+
+    /// Sets the verbosity of a topic, or the default one for all the other topics if `topic`
+    /// is None
+    pub fn set_verbosity(topic: Option<Path>, level: LevelFilter) {
+        let mut verbosity = VERBOSITY.lock().unwrap();
+        match topic {
+            Some(topic) => {
+                verbosity.topics.insert(topic, level);
+            }
+            None => verbosity.default = level,
+        }
+    }
+
+    /// Removes the verbosity override of a topic
+    pub fn reset_verbosity(topic: &Path) {
+        VERBOSITY.lock().unwrap().topics.remove(topic);
+    }
+
+    fn enabled(topic: &Path, level: Level) -> bool {
+        let verbosity = VERBOSITY.lock().unwrap();
+        let filter = verbosity
+            .topics
+            .get(topic)
+            .copied()
+            .unwrap_or(verbosity.default);
+        level <= filter
+    }
+
+    /// How a message is sent
+    #[derive(Debug)]
+    pub enum Policy {
+        Plain,
+        Encrypted(CipherSuite),
+        Rejected(&'static str),
+    }
+
+    impl Policy {
+        fn level(&self) -> Level {
+            match self {
+                Policy::Plain => Level::Info,
+                Policy::Encrypted(_) => Level::Debug,
+                Policy::Rejected(_) => Level::Warn,
+            }
+        }
+    }
+
+    pub fn log_decision(
+        topic: &Path,
+        remote_key_present: bool,
+        policy: Policy,
+        payload_size: usize,
+    ) {
+        let level = policy.level();
+        if !enabled(topic, level) {
+            return;
+        }
+        log::log!(
+            target: "osp",
+            level,
+            "osp_sendto topic={} remote_key={} policy={:?} payload_size={}",
+            String::from_utf8_lossy(topic),
+            remote_key_present,
+            policy,
+            payload_size,
+        );
+    }
+}
+
+mod encrypt {
+    use super::decision_log::{log_decision, Policy};
+    use super::{
+        CipherSuite, OspPayload, OspStamp, TopicKeyRecord, DEFAULT_CHUNK_SIZE,
+        MAX_CHUNKED_PAYLOAD_SIZE, MAX_CHUNKS,
+    };
+    use crate::std::vec::Vec;
+    use parity_scale_codec::Encode;
+    use phala_crypto::ecdh;
+    use phala_mq::{BindTopic, Path, Sr25519MessageChannel};
+    pub struct KeyPair(ecdh::EcdhKey);
+
+    impl KeyPair {
+        pub fn new(ecdh_key: ecdh::EcdhKey) -> Self {
+            KeyPair(ecdh_key)
+        }
+    }
+
+    pub struct OspMq<'a> {
+        key: &'a KeyPair,
+        mq: &'a Sr25519MessageChannel,
+        key_map: &'a dyn Fn(&Path) -> Option<Vec<u8>>,
+        /// The block time and the TTL to stamp the messages with, if any
+        stamp: Option<(u64, u64)>,
+        /// The max size of the data sent in a single message
+        chunk_size: usize,
+    }
+
+    impl<'a> OspMq<'a> {
+        pub fn new(
+            key: &'a KeyPair,
+            mq: &'a Sr25519MessageChannel,
+            key_map: &'a dyn Fn(&Path) -> Option<Vec<u8>>,
+        ) -> Self {
+            OspMq {
+                key,
+                mq,
+                key_map,
+                stamp: None,
+                chunk_size: DEFAULT_CHUNK_SIZE,
+            }
+        }
+
+        /// Sets the max size of the data sent in a single message. Larger payloads are split
+        /// into `OspPayload::Chunk`s.
+        pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+            self.chunk_size = chunk_size.max(1);
+            self
+        }
+
+        /// Stamps the messages sent by this mq with `now_ms` and an expiry of `ttl_ms` later,
+        /// so that the receivers can drop them if replayed late
+        pub fn with_timestamp(mut self, now_ms: u64, ttl_ms: u64) -> Self {
+            self.stamp = Some((now_ms, ttl_ms));
+            self
+        }
+
+        fn stamped(&self, data: Vec<u8>) -> Vec<u8> {
+            match self.stamp {
+                Some((now_ms, ttl_ms)) => OspPayload::<()>::Stamped {
+                    stamp: OspStamp::new(now_ms, ttl_ms),
+                    payload: data,
+                }
+                .encode(),
+                None => data,
+            }
+        }
+
+        /// Sends the data in a single message, or in chunks if it exceeds the chunk size
+        ///
+        /// The chunks are sent in order. If the egress queue fills up in the middle, the chunks
+        /// already sent are dropped by the receiver after `CHUNK_TIMEOUT_MS`.
+        fn send_data(&self, data: Vec<u8>, to: Path) -> Result<(), anyhow::Error> {
+            if data.len() <= self.chunk_size {
+                return self
+                    .mq
+                    .try_send_data(data, to)
+                    .map_err(|e| anyhow::anyhow!("{}", e));
+            }
+            let total = (data.len() + self.chunk_size - 1) / self.chunk_size;
+            if data.len() > MAX_CHUNKED_PAYLOAD_SIZE || total > MAX_CHUNKS as usize {
+                return Err(anyhow::anyhow!(
+                    "Osp payload too large, size={} chunks={}",
+                    data.len(),
+                    total
+                ));
+            }
+            let id = chunk_id();
+            for (index, piece) in data.chunks(self.chunk_size).enumerate() {
+                let chunk = OspPayload::<()>::Chunk {
+                    id,
+                    index: index as u32,
+                    total: total as u32,
+                    data: piece.to_vec(),
+                };
+                self.mq
+                    .try_send_data(chunk.encode(), to.clone())
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+            }
+            Ok(())
+        }
+
+        pub fn get_pubkey(&self, topic: &Path) -> Option<Vec<u8>> {
+            (self.key_map)(topic)
+        }
+
+        /// The number of the pending egress messages, not yet accepted on chain
+        pub fn queue_depth(&self) -> usize {
+            self.mq.queue_depth()
+        }
+
+        /// Returns true if the egress queue is full, and further sending would fail
+        pub fn is_congested(&self) -> bool {
+            self.mq.is_congested()
+        }
+
+        /// Sends a message to `to`, encrypted if the remote topic key record is given.
+        ///
+        /// Fails with `SendError::Full` if the egress queue has reached its capacity.
+        ///
+        /// `remote_key` is the raw topic key record in the registry. The cipher suite is
+        /// negotiated from the record. Messages for the default suite are sent in the legacy
+        /// format, so that the receivers not aware of cipher suites can still decrypt them.
+        pub fn osp_sendto<M: Encode>(
+            &self,
+            message: &M,
+            to: impl Into<Path>,
+            remote_key: Option<Vec<u8>>,
+        ) -> Result<(), anyhow::Error> {
+            let to = to.into();
+            match remote_key {
+                None => {
+                    let msg = OspPayload::Plain(message);
+                    let data = self.stamped(msg.encode());
+                    log_decision(&to, false, Policy::Plain, data.len());
+                    self.send_data(data, to)?;
+                }
+                Some(raw_record) => {
+                    let record = match TopicKeyRecord::decode_raw(&raw_record) {
+                        Some(record) => record,
+                        None => {
+                            log_decision(&to, true, Policy::Rejected("malformed key"), 0);
+                            return Err(anyhow::anyhow!("Malformed topic key record"));
+                        }
+                    };
+                    let suite = match record.negotiate() {
+                        Some(suite) => suite,
+                        None => {
+                            log_decision(&to, true, Policy::Rejected("unsupported suite"), 0);
+                            return Err(anyhow::anyhow!(
+                                "Unsupported cipher suite {:?}",
+                                record.suite
+                            ));
+                        }
+                    };
+                    let cipher =
+                        super::suite::seal(suite, &self.key.0, &record.pubkey, message.encode())?;
+                    let payload: OspPayload<M> = if suite == CipherSuite::default() {
+                        OspPayload::Encrypted(cipher)
+                    } else {
+                        OspPayload::SuiteEncrypted { suite, cipher }
+                    };
+                    let data = self.stamped(payload.encode());
+                    log_decision(&to, true, Policy::Encrypted(suite), data.len());
+                    self.send_data(data, to)?;
+                }
+            }
+            Ok(())
+        }
+
+        pub fn osp_send<M: Encode + BindTopic>(
+            &self,
+            message: &M,
+            remote_key: Option<Vec<u8>>,
+        ) -> Result<(), anyhow::Error> {
+            self.osp_sendto(message, <M as BindTopic>::TOPIC, remote_key)
+        }
+    }
+
+    fn chunk_id() -> u64 {
+        let iv = crate::generate_random_iv();
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&iv[..8]);
+        u64::from_le_bytes(id)
+    }
+}
+
+mod decrypt {
+    use super::{
+        AeadCipher, CipherSuite, OspPayload, OspStamp, CHUNK_TIMEOUT_MS, MAX_CHUNKED_PAYLOAD_SIZE,
+        MAX_CHUNKS, MAX_PENDING_CHUNKED_PAYLOADS,
+    };
+    use crate::std::collections::BTreeMap;
+    use crate::std::vec::Vec;
+    use core::marker::PhantomData;
+    use parity_scale_codec::Decode;
+    use phala_crypto::ecdh;
+    use phala_mq::{BindTopic, MessageOrigin, ReceiveError, TypedReceiver};
+
+    impl<T: BindTopic> BindTopic for OspPayload<T> {
+        const TOPIC: &'static [u8] = T::TOPIC;
+    }
+
+    pub trait Peeler {
+        type Wrp;
+        type Msg;
+        fn peel(&self, msg: Self::Wrp) -> Result<Self::Msg, anyhow::Error>;
+        /// Updates the receiver's clock, to check the validity period of the messages
+        fn set_now_ms(&mut self, _now_ms: u64) {}
+        /// Takes the chunk out of `msg` if it's a piece of a chunked payload
+        fn split_chunk(&self, msg: Self::Wrp) -> Piece<Self::Wrp> {
+            Piece::Whole(msg)
+        }
+    }
+
+    /// A received message, either whole or a chunk of a larger payload
+    pub enum Piece<W> {
+        Whole(W),
+        Chunk {
+            id: u64,
+            index: u32,
+            total: u32,
+            data: Vec<u8>,
+        },
+    }
+
+    pub struct PlainPeeler<T>(PhantomData<T>);
+
+    impl<T> Peeler for PlainPeeler<T> {
+        type Wrp = T;
+        type Msg = T;
+        fn peel(&self, msg: Self::Wrp) -> Result<Self::Msg, anyhow::Error> {
+            Ok(msg)
+        }
+    }
+
+    pub struct OspPeeler<T> {
+        ecdh_key: ecdh::EcdhKey,
+        /// The retiring key, still accepted while the senders switch to the new key record
+        transition_key: Option<ecdh::EcdhKey>,
+        /// The current block time, unknown until the first `set_now_ms`
+        now_ms: Option<u64>,
+        /// The tolerated clock skew between the sender and the receiver
+        max_skew_ms: u64,
+        _t: PhantomData<T>,
+    }
+
+    /// The default tolerated clock skew of the stamped messages
+    pub const DEFAULT_MAX_SKEW_MS: u64 = 60_000;
+
+    impl<T> OspPeeler<T> {
+        pub fn new(ecdh_key: ecdh::EcdhKey) -> Self {
+            OspPeeler {
+                ecdh_key: ecdh_key,
+                transition_key: None,
+                now_ms: None,
+                max_skew_ms: DEFAULT_MAX_SKEW_MS,
+                _t: PhantomData,
+            }
+        }
+
+        /// Keeps accepting messages encrypted to `old_key` during a key or suite transition
+        pub fn with_transition_key(mut self, old_key: ecdh::EcdhKey) -> Self {
+            self.transition_key = Some(old_key);
+            self
+        }
+
+        /// Sets the tolerated clock skew when checking the stamped messages
+        pub fn with_max_skew(mut self, max_skew_ms: u64) -> Self {
+            self.max_skew_ms = max_skew_ms;
+            self
+        }
+
+        fn check_stamp(&self, stamp: &OspStamp) -> Result<(), anyhow::Error> {
+            match self.now_ms {
+                Some(now_ms) => stamp.check(now_ms, self.max_skew_ms),
+                None => Err(anyhow::anyhow!(
+                    "Osp stamp received before the clock is set"
+                )),
+            }
+        }
+
+        fn open(
+            &self,
+            suite: CipherSuite,
+            mut cipher: AeadCipher,
+        ) -> Result<Vec<u8>, anyhow::Error> {
+            if !suite.is_supported() {
+                return Err(anyhow::anyhow!("Unsupported cipher suite {:?}", suite));
+            }
+            let mut backup = None;
+            if self.transition_key.is_some() {
+                // Decryption is in-place. Keep a copy for the second attempt.
+                backup = Some(cipher.clone());
+            }
+            match super::suite::open(suite, &self.ecdh_key, &mut cipher) {
+                Ok(msg) => Ok(msg),
+                Err(err) => match (&self.transition_key, backup) {
+                    (Some(old_key), Some(mut cipher)) => {
+                        super::suite::open(suite, old_key, &mut cipher)
+                    }
+                    _ => Err(err),
+                },
+            }
+        }
+    }
+
+    impl<T: Decode> Peeler for OspPeeler<T> {
+        type Wrp = OspPayload<T>;
+        type Msg = T;
+        fn peel(&self, msg: Self::Wrp) -> Result<Self::Msg, anyhow::Error> {
+            let msg = match msg {
+                OspPayload::Stamped { stamp, payload } => {
+                    self.check_stamp(&stamp)?;
+                    let inner: OspPayload<T> = Decode::decode(&mut payload.as_ref())
+                        .map_err(|_| anyhow::anyhow!("SCALE decode Osp stamped data failed"))?;
+                    if let OspPayload::Stamped { .. } = inner {
+                        return Err(anyhow::anyhow!("Nested Osp stamp"));
+                    }
+                    inner
+                }
+                msg => msg,
+            };
+            let (suite, cipher) = match msg {
+                OspPayload::Plain(msg) => return Ok(msg),
+                OspPayload::Encrypted(cipher) => (CipherSuite::default(), cipher),
+                OspPayload::SuiteEncrypted { suite, cipher } => (suite, cipher),
+                OspPayload::Stamped { .. } => unreachable!(),
+                OspPayload::Chunk { .. } => return Err(anyhow::anyhow!("Unexpected Osp chunk")),
+            };
+            let msg = self.open(suite, cipher)?;
+            let msg = Decode::decode(&mut msg.as_ref())
+                .map_err(|_| anyhow::anyhow!("SCALE decode Osp decrypted data failed"))?;
+            Ok(msg)
+        }
+
+        fn set_now_ms(&mut self, now_ms: u64) {
+            self.now_ms = Some(now_ms);
+        }
+
+        fn split_chunk(&self, msg: Self::Wrp) -> Piece<Self::Wrp> {
+            match msg {
+                OspPayload::Chunk {
+                    id,
+                    index,
+                    total,
+                    data,
+                } => Piece::Chunk {
+                    id,
+                    index,
+                    total,
+                    data,
+                },
+                msg => Piece::Whole(msg),
+            }
+        }
+    }
+
+    struct PartialPayload {
+        total: u32,
+        chunks: BTreeMap<u32, Vec<u8>>,
+        size: usize,
+        /// The receiver's clock when the first chunk arrived, if known
+        started_at_ms: Option<u64>,
+    }
+
+    /// The incomplete chunked payloads, by the sender and the payload id
+    #[derive(Default)]
+    struct ChunkBuffer {
+        pending: BTreeMap<(MessageOrigin, u64), PartialPayload>,
+    }
+
+    impl ChunkBuffer {
+        /// Buffers a chunk, and returns the reassembled payload if it's the last missing one
+        fn push(
+            &mut self,
+            origin: &MessageOrigin,
+            id: u64,
+            index: u32,
+            total: u32,
+            data: Vec<u8>,
+            now_ms: Option<u64>,
+        ) -> Result<Option<Vec<u8>>, anyhow::Error> {
+            if total == 0 || total > MAX_CHUNKS || index >= total {
+                return Err(anyhow::anyhow!(
+                    "Malformed Osp chunk, index={} total={}",
+                    index,
+                    total
+                ));
+            }
+            let key = (origin.clone(), id);
+            if !self.pending.contains_key(&key)
+                && self.pending.len() >= MAX_PENDING_CHUNKED_PAYLOADS
+            {
+                self.evict_oldest();
+            }
+            let partial = self
+                .pending
+                .entry(key.clone())
+                .or_insert_with(|| PartialPayload {
+                    total,
+                    chunks: Default::default(),
+                    size: 0,
+                    started_at_ms: now_ms,
+                });
+            if partial.total != total {
+                self.pending.remove(&key);
+                return Err(anyhow::anyhow!("Inconsistent Osp chunk total"));
+            }
+            if partial.chunks.contains_key(&index) {
+                // Duplicated
+                return Ok(None);
+            }
+            partial.size += data.len();
+            if partial.size > MAX_CHUNKED_PAYLOAD_SIZE {
+                self.pending.remove(&key);
+                return Err(anyhow::anyhow!("Osp chunked payload too large"));
+            }
+            partial.chunks.insert(index, data);
+            if partial.chunks.len() < total as usize {
+                return Ok(None);
+            }
+            let partial = self.pending.remove(&key).expect("Checked above; qed.");
+            let mut data = Vec::with_capacity(partial.size);
+            for (_, chunk) in partial.chunks {
+                data.extend(chunk);
+            }
+            Ok(Some(data))
+        }
+
+        fn evict_oldest(&mut self) {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|(_, partial)| partial.started_at_ms)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                log::warn!("Osp chunk buffer full, dropped payload {:?}", key);
+                self.pending.remove(&key);
+            }
+        }
+
+        /// Drops the payloads not completed within `CHUNK_TIMEOUT_MS`
+        fn expire(&mut self, now_ms: u64) {
+            let mut expired = Vec::new();
+            for (key, partial) in self.pending.iter_mut() {
+                match partial.started_at_ms {
+                    Some(started_at_ms) => {
+                        if started_at_ms.saturating_add(CHUNK_TIMEOUT_MS) < now_ms {
+                            expired.push(key.clone());
+                        }
+                    }
+                    // Received before the clock is set
+                    None => partial.started_at_ms = Some(now_ms),
+                }
+            }
+            for key in expired {
+                log::warn!("Osp chunked payload timed out {:?}", key);
+                self.pending.remove(&key);
+            }
+        }
+    }
+
+    pub struct PeelingReceiver<Msg, Wrp, Plr> {
+        receiver: TypedReceiver<Wrp>,
+        peeler: Plr,
+        chunks: ChunkBuffer,
+        /// The current block time, unknown until the first `set_now_ms`
+        now_ms: Option<u64>,
+        _msg: PhantomData<Msg>,
+    }
+
+    impl<Msg, Wrp> PeelingReceiver<Msg, Wrp, PlainPeeler<Msg>> {
+        pub fn new_plain(receiver: TypedReceiver<Wrp>) -> Self {
+            PeelingReceiver {
+                receiver,
+                peeler: PlainPeeler(Default::default()),
+                chunks: Default::default(),
+                now_ms: None,
+                _msg: Default::default(),
+            }
+        }
+    }
+
+    impl<Msg, Wrp> PeelingReceiver<Msg, Wrp, OspPeeler<Msg>> {
+        pub fn new_osp(receiver: TypedReceiver<Wrp>, ecdh_key: ecdh::EcdhKey) -> Self {
+            PeelingReceiver {
+                receiver,
+                peeler: OspPeeler::new(ecdh_key),
+                chunks: Default::default(),
+                now_ms: None,
+                _msg: Default::default(),
+            }
+        }
+    }
+
+    impl<Msg, Plr, Wrp> PeelingReceiver<Msg, Wrp, Plr>
+    where
+        Plr: Peeler<Wrp = Wrp, Msg = Msg>,
+        Msg: Decode,
+        Wrp: Decode,
+    {
+        /// Takes the next message, skipping the chunks until a chunked payload is complete
+        pub fn try_next(&mut self) -> Result<Option<(u64, Msg, MessageOrigin)>, anyhow::Error> {
+            loop {
+                let omsg = self
+                    .receiver
+                    .try_next()
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                let (seq, msg, origin) = match omsg {
+                    Some(x) => x,
+                    None => return Ok(None),
+                };
+                let msg = match self.peeler.split_chunk(msg) {
+                    Piece::Whole(msg) => msg,
+                    Piece::Chunk {
+                        id,
+                        index,
+                        total,
+                        data,
+                    } => {
+                        let data =
+                            match self
+                                .chunks
+                                .push(&origin, id, index, total, data, self.now_ms)?
+                            {
+                                Some(data) => data,
+                                None => continue,
+                            };
+                        let msg: Wrp = Decode::decode(&mut data.as_ref()).map_err(|_| {
+                            anyhow::anyhow!("SCALE decode Osp reassembled data failed")
+                        })?;
+                        match self.peeler.split_chunk(msg) {
+                            Piece::Whole(msg) => msg,
+                            Piece::Chunk { .. } => {
+                                return Err(anyhow::anyhow!("Nested Osp chunk"));
+                            }
+                        }
+                    }
+                };
+                let msg = self.peeler.peel(msg)?;
+                return Ok(Some((seq, msg, origin)));
+            }
+        }
+
+        pub fn peek_ind(&self) -> Result<Option<u64>, ReceiveError> {
+            self.receiver.peek_ind()
+        }
+
+        /// Updates the clock of the peeler to the time of the block being processed
+        pub fn set_now_ms(&mut self, now_ms: u64) {
+            self.peeler.set_now_ms(now_ms);
+            self.now_ms = Some(now_ms);
+            self.chunks.expire(now_ms);
+        }
+    }
+}
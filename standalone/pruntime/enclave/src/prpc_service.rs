@@ -325,6 +325,11 @@ pub fn init_runtime(
                 return Err(from_display("Error while connecting to IAS"));
             }
         };
+        if let Ok(report) = serde_json::from_str::<Value>(&attn_report) {
+            system::on_platform_attested(
+                report["isvEnclaveQuoteStatus"].as_str().unwrap_or_default(),
+            );
+        }
 
         attestation = Some(Attestation {
             version: 1,
@@ -375,22 +380,48 @@ pub fn init_runtime(
 
     let mut other_contracts: BTreeMap<ContractId, Box<dyn contracts::Contract + Send>> =
         Default::default();
+    // The contract ecdh keys are rotated at each initialization
+    let topic_key_salt = generate_random_info();
+    let mut topic_keys = Vec::new();
 
     if local_state.dev_mode {
         // Install contracts when running in dev_mode.
 
         macro_rules! install_contract {
             ($id: expr, $inner: expr) => {{
-                let sender = MessageOrigin::native_contract($id);
-                let mq = send_mq.channel(sender, id_pair.clone());
+                let contract_id = H256::from_low_u64_be($id as u64);
+                let mq = msg_channel::contract_channel(
+                    &send_mq,
+                    contract_id,
+                    &id_pair,
+                    msg_channel::ContractSigner::Derived,
+                );
+                info!(
+                    "Contract {} signs with {}",
+                    $id,
+                    hex::encode(
+                        msg_channel::derive_contract_signing_key(&id_pair, &contract_id).public()
+                    )
+                );
+                mq.set_capacity(Some(contracts::EGRESS_QUEUE_CAPACITY));
                 let cmd_mq = PeelingReceiver::new_plain(recv_mq.subscribe_bound());
                 let evt_mq = PeelingReceiver::new_plain(recv_mq.subscribe_bound());
+                let ecdh_key =
+                    msg_channel::derive_contract_ecdh_key(&id_pair, &contract_id, &topic_key_salt);
+                let record = msg_channel::osp::TopicKeyRecord {
+                    suite: Default::default(),
+                    pubkey: ecdh_key.public().to_vec(),
+                };
                 let wrapped = Box::new(contracts::NativeCompatContract::new(
                     $inner,
                     mq,
                     cmd_mq,
                     evt_mq,
-                    KeyPair::new(local_state.ecdh_key.as_ref().unwrap().clone()),
+                    KeyPair::new(ecdh_key),
+                ));
+                topic_keys.push((
+                    contracts::Contract::command_topic(wrapped.as_ref()),
+                    record.encode(),
                 ));
                 other_contracts.insert($id, wrapped);
             }};
@@ -417,6 +448,14 @@ pub fn init_runtime(
         );
     }
 
+    if !topic_keys.is_empty() {
+        let mut system_state = SYSTEM_STATE.lock().unwrap();
+        let system = system_state.as_mut().expect("system initialized above");
+        for (topic, record) in topic_keys {
+            system.publish_topic_key(topic, record);
+        }
+    }
+
     let mut runtime_state = RuntimeState {
         contracts: other_contracts,
         send_mq,
@@ -7,9 +7,10 @@ use phala_crypto::{
 use phala_mq::{BindTopic, MessageDispatcher, MessageSendQueue, Sr25519MessageChannel};
 use phala_types::{
     messaging::{
-        DispatchMasterKeyEvent, GatekeeperEvent, MessageOrigin, MiningInfoUpdateEvent,
-        MiningReportEvent, NewGatekeeperEvent, RandomNumber, RandomNumberEvent, SettleInfo,
-        SystemEvent, WorkerEvent, WorkerEventWithKey,
+        DispatchMasterKeyEvent, GatekeeperEvent, GatekeeperTelemetry, MessageOrigin,
+        MiningInfoUpdateConfirm, MiningInfoUpdateEvent, MiningReportEvent, NewGatekeeperEvent,
+        RandomNumber, RandomNumberEvent, SettleBinding, SettleInfo, SystemEvent, WorkerEvent,
+        WorkerEventWithKey, MAX_STATUS_UPDATES_PER_MESSAGE,
     },
     WorkerPublicKey,
 };
@@ -33,6 +34,15 @@ use tokenomic::{FixedPoint, TokenomicInfo};
 /// WARNING: this interval need to be large enough considering the latency of mq
 const VRF_INTERVAL: u32 = 5;
 
+/// The interval (in blocks) to send the gatekeeper telemetry
+const TELEMETRY_INTERVAL: u32 = 600;
+
+/// The max latency (in ms) of a heartbeat to recover an unresponsive worker
+///
+/// A heartbeat arriving later than this is still processed, but can't prove the worker is back
+/// online.
+const MAX_RECOVERY_HEARTBEAT_LATENCY_MS: u64 = 60_000;
+
 /// Master key filepath
 pub const MASTER_KEY_FILEPATH: &str = "master_key.seal";
 
@@ -62,6 +72,14 @@ struct WorkerInfo {
     unresponsive: bool,
     tokenomic: TokenomicInfo,
     heartbeat_flag: bool,
+    /// The latency (in ms) of the last heartbeat, from the challenge to its inclusion
+    last_heartbeat_latency: u64,
+    /// The rolling average of the heartbeat latency (in ms)
+    heartbeat_latency: u64,
+    /// In the observation mode, where the performance is tracked without V, payout or slash
+    observing: bool,
+    /// The multiplier of the slash rate by the reputation tier of the miner
+    slash_multiplier: FixedPoint,
 }
 
 impl WorkerInfo {
@@ -72,6 +90,28 @@ impl WorkerInfo {
             unresponsive: false,
             tokenomic: Default::default(),
             heartbeat_flag: false,
+            last_heartbeat_latency: 0,
+            heartbeat_latency: 0,
+            observing: false,
+            slash_multiplier: FixedPoint::from_num(1),
+        }
+    }
+
+    /// Records the latency of a heartbeat, folding it into the rolling average (weight of 1/8)
+    fn record_heartbeat_latency(&mut self, latency: u64) {
+        self.last_heartbeat_latency = latency;
+        self.heartbeat_latency = if self.heartbeat_latency == 0 {
+            latency
+        } else {
+            self.heartbeat_latency - self.heartbeat_latency / 8 + latency / 8
+        };
+    }
+
+    /// The miner binding the worker is settled under, for the chain to verify the payout routing
+    fn settle_binding(&self) -> SettleBinding {
+        SettleBinding {
+            pubkey: self.state.pubkey.clone(),
+            binding_hash: self.state.bound_miner.as_deref().map(hashing::blake2_256),
         }
     }
 }
@@ -101,6 +141,8 @@ pub(super) struct Gatekeeper<MsgChan> {
     // Randomness
     last_random_number: RandomNumber,
     last_random_block: chain::BlockNumber,
+    // The number of mining update events emitted since the last telemetry
+    events_emitted: u32,
 
     tokenomic_params: tokenomic::Params,
 }
@@ -128,6 +170,7 @@ where
             workers: Default::default(),
             last_random_number: [0_u8; 32],
             last_random_block: 0,
+            events_emitted: 0,
             tokenomic_params: tokenomic::test_params(),
         }
     }
@@ -261,13 +304,16 @@ where
         let sum_share: FixedPoint = self
             .workers
             .values()
-            .map(|info| info.tokenomic.share())
+            .filter(|info| !info.observing)
+            .map(|info| info.tokenomic.share(&self.tokenomic_params))
             .sum();
 
+        let mut report = MiningInfoUpdateEvent::new(block.block_number, block.now_ms);
+        report.protocol_version = crate::gatekeeper::negotiate_gk_protocol(block.storage);
         let mut processor = GKMessageProcesser {
             state: self,
             block,
-            report: MiningInfoUpdateEvent::new(block.block_number, block.now_ms),
+            report,
             sum_share,
         };
 
@@ -276,10 +322,38 @@ where
         let report = processor.report;
 
         if !report.is_empty() {
-            self.push_gatekeeper_message(report);
+            // The legacy chains can't tell the pages apart
+            let pages = if report.protocol_version >= 4 {
+                report.paginate(MAX_STATUS_UPDATES_PER_MESSAGE as usize)
+            } else {
+                vec![report]
+            };
+            for page in pages {
+                // Vouch for the report with our own identity, in case the chain requires a quorum
+                // of gatekeepers to apply it.
+                if self.registered_on_chain {
+                    let digest = hashing::blake2_256(&page.encode());
+                    self.worker_egress.send(&MiningInfoUpdateConfirm { digest });
+                }
+                self.push_gatekeeper_message(page);
+                self.events_emitted = self.events_emitted.saturating_add(1);
+            }
         }
     }
 
+    /// Sends the telemetry of this gatekeeper every `TELEMETRY_INTERVAL` blocks
+    pub fn emit_telemetry(&mut self, block_number: chain::BlockNumber) {
+        if block_number % TELEMETRY_INTERVAL != 0 || !self.registered_on_chain {
+            return;
+        }
+        self.worker_egress.send(&GatekeeperTelemetry {
+            block_number,
+            workers_tracked: self.workers.len() as u32,
+            events_emitted: self.events_emitted,
+        });
+        self.events_emitted = 0;
+    }
+
     pub fn emit_random_number(&mut self, block_number: chain::BlockNumber) {
         if block_number % VRF_INTERVAL != 0 {
             return;
@@ -373,13 +447,15 @@ where
                 .state
                 .on_block_processed(self.block, &mut tracker);
 
-            if worker_info.state.mining_state.is_none() {
-                // Mining already stopped, do nothing.
+            if worker_info.state.mining_state.is_none() || worker_info.observing {
+                // Mining already stopped or only observing, do nothing.
                 continue;
             }
 
             if worker_info.unresponsive {
-                if worker_info.heartbeat_flag {
+                if worker_info.heartbeat_flag
+                    && worker_info.last_heartbeat_latency <= MAX_RECOVERY_HEARTBEAT_LATENCY_MS
+                {
                     // case5: Unresponsive, successful heartbeat
                     worker_info.unresponsive = false;
                     self.report
@@ -403,7 +479,9 @@ where
                 // case3/case4:
                 // Idle, heartbeat failed or
                 // Unresponsive, no event
-                worker_info.tokenomic.update_v_slash(&params);
+                worker_info
+                    .tokenomic
+                    .update_v_slash(&params, worker_info.slash_multiplier);
             } else if !worker_info.heartbeat_flag {
                 // case1: Idle, no event
                 worker_info.tokenomic.update_v_idle(&params);
@@ -424,6 +502,7 @@ where
                 challenge_block,
                 challenge_time,
                 iterations,
+                ..
             } => {
                 let worker_info = match self.state.workers.get_mut(&worker_pubkey) {
                     Some(info) => info,
@@ -461,13 +540,17 @@ where
                 }
 
                 worker_info.heartbeat_flag = true;
+                worker_info
+                    .record_heartbeat_latency(self.block.now_ms.saturating_sub(challenge_time));
 
                 let tokenomic = &mut worker_info.tokenomic;
                 tokenomic.update_p_instant(self.block.now_ms, iterations);
                 tokenomic.challenge_time_last = challenge_time;
                 tokenomic.iteration_last = iterations;
 
-                if worker_info.unresponsive {
+                if worker_info.observing {
+                    // Only the performance is tracked in the observation mode.
+                } else if worker_info.unresponsive {
                     // case5: Unresponsive, successful heartbeat.
                 } else {
                     // case2: Idle, successful heartbeat, report to pallet
@@ -482,7 +565,11 @@ where
                         pubkey: worker_pubkey.clone(),
                         v: worker_info.tokenomic.v.to_bits(),
                         payout: payout.to_bits(),
-                    })
+                    });
+                    if self.report.protocol_version >= 3 {
+                        let binding = worker_info.settle_binding();
+                        self.report.settle_bindings.push(binding);
+                    }
                 }
             }
         }
@@ -498,6 +585,7 @@ where
         if let SystemEvent::WorkerEvent(WorkerEventWithKey {
             pubkey,
             event: WorkerEvent::Registered(_),
+            ..
         }) = &event
         {
             let _ = self
@@ -520,6 +608,15 @@ where
 
         match &event {
             SystemEvent::WorkerEvent(e) => {
+                // The session being transferred must be read before borrowing the new worker.
+                let transferred = match &e.event {
+                    WorkerEvent::SessionTransferred { from, .. } => self
+                        .state
+                        .workers
+                        .get(from)
+                        .map(|w| (w.tokenomic, w.slash_multiplier)),
+                    _ => None,
+                };
                 if let Some(worker) = self.state.workers.get_mut(&e.pubkey) {
                     match &e.event {
                         WorkerEvent::Registered(info) => {
@@ -532,9 +629,12 @@ where
                         WorkerEvent::MiningStart {
                             session_id: _, // Aready recorded by the state machine.
                             init_v,
-                        } => {
+                        }
+                        | WorkerEvent::MinerBoundAndStarted { init_v, .. } => {
                             let v = FixedPoint::from_bits(*init_v);
                             let prev = worker.tokenomic;
+                            worker.observing = false;
+                            worker.slash_multiplier = FixedPoint::from_num(1);
                             // NOTE.kevin: To track the heartbeats by global timeline, don't clear the waiting_heartbeats.
                             // worker.waiting_heartbeats.clear();
                             worker.unresponsive = false;
@@ -549,6 +649,9 @@ where
                                 confidence_level: prev.confidence_level,
                             };
                         }
+                        WorkerEvent::MiningStop if worker.observing => {
+                            worker.observing = false;
+                        }
                         WorkerEvent::MiningStop => {
                             // TODO.kevin: report the final V?
                             // We may need to report a Stop event in worker.
@@ -565,10 +668,68 @@ where
                                 pubkey: worker.state.pubkey.clone(),
                                 v: worker.tokenomic.v.to_bits(),
                                 payout: 0,
-                            })
+                            });
+                            if self.report.protocol_version >= 3 {
+                                let binding = worker.settle_binding();
+                                self.report.settle_bindings.push(binding);
+                            }
                         }
                         WorkerEvent::MiningEnterUnresponsive => {}
                         WorkerEvent::MiningExitUnresponsive => {}
+                        WorkerEvent::CoolDownEnded => {}
+                        WorkerEvent::MinerBound { .. } => {}
+                        WorkerEvent::MinerUnbound => {}
+                        WorkerEvent::MiningStakeUpdated { .. } => {}
+                        WorkerEvent::TierChanged { slash_multiplier } => {
+                            worker.slash_multiplier =
+                                FixedPoint::from_num(*slash_multiplier) / 1_000_000;
+                        }
+                        WorkerEvent::ObservingStart { .. } => {
+                            let prev = worker.tokenomic;
+                            worker.observing = true;
+                            worker.unresponsive = false;
+                            worker.tokenomic = TokenomicInfo {
+                                v: FixedPoint::from_num(0),
+                                v_last: FixedPoint::from_num(0),
+                                v_update_at: self.block.now_ms,
+                                iteration_last: 0,
+                                challenge_time_last: self.block.now_ms,
+                                p_bench: prev.p_bench,
+                                p_instant: prev.p_bench,
+                                confidence_level: prev.confidence_level,
+                            };
+                        }
+                        WorkerEvent::TrialCompleted { v_delta } => {
+                            // Raise V without producing any payout
+                            let v_delta = FixedPoint::from_bits(*v_delta);
+                            worker.tokenomic.v += v_delta;
+                            worker.tokenomic.v_last += v_delta;
+                        }
+                        WorkerEvent::SessionTransferred { from, .. } => {
+                            let (prev, slash_multiplier) = match transferred {
+                                Some(prev) => prev,
+                                None => {
+                                    error!(
+                                        "Session transferred from unknown worker {}",
+                                        hex::encode(from)
+                                    );
+                                    return;
+                                }
+                            };
+                            // Keep V continuous, but evaluate the new hardware by its own benchmark.
+                            worker.unresponsive = false;
+                            worker.slash_multiplier = slash_multiplier;
+                            worker.tokenomic = TokenomicInfo {
+                                v: prev.v,
+                                v_last: prev.v_last,
+                                v_update_at: prev.v_update_at,
+                                iteration_last: 0,
+                                challenge_time_last: self.block.now_ms,
+                                p_bench: worker.tokenomic.p_bench,
+                                p_instant: worker.tokenomic.p_bench,
+                                confidence_level: worker.tokenomic.confidence_level,
+                            };
+                        }
                     }
                 }
             }
@@ -590,7 +751,20 @@ where
             }
             GatekeeperEvent::TokenomicParametersChanged(params) => {
                 if origin.is_pallet() {
+                    let confidence_scores = self.state.tokenomic_params.confidence_scores;
                     self.state.tokenomic_params = params.into();
+                    self.state.tokenomic_params.confidence_scores = confidence_scores;
+                }
+            }
+            GatekeeperEvent::ConfidenceScoresChanged(scores) => {
+                if origin.is_pallet() {
+                    self.state.tokenomic_params.confidence_scores = scores.map(|bits| {
+                        let mut scores = [FixedPoint::from_num(0); 5];
+                        for (score, bits) in scores.iter_mut().zip(bits.iter()) {
+                            *score = FixedPoint::from_bits(*bits);
+                        }
+                        scores
+                    });
                 }
             }
         }
@@ -757,7 +931,10 @@ mod tokenomic {
         v * v
     }
 
-    fn conf_score(level: u8) -> FixedPoint {
+    fn conf_score(level: u8, scores: &Option<[FixedPoint; 5]>) -> FixedPoint {
+        if let (1..=5, Some(scores)) = (level, scores) {
+            return scores[level as usize - 1];
+        }
         match level {
             1 | 2 | 3 | 128 => fp(1),
             4 => fp(8) / 10,
@@ -788,6 +965,9 @@ mod tokenomic {
         cost_k: FixedPoint,
         cost_b: FixedPoint,
         pub heartbeat_window: u32,
+        /// The scores of the confidence levels 1 to 5 set by the chain, or None for the builtin
+        /// table
+        pub confidence_scores: Option<[FixedPoint; 5]>,
     }
 
     impl From<TokenomicParameters> for Params {
@@ -801,6 +981,7 @@ mod tokenomic {
                 cost_k: FixedPoint::from_bits(params.cost_k),
                 cost_b: FixedPoint::from_bits(params.cost_b),
                 heartbeat_window: params.heartbeat_window,
+                confidence_scores: None,
             }
         }
     }
@@ -815,6 +996,7 @@ mod tokenomic {
             cost_k: fp(287) / 10000 / 300, // 0.0287
             cost_b: fp(15) / 300,
             heartbeat_window: 10, // 10 blocks
+            confidence_scores: None,
         }
     }
 
@@ -853,26 +1035,31 @@ mod tokenomic {
             let dv = self.v - self.v_last;
             let dt = fp(now_ms - self.v_update_at) / 1000;
             let budget = params.budget_per_sec * dt;
-            let w = dv.max(fp(0)).min(self.share() / sum_share * budget);
+            let w = dv.max(fp(0)).min(self.share(params) / sum_share * budget);
             self.v -= w;
             self.v_last = self.v;
             self.v_update_at = now_ms;
             w
         }
 
-        pub fn update_v_slash(&mut self, params: &Params) {
-            self.v -= self.v * params.slash_rate;
+        pub fn update_v_slash(&mut self, params: &Params, multiplier: FixedPoint) {
+            self.v -= self.v * params.slash_rate * multiplier;
         }
 
-        pub fn share(&self) -> FixedPoint {
-            (square(self.v) + square(fp(2) * self.p_instant * conf_score(self.confidence_level)))
-                .sqrt()
+        pub fn share(&self, params: &Params) -> FixedPoint {
+            let score = conf_score(self.confidence_level, &params.confidence_scores);
+            (square(self.v) + square(fp(2) * self.p_instant * score)).sqrt()
         }
 
         pub fn update_p_instant(&mut self, now: u64, iterations: u64) {
             if now <= self.challenge_time_last {
                 return;
             }
+            // The iterations counter of a restarted worker starts over. Keep the last p_instant
+            // and restart the baseline, as the chain does.
+            if iterations < self.iteration_last {
+                return;
+            }
             let dt = fp(now - self.challenge_time_last) / 1000;
             let p = fp(iterations - self.iteration_last) / dt * 6; // 6s iterations
             self.p_instant = p.min(self.p_bench * fp(12) / fp(10));
@@ -1058,6 +1245,8 @@ pub mod tests {
             let challenge = msg::HeartbeatChallenge {
                 seed: hashed_id,
                 online_target: U256::zero(),
+                targets: None,
+                stake_weighting: None,
             };
             let message = msg::SystemEvent::HeartbeatChallenge(challenge);
             self.mq.dispatch_bound(&sender, message);
@@ -1069,6 +1258,8 @@ pub mod tests {
                 challenge_block: block,
                 challenge_time: block_ts(block),
                 iterations,
+                platform: None,
+                restarted: false,
             };
             self.say(message)
         }
@@ -1389,6 +1580,9 @@ pub mod tests {
                 offline,
                 recovered_to_online: Vec::new(),
                 settle: Vec::new(),
+                protocol_version: 0,
+                settle_bindings: Vec::new(),
+                continues: false,
             };
             let messages = r.gk.egress.drain_mining_info_update_event();
             assert_eq!(messages.len(), 1);
@@ -1549,6 +1743,9 @@ pub mod tests {
                 offline: Vec::new(),
                 recovered_to_online,
                 settle: Vec::new(),
+                protocol_version: 0,
+                settle_bindings: Vec::new(),
+                continues: false,
             };
             let messages = r.gk.egress.drain_mining_info_update_event();
             assert_eq!(messages.len(), 1, "Should report recover event");
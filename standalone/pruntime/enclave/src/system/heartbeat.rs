@@ -0,0 +1,142 @@
+//! Matching of the heartbeat challenges and scheduling of the responses
+//!
+//! The responses are delayed by a jitter derived from the worker and the challenge, so that the
+//! workers hit by the same challenge spread their heartbeats across blocks.
+
+use crate::std::boxed::Box;
+use crate::std::collections::VecDeque;
+use crate::std::vec::Vec;
+
+use phala_types::{messaging::HeartbeatChallenge, WorkerPublicKey};
+use sp_core::{hashing::blake2_256, U256};
+
+/// The default max number of blocks a heartbeat response is delayed. It must be far below the
+/// heartbeat window of the tokenomic, or the delayed workers would be seen as offline.
+pub const DEFAULT_MAX_JITTER_BLOCKS: u32 = 2;
+
+/// Returns if a worker is challenged by `challenge`
+///
+/// The worker must be in the targets of the challenge if any, and its `hashed_id` must hit the
+/// online target, which is weighted by `stake` if the worker knows its stake.
+pub fn is_challenged(
+    pubkey: &WorkerPublicKey,
+    hashed_id: U256,
+    stake: Option<u128>,
+    challenge: &HeartbeatChallenge,
+) -> bool {
+    if let Some(targets) = &challenge.targets {
+        if !targets.contains(pubkey) {
+            return false;
+        }
+    }
+    let x = hashed_id ^ challenge.seed;
+    // The workers not knowing their stake are sampled with the average weight
+    let online_target = match (&challenge.stake_weighting, stake) {
+        (Some(weighting), Some(stake)) => weighting.target_of(challenge.online_target, stake),
+        _ => challenge.online_target,
+    };
+    x <= online_target
+}
+
+/// A heartbeat to send in response to a challenge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeartbeatResponse {
+    pub session_id: u32,
+    pub challenge_block: chain::BlockNumber,
+    pub challenge_time: u64,
+    /// The benchmark iterations of the session, counted when the challenge was received
+    pub iterations: u64,
+    /// The block to send the heartbeat at
+    pub due_block: chain::BlockNumber,
+}
+
+/// The callback sending the heartbeat responses
+pub type Responder = Box<dyn FnMut(&HeartbeatResponse) + Send>;
+
+/// Delays the heartbeat responses by a jitter and hands them to the registered responders
+pub struct HeartbeatScheduler {
+    max_jitter_blocks: u32,
+    pending: VecDeque<HeartbeatResponse>,
+    responders: Vec<Responder>,
+}
+
+impl HeartbeatScheduler {
+    pub fn new(max_jitter_blocks: u32) -> Self {
+        HeartbeatScheduler {
+            max_jitter_blocks,
+            pending: Default::default(),
+            responders: Default::default(),
+        }
+    }
+
+    /// Registers a callback to receive the due heartbeat responses
+    pub fn register_responder(&mut self, responder: Responder) {
+        self.responders.push(responder);
+    }
+
+    /// The number of blocks the response of the worker to the challenge of `challenge_block` is
+    /// delayed
+    ///
+    /// It's deterministic, so that a replayed challenge is responded at the same block.
+    pub fn jitter_of(&self, hashed_id: U256, challenge_block: chain::BlockNumber) -> u32 {
+        if self.max_jitter_blocks == 0 {
+            return 0;
+        }
+        let mut buf = [0u8; 36];
+        hashed_id.to_big_endian(&mut buf[..32]);
+        buf[32..].copy_from_slice(&challenge_block.to_be_bytes());
+        let hash = blake2_256(&buf);
+        let n = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+        n % (self.max_jitter_blocks + 1)
+    }
+
+    /// Schedules a response to the challenge of `challenge_block`
+    pub fn schedule(
+        &mut self,
+        hashed_id: U256,
+        session_id: u32,
+        challenge_block: chain::BlockNumber,
+        challenge_time: u64,
+        iterations: u64,
+    ) {
+        let due_block = challenge_block + self.jitter_of(hashed_id, challenge_block);
+        let response = HeartbeatResponse {
+            session_id,
+            challenge_block,
+            challenge_time,
+            iterations,
+            due_block,
+        };
+        // Kept ordered by the due block
+        let pos = self
+            .pending
+            .iter()
+            .position(|r| r.due_block > due_block)
+            .unwrap_or(self.pending.len());
+        self.pending.insert(pos, response);
+    }
+
+    /// Hands the responses due at `block_number` to the responders, returning the number of them
+    pub fn on_block(&mut self, block_number: chain::BlockNumber) -> usize {
+        let mut sent = 0;
+        while matches!(self.pending.front(), Some(r) if r.due_block <= block_number) {
+            let response = self.pending.pop_front().expect("Checked above; qed.");
+            for responder in self.responders.iter_mut() {
+                responder(&response);
+            }
+            sent += 1;
+        }
+        sent
+    }
+
+    /// Drops the pending responses of the sessions other than `session_id`, e.g. after the
+    /// mining stopped
+    pub fn retain_session(&mut self, session_id: Option<u32>) {
+        self.pending.retain(|r| Some(r.session_id) == session_id);
+    }
+
+    /// The number of the responses not sent yet
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+}
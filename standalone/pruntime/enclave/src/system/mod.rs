@@ -1,11 +1,14 @@
 mod gk;
+pub mod heartbeat;
 
 use crate::{benchmark, std::prelude::v1::*, types::BlockInfo};
 use anyhow::Result;
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use log::info;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use chain::pallet_registry::RegistryEvent;
 pub use enclave_api::prpc::{GatekeeperRole, GatekeeperStatus};
@@ -14,11 +17,16 @@ use phala_mq::{
     TypedReceiver,
 };
 use phala_types::{
-    messaging::{HeartbeatChallenge, MiningReportEvent, SystemEvent, WorkerEvent},
+    messaging::{HeartbeatChallenge, MiningReportEvent, PlatformStatus, SystemEvent, WorkerEvent},
     WorkerPublicKey,
 };
 use sp_core::{hashing::blake2_256, sr25519, Pair, U256};
 
+/// The confidence level of the last attestation report (0 if not attested)
+static PLATFORM_CONFIDENCE_LEVEL: AtomicU8 = AtomicU8::new(0);
+/// The time (in sec) of the last attestation
+static PLATFORM_ATTESTED_AT: AtomicU64 = AtomicU64::new(0);
+
 pub type CommandIndex = u64;
 
 type Event = SystemEvent;
@@ -99,6 +107,13 @@ struct WorkerState {
     registered: bool,
     bench_state: Option<BenchState>,
     mining_state: Option<MiningInfo>,
+    cooling_down: bool,
+    /// The SCALE-encoded miner account bound to the worker
+    bound_miner: Option<Vec<u8>>,
+    /// The nonce of the last applied sequenced worker event
+    last_event_nonce: Option<u64>,
+    /// The stake of the current mining session, if told by the chain
+    stake: Option<u128>,
 }
 
 impl WorkerState {
@@ -112,6 +127,10 @@ impl WorkerState {
             registered: false,
             bench_state: None,
             mining_state: None,
+            cooling_down: false,
+            bound_miner: None,
+            last_event_nonce: None,
+            stake: None,
         }
     }
 
@@ -127,6 +146,17 @@ impl WorkerState {
                 if evt.pubkey != self.pubkey {
                     return;
                 }
+                if let Some(seq) = &evt.seq {
+                    // A replayed or reordered event from an earlier point of the stream, e.g. a
+                    // stale MiningStart of a previous session
+                    if matches!(self.last_event_nonce, Some(last) if seq.nonce <= last) {
+                        if log_on {
+                            warn!("Ignored stale worker event: {:?}, seq={:?}", evt.event, seq);
+                        }
+                        return;
+                    }
+                    self.last_event_nonce = Some(seq.nonce);
+                }
 
                 use MiningState::*;
                 use WorkerEvent::*;
@@ -151,20 +181,43 @@ impl WorkerState {
                             info!("My benchmark score is {}", score);
                         }
                     }
+                    MinerBoundAndStarted {
+                        ref miner,
+                        session_id,
+                        ..
+                    } => {
+                        self.bound_miner = Some(miner.clone());
+                        if log_on {
+                            info!("Bound to miner 0x{}", hex::encode(miner));
+                        }
+                        self.mining_state = Some(MiningInfo {
+                            session_id,
+                            state: Mining,
+                            start_time: block.now_ms,
+                            start_iter: callback.bench_iterations(),
+                        });
+                        self.cooling_down = false;
+                        callback.bench_resume();
+                    }
                     MiningStart {
                         session_id,
                         init_v: _,
-                    } => {
+                    }
+                    | SessionTransferred { session_id, .. }
+                    | ObservingStart { session_id } => {
                         self.mining_state = Some(MiningInfo {
                             session_id,
                             state: Mining,
                             start_time: block.now_ms,
                             start_iter: callback.bench_iterations(),
                         });
+                        self.cooling_down = false;
                         callback.bench_resume();
                     }
                     MiningStop => {
                         self.mining_state = None;
+                        self.cooling_down = true;
+                        self.stake = None;
                         if self.need_pause() {
                             callback.bench_pause();
                         }
@@ -197,6 +250,37 @@ impl WorkerState {
                             );
                         }
                     }
+                    CoolDownEnded => {
+                        self.cooling_down = false;
+                        if log_on {
+                            info!("Cool down ended, the worker is ready to mine again");
+                        }
+                    }
+                    MinerBound { ref miner } => {
+                        self.bound_miner = Some(miner.clone());
+                        if log_on {
+                            info!("Bound to miner 0x{}", hex::encode(miner));
+                        }
+                    }
+                    MinerUnbound => {
+                        self.bound_miner = None;
+                        if log_on {
+                            info!("Unbound from the miner");
+                        }
+                    }
+                    TrialCompleted { .. } => {
+                        if log_on {
+                            info!("Trial mining completed");
+                        }
+                    }
+                    MiningStakeUpdated { stake } => {
+                        self.stake = Some(stake);
+                    }
+                    TierChanged { slash_multiplier } => {
+                        if log_on {
+                            info!("Slash rate multiplier changed to {}ppm", slash_multiplier);
+                        }
+                    }
                 }
             }
             Event::HeartbeatChallenge(seed_info) => {
@@ -233,11 +317,8 @@ impl WorkerState {
             return;
         }
 
-        let x = self.hashed_id ^ seed_info.seed;
-        let online_hit = x <= seed_info.online_target;
-
         // Push queue when necessary
-        if online_hit {
+        if heartbeat::is_challenged(&self.pubkey, self.hashed_id, self.stake, seed_info) {
             let iterations = callback.bench_iterations() - mining_state.start_iter;
             callback.heartbeat(
                 mining_state.session_id,
@@ -294,7 +375,14 @@ trait WorkerStateMachineCallback {
     }
 }
 
-struct WorkerSMDelegate<'a>(&'a Sr25519MessageChannel);
+/// Whether any heartbeat has been sent since the pRuntime started
+static HEARTBEAT_SENT: AtomicBool = AtomicBool::new(false);
+
+struct WorkerSMDelegate<'a> {
+    egress: &'a Sr25519MessageChannel,
+    heartbeats: &'a mut heartbeat::HeartbeatScheduler,
+    hashed_id: U256,
+}
 
 impl WorkerStateMachineCallback for WorkerSMDelegate<'_> {
     fn bench_iterations(&self) -> u64 {
@@ -312,7 +400,7 @@ impl WorkerStateMachineCallback for WorkerSMDelegate<'_> {
             iterations,
         };
         info!("Reporting benchmark: {:?}", report);
-        self.0.send(&report);
+        self.egress.send(&report);
     }
     fn heartbeat(
         &mut self,
@@ -321,15 +409,69 @@ impl WorkerStateMachineCallback for WorkerSMDelegate<'_> {
         challenge_time: u64,
         iterations: u64,
     ) {
-        let event = MiningReportEvent::Heartbeat {
+        self.heartbeats.schedule(
+            self.hashed_id,
             session_id,
             challenge_block,
             challenge_time,
             iterations,
+        );
+    }
+}
+
+/// Returns the responder sending the heartbeats to the chain
+fn heartbeat_sender(egress: Sr25519MessageChannel) -> heartbeat::Responder {
+    Box::new(move |response: &heartbeat::HeartbeatResponse| {
+        // The iterations counter starts over in a new pRuntime process
+        let restarted = !HEARTBEAT_SENT.swap(true, Ordering::Relaxed);
+        let event = MiningReportEvent::Heartbeat {
+            session_id: response.session_id,
+            challenge_block: response.challenge_block,
+            challenge_time: response.challenge_time,
+            iterations: response.iterations,
+            platform: platform_status(),
+            restarted,
         };
         info!("System: sending {:?}", event);
-        self.0.send(&event);
+        egress.send(&event);
+    })
+}
+
+fn now_sec() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Records the quote status of the attestation report of the platform
+pub fn on_platform_attested(quote_status: &str) {
+    let confidence_level = match quote_status {
+        "OK" => 1,
+        "SW_HARDENING_NEEDED" => 2,
+        "CONFIGURATION_NEEDED" | "CONFIGURATION_AND_SW_HARDENING_NEEDED" => 3,
+        "GROUP_OUT_OF_DATE" => 5,
+        _ => 0,
+    };
+    info!(
+        "Platform attested with confidence level {}",
+        confidence_level
+    );
+    PLATFORM_CONFIDENCE_LEVEL.store(confidence_level, Ordering::Relaxed);
+    PLATFORM_ATTESTED_AT.store(now_sec(), Ordering::Relaxed);
+}
+
+/// The platform status reported along with the heartbeats, or None if not attested
+fn platform_status() -> Option<PlatformStatus> {
+    let confidence_level = PLATFORM_CONFIDENCE_LEVEL.load(Ordering::Relaxed);
+    if confidence_level == 0 {
+        return None;
     }
+    let attested_at = PLATFORM_ATTESTED_AT.load(Ordering::Relaxed);
+    Some(PlatformStatus {
+        confidence_level,
+        uptime_sec: now_sec().saturating_sub(attested_at),
+    })
 }
 
 pub struct System {
@@ -340,7 +482,10 @@ pub struct System {
     ingress: TypedReceiver<Event>,
 
     worker_state: WorkerState,
+    heartbeats: heartbeat::HeartbeatScheduler,
     gatekeeper: gk::Gatekeeper<Sr25519MessageChannel>,
+    // The contract topic keys to provision once registered
+    pending_topic_keys: Vec<(Vec<u8>, Vec<u8>)>,
 }
 
 impl System {
@@ -360,12 +505,46 @@ impl System {
         );
         gatekeeper.try_unseal_master_key(gk::MASTER_KEY_FILEPATH);
 
+        let egress = send_mq.channel(sender, pair.clone());
+        let mut heartbeats =
+            heartbeat::HeartbeatScheduler::new(heartbeat::DEFAULT_MAX_JITTER_BLOCKS);
+        heartbeats.register_responder(heartbeat_sender(egress.clone()));
+
         System {
             receipts: Default::default(),
-            egress: send_mq.channel(sender, pair.clone()),
+            egress,
             ingress: recv_mq.subscribe_bound(),
             worker_state: WorkerState::new(pubkey.clone()),
+            heartbeats,
             gatekeeper: gatekeeper,
+            pending_topic_keys: Vec::new(),
+        }
+    }
+
+    /// Registers an extra callback to receive the heartbeat responses when they are due
+    pub fn register_heartbeat_responder(&mut self, responder: heartbeat::Responder) {
+        self.heartbeats.register_responder(responder);
+    }
+
+    /// Provisions the `TopicKey` record of a hosted contract on chain
+    ///
+    /// The registry pallet anchors the topic to this worker, so only it can rotate the key later.
+    /// The record is held until the worker is registered, as the pallet rejects it before.
+    pub fn publish_topic_key(&mut self, topic: Vec<u8>, record: Vec<u8>) {
+        self.pending_topic_keys.push((topic, record));
+    }
+
+    fn flush_topic_keys(&mut self) {
+        if !self.worker_state.registered {
+            return;
+        }
+        for (topic, record) in self.pending_topic_keys.drain(..) {
+            let event = RegistryEvent::TopicKey {
+                topic,
+                pubkey: record,
+            };
+            info!("Provisioning topic key: {:?}", event);
+            self.egress.send(&event);
         }
     }
 
@@ -431,8 +610,14 @@ impl System {
                 },
             }
         }
-        self.worker_state
-            .on_block_processed(block, &mut WorkerSMDelegate(&self.egress));
+        let mut delegate = WorkerSMDelegate {
+            egress: &self.egress,
+            heartbeats: &mut self.heartbeats,
+            hashed_id: self.worker_state.hashed_id,
+        };
+        self.worker_state.on_block_processed(block, &mut delegate);
+        self.heartbeats.on_block(block.block_number);
+        self.flush_topic_keys();
 
         // allow to process gatekeeper messages silently
         // if pRuntime possesses master key but is not registered on chain
@@ -443,13 +628,27 @@ impl System {
             self.gatekeeper.process_messages(block);
 
             self.gatekeeper.emit_random_number(block.block_number);
+
+            self.gatekeeper.emit_telemetry(block.block_number);
         }
         Ok(())
     }
 
     fn process_event(&mut self, block: &BlockInfo, event: &Event) -> Result<()> {
+        let mut delegate = WorkerSMDelegate {
+            egress: &self.egress,
+            heartbeats: &mut self.heartbeats,
+            hashed_id: self.worker_state.hashed_id,
+        };
         self.worker_state
-            .process_event(block, event, &mut WorkerSMDelegate(&self.egress), true);
+            .process_event(block, event, &mut delegate, true);
+        // The responses of a stopped session are no longer accepted
+        let session_id = self
+            .worker_state
+            .mining_state
+            .as_ref()
+            .map(|m| m.session_id);
+        self.heartbeats.retain_session(session_id);
         Ok(())
     }
 
@@ -457,6 +656,15 @@ impl System {
         self.worker_state.registered
     }
 
+    pub fn is_cooling_down(&self) -> bool {
+        self.worker_state.cooling_down
+    }
+
+    /// The SCALE-encoded miner account bound to the worker, if any
+    pub fn bound_miner(&self) -> Option<&[u8]> {
+        self.worker_state.bound_miner.as_deref()
+    }
+
     pub fn gatekeeper_status(&self) -> GatekeeperStatus {
         let active = self.gatekeeper.is_registered_on_chain();
         let has_key = self.gatekeeper.possess_master_key();
@@ -0,0 +1,333 @@
+//! Host side test harness of the enclave OSP module
+//!
+//! `enclave/src/msg_channel/osp.rs` is compiled as is, with the crate root items it relies on
+//! (the `std` facade of sgx_tstd and `generate_random_iv`) shimmed by the host std.
+
+#[macro_use]
+extern crate lazy_static;
+
+/// Mimics the sgx_tstd paths used by the OSP module
+mod std {
+    pub use ::std::{collections, string, vec};
+
+    pub mod sync {
+        pub type SgxMutex<T> = ::std::sync::Mutex<T>;
+    }
+}
+
+fn generate_random_iv() -> phala_crypto::aead::IV {
+    use ring::rand::SecureRandom;
+    let mut nonce_vec = [0u8; phala_crypto::aead::IV_BYTES];
+    let rand = ring::rand::SystemRandom::new();
+    rand.fill(&mut nonce_vec).unwrap();
+    nonce_vec
+}
+
+#[allow(dead_code)]
+#[path = "../../enclave/src/msg_channel/osp.rs"]
+pub mod osp;
+
+#[cfg(test)]
+mod tests {
+    use super::osp::*;
+    use parity_scale_codec::{Decode, Encode};
+    use phala_crypto::ecdh::EcdhKey;
+    use phala_mq::{
+        BindTopic, MessageDispatcher, MessageOrigin, MessageSendQueue, Path, SignedMessage,
+    };
+    use sp_core::{sr25519, Pair};
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+
+    #[derive(Encode, Decode, Debug, PartialEq)]
+    struct Ping(u32);
+
+    impl BindTopic for Ping {
+        const TOPIC: &'static [u8] = b"^phala/test/osp";
+    }
+
+    fn signer() -> sr25519::Pair {
+        sr25519::Pair::from_seed(&[1; 32])
+    }
+
+    fn ecdh_key(seed: u8) -> EcdhKey {
+        EcdhKey::create(&[seed; 32]).unwrap()
+    }
+
+    /// Sends a `Ping(42)` by OSP, with `topic_key` as the `TopicKey` record of the receiver in
+    /// the simulated chain storage
+    fn send_ping(topic_key: Option<Vec<u8>>, stamp: Option<(u64, u64)>) -> Vec<SignedMessage> {
+        send_ping_chunked(topic_key, stamp, DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Same as `send_ping`, split into chunks of at most `chunk_size` bytes
+    fn send_ping_chunked(
+        topic_key: Option<Vec<u8>>,
+        stamp: Option<(u64, u64)>,
+        chunk_size: usize,
+    ) -> Vec<SignedMessage> {
+        let queue = MessageSendQueue::new();
+        let mq = queue.channel(MessageOrigin::Worker(signer().public()), signer());
+        let key = KeyPair::new(ecdh_key(1));
+        let topic_keys: BTreeMap<Path, Vec<u8>> = topic_key
+            .into_iter()
+            .map(|record| (Ping::TOPIC.to_vec(), record))
+            .collect();
+        let key_map = |topic: &Path| topic_keys.get(topic).cloned();
+        let mut osp_mq = OspMq::new(&key, &mq, &key_map).with_chunk_size(chunk_size);
+        if let Some((now_ms, ttl_ms)) = stamp {
+            osp_mq = osp_mq.with_timestamp(now_ms, ttl_ms);
+        }
+        let remote_key = osp_mq.get_pubkey(&Ping::TOPIC.to_vec());
+        osp_mq.osp_send(&Ping(42), remote_key).unwrap();
+        queue.all_messages()
+    }
+
+    /// Dispatches the messages to an OSP receiver with `key`, and takes the first message
+    fn receive(
+        messages: Vec<SignedMessage>,
+        key: EcdhKey,
+        now_ms: Option<u64>,
+    ) -> Result<Option<Ping>, anyhow::Error> {
+        let mut dispatcher = MessageDispatcher::new();
+        let mut receiver: PeelingReceiver<Ping, OspPayload<Ping>, OspPeeler<Ping>> =
+            PeelingReceiver::new_osp(dispatcher.subscribe_bound(), key);
+        if let Some(now_ms) = now_ms {
+            receiver.set_now_ms(now_ms);
+        }
+        for signed in messages {
+            dispatcher.dispatch(signed.message);
+        }
+        Ok(receiver.try_next()?.map(|(_, msg, _)| msg))
+    }
+
+    fn decode_payload(message: &SignedMessage) -> OspPayload<Ping> {
+        Decode::decode(&mut &message.message.payload[..]).unwrap()
+    }
+
+    #[test]
+    fn test_signed_by_the_channel() {
+        let messages = send_ping(None, None);
+        assert_eq!(messages.len(), 1);
+        let signed = &messages[0];
+        assert_eq!(signed.message.destination.path(), &Ping::TOPIC.to_vec());
+        let signature = sr25519::Signature::try_from(&signed.signature[..]).unwrap();
+        assert!(sr25519::Pair::verify(
+            &signature,
+            signed.data_be_signed(),
+            &signer().public()
+        ));
+    }
+
+    #[test]
+    fn test_plain_round_trip() {
+        let messages = send_ping(None, None);
+        assert!(matches!(
+            decode_payload(&messages[0]),
+            OspPayload::Plain(Ping(42))
+        ));
+        assert_eq!(
+            receive(messages, ecdh_key(2), None).unwrap(),
+            Some(Ping(42))
+        );
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        // The legacy record, the raw ecdh pubkey
+        let messages = send_ping(Some(ecdh_key(2).public().to_vec()), None);
+        assert!(matches!(
+            decode_payload(&messages[0]),
+            OspPayload::Encrypted(_)
+        ));
+        assert_eq!(
+            receive(messages, ecdh_key(2), None).unwrap(),
+            Some(Ping(42))
+        );
+        // The record with the default suite is sent in the legacy format
+        let record = TopicKeyRecord {
+            suite: CipherSuite::default(),
+            pubkey: ecdh_key(2).public().to_vec(),
+        };
+        let messages = send_ping(Some(record.encode()), None);
+        assert!(matches!(
+            decode_payload(&messages[0]),
+            OspPayload::Encrypted(_)
+        ));
+        assert_eq!(
+            receive(messages, ecdh_key(2), None).unwrap(),
+            Some(Ping(42))
+        );
+    }
+
+    #[test]
+    fn test_topic_key_record() {
+        let pubkey = ecdh_key(2).public().to_vec();
+        // 32 bytes are always the legacy raw pubkey
+        assert_eq!(
+            TopicKeyRecord::decode_raw(&pubkey),
+            Some(TopicKeyRecord {
+                suite: CipherSuite::default(),
+                pubkey: pubkey.clone(),
+            })
+        );
+        let record = TopicKeyRecord {
+            suite: CipherSuite::default(),
+            pubkey,
+        };
+        assert_eq!(TopicKeyRecord::decode_raw(&record.encode()), Some(record));
+        // The pubkey must match the suite
+        let short = TopicKeyRecord {
+            suite: CipherSuite::default(),
+            pubkey: vec![1; 31],
+        };
+        assert_eq!(TopicKeyRecord::decode_raw(&short.encode()), None);
+    }
+
+    #[test]
+    fn test_tampered_cipher() {
+        let mut messages = send_ping(Some(ecdh_key(2).public().to_vec()), None);
+        let mut payload = decode_payload(&messages[0]);
+        match &mut payload {
+            OspPayload::Encrypted(cipher) => cipher.cipher[0] ^= 1,
+            _ => panic!("Not encrypted"),
+        }
+        messages[0].message.payload = payload.encode();
+        assert!(receive(messages, ecdh_key(2), None).is_err());
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let messages = send_ping(Some(ecdh_key(2).public().to_vec()), None);
+        assert!(receive(messages, ecdh_key(3), None).is_err());
+        // Malformed topic key records are rejected by the sender
+        let queue = MessageSendQueue::new();
+        let mq = queue.channel(MessageOrigin::Worker(signer().public()), signer());
+        let key = KeyPair::new(ecdh_key(1));
+        let key_map = |_: &Path| None;
+        let osp_mq = OspMq::new(&key, &mq, &key_map);
+        assert!(osp_mq.osp_send(&Ping(42), Some(vec![0; 5])).is_err());
+        assert_eq!(queue.count_messages(), 0);
+    }
+
+    #[test]
+    fn test_stamped_round_trip() {
+        let messages = send_ping(Some(ecdh_key(2).public().to_vec()), Some((1_000, 500)));
+        assert!(matches!(
+            decode_payload(&messages[0]),
+            OspPayload::Stamped { .. }
+        ));
+        // Rejected before the receiver's clock is set
+        assert!(receive(messages.clone(), ecdh_key(2), None).is_err());
+        assert_eq!(
+            receive(messages.clone(), ecdh_key(2), Some(1_200)).unwrap(),
+            Some(Ping(42))
+        );
+        // Expired, beyond the tolerated skew
+        let late = 1_500 + DEFAULT_MAX_SKEW_MS + 1;
+        assert!(receive(messages, ecdh_key(2), Some(late)).is_err());
+    }
+
+    #[test]
+    fn test_chunked_round_trip() {
+        let messages =
+            send_ping_chunked(Some(ecdh_key(2).public().to_vec()), Some((1_000, 500)), 16);
+        assert!(messages.len() > 1);
+        let total = messages.len() as u32;
+        for (i, message) in messages.iter().enumerate() {
+            assert!(matches!(
+                decode_payload(message),
+                OspPayload::Chunk { index, total: t, .. } if index == i as u32 && t == total
+            ));
+        }
+        assert_eq!(
+            receive(messages.clone(), ecdh_key(2), Some(1_200)).unwrap(),
+            Some(Ping(42))
+        );
+        // In any order
+        let mut reversed = messages.clone();
+        reversed.reverse();
+        assert_eq!(
+            receive(reversed, ecdh_key(2), Some(1_200)).unwrap(),
+            Some(Ping(42))
+        );
+        // Incomplete
+        let mut incomplete = messages.clone();
+        incomplete.remove(1);
+        assert_eq!(receive(incomplete, ecdh_key(2), Some(1_200)).unwrap(), None);
+        // Not chunked within the chunk size
+        let messages = send_ping_chunked(None, None, 1024);
+        assert!(matches!(
+            decode_payload(&messages[0]),
+            OspPayload::Plain(Ping(42))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_timeout() {
+        let mut messages = send_ping_chunked(None, None, 2);
+        assert!(messages.len() > 1);
+        let last = messages.pop().unwrap();
+        let mut dispatcher = MessageDispatcher::new();
+        let mut receiver: PeelingReceiver<Ping, OspPayload<Ping>, OspPeeler<Ping>> =
+            PeelingReceiver::new_osp(dispatcher.subscribe_bound(), ecdh_key(2));
+        receiver.set_now_ms(1_000);
+        for signed in messages.clone() {
+            dispatcher.dispatch(signed.message);
+        }
+        assert!(receiver.try_next().unwrap().is_none());
+        // The partial payload is dropped after the timeout
+        receiver.set_now_ms(1_000 + CHUNK_TIMEOUT_MS + 1);
+        dispatcher.dispatch(last.message.clone());
+        assert!(receiver.try_next().unwrap().is_none());
+        // Completed by the resent chunks
+        for signed in messages {
+            dispatcher.dispatch(signed.message);
+        }
+        assert_eq!(
+            receiver.try_next().unwrap().map(|(_, msg, _)| msg),
+            Some(Ping(42))
+        );
+    }
+
+    #[test]
+    fn test_malformed_chunk() {
+        let chunk = |index, total| {
+            let queue = MessageSendQueue::new();
+            let mq = queue.channel(MessageOrigin::Worker(signer().public()), signer());
+            mq.sendto(
+                &OspPayload::<Ping>::Chunk {
+                    id: 1,
+                    index,
+                    total,
+                    data: vec![0],
+                },
+                Ping::TOPIC,
+            );
+            queue.all_messages()
+        };
+        assert!(receive(chunk(1, 1), ecdh_key(2), None).is_err());
+        assert!(receive(chunk(0, 0), ecdh_key(2), None).is_err());
+        assert!(receive(chunk(0, MAX_CHUNKS + 1), ecdh_key(2), None).is_err());
+        // A chunk of a chunk
+        let nested = OspPayload::<Ping>::Chunk {
+            id: 2,
+            index: 0,
+            total: 1,
+            data: vec![],
+        }
+        .encode();
+        let queue = MessageSendQueue::new();
+        let mq = queue.channel(MessageOrigin::Worker(signer().public()), signer());
+        mq.sendto(
+            &OspPayload::<Ping>::Chunk {
+                id: 1,
+                index: 0,
+                total: 1,
+                data: nested,
+            },
+            Ping::TOPIC,
+        );
+        assert!(receive(queue.all_messages(), ecdh_key(2), None).is_err());
+    }
+}
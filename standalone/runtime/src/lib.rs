@@ -38,7 +38,7 @@ use frame_support::{
 	},
 };
 use frame_system::{
-	EnsureRoot, EnsureOneOf,
+	EnsureRoot, EnsureOneOf, EnsureSigned,
 	limits::{BlockWeights, BlockLength}
 };
 use frame_support::{traits::InstanceFilter, PalletId};
@@ -1068,6 +1068,11 @@ impl pallet_bridge_transfer::Config for Runtime {
 
 parameter_types! {
 	pub const ExpectedBlockTimeSec: u32 = SECS_PER_BLOCK as u32;
+	pub const MaxStatusUpdatesPerMessage: u32 =
+		phala_types::messaging::MAX_STATUS_UPDATES_PER_MESSAGE;
+	pub const MiningRandomnessSubject: &'static [u8] = b"PhalaPoW";
+	pub const MiningPalletId: PalletId = PalletId(*b"phala/pp");
+	pub const MiningTopicPrefix: &'static [u8] = b"";
 	pub const MinMiningStaking: Balance = 1 * PHAS;
 	pub const MinContribution: Balance = 1 * CENTS;
 	pub const MiningInsurancePeriod: BlockNumber = 3 * DAYS;
@@ -1076,6 +1081,9 @@ parameter_types! {
 impl pallet_registry::Config for Runtime {
 	type Event = Event;
 	type UnixTime = Timestamp;
+	type OnWorkerKeyRotated = PhalaMining;
+	type OnAttestationRevoked = PhalaMining;
+	type WorkerBindingInfo = PhalaMining;
 }
 impl pallet_mq::Config for Runtime {
 	type QueueNotifyConfig = msg_routing::MessageRouteConfig;
@@ -1083,17 +1091,29 @@ impl pallet_mq::Config for Runtime {
 impl pallet_mining::Config for Runtime {
 	type Event = Event;
 	type ExpectedBlockTimeSec = ExpectedBlockTimeSec;
+	type MaxStatusUpdatesPerMessage = MaxStatusUpdatesPerMessage;
+	type RandomnessSubject = MiningRandomnessSubject;
+	type PalletId = MiningPalletId;
+	type TopicPrefix = MiningTopicPrefix;
+	type OtherInstances = ();
 	type Currency = Balances;
 	type Randomness = RandomnessCollectiveFlip;
-	type OnReward = PhalaStakePool;
-	type OnUnbound = PhalaStakePool;
-	type OnReclaim = PhalaStakePool;
+	type LifecycleObserver = PhalaStakePool;
+	type PriceFeed = ();
+	type SecondaryAssets = ();
+	type WorkerInfo = PhalaRegistry;
+	type GovernanceOrigin = EnsureRootOrHalfCouncil;
+	type TestingOrigin = EnsureRoot<AccountId>;
+	type StakePools = PhalaStakePool;
 }
 impl pallet_stakepool::Config for Runtime {
 	type Event = Event;
 	type Currency = Balances;
 	type MinContribution = MinContribution;
 	type InsurancePeriod = MiningInsurancePeriod;
+	type MiningInfo = PhalaMining;
+	// No XCM on the standalone chain. The signed accounts stake for themselves.
+	type XcmStakeOrigin = EnsureSigned<AccountId>;
 }
 
 construct_runtime!(
@@ -1337,6 +1357,61 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl phala_pallets::runtime_api::MiningApi<Block, AccountId, Balance, BlockNumber> for Runtime {
+		fn simulate_settlement(
+			worker: phala_types::WorkerPublicKey,
+			blocks_online: u32,
+			blocks_offline: u32,
+		) -> Option<pallet_mining::SettlementSimulation> {
+			PhalaMining::simulate_settlement(worker, blocks_online, blocks_offline)
+		}
+
+		fn free_stakable_balance(account: AccountId) -> Balance {
+			PhalaMining::free_stakable_balance(&account)
+		}
+
+		fn settlements_in_range(
+			miner: AccountId,
+			from_block: BlockNumber,
+			to_block: BlockNumber,
+		) -> Vec<(BlockNumber, pallet_mining::SettlementRecord<Balance>)> {
+			PhalaMining::settlements_in_range(&miner, from_block, to_block)
+		}
+
+		fn export_mining_state() -> pallet_mining::MiningStateSnapshot<AccountId, Balance> {
+			PhalaMining::export_mining_state()
+		}
+
+		fn miners_by_state(state: pallet_mining::MinerState, page: u32) -> Vec<AccountId> {
+			PhalaMining::miners_by_state(state, page)
+		}
+
+		fn miner_v(miner: AccountId) -> Option<(u128, Balance)> {
+			PhalaMining::miner_v(&miner)
+		}
+
+		fn why_cannot_reclaim(miner: AccountId) -> Option<pallet_mining::ReclaimBlocker> {
+			PhalaMining::why_cannot_reclaim(&miner)
+		}
+
+		fn why_cannot_start(
+			miner: AccountId,
+			stake: Balance,
+		) -> Option<pallet_mining::StartBlocker<Balance>> {
+			PhalaMining::why_cannot_start(&miner, stake)
+		}
+
+		fn topics() -> Vec<pallet_mining::TopicInfo> {
+			PhalaMining::topics()
+		}
+	}
+
+	impl phala_pallets::runtime_api::StakePoolApi<Block, AccountId> for Runtime {
+		fn pool_sub_account(pid: u64, worker: phala_types::WorkerPublicKey) -> AccountId {
+			pallet_mining::pool_sub_account(pid, &worker)
+		}
+	}
+
 	impl frame_system_rpc_runtime_api::AccountNonceApi<Block, AccountId, Index> for Runtime {
 		fn account_nonce(account: AccountId) -> Index {
 			System::account_nonce(account)
@@ -2,6 +2,7 @@ use super::pallet_mq;
 use codec::Decode;
 use frame_support::dispatch::{DispatchError, DispatchResult};
 use phala_types::messaging::{BindTopic, DecodedMessage, Message};
+use sp_std::vec::Vec;
 
 pub struct MessageRouteConfig;
 
@@ -10,7 +11,21 @@ where
     Msg: Decode + BindTopic,
     Func: Fn(DecodedMessage<Msg>) -> DispatchResult,
 {
-    if message.destination.path() == Msg::TOPIC {
+    try_dispatch_mapped(|topic| topic.to_vec(), func, message)
+}
+
+/// Dispatches the message if it's sent to the topic of `Msg` mapped by `map_topic`, e.g. the
+/// topic of a pallet instance
+fn try_dispatch_mapped<Msg, Func>(
+    map_topic: impl Fn(&[u8]) -> Vec<u8>,
+    func: Func,
+    message: &Message,
+) -> DispatchResult
+where
+    Msg: Decode + BindTopic,
+    Func: Fn(DecodedMessage<Msg>) -> DispatchResult,
+{
+    if message.destination.path() == &map_topic(Msg::TOPIC) {
         let msg: DecodedMessage<Msg> = message
             .decode()
             .ok_or(DispatchError::Other("MessageCodecError"))?;
@@ -29,10 +44,22 @@ impl pallet_mq::QueueNotifyConfig for MessageRouteConfig {
             }
         }
 
+        macro_rules! route_instance_handlers {
+            ($instance: ident => $($handler: ident,)+) => {
+                $(try_dispatch_mapped($instance::instance_topic, $instance::$handler, message)?;)+
+            }
+        }
+
+        route_instance_handlers! {
+            PhalaMining =>
+            on_gk_message_received,
+            on_gk_confirm_received,
+            on_gk_emergency_received,
+            on_gk_telemetry_received,
+            on_mining_message_received,
+        };
         route_handlers! {
             PhalaRegistry::on_message_received,
-            PhalaMining::on_gk_message_received,
-            PhalaMining::on_mining_message_received,
             BridgeTransfer::on_message_received,
             // KittyStorage::on_message_received,
         };